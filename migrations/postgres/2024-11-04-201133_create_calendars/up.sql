@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE calendars (
+    id SERIAL PRIMARY KEY,
+    integration_id INT NOT NULL REFERENCES integrations(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    external_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    background_color VARCHAR(255) NOT NULL,
+    foreground_color VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (integration_id, external_id)
+)
\ No newline at end of file
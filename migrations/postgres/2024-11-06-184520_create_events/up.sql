@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE events (
+    id SERIAL PRIMARY KEY,
+    calendar_id INT NOT NULL REFERENCES calendars(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    external_id TEXT NOT NULL,
+    etag TEXT,
+    summary TEXT,
+    description TEXT,
+    location TEXT,
+    starts_at TIMESTAMP NOT NULL,
+    ends_at TIMESTAMP NOT NULL,
+    all_day BOOLEAN NOT NULL DEFAULT FALSE,
+    time_zone VARCHAR(255),
+    status SMALLINT NOT NULL,
+    transparency SMALLINT NOT NULL,
+    organizer TEXT,
+    attendees TEXT NOT NULL DEFAULT '[]',
+    recurrence TEXT,
+    recurring_event_id TEXT,
+    original_starts_at TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (calendar_id, external_id)
+)
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE calendars DROP COLUMN sync_token;
\ No newline at end of file
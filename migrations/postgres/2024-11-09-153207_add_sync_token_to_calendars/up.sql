@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE calendars ADD COLUMN sync_token TEXT;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE integrations DROP COLUMN last_synced_at;
\ No newline at end of file
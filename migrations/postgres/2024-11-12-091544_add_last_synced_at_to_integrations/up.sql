@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE integrations ADD COLUMN last_synced_at TIMESTAMP;
\ No newline at end of file
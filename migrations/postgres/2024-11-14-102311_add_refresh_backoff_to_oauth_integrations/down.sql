@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE oauth_integrations DROP COLUMN next_refresh_at;
+ALTER TABLE oauth_integrations DROP COLUMN refresh_failures;
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE oauth_integrations ADD COLUMN refresh_failures INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE oauth_integrations ADD COLUMN next_refresh_at TIMESTAMP;
\ No newline at end of file
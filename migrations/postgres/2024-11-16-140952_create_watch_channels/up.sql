@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE watch_channels (
+    id SERIAL PRIMARY KEY,
+    calendar_id INT NOT NULL REFERENCES calendars(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    service SMALLINT NOT NULL,
+    external_id VARCHAR(255) NOT NULL UNIQUE,
+    resource_id TEXT,
+    token VARCHAR(255) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+)
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE integrations DROP CONSTRAINT integrations_group_id_fkey;
+ALTER TABLE integrations ADD CONSTRAINT integrations_group_id_fkey
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE oauth_integrations DROP CONSTRAINT oauth_integrations_integration_id_fkey;
+ALTER TABLE oauth_integrations ADD CONSTRAINT oauth_integrations_integration_id_fkey
+    FOREIGN KEY (integration_id) REFERENCES integrations(id) ON DELETE CASCADE ON UPDATE CASCADE;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE event_mirrors;
+ALTER TABLE calendars DROP COLUMN mirror;
+ALTER TABLE groups DROP COLUMN mirroring;
\ No newline at end of file
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TABLE groups ADD COLUMN mirroring BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE calendars ADD COLUMN mirror BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE event_mirrors (
+    id SERIAL PRIMARY KEY,
+    source_calendar_id INT NOT NULL REFERENCES calendars(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    source_event_id TEXT NOT NULL,
+    calendar_id INT NOT NULL REFERENCES calendars(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    external_id TEXT NOT NULL,
+    etag TEXT,
+    starts_at TIMESTAMP NOT NULL,
+    ends_at TIMESTAMP NOT NULL,
+    all_day BOOLEAN NOT NULL,
+    time_zone VARCHAR(255),
+    recurrence TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (source_calendar_id, source_event_id, calendar_id)
+);
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE groups DROP COLUMN max_days;
+ALTER TABLE groups DROP COLUMN minimum_notice;
+ALTER TABLE groups DROP COLUMN buffer_after;
+ALTER TABLE groups DROP COLUMN buffer_before;
+ALTER TABLE groups DROP COLUMN working_hours;
+ALTER TABLE groups DROP COLUMN time_zone;
\ No newline at end of file
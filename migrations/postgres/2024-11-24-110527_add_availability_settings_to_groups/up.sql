@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE groups ADD COLUMN time_zone VARCHAR(255) NOT NULL DEFAULT 'UTC';
+ALTER TABLE groups ADD COLUMN working_hours TEXT NOT NULL DEFAULT '[{"weekday":"Mon","start":"09:00:00","end":"17:00:00"},{"weekday":"Tue","start":"09:00:00","end":"17:00:00"},{"weekday":"Wed","start":"09:00:00","end":"17:00:00"},{"weekday":"Thu","start":"09:00:00","end":"17:00:00"},{"weekday":"Fri","start":"09:00:00","end":"17:00:00"}]';
+ALTER TABLE groups ADD COLUMN buffer_before INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE groups ADD COLUMN buffer_after INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE groups ADD COLUMN minimum_notice INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE groups ADD COLUMN max_days INTEGER NOT NULL DEFAULT 60;
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE feeds (
+    id SERIAL PRIMARY KEY,
+    group_id INT NOT NULL REFERENCES groups(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    token VARCHAR(255) NOT NULL UNIQUE,
+    privacy SMALLINT NOT NULL DEFAULT 1,
+    past_days INT NOT NULL DEFAULT 30,
+    future_days INT NOT NULL DEFAULT 365,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+)
\ No newline at end of file
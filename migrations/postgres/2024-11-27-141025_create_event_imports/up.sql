@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE event_imports (
+    id SERIAL PRIMARY KEY,
+    calendar_id INT NOT NULL REFERENCES calendars(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    uid VARCHAR(255) NOT NULL,
+    external_id VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (calendar_id, uid)
+)
\ No newline at end of file
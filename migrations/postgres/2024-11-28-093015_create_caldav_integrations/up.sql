@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE caldav_integrations (
+    id SERIAL PRIMARY KEY,
+    integration_id INT NOT NULL UNIQUE REFERENCES integrations(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    url TEXT NOT NULL,
+    username VARCHAR(255) NOT NULL,
+    password TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+)
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE calendar_objects (
+    id SERIAL PRIMARY KEY,
+    calendar_id INT NOT NULL REFERENCES calendars(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    href TEXT NOT NULL,
+    etag TEXT NOT NULL,
+    uid TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (calendar_id, href)
+)
\ No newline at end of file
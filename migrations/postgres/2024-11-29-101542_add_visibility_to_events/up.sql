@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE events ADD COLUMN visibility SMALLINT NOT NULL DEFAULT 1;
\ No newline at end of file
@@ -0,0 +1,84 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+
+use crate::{models::{event::{Event, EventStatus, EventTransparency}, group::Group}, recurrence};
+
+pub mod slots;
+
+/**
+ * A stretch of time in which someone is busy, in UTC.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BusyInterval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl BusyInterval {
+    pub fn new(start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        Self {
+            start: start.and_utc(),
+            end: end.and_utc(),
+        }
+    }
+
+    /**
+     * The interval of an occurrence. All-day events are stored as their dates at midnight,
+     * they block those days in the given time zone.
+     */
+    pub fn from_occurrence(starts_at: NaiveDateTime, ends_at: NaiveDateTime, all_day: bool, time_zone: &Tz) -> Self {
+        match all_day {
+            true => Self::new(recurrence::local_to_utc(time_zone, starts_at), recurrence::local_to_utc(time_zone, ends_at)),
+            false => Self::new(starts_at, ends_at),
+        }
+    }
+}
+
+/**
+ * Find the busy intervals of a group between start and end. The events of every calendar of
+ * every integration in the group are combined, so the result is the availability of the person
+ * the group belongs to. Recurring events are expanded into their occurrences, and cancelled or
+ * transparent occurrences are left out. Intervals are clipped to the requested range.
+ *
+ * All-day events block their days in the time zone of the group, which is UTC when the group
+ * has none. Events are loaded a day beyond the range so all-day events of every zone are found.
+ */
+pub fn group_busy(group: &Group, start: DateTime<Utc>, end: DateTime<Utc>, conn: &mut crate::db::Connection) -> Vec<BusyInterval> {
+    let time_zone = group.time_zone.parse::<Tz>().unwrap_or(Tz::UTC);
+    let (from, to) = (start.naive_utc() - Duration::days(1), end.naive_utc() + Duration::days(1));
+
+    let events = Event::find_by_group_between(group, from, to, conn);
+    let intervals = recurrence::expand_events(&events, from, to)
+        .iter()
+        .filter(|occurrence| occurrence.event.status != EventStatus::Cancelled)
+        .filter(|occurrence| occurrence.event.transparency == EventTransparency::Opaque)
+        .map(|occurrence| BusyInterval::from_occurrence(occurrence.starts_at, occurrence.ends_at, occurrence.event.all_day, &time_zone))
+        .collect();
+
+    merge_intervals(intervals)
+        .into_iter()
+        .map(|interval| BusyInterval {
+            start: interval.start.max(start),
+            end: interval.end.min(end),
+        })
+        .filter(|interval| interval.start < interval.end)
+        .collect()
+}
+
+/**
+ * Sort the intervals and merge the ones that overlap or touch. Empty intervals are dropped.
+ */
+pub fn merge_intervals(mut intervals: Vec<BusyInterval>) -> Vec<BusyInterval> {
+    intervals.retain(|interval| interval.start < interval.end);
+    intervals.sort_by_key(|interval| interval.start);
+
+    let mut merged: Vec<BusyInterval> = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        match merged.last_mut() {
+            Some(last) if interval.start <= last.end => last.end = last.end.max(interval.end),
+            _ => merged.push(interval),
+        }
+    }
+    merged
+}
@@ -0,0 +1,147 @@
+use chrono::{DateTime, Datelike, Duration, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+
+use crate::{models::group::{Group, WorkingHours}, recurrence};
+
+use super::BusyInterval;
+
+/**
+ * The furthest ahead slots can be booked, in days.
+ */
+pub const MAX_DAYS: i64 = 365;
+
+/**
+ * The rules a group's bookable slots are found with. Buffers are kept free before and after
+ * every booking, and nothing can be booked sooner than the minimum notice or further ahead than
+ * the maximum number of days.
+ */
+#[derive(Debug, Clone)]
+pub struct AvailabilitySettings {
+    pub time_zone: Tz,
+    pub working_hours: WorkingHours,
+    pub buffer_before: Duration,
+    pub buffer_after: Duration,
+    pub minimum_notice: Duration,
+    pub max_days: i64,
+}
+
+impl AvailabilitySettings {
+    /**
+     * Read the settings of a group. Returns None when the time zone of the group is unknown.
+     */
+    pub fn from_group(group: &Group) -> Option<Self> {
+        Some(Self {
+            time_zone: group.time_zone.parse::<Tz>().ok()?,
+            working_hours: group.working_hours.clone(),
+            buffer_before: Duration::minutes(group.buffer_before.into()),
+            buffer_after: Duration::minutes(group.buffer_after.into()),
+            minimum_notice: Duration::minutes(group.minimum_notice.into()),
+            max_days: i64::from(group.max_days).min(MAX_DAYS),
+        })
+    }
+}
+
+/**
+ * A bookable stretch of time, in UTC.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Slot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/**
+ * The range to find slots in, the length of the slots and the time between the starts of two
+ * consecutive slots.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct SlotQuery {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub duration: Duration,
+    pub interval: Duration,
+}
+
+/**
+ * Find the bookable slots of a group. The busy intervals are loaded up to the booking horizon,
+ * wide enough to cover the buffers of the first and last slots.
+ */
+pub fn group_slots(
+    group: &Group,
+    settings: &AvailabilitySettings,
+    query: &SlotQuery,
+    now: DateTime<Utc>,
+    conn: &mut crate::db::Connection,
+) -> Vec<Slot> {
+    let end = query.end.min(now + Duration::days(settings.max_days));
+    let busy = super::group_busy(
+        group,
+        query.start - settings.buffer_before,
+        end + settings.buffer_after,
+        conn,
+    );
+    find_slots(settings, &busy, query, now)
+}
+
+/**
+ * Find the slots within the working hours which do not overlap any busy interval, buffers
+ * included. Slots start at the beginning of a working period and every interval after it, so
+ * they line up on the clock in the group's time zone. Working hours follow the local time of
+ * the group across daylight saving time changes.
+ */
+pub fn find_slots(settings: &AvailabilitySettings, busy: &[BusyInterval], query: &SlotQuery, now: DateTime<Utc>) -> Vec<Slot> {
+    let mut slots = Vec::new();
+    if query.duration <= Duration::zero() || query.interval <= Duration::zero() {
+        return slots;
+    }
+
+    let start = query.start.max(now + settings.minimum_notice);
+    let end = query.end.min(now + Duration::days(settings.max_days));
+    if start >= end {
+        return slots;
+    }
+
+    let mut date = start.with_timezone(&settings.time_zone).date_naive();
+    let last_date = end.with_timezone(&settings.time_zone).date_naive();
+
+    while date <= last_date {
+        let mut periods = settings.working_hours.0.iter()
+            .filter(|period| period.weekday == date.weekday() && period.start < period.end)
+            .collect::<Vec<_>>();
+        periods.sort_by_key(|period| period.start);
+
+        for period in periods {
+            let period_start = recurrence::local_to_utc(&settings.time_zone, date.and_time(period.start)).and_utc();
+            let period_end = recurrence::local_to_utc(&settings.time_zone, date.and_time(period.end)).and_utc();
+
+            let mut slot_start = period_start;
+            while slot_start + query.duration <= period_end && slot_start + query.duration <= end {
+                let slot = Slot {
+                    start: slot_start,
+                    end: slot_start + query.duration,
+                };
+                if slot.start >= start && is_free(&slot, busy, settings) {
+                    slots.push(slot);
+                }
+                slot_start += query.interval;
+            }
+        }
+
+        let Some(next) = date.succ_opt() else {
+            break;
+        };
+        date = next;
+    }
+
+    slots
+}
+
+/**
+ * Whether the slot and its buffers stay clear of every busy interval.
+ */
+fn is_free(slot: &Slot, busy: &[BusyInterval], settings: &AvailabilitySettings) -> bool {
+    let start = slot.start - settings.buffer_before;
+    let end = slot.end + settings.buffer_after;
+    !busy.iter().any(|interval| interval.start < end && interval.end > start)
+}
@@ -2,16 +2,58 @@
 
 pub struct Config {
     pub oauth2: Oauth2ConfigGroup,
+    pub scheduler: SchedulerConfig,
+    pub webhook_url: Option<String>,
 }
 
 impl Config {
     pub fn new() -> Self {
         Self {
             oauth2: Oauth2ConfigGroup::new(),
+            scheduler: SchedulerConfig::new(),
+            webhook_url: std::env::var("WEBHOOK_URL").ok(),
         }
     }
 }
 
+/**
+ * Configuration for the background jobs. Every value is optional and falls back to a default.
+ */
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub enabled: bool,
+    pub sync_interval: std::time::Duration,
+    pub sync_concurrency: usize,
+    pub token_refresh_interval: std::time::Duration,
+    pub token_refresh_window: std::time::Duration,
+    pub watch_renew_interval: std::time::Duration,
+    pub watch_renew_window: std::time::Duration,
+}
+
+impl SchedulerConfig {
+    fn new() -> Self {
+        Self {
+            enabled: env_or("SCHEDULER_ENABLED", true),
+            sync_interval: std::time::Duration::from_secs(env_or("SYNC_INTERVAL_SECONDS", 900)),
+            sync_concurrency: env_or::<usize>("SYNC_CONCURRENCY", 4).max(1),
+            token_refresh_interval: std::time::Duration::from_secs(env_or("TOKEN_REFRESH_INTERVAL_SECONDS", 60)),
+            token_refresh_window: std::time::Duration::from_secs(env_or("TOKEN_REFRESH_WINDOW_SECONDS", 600)),
+            watch_renew_interval: std::time::Duration::from_secs(env_or("WATCH_RENEW_INTERVAL_SECONDS", 3600)),
+            watch_renew_window: std::time::Duration::from_secs(env_or("WATCH_RENEW_WINDOW_SECONDS", 86400)),
+        }
+    }
+}
+
+/**
+ * Read and parse an optional environment variable, using the default when it is missing.
+ */
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    match std::env::var(key) {
+        Ok(value) => value.parse::<T>().unwrap_or_else(|_| panic!("{} is not valid.", key)),
+        Err(_) => default,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Oauth2ConfigGroup {
     pub google: Oauth2Config,
@@ -32,6 +74,7 @@ pub struct Oauth2Config {
     pub authorization_url: String,
     pub token_url: String,
     pub revoke_url: String,
+    pub api_url: String,
     pub client_id: String,
     pub client_secret: String,
     pub redirect_uri: String,
@@ -41,39 +84,39 @@ pub struct Oauth2Config {
 impl Oauth2Config {
 
     fn outlook() -> Self {
-        Self::build(
-            "https://login.microsoftonline.com/consumers/oauth2/v2.0/authorize".to_string(),
-            "https://login.microsoftonline.com/consumers/oauth2/v2.0/token".to_string(),
-            "https://login.microsoftonline.com/consumers/oauth2/v2.0/revoke".to_string(),
-            std::env::var("OUTLOOK_CLIENT_ID").expect("OUTLOOK_API_TOKEN must be set."),
-            std::env::var("OUTLOOK_CLIENT_SECRET").expect("OUTLOOK_API_SECRET must be set."),
-            std::env::var("OUTLOOK_REDIRECT_URI").expect("OUTLOOK_REDIRECT_URI must be set."),
-            std::env::var("OUTLOOK_SCOPES").expect("OUTLOOK_SCOPES must be set."),
-        )
+        Self {
+            authorization_url: "https://login.microsoftonline.com/consumers/oauth2/v2.0/authorize".to_string(),
+            token_url: "https://login.microsoftonline.com/consumers/oauth2/v2.0/token".to_string(),
+            revoke_url: "https://login.microsoftonline.com/consumers/oauth2/v2.0/revoke".to_string(),
+            api_url: env_or("OUTLOOK_API_URL", "https://graph.microsoft.com/v1.0".to_string()),
+            client_id: std::env::var("OUTLOOK_CLIENT_ID").expect("OUTLOOK_API_TOKEN must be set."),
+            client_secret: std::env::var("OUTLOOK_CLIENT_SECRET").expect("OUTLOOK_API_SECRET must be set."),
+            redirect_uri: std::env::var("OUTLOOK_REDIRECT_URI").expect("OUTLOOK_REDIRECT_URI must be set."),
+            scope: std::env::var("OUTLOOK_SCOPES").expect("OUTLOOK_SCOPES must be set."),
+        }
     }
 
     fn google() -> Self {
-        Self::build(
-            "https://accounts.google.com/o/oauth2/auth".to_string(),
-            "https://oauth2.googleapis.com/token".to_string(),
-            "https://accounts.google.com/o/oauth2/revoke".to_string(),
-            std::env::var("GOOGLE_CLIENT_ID").expect("GOOGLE_API_TOKEN must be set."),
-            std::env::var("GOOGLE_CLIENT_SECRET").expect("GOOGLE_API_SECRET must be set."),
-            std::env::var("GOOGLE_REDIRECT_URI").expect("GOOGLE_REDIRECT_URI must be set."),
-            std::env::var("GOOGLE_SCOPES").expect("GOOGLE_SCOPES must be set."),
-        )
-    }
-
-    fn build(authorization_url: String, token_url: String, revoke_url: String, client_id: String, client_secret: String, redirect_uri: String, scope: String) -> Self {
         Self {
-            authorization_url,
-            token_url,
-            revoke_url,
-            client_id,
-            client_secret,
-            redirect_uri,
-            scope,
+            authorization_url: "https://accounts.google.com/o/oauth2/auth".to_string(),
+            token_url: "https://oauth2.googleapis.com/token".to_string(),
+            revoke_url: "https://accounts.google.com/o/oauth2/revoke".to_string(),
+            api_url: env_or("GOOGLE_API_URL", "https://www.googleapis.com/calendar/v3".to_string()),
+            client_id: std::env::var("GOOGLE_CLIENT_ID").expect("GOOGLE_API_TOKEN must be set."),
+            client_secret: std::env::var("GOOGLE_CLIENT_SECRET").expect("GOOGLE_API_SECRET must be set."),
+            redirect_uri: std::env::var("GOOGLE_REDIRECT_URI").expect("GOOGLE_REDIRECT_URI must be set."),
+            scope: std::env::var("GOOGLE_SCOPES").expect("GOOGLE_SCOPES must be set."),
         }
+    }
 
+    /**
+     * Build the URL of an API resource from its path segments, which are escaped.
+     */
+    pub fn api_url(&self, segments: &[&str]) -> reqwest::Url {
+        let mut url = reqwest::Url::parse(&self.api_url).expect("The API URL is not valid.");
+        url.path_segments_mut().expect("The API URL is not valid.")
+            .pop_if_empty()
+            .extend(segments);
+        url
     }
 }
\ No newline at end of file
@@ -1,9 +1,14 @@
 use core::panic;
-use std::{collections::HashMap, io::{BufReader, Cursor}};
+use std::{collections::{HashMap, HashSet}, io::{BufReader, Cursor}};
 
 use ical::{line, parser::ical::component::IcalEvent, property::Property};
 use serde::{Deserialize, Serialize};
 use quick_xml::{se::Serializer, de::Deserializer, de::DeError};
+use reqwest::StatusCode;
+
+use crate::{helper::foreground_color, ics::unescape_text, models::{calendar::CalendarResult, event::{Attendees, EventResult, EventStatus, EventTransparency, EventVisibility}, task::TaskResult}};
+
+use super::{datetime::{IcalDateTime, IcalDateTimeError, TimeZones}, participant::{CaldavAttendee, CaldavOrganizer}, task::{parse_tasks, CaldavCalendarTasks}};
 
 fn to_xml_string<T: Serialize>(data: &T) -> Result<String, Box<dyn std::error::Error>> {
     // Create a serializer with the writer (Cursor in this case)
@@ -20,6 +25,11 @@ fn parse_xml<T: for<'a> Deserialize<'a>>(data: &str) -> Result<T, DeError> {
     quick_xml::de::from_str(data)
 }
 
+/**
+ * The amount of resources requested in a single calendar-multiget REPORT.
+ */
+const MULTIGET_BATCH_SIZE: usize = 100;
+
 /**
  * Get the principal data from the CalDAV server.
  */
@@ -151,58 +161,83 @@ pub async fn get_events(
     username: String,
     password: Option<String>
 ) -> Result<Vec<CaldavCalendarEvents>, anyhow::Error> {
+    let data = query_calendar(data, "VEVENT", url, username, password).await?;
+    let list = parse_event_responses(&data);
+
+    if list.len() == 0 {
+        return Err(anyhow::anyhow!("get_events: Error - list is empty"));
+    }
 
+    Ok(list)
+}
+
+/**
+ * Get every resource of the calendar holding the given component type, VEVENT or VTODO, with
+ * a calendar-query REPORT.
+ */
+async fn query_calendar(
+    data: &CaldavCalendar,
+    component: &str,
+    url: String,
+    username: String,
+    password: Option<String>
+) -> Result<MultiStatus<EventResponse>, anyhow::Error> {
     let method = reqwest::Method::from_bytes(b"REPORT").unwrap();
     let client = reqwest::Client::new();
 
     // Serialize the payload
-    let payload = match to_xml_string(&CalendarQuery {
+    let Ok(payload) = to_xml_string(&CalendarQuery {
         xmlns_c: "urn:ietf:params:xml:ns:caldav".to_string(),
         xmlns_d: "DAV:".to_string(),
-        prop: EventRequestProp::make(),
+        prop: match component {
+            "VTODO" => EventRequestProp::make_tasks(),
+            _ => EventRequestProp::make(),
+        },
         filter: Filter {
             comp_filter: CompFilter {
                 name: "VCALENDAR".to_string(),
                 comp_filter: Some(Box::new(CompFilter {
-                    name: "VEVENT".to_string(),
+                    name: component.to_string(),
                     comp_filter: None,
                 })),
             },
         },
-    }) {
-        Ok(payload) => {
-            payload
-        },
-        Err(err) => {
-            println!("Event Error {:?}", err);
-            return Err(anyhow::anyhow!("get_events: Error serializing payload"));
-        }
+    }) else {
+        return Err(anyhow::anyhow!("query_calendar: Error serializing payload"));
     };
-    
-    // Send the get events request
+
+    // Send the calendar query
     let Ok(response) = client
         .request(method, url + &data.path)
         .header("Depth", "1")
+        .header("Content-Type", "application/xml; charset=utf-8")
         .basic_auth(username, password)
         .body(payload).send().await else {
-        return Err(anyhow::anyhow!("get_events: Error sending request"));
+        return Err(anyhow::anyhow!("query_calendar: Error sending request"));
     };
 
     // Expect a 207 status code
     if response.status() != 207 {
-        return Err(anyhow::anyhow!("get_events: Error status code: {}", response.status()));
+        return Err(anyhow::anyhow!("query_calendar: Error status code: {}", response.status()));
     }
 
     // Read the response
     let Ok(text) = response.text().await else {
-        return Err(anyhow::anyhow!("get_events: Error reading response"));
+        return Err(anyhow::anyhow!("query_calendar: Error reading response"));
     };
 
     // Deserialize the response
     let Ok(data) = parse_xml::<MultiStatus<EventResponse>>(&text) else {
-        return Err(anyhow::anyhow!("get_events: Error deserializing response"));
+        return Err(anyhow::anyhow!("query_calendar: Error deserializing response"));
     };
 
+    Ok(data)
+}
+
+/**
+ * Extract the etag and the parsed calendar data of every resource with a 200 status code.
+ */
+fn parse_event_responses(data: &MultiStatus<EventResponse>) -> Vec<CaldavCalendarEvents> {
     // Filter responses with a 200 status code
     let elements = data.response.iter().filter(|x| {
         let Some(propstat) = x.propstat.first() else {
@@ -212,7 +247,7 @@ pub async fn get_events(
     });
 
     // Loop through the elements and extract the etag and calendar data
-    let mut list = elements.map(|events_data| {
+    elements.map(|events_data| {
         let propstat = events_data.propstat.first().unwrap();
         let prop = propstat.prop.as_ref().unwrap();
 
@@ -224,41 +259,584 @@ pub async fn get_events(
             return None;
         };
 
-        let cursor = Cursor::new(calendar_data.as_bytes());
-        let buffered_reader = BufReader::new(cursor);
-        let reader = ical::IcalParser::new(buffered_reader);
-
-        let mut events: Vec<CaldavEvent> = Vec::new();
-
-        for line in reader {
-            match line {
-                Ok(calendar) => {
-                    calendar.events.iter().for_each(|event| {
-                        events.push(CaldavEvent::from_ical_evel(event.clone()));
-                    });
-                },
-                Err(_) => {
-                    println!("Error");
-                }
-            }
-        }
-    
         Some(CaldavCalendarEvents {
+            href: events_data.href.clone(),
             etag: etag.to_string(),
-            events,
+            events: parse_events(calendar_data),
         })
     })
         .filter(|x| x.is_some())
         .map(|x| x.unwrap())
-        .collect::<Vec<CaldavCalendarEvents>>();
+        .collect::<Vec<CaldavCalendarEvents>>()
+}
 
-    if list.len() == 0 {
-        return Err(anyhow::anyhow!("get_events: Error - list is empty"));
+/**
+ * Parse the VEVENTs of iCalendar data. Date and time values are read with the VTIMEZONE
+ * definitions of the calendar they are in. Events which cannot be read are left out.
+ */
+pub fn parse_events(calendar_data: &str) -> Vec<CaldavEvent> {
+    let cursor = Cursor::new(calendar_data.as_bytes());
+    let buffered_reader = BufReader::new(cursor);
+    let reader = ical::IcalParser::new(buffered_reader);
+
+    let mut events: Vec<CaldavEvent> = Vec::new();
+
+    for line in reader {
+        match line {
+            Ok(calendar) => {
+                let time_zones = TimeZones::from_calendar(&calendar.timezones);
+                calendar.events.iter().for_each(|event| {
+                    match CaldavEvent::from_ical_evel(event.clone(), &time_zones) {
+                        Ok(event) => events.push(event),
+                        Err(err) => println!("Skipping event: {}", err),
+                    }
+                });
+            },
+            Err(_) => {
+                println!("Error");
+            }
+        }
+    }
+
+    events
+}
+
+/**
+ * Get the calendar data of the given resources of a calendar with calendar-multiget REPORTs.
+ * The hrefs are requested in batches, resources which no longer exist are left out.
+ */
+pub async fn get_events_by_href(
+    path: &str,
+    hrefs: &[String],
+    url: String,
+    username: String,
+    password: Option<String>
+) -> Result<Vec<CaldavCalendarEvents>, anyhow::Error> {
+    let client = reqwest::Client::new();
+    let collection_url = url + path;
+    let mut list: Vec<CaldavCalendarEvents> = Vec::new();
+
+    for batch in hrefs.chunks(MULTIGET_BATCH_SIZE) {
+        let method = reqwest::Method::from_bytes(b"REPORT").unwrap();
+
+        // Serialize the payload
+        let Ok(payload) = to_xml_string(&CalendarMultiget {
+            xmlns_c: "urn:ietf:params:xml:ns:caldav".to_string(),
+            xmlns_d: "DAV:".to_string(),
+            prop: EventRequestProp::make(),
+            href: batch.to_vec(),
+        }) else {
+            return Err(anyhow::anyhow!("get_events_by_href: Error serializing payload"));
+        };
+
+        // Send the multiget request, the hrefs select the resources so no Depth header is sent
+        let Ok(response) = client
+            .request(method, collection_url.as_str())
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .basic_auth(&username, password.as_ref())
+            .body(payload).send().await else {
+            return Err(anyhow::anyhow!("get_events_by_href: Error sending request"));
+        };
+
+        // Expect a 207 status code
+        if response.status() != 207 {
+            return Err(anyhow::anyhow!("get_events_by_href: Error status code: {}", response.status()));
+        }
+
+        // Read the response
+        let Ok(text) = response.text().await else {
+            return Err(anyhow::anyhow!("get_events_by_href: Error reading response"));
+        };
+
+        // Deserialize the response
+        let Ok(data) = parse_xml::<MultiStatus<EventResponse>>(&text) else {
+            return Err(anyhow::anyhow!("get_events_by_href: Error deserializing response"));
+        };
+
+        list.extend(parse_event_responses(&data));
     }
 
     Ok(list)
 }
 
+/**
+ * Get the tasks (VTODOs) from the CalDAV server. Fails for calendars which cannot hold tasks.
+ */
+pub async fn get_tasks(
+    data: &CaldavCalendar,
+    url: String,
+    username: String,
+    password: Option<String>
+) -> Result<Vec<CaldavCalendarTasks>, anyhow::Error> {
+    if !data.supports_component("VTODO") {
+        return Err(anyhow::anyhow!("get_tasks: Calendar {} does not support tasks", data.path));
+    }
+
+    let data = query_calendar(data, "VTODO", url, username, password).await?;
+    Ok(data.response.iter()
+        .filter_map(|response| {
+            let propstat = response.propstat.iter().find(|propstat| propstat.status.contains("200"))?;
+            let prop = propstat.prop.as_ref()?;
+            Some(CaldavCalendarTasks {
+                href: response.href.clone(),
+                etag: prop.getetag.clone()?,
+                tasks: parse_tasks(prop.calendar_data.as_deref()?),
+            })
+        })
+        .collect())
+}
+
+/**
+ * Create or update a task in the calendar, in the same way as put_event. Tasks are deleted
+ * with delete_event. Calendars which cannot hold tasks are refused, and occurrences of a
+ * recurring task live in the object of their series and cannot be written on their own.
+ */
+pub async fn put_task(
+    calendar: &CaldavCalendar,
+    task: &TaskResult,
+    etag: Option<&str>,
+    url: String,
+    username: String,
+    password: Option<String>
+) -> Result<Option<String>, CaldavWriteError> {
+    if !calendar.supports_component("VTODO") {
+        return Err(CaldavWriteError::InvalidEvent(format!("Calendar {} does not support tasks", calendar.path)));
+    }
+    if task.recurring_task_id.is_some() {
+        return Err(CaldavWriteError::InvalidEvent("Occurrences of a recurring task cannot be written on their own".to_string()));
+    }
+
+    let resource_url = event_url(&url, &calendar.path, &task.external_id)?;
+    let request = reqwest::Client::new()
+        .put(resource_url)
+        .header("Content-Type", "text/calendar; charset=utf-8")
+        .basic_auth(username, password)
+        .body(crate::ics::write_tasks(std::slice::from_ref(task)));
+
+    // Guard against overwriting changes made since the task was last read
+    let request = match etag {
+        Some(etag) => request.header("If-Match", etag),
+        None => request.header("If-None-Match", "*"),
+    };
+
+    let response = request.send().await
+        .map_err(CaldavWriteError::NetworkError)?;
+
+    match response.status() {
+        StatusCode::OK | StatusCode::CREATED | StatusCode::NO_CONTENT => Ok(response.headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_string())),
+        StatusCode::PRECONDITION_FAILED => Err(CaldavWriteError::PreconditionFailed),
+        status => Err(CaldavWriteError::InvalidStatusError(status, response.text().await.unwrap_or_default())),
+    }
+}
+
+/**
+ * Create or update an event in the calendar at the given path. The event is stored at
+ * `<path>/<uid>.ics`. Without an etag the event is only created when it does not exist yet,
+ * with an etag it is only updated when it was not changed since. Instances of a recurring
+ * event live in the object of their series and cannot be written on their own.
+ *
+ * Returns the etag of the stored event. Servers may leave it out when they altered the event
+ * while storing it, in which case the event must be fetched again to learn its etag.
+ */
+pub async fn put_event(
+    path: &str,
+    event: &EventResult,
+    etag: Option<&str>,
+    url: String,
+    username: String,
+    password: Option<String>
+) -> Result<Option<String>, CaldavWriteError> {
+    if event.recurring_event_id.is_some() {
+        return Err(CaldavWriteError::InvalidEvent("Instances of a recurring event cannot be written on their own".to_string()));
+    }
+
+    let resource_url = event_url(&url, path, crate::ics::event_uid(event))?;
+    let request = reqwest::Client::new()
+        .put(resource_url)
+        .header("Content-Type", "text/calendar; charset=utf-8")
+        .basic_auth(username, password)
+        .body(crate::ics::write_calendar(None, std::slice::from_ref(event)));
+
+    // Guard against overwriting changes made since the event was last read
+    let request = match etag {
+        Some(etag) => request.header("If-Match", etag),
+        None => request.header("If-None-Match", "*"),
+    };
+
+    let response = request.send().await
+        .map_err(CaldavWriteError::NetworkError)?;
+
+    match response.status() {
+        StatusCode::OK | StatusCode::CREATED | StatusCode::NO_CONTENT => Ok(response.headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_string())),
+        StatusCode::PRECONDITION_FAILED => Err(CaldavWriteError::PreconditionFailed),
+        status => Err(CaldavWriteError::InvalidStatusError(status, response.text().await.unwrap_or_default())),
+    }
+}
+
+/**
+ * Delete an event from the calendar at the given path. With an etag the event is only deleted
+ * when it was not changed since. Events which no longer exist are considered deleted.
+ */
+pub async fn delete_event(
+    path: &str,
+    uid: &str,
+    etag: Option<&str>,
+    url: String,
+    username: String,
+    password: Option<String>
+) -> Result<(), CaldavWriteError> {
+    let resource_url = event_url(&url, path, uid)?;
+    let mut request = reqwest::Client::new()
+        .delete(resource_url)
+        .basic_auth(username, password);
+
+    if let Some(etag) = etag {
+        request = request.header("If-Match", etag);
+    }
+
+    let response = request.send().await
+        .map_err(CaldavWriteError::NetworkError)?;
+
+    match response.status() {
+        StatusCode::OK | StatusCode::NO_CONTENT | StatusCode::NOT_FOUND => Ok(()),
+        StatusCode::PRECONDITION_FAILED => Err(CaldavWriteError::PreconditionFailed),
+        status => Err(CaldavWriteError::InvalidStatusError(status, response.text().await.unwrap_or_default())),
+    }
+}
+
+/**
+ * Build the URL of the object resource of an event, encoding the UID as a path segment.
+ */
+fn event_url(url: &str, path: &str, uid: &str) -> Result<reqwest::Url, CaldavWriteError> {
+    let Ok(mut resource_url) = reqwest::Url::parse(&(url.to_string() + path)) else {
+        return Err(CaldavWriteError::InvalidEvent(format!("Invalid calendar URL {}{}", url, path)));
+    };
+    let Ok(mut segments) = resource_url.path_segments_mut() else {
+        return Err(CaldavWriteError::InvalidEvent(format!("Invalid calendar URL {}{}", url, path)));
+    };
+    segments.pop_if_empty().push(&format!("{}.ics", uid));
+    drop(segments);
+    Ok(resource_url)
+}
+
+/**
+ * Get the resources of the calendar at the given path which changed since the last sync. The
+ * sync-collection REPORT (RFC 6578) is used when the server supports it. Other servers are
+ * compared by their ctag and the etag of every resource, in which case `known_etags` must map
+ * the href of every stored resource to its etag. The returned sync token is stored with the
+ * calendar and passed back on the next sync.
+ */
+pub async fn sync_events(
+    path: &str,
+    sync_token: Option<&str>,
+    known_etags: &HashMap<String, String>,
+    url: String,
+    username: String,
+    password: Option<String>
+) -> Result<CaldavChanges, anyhow::Error> {
+    let client = reqwest::Client::new();
+    let collection_url = url + path;
+    let sync_token = sync_token.map(CaldavSyncToken::parse);
+
+    // Servers which failed the sync-collection REPORT before go straight to the fallback
+    if let Some(CaldavSyncToken::Ctag(ctag)) = &sync_token {
+        return compare_etags(&client, &collection_url, path, Some(ctag), known_etags, &username, &password).await;
+    }
+
+    let mut token = match &sync_token {
+        Some(CaldavSyncToken::SyncToken(token)) => Some(token.clone()),
+        _ => None,
+    };
+    let mut changes = CaldavChanges {
+        full: token.is_none(),
+        ..Default::default()
+    };
+
+    loop {
+        let page = match sync_collection(&client, &collection_url, path, token.as_deref(), &username, &password).await? {
+            SyncCollectionResult::Page(page) => page,
+            SyncCollectionResult::InvalidToken if token.is_some() => {
+                // The token expired, start over with a full sync
+                token = None;
+                changes = CaldavChanges { full: true, ..Default::default() };
+                continue;
+            },
+            SyncCollectionResult::Rejected(_) if token.is_none() => {
+                return compare_etags(&client, &collection_url, path, None, known_etags, &username, &password).await;
+            },
+            SyncCollectionResult::InvalidToken => {
+                return Err(anyhow::anyhow!("sync_events: The server rejected the initial sync token"));
+            },
+            SyncCollectionResult::Rejected(status) => {
+                return Err(anyhow::anyhow!("sync_events: Error status code: {}", status));
+            },
+        };
+
+        changes.changed.extend(page.changed);
+        changes.deleted.extend(page.deleted);
+        token = page.sync_token;
+
+        // Truncated results continue from the token of the partial result
+        if !page.truncated || token.is_none() {
+            break;
+        }
+    }
+
+    changes.sync_token = token.map(|token| CaldavSyncToken::SyncToken(token).to_string());
+    Ok(changes)
+}
+
+/**
+ * Run a single sync-collection REPORT. A token the server no longer accepts is answered with
+ * the DAV:valid-sync-token precondition (RFC 6578 section 3.2), any other rejection means the
+ * server does not support the report.
+ */
+async fn sync_collection(
+    client: &reqwest::Client,
+    collection_url: &str,
+    collection_path: &str,
+    sync_token: Option<&str>,
+    username: &str,
+    password: &Option<String>,
+) -> Result<SyncCollectionResult, anyhow::Error> {
+    let method = reqwest::Method::from_bytes(b"REPORT").unwrap();
+
+    // Serialize the payload
+    let Ok(payload) = to_xml_string(&SyncCollection {
+        xmlns_d: "DAV:".to_string(),
+        sync_token: sync_token.unwrap_or("").to_string(),
+        sync_level: "1".to_string(),
+        prop: EtagRequestProp::make(),
+    }) else {
+        return Err(anyhow::anyhow!("sync_collection: Error serializing payload"));
+    };
+
+    // Send the sync collection request
+    let Ok(response) = client
+        .request(method, collection_url)
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .basic_auth(username, password.as_ref())
+        .body(payload).send().await else {
+        return Err(anyhow::anyhow!("sync_collection: Error sending request"));
+    };
+
+    let status = response.status();
+    if status == StatusCode::FORBIDDEN || status == StatusCode::CONFLICT {
+        let body = response.text().await.unwrap_or_default();
+        if body.contains("valid-sync-token") {
+            return Ok(SyncCollectionResult::InvalidToken);
+        }
+        return Ok(SyncCollectionResult::Rejected(status));
+    }
+    if status != 207 {
+        return Ok(SyncCollectionResult::Rejected(status));
+    }
+
+    // Read the response
+    let Ok(text) = response.text().await else {
+        return Err(anyhow::anyhow!("sync_collection: Error reading response"));
+    };
+
+    // Deserialize the response
+    let data = match parse_xml::<SyncMultiStatus>(&text) {
+        Ok(data) => data,
+        Err(err) => {
+            return Err(anyhow::anyhow!("sync_collection: Error deserializing response: {}", err));
+        }
+    };
+
+    let mut page = SyncCollectionPage {
+        sync_token: data.sync_token,
+        ..Default::default()
+    };
+
+    for response in data.response {
+        // The collection itself is only listed when the results are truncated
+        if same_path(&response.href, collection_path) {
+            page.truncated = response.status.as_deref().is_some_and(|status| status.contains("507"));
+            continue;
+        }
+
+        // Removed resources only carry a 404 status
+        if response.status.as_deref().is_some_and(|status| status.contains("404")) {
+            page.deleted.push(response.href);
+            continue;
+        }
+
+        let etag = response.propstat.iter()
+            .filter(|propstat| propstat.status.contains("200"))
+            .find_map(|propstat| propstat.prop.as_ref().and_then(|prop| prop.getetag.clone()));
+
+        if let Some(etag) = etag {
+            page.changed.push(CaldavResource {
+                href: response.href,
+                etag,
+            });
+        }
+    }
+
+    Ok(SyncCollectionResult::Page(page))
+}
+
+/**
+ * Find the changes of a calendar on servers without sync-collection support. Nothing has
+ * changed when the ctag is the same as the last sync, otherwise the etag of every resource
+ * is compared with the known etags.
+ */
+async fn compare_etags(
+    client: &reqwest::Client,
+    collection_url: &str,
+    collection_path: &str,
+    previous_ctag: Option<&str>,
+    known_etags: &HashMap<String, String>,
+    username: &str,
+    password: &Option<String>,
+) -> Result<CaldavChanges, anyhow::Error> {
+    let ctag = get_ctag(client, collection_url, username, password).await?;
+    let sync_token = ctag.as_ref().map(|ctag| CaldavSyncToken::Ctag(ctag.clone()).to_string());
+
+    if ctag.is_some() && ctag.as_deref() == previous_ctag {
+        return Ok(CaldavChanges {
+            sync_token,
+            ..Default::default()
+        });
+    }
+
+    let method = reqwest::Method::from_bytes(b"PROPFIND").unwrap();
+
+    // Serialize the payload
+    let Ok(payload) = to_xml_string(&Propfind {
+        d: "DAV:".to_string(),
+        cal: "urn:ietf:params:xml:ns:caldav".to_string(),
+        cs: "http://calendarserver.org/ns/".to_string(),
+        prop: EtagRequestProp::make(),
+    }) else {
+        return Err(anyhow::anyhow!("compare_etags: Error serializing payload"));
+    };
+
+    // Send the list etags request
+    let Ok(response) = client
+        .request(method, collection_url)
+        .header("Depth", "1")
+        .basic_auth(username, password.as_ref())
+        .body(payload).send().await else {
+        return Err(anyhow::anyhow!("compare_etags: Error sending request"));
+    };
+
+    // Expect a 207 status code
+    if response.status() != 207 {
+        return Err(anyhow::anyhow!("compare_etags: Error status code: {}", response.status()));
+    }
+
+    // Read the response
+    let Ok(text) = response.text().await else {
+        return Err(anyhow::anyhow!("compare_etags: Error reading response"));
+    };
+
+    // Deserialize the response
+    let Ok(data) = parse_xml::<MultiStatus<EtagProp>>(&text) else {
+        return Err(anyhow::anyhow!("compare_etags: Error deserializing response"));
+    };
+
+    let mut changes = CaldavChanges {
+        sync_token,
+        ..Default::default()
+    };
+
+    let mut seen: HashSet<&str> = HashSet::new();
+    for response in data.response.iter() {
+        if same_path(&response.href, collection_path) {
+            continue;
+        }
+
+        let etag = response.propstat.iter()
+            .filter(|propstat| propstat.status.contains("200"))
+            .find_map(|propstat| propstat.prop.as_ref().and_then(|prop| prop.getetag.as_ref()));
+
+        let Some(etag) = etag else {
+            continue;
+        };
+
+        seen.insert(&response.href);
+        if known_etags.get(&response.href) != Some(etag) {
+            changes.changed.push(CaldavResource {
+                href: response.href.clone(),
+                etag: etag.clone(),
+            });
+        }
+    }
+
+    changes.deleted = known_etags.keys()
+        .filter(|href| !seen.contains(href.as_str()))
+        .cloned()
+        .collect::<Vec<String>>();
+
+    Ok(changes)
+}
+
+/**
+ * Get the ctag of a calendar, which changes whenever anything in the calendar changes. Returns
+ * None for servers which do not support ctags.
+ */
+async fn get_ctag(
+    client: &reqwest::Client,
+    collection_url: &str,
+    username: &str,
+    password: &Option<String>,
+) -> Result<Option<String>, anyhow::Error> {
+    let method = reqwest::Method::from_bytes(b"PROPFIND").unwrap();
+
+    // Serialize the payload
+    let Ok(payload) = to_xml_string(&Propfind {
+        d: "DAV:".to_string(),
+        cal: "urn:ietf:params:xml:ns:caldav".to_string(),
+        cs: "http://calendarserver.org/ns/".to_string(),
+        prop: CtagRequestProp::make(),
+    }) else {
+        return Err(anyhow::anyhow!("get_ctag: Error serializing payload"));
+    };
+
+    // Send the get ctag request
+    let Ok(response) = client
+        .request(method, collection_url)
+        .header("Depth", "0")
+        .basic_auth(username, password.as_ref())
+        .body(payload).send().await else {
+        return Err(anyhow::anyhow!("get_ctag: Error sending request"));
+    };
+
+    if response.status() != 207 {
+        return Ok(None);
+    }
+
+    // Read the response
+    let Ok(text) = response.text().await else {
+        return Err(anyhow::anyhow!("get_ctag: Error reading response"));
+    };
+
+    let Ok(data) = parse_xml::<MultiStatus<CtagProp>>(&text) else {
+        return Ok(None);
+    };
+
+    Ok(data.response.iter()
+        .flat_map(|response| response.propstat.iter())
+        .filter(|propstat| propstat.status.contains("200"))
+        .find_map(|propstat| propstat.prop.as_ref().and_then(|prop| prop.getctag.clone())))
+}
+
+/**
+ * Compare two hrefs, ignoring a trailing slash.
+ */
+fn same_path(href: &str, path: &str) -> bool {
+    href.trim_end_matches('/') == path.trim_end_matches('/')
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename = "d:propfind")]
 struct Propfind<T: Serialize> {
@@ -389,14 +967,18 @@ impl EventRequestProp {
                                     CompType::Prop(Prop { name: "CREATED".to_string() }),
                                     CompType::Prop(Prop { name: "LAST-MODIFIED".to_string() }),
                                     CompType::Prop(Prop { name: "SUMMARY".to_string() }),
+                                    CompType::Prop(Prop { name: "DESCRIPTION".to_string() }),
                                     CompType::Prop(Prop { name: "DTSTART".to_string() }),
                                     CompType::Prop(Prop { name: "DTEND".to_string() }),
                                     CompType::Prop(Prop { name: "ORGANIZER".to_string() }),
                                     CompType::Prop(Prop { name: "STATUS".to_string() }),
                                     CompType::Prop(Prop { name: "RECURRENCE-ID".to_string() }),
                                     CompType::Prop(Prop { name: "RRULE".to_string() }),
+                                    CompType::Prop(Prop { name: "RDATE".to_string() }),
+                                    CompType::Prop(Prop { name: "EXDATE".to_string() }),
                                     CompType::Prop(Prop { name: "LOCATION".to_string() }),
                                     CompType::Prop(Prop { name: "TRANSP".to_string() }),
+                                    CompType::Prop(Prop { name: "CLASS".to_string() }),
                                     CompType::Prop(Prop { name: "CATEGORIES".to_string() }),
                                     CompType::Prop(Prop { name: "ATTACH".to_string() }),
                                     CompType::Prop(Prop { name: "ATTENDEE".to_string() }),
@@ -410,6 +992,43 @@ impl EventRequestProp {
     }
 }
 
+impl EventRequestProp {
+    /**
+     * Request the properties of the VTODOs of a calendar.
+     */
+    fn make_tasks() -> Self {
+        EventRequestProp {
+            getetag: "".to_string(),
+            calendar_data: CalendarData {
+                comp: Comp {
+                    name: "VCALENDAR".to_string(),
+                    children: Some(vec![
+                        CompType::Prop(Prop { name: "VERSION".to_string() }),
+                        CompType::Comp(
+                            Comp {
+                                name: "VTODO".to_string(),
+                                children: Some(vec![
+                                    CompType::Prop(Prop { name: "UID".to_string() }),
+                                    CompType::Prop(Prop { name: "SUMMARY".to_string() }),
+                                    CompType::Prop(Prop { name: "DESCRIPTION".to_string() }),
+                                    CompType::Prop(Prop { name: "DTSTART".to_string() }),
+                                    CompType::Prop(Prop { name: "DUE".to_string() }),
+                                    CompType::Prop(Prop { name: "COMPLETED".to_string() }),
+                                    CompType::Prop(Prop { name: "PERCENT-COMPLETE".to_string() }),
+                                    CompType::Prop(Prop { name: "PRIORITY".to_string() }),
+                                    CompType::Prop(Prop { name: "STATUS".to_string() }),
+                                    CompType::Prop(Prop { name: "RECURRENCE-ID".to_string() }),
+                                    CompType::Prop(Prop { name: "RRULE".to_string() }),
+                                ])
+                            }
+                        )
+                    ]),
+                },
+            },
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename = "c:calendar-query")]
 struct CalendarQuery<T: Serialize> {
@@ -437,16 +1056,125 @@ struct CompFilter {
     comp_filter: Option<Box<CompFilter>>,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename = "c:calendar-multiget")]
+struct CalendarMultiget {
+    #[serde(rename = "@xmlns:c")]
+    xmlns_c: String,
+    #[serde(rename = "@xmlns:d")]
+    xmlns_d: String,
+    #[serde(rename = "d:prop")]
+    prop: EventRequestProp,
+    #[serde(rename = "d:href")]
+    href: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename = "d:sync-collection")]
+struct SyncCollection {
+    #[serde(rename = "@xmlns:d")]
+    xmlns_d: String,
+    #[serde(rename = "d:sync-token")]
+    sync_token: String,
+    #[serde(rename = "d:sync-level")]
+    sync_level: String,
+    #[serde(rename = "d:prop")]
+    prop: EtagRequestProp,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct EtagRequestProp {
+    #[serde(rename = "d:getetag")]
+    getetag: String,
+}
+
+impl EtagRequestProp {
+    fn make() -> Self {
+        EtagRequestProp {
+            getetag: "".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct CtagRequestProp {
+    #[serde(rename = "cs:getctag")]
+    getctag: String,
+}
+
+impl CtagRequestProp {
+    fn make() -> Self {
+        CtagRequestProp {
+            getctag: "".to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename = "multistatus")]
-struct MultiStatus<T: Serialize> {
-    response: Vec<PropfindResponse<T>>,
+struct SyncMultiStatus {
+    #[serde(default)]
+    response: Vec<SyncResponse>,
+    #[serde(rename = "sync-token")]
+    sync_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
-struct PropfindResponse<T: Serialize> {
+struct SyncResponse {
     href: String,
-    propstat: Vec<Propstat<T>>,
+    #[serde(default)]
+    propstat: Vec<Propstat<EtagProp>>,
+    status: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct EtagProp {
+    getetag: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct CtagProp {
+    getctag: Option<String>,
+}
+
+/**
+ * The outcome of a single sync-collection REPORT.
+ */
+#[derive(Debug)]
+enum SyncCollectionResult {
+    Page(SyncCollectionPage),
+    InvalidToken,
+    Rejected(StatusCode),
+}
+
+#[derive(Debug, Default)]
+struct SyncCollectionPage {
+    changed: Vec<CaldavResource>,
+    deleted: Vec<String>,
+    sync_token: Option<String>,
+    truncated: bool,
+}
+
+/**
+ * A multistatus response. The namespace declarations are only written when serving CalDAV,
+ * the elements without a prefix are in the DAV: namespace.
+ */
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename = "multistatus")]
+pub(crate) struct MultiStatus<T: Serialize> {
+    #[serde(rename = "@xmlns", default, skip_serializing_if = "Option::is_none")]
+    pub(crate) xmlns: Option<String>,
+    #[serde(rename = "@xmlns:C", default, skip_serializing_if = "Option::is_none")]
+    pub(crate) xmlns_caldav: Option<String>,
+    #[serde(rename = "@xmlns:CS", default, skip_serializing_if = "Option::is_none")]
+    pub(crate) xmlns_calendarserver: Option<String>,
+    pub(crate) response: Vec<PropfindResponse<T>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub(crate) struct PropfindResponse<T: Serialize> {
+    pub(crate) href: String,
+    pub(crate) propstat: Vec<Propstat<T>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -458,9 +1186,9 @@ struct EventResponse {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
-struct Propstat<T: Serialize> {
-    prop: Option<T>,
-    status: String,
+pub(crate) struct Propstat<T: Serialize> {
+    pub(crate) prop: Option<T>,
+    pub(crate) status: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -470,8 +1198,8 @@ struct CurrentPrincipleProp {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
-struct Href {
-    href: String,
+pub(crate) struct Href {
+    pub(crate) href: String,
 }
 
 #[derive(Debug)]
@@ -510,8 +1238,8 @@ struct CalendarResourceType {
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 struct CalendarComponent {
-    // #[serde(rename = "name")]
-    // name: String,
+    #[serde(rename = "@name", default)]
+    name: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -561,43 +1289,177 @@ impl CaldavCalendar {
             })
         }
     }
+
+    /**
+     * Map the calendar to the CalendarResult, identified by its path. Servers write the color
+     * as #RRGGBB or #RRGGBBAA, the alpha channel is left out.
+     */
+    pub fn to_calendar_result(&self) -> CalendarResult {
+        let background_color = match self.calendar_color.get(..7) {
+            Some(color) if color.starts_with('#') => color.to_lowercase(),
+            _ => "#9a9cff".to_string(),
+        };
+        CalendarResult {
+            external_id: self.path.clone(),
+            name: self.displayname.clone(),
+            foreground_color: foreground_color(&background_color),
+            background_color,
+        }
+    }
+
+    /**
+     * Whether the calendar can hold the given component type, such as VEVENT or VTODO. Servers
+     * which do not list the supported components accept every type.
+     */
+    pub fn supports_component(&self, name: &str) -> bool {
+        match &self.components.components {
+            Some(components) => components.iter()
+                .any(|component| component.name.as_deref().is_some_and(|value| value.eq_ignore_ascii_case(name))),
+            None => true,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct CaldavCalendarEvents {
+    pub href: String,
     pub etag: String,
     pub events: Vec<CaldavEvent>,
 }
 
+impl CaldavCalendarEvents {
+    /**
+     * Map the events of this calendar resource to EventResults. Every event in a resource
+     * shares the etag of the resource.
+     */
+    pub fn to_event_results(&self) -> Vec<EventResult> {
+        self.events.iter()
+            .filter_map(|event| event.to_event_result(&self.etag))
+            .collect::<Vec<EventResult>>()
+    }
+}
+
+/**
+ * The error types for writing to a CalDAV server. PreconditionFailed means the event was
+ * created or changed by someone else since it was last read.
+ */
+#[derive(Debug)]
+pub enum CaldavWriteError {
+    PreconditionFailed,
+    InvalidEvent(String),
+    NetworkError(reqwest::Error),
+    InvalidStatusError(StatusCode, String),
+}
+
+impl std::fmt::Display for CaldavWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PreconditionFailed => write!(f, "The event was changed on the server"),
+            Self::InvalidEvent(message) => write!(f, "{}", message),
+            Self::NetworkError(err) => write!(f, "Error sending request: {}", err),
+            Self::InvalidStatusError(status, body) => write!(f, "Error status code: {} {}", status, body),
+        }
+    }
+}
+
+impl std::error::Error for CaldavWriteError {}
+
+/**
+ * The resources of a calendar which changed since the last sync. A full sync lists every
+ * resource, in which case stored resources missing from the list were deleted.
+ */
+#[derive(Debug, Default)]
+pub struct CaldavChanges {
+    pub changed: Vec<CaldavResource>,
+    pub deleted: Vec<String>,
+    pub full: bool,
+    pub sync_token: Option<String>,
+}
+
+/**
+ * A calendar object resource, identified by its href.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaldavResource {
+    pub href: String,
+    pub etag: String,
+}
+
+/**
+ * The state of the last sync stored with a calendar. Servers supporting sync-collection hand
+ * out a sync token, other servers are tracked by the ctag of the calendar.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaldavSyncToken {
+    SyncToken(String),
+    Ctag(String),
+}
+
+impl CaldavSyncToken {
+    pub fn parse(value: &str) -> Self {
+        match value.strip_prefix("ctag:") {
+            Some(ctag) => Self::Ctag(ctag.to_string()),
+            None => Self::SyncToken(value.strip_prefix("sync:").unwrap_or(value).to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for CaldavSyncToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SyncToken(token) => write!(f, "sync:{}", token),
+            Self::Ctag(ctag) => write!(f, "ctag:{}", ctag),
+        }
+    }
+}
+
 /**
  * Represent the CalDAV event data structure
  */
 #[derive(Debug)]
 pub struct CaldavEvent {
     pub uid: String,
-    pub created: String,
+    pub created: Option<String>,
     pub last_modified: Option<String>,
-    pub summary: String,
-    pub dtstart: String,
-    pub dtend: String,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub dtstart: IcalDateTime,
+    pub dtend: Option<IcalDateTime>,
     pub status: Option<String>,
-    pub organizer: Option<String>,
-    pub recurrence_id: Option<String>,
+    pub organizer: Option<CaldavOrganizer>,
+    pub recurrence_id: Option<IcalDateTime>,
     pub rrule: Option<String>,
+    pub rdate: Vec<String>,
+    pub exdate: Vec<String>,
     pub location: Option<String>,
     pub transp: Option<String>,
+    pub class: Option<String>,
     pub categories: Option<String>,
     pub attach: Option<String>,
-    pub attendee: Option<String>,
+    pub attendees: Vec<CaldavAttendee>,
 }
 
 /**
  * Convert an ical.rs IcalEvent to a CaldavEvent
  */
 impl CaldavEvent {
-    fn from_ical_evel(event: IcalEvent) -> Self {
+    pub fn from_ical_evel(event: IcalEvent, time_zones: &TimeZones) -> Result<Self, IcalDateTimeError> {
+        // RDATE and EXDATE may appear more than once, they are kept as complete content lines
+        // so their TZID parameters survive. Every ATTENDEE is kept as well.
         let mut property_map: HashMap<String, Property> = HashMap::new();
+        let mut rdate = Vec::new();
+        let mut exdate = Vec::new();
+        let mut attendees = Vec::new();
         for property in event.properties {
+            match property.name.as_str() {
+                "RDATE" => rdate.push(content_line(&property)),
+                "EXDATE" => exdate.push(content_line(&property)),
+                "ATTENDEE" => match CaldavAttendee::from_property(&property) {
+                    Ok(attendee) => attendees.push(attendee),
+                    Err(err) => println!("Skipping attendee: {}", err),
+                },
+                _ => {},
+            }
             property_map.insert(property.name.clone(), property);
         }
 
@@ -613,22 +1475,126 @@ impl CaldavEvent {
             Some(value.clone())
         }
 
-        Self {
-            uid: get_value_safe(&property_map, "UID".to_string()).unwrap(),
-            created: get_value_safe(&property_map, "CREATED".to_string()).unwrap(),
-            summary: get_value_safe(&property_map, "SUMMARY".to_string()).unwrap(),
+        let get_datetime = |key: &str| property_map.get(key)
+            .map(|property| IcalDateTime::from_property(property, time_zones))
+            .transpose();
+
+        let Some(uid) = get_value_safe(&property_map, "UID".to_string()) else {
+            return Err(IcalDateTimeError::MissingValue("UID".to_string()));
+        };
+        let Some(dtstart) = get_datetime("DTSTART")? else {
+            return Err(IcalDateTimeError::MissingValue("DTSTART".to_string()));
+        };
+
+        Ok(Self {
+            uid,
+            created: get_value_safe(&property_map, "CREATED".to_string()),
+            summary: get_value_safe(&property_map, "SUMMARY".to_string()).as_deref().map(unescape_text),
+            description: get_value_safe(&property_map, "DESCRIPTION".to_string()).as_deref().map(unescape_text),
             last_modified: get_value_safe(&property_map, "LAST-MODIFIED".to_string()),
-            dtstart: get_value_safe(&property_map, "DTSTART".to_string()).unwrap(),
-            dtend: get_value_safe(&property_map, "DTEND".to_string()).unwrap(),
+            dtstart,
+            dtend: get_datetime("DTEND")?,
             status: get_value_safe(&property_map, "STATUS".to_string()),
-            organizer: get_value_safe(&property_map, "ORGANIZER".to_string()),
-            recurrence_id: get_value_safe(&property_map, "RECURRENCE-ID".to_string()),
+            organizer: property_map.get("ORGANIZER").and_then(|property| CaldavOrganizer::from_property(property).ok()),
+            recurrence_id: get_datetime("RECURRENCE-ID")?,
             rrule: get_value_safe(&property_map, "RRULE".to_string()),
-            location: get_value_safe(&property_map, "LOCATION".to_string()),
+            rdate,
+            exdate,
+            location: get_value_safe(&property_map, "LOCATION".to_string()).as_deref().map(unescape_text),
             transp: get_value_safe(&property_map, "TRANSP".to_string()),
+            class: get_value_safe(&property_map, "CLASS".to_string()),
             categories: get_value_safe(&property_map, "CATEGORIES".to_string()),
             attach: get_value_safe(&property_map, "ATTACH".to_string()),
-            attendee: get_value_safe(&property_map, "ATTENDEE".to_string()),
+            attendees,
+        })
+    }
+}
+
+impl CaldavEvent {
+    /**
+     * Map the event to the provider-neutral EventResult. Overridden instances of a recurring
+     * event share the UID of their series, so their external_id is suffixed with the
+     * RECURRENCE-ID the same way Google identifies instances.
+     */
+    pub fn to_event_result(&self, etag: &str) -> Option<EventResult> {
+        let starts_at = self.dtstart.naive_utc();
+        let all_day = self.dtstart.is_date();
+
+        // Without DTEND an all-day event lasts a day and any other event is instantaneous
+        let ends_at = match (&self.dtend, all_day) {
+            (Some(dtend), _) => dtend.naive_utc(),
+            (None, true) => starts_at + chrono::Duration::days(1),
+            (None, false) => starts_at,
+        };
+
+        let original_starts_at = self.recurrence_id.as_ref().map(|value| value.naive_utc());
+
+        let external_id = match &self.recurrence_id {
+            Some(recurrence_id) => format!("{}_{}", self.uid, recurrence_id),
+            None => self.uid.clone(),
+        };
+
+        Some(EventResult {
+            external_id,
+            etag: Some(etag.to_string()),
+            summary: self.summary.clone(),
+            description: self.description.clone(),
+            location: self.location.clone(),
+            starts_at,
+            ends_at,
+            all_day,
+            time_zone: self.dtstart.time_zone(),
+            status: match self.status.as_deref() {
+                Some("TENTATIVE") => EventStatus::Tentative,
+                Some("CANCELLED") => EventStatus::Cancelled,
+                _ => EventStatus::Confirmed,
+            },
+            transparency: match self.transp.as_deref() {
+                Some("TRANSPARENT") => EventTransparency::Transparent,
+                _ => EventTransparency::Opaque,
+            },
+            visibility: match self.class.as_deref() {
+                Some("PUBLIC") => EventVisibility::Public,
+                Some("PRIVATE") => EventVisibility::Private,
+                Some("CONFIDENTIAL") => EventVisibility::Confidential,
+                _ => EventVisibility::Default,
+            },
+            organizer: self.organizer.as_ref().map(CaldavOrganizer::to_participant),
+            attendees: Attendees(self.attendees.iter().map(CaldavAttendee::to_attendee).collect()),
+            recurrence: self.recurrence(),
+            recurring_event_id: self.recurrence_id.as_ref().map(|_| self.uid.clone()),
+            original_starts_at,
+        })
+    }
+}
+
+impl CaldavEvent {
+    /**
+     * The recurrence lines of the event in the format events are stored in, one property per
+     * line.
+     */
+    fn recurrence(&self) -> Option<String> {
+        let lines = self.rrule.iter()
+            .map(|rrule| format!("RRULE:{}", rrule))
+            .chain(self.rdate.iter().cloned())
+            .chain(self.exdate.iter().cloned())
+            .collect::<Vec<String>>();
+        match lines.is_empty() {
+            true => None,
+            false => Some(lines.join("\n")),
         }
     }
+}
+
+/**
+ * Rebuild the content line of a property along with its parameters.
+ */
+fn content_line(property: &Property) -> String {
+    let mut line = property.name.clone();
+    for (name, values) in property.params.iter().flatten() {
+        line.push_str(&format!(";{}={}", name, values.join(",")));
+    }
+    line.push(':');
+    line.push_str(property.value.as_deref().unwrap_or_default());
+    line
 }
\ No newline at end of file
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use ical::{parser::ical::component::IcalTimeZone, property::Property};
+
+use crate::{helper, recurrence::{self, Recurrence, RecurrenceDate}};
+
+/**
+ * The error types for reading iCalendar date and time values.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IcalDateTimeError {
+    MissingValue(String),
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for IcalDateTimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingValue(name) => write!(f, "The {} property has no value", name),
+            Self::InvalidValue(value) => write!(f, "Invalid date or date time: {}", value),
+        }
+    }
+}
+
+impl std::error::Error for IcalDateTimeError {}
+
+/**
+ * A DATE or DATE-TIME value of an iCalendar property. Floating times are not bound to any time
+ * zone, zoned times carry the offset their TZID had at that moment along with the TZID itself.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IcalDateTime {
+    Date(NaiveDate),
+    Floating(NaiveDateTime),
+    Utc(DateTime<Utc>),
+    Zoned(DateTime<FixedOffset>, String),
+}
+
+impl IcalDateTime {
+    /**
+     * Read the value of a DTSTART, DTEND, RECURRENCE-ID or similar property. A TZID parameter is
+     * resolved with the VTIMEZONE definitions of the calendar first, and with the IANA time zone
+     * database when the calendar does not define it. Times in an unknown time zone are floating.
+     */
+    pub fn from_property(property: &Property, time_zones: &TimeZones) -> Result<Self, IcalDateTimeError> {
+        let Some(value) = property.value.as_deref() else {
+            return Err(IcalDateTimeError::MissingValue(property.name.clone()));
+        };
+        let param = |name: &str| property.params.iter()
+            .flatten()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .and_then(|(_, values)| values.first())
+            .map(|value| value.trim_matches('"'));
+
+        match param("VALUE") {
+            Some(kind) if kind.eq_ignore_ascii_case("DATE") => Self::parse(value.get(..8).unwrap_or(value), None, time_zones),
+            _ => Self::parse(value, param("TZID"), time_zones),
+        }
+    }
+
+    /**
+     * Parse a DATE or DATE-TIME value. A trailing Z marks UTC, other date times are in the time
+     * zone of the TZID or floating without one.
+     */
+    pub fn parse(value: &str, tzid: Option<&str>, time_zones: &TimeZones) -> Result<Self, IcalDateTimeError> {
+        let value = value.trim();
+        let invalid = || IcalDateTimeError::InvalidValue(value.to_string());
+
+        if value.len() == 8 {
+            return NaiveDate::parse_from_str(value, "%Y%m%d").map(Self::Date).map_err(|_| invalid());
+        }
+        if let Some(utc) = value.strip_suffix('Z') {
+            return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+                .map(|utc| Self::Utc(utc.and_utc()))
+                .map_err(|_| invalid());
+        }
+
+        let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        let Some(tzid) = tzid else {
+            return Ok(Self::Floating(local));
+        };
+        match time_zones.resolve(tzid, local) {
+            Some(value) => Ok(Self::Zoned(value, tzid.to_string())),
+            None => {
+                println!("Unknown time zone {}, reading {} as floating time", tzid, value);
+                Ok(Self::Floating(local))
+            },
+        }
+    }
+
+    /**
+     * The moment in UTC. Dates start at midnight and floating times are read as UTC.
+     */
+    pub fn naive_utc(&self) -> NaiveDateTime {
+        match self {
+            Self::Date(date) => date.and_time(chrono::NaiveTime::MIN),
+            Self::Floating(local) => *local,
+            Self::Utc(value) => value.naive_utc(),
+            Self::Zoned(value, _) => value.naive_utc(),
+        }
+    }
+
+    /**
+     * Whether the value is a DATE, which is how iCalendar marks all-day events.
+     */
+    pub fn is_date(&self) -> bool {
+        matches!(self, Self::Date(_))
+    }
+
+    /**
+     * The IANA name of the time zone of the value, if it has one. See iana_time_zone for the
+     * TZIDs which are understood.
+     */
+    pub fn time_zone(&self) -> Option<String> {
+        match self {
+            Self::Zoned(_, tzid) => iana_time_zone(tzid).map(|time_zone| time_zone.name().to_string()),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Format the value as DATE, floating DATE-TIME or UTC DATE-TIME. Zoned times are converted to
+ * UTC.
+ */
+impl std::fmt::Display for IcalDateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Date(date) => write!(f, "{}", date.format("%Y%m%d")),
+            Self::Floating(local) => write!(f, "{}", local.format("%Y%m%dT%H%M%S")),
+            _ => write!(f, "{}", self.naive_utc().format("%Y%m%dT%H%M%SZ")),
+        }
+    }
+}
+
+/**
+ * The VTIMEZONE definitions of a calendar by their TZID.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TimeZones(HashMap<String, TimeZoneDefinition>);
+
+impl TimeZones {
+    /**
+     * Read the VTIMEZONE components of a calendar. Definitions which cannot be read are left
+     * out, their TZID is looked up in the IANA time zone database instead.
+     */
+    pub fn from_calendar(time_zones: &[IcalTimeZone]) -> Self {
+        let mut definitions = HashMap::new();
+        for time_zone in time_zones {
+            let Some(tzid) = time_zone.properties.iter()
+                .find(|property| property.name == "TZID")
+                .and_then(|property| property.value.clone()) else {
+                continue;
+            };
+            match TimeZoneDefinition::from_ical(time_zone) {
+                Some(definition) => {
+                    definitions.insert(tzid, definition);
+                },
+                None => println!("Ignoring the invalid definition of time zone {}", tzid),
+            }
+        }
+        Self(definitions)
+    }
+
+    /**
+     * Find the moment a local time of the time zone stands for, with the offset in effect at
+     * that moment.
+     */
+    fn resolve(&self, tzid: &str, local: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self.0.get(tzid) {
+            Some(definition) => {
+                let offset = definition.offset_at_local(local)?;
+                let utc = local - Duration::seconds(offset.local_minus_utc().into());
+                Some(definition.offset_at_utc(utc).from_utc_datetime(&utc))
+            },
+            None => {
+                let time_zone = iana_time_zone(tzid)?;
+                let utc = recurrence::local_to_utc(&time_zone, local);
+                Some(time_zone.from_utc_datetime(&utc).fixed_offset())
+            },
+        }
+    }
+}
+
+/**
+ * A VTIMEZONE definition, made of its STANDARD and DAYLIGHT observances.
+ */
+#[derive(Debug, Clone)]
+struct TimeZoneDefinition {
+    observances: Vec<Observance>,
+}
+
+/**
+ * An observance of a time zone. It starts at local time start, expressed in the offset before
+ * it, and repeats with its RRULE and RDATEs.
+ */
+#[derive(Debug, Clone)]
+struct Observance {
+    start: NaiveDateTime,
+    offset_from: FixedOffset,
+    offset_to: FixedOffset,
+    recurrence: Recurrence,
+}
+
+impl TimeZoneDefinition {
+    fn from_ical(time_zone: &IcalTimeZone) -> Option<Self> {
+        let observances = time_zone.transitions.iter()
+            .map(|transition| {
+                let value = |name: &str| transition.properties.iter()
+                    .find(|property| property.name == name)
+                    .and_then(|property| property.value.as_deref());
+                let lines = transition.properties.iter()
+                    .filter(|property| property.name == "RRULE" || property.name == "RDATE")
+                    .map(|property| format!("{}:{}", property.name, property.value.as_deref().unwrap_or_default()))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                let offset_from = parse_offset(value("TZOFFSETFROM")?)?;
+                let mut recurrence = Recurrence::parse(&lines).ok()?;
+
+                // Observances are expanded in local time, while UNTIL is given in UTC
+                for rule in recurrence.rules.iter_mut() {
+                    if let Some(RecurrenceDate::Utc(until)) = rule.until {
+                        let local = until + Duration::seconds(offset_from.local_minus_utc().into());
+                        rule.until = Some(RecurrenceDate::Local(local, None));
+                    }
+                }
+
+                Some(Observance {
+                    start: NaiveDateTime::parse_from_str(value("DTSTART")?, "%Y%m%dT%H%M%S").ok()?,
+                    offset_from,
+                    offset_to: parse_offset(value("TZOFFSETTO")?)?,
+                    recurrence,
+                })
+            })
+            .collect::<Option<Vec<Observance>>>()?;
+
+        match observances.is_empty() {
+            true => None,
+            false => Some(Self { observances }),
+        }
+    }
+
+    /**
+     * The offset in effect at a moment in UTC: the offset of the observance which started last.
+     * Moments before the first observance use the offset it started from.
+     */
+    fn offset_at_utc(&self, utc: NaiveDateTime) -> FixedOffset {
+        self.observances.iter()
+            .filter_map(|observance| observance.last_onset(utc).map(|onset| (onset, observance.offset_to)))
+            .max_by_key(|(onset, _)| *onset)
+            .map(|(_, offset)| offset)
+            .unwrap_or_else(|| {
+                let first = self.observances.iter().min_by_key(|observance| observance.start).unwrap();
+                first.offset_from
+            })
+    }
+
+    /**
+     * The offset of a local time. Ambiguous times take their first occurrence, times skipped
+     * by a change use the offset from before the change, like local_to_utc does.
+     */
+    fn offset_at_local(&self, local: NaiveDateTime) -> Option<FixedOffset> {
+        let mut offsets = self.observances.iter()
+            .map(|observance| observance.offset_to)
+            .collect::<Vec<FixedOffset>>();
+        offsets.sort_by_key(|offset| std::cmp::Reverse(offset.local_minus_utc()));
+        offsets.dedup();
+
+        offsets.iter()
+            .find(|offset| self.offset_at_utc(local - Duration::seconds(offset.local_minus_utc().into())) == **offset)
+            .copied()
+            .or_else(|| {
+                let largest = offsets.first()?;
+                Some(self.offset_at_utc(local - Duration::seconds(largest.local_minus_utc().into())))
+            })
+    }
+}
+
+impl Observance {
+    /**
+     * The last moment in UTC at or before utc at which the observance started.
+     */
+    fn last_onset(&self, utc: NaiveDateTime) -> Option<NaiveDateTime> {
+        let offset = Duration::seconds(self.offset_from.local_minus_utc().into());
+        let end = utc + offset + Duration::seconds(1);
+        if self.start >= end {
+            return None;
+        }
+
+        let mut onsets = self.recurrence.occurrences(self.start, Duration::zero(), None, false, self.start - Duration::seconds(1), end);
+        if !onsets.contains(&self.start) {
+            onsets.push(self.start);
+        }
+        onsets.into_iter().max().map(|onset| onset - offset)
+    }
+}
+
+/**
+ * Find the IANA time zone a TZID stands for. Besides IANA names this understands the Windows
+ * names Outlook and Exchange write, and the prefixed names of Mozilla based clients such as
+ * /mozilla.org/20050126_1/Europe/Berlin.
+ */
+pub fn iana_time_zone(tzid: &str) -> Option<Tz> {
+    let tzid = match tzid.strip_prefix('/') {
+        Some(prefixed) => prefixed.splitn(3, '/').nth(2).unwrap_or(prefixed),
+        None => tzid,
+    };
+    helper::parse_time_zone(tzid)
+}
+
+/**
+ * Parse a UTC offset such as +0100 or -023000.
+ */
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    let (sign, digits) = match (value.strip_prefix('+'), value.strip_prefix('-')) {
+        (Some(digits), _) => (1, digits),
+        (_, Some(digits)) => (-1, digits),
+        _ => return None,
+    };
+    if !(digits.len() == 4 || digits.len() == 6) || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let hours = digits[0..2].parse::<i32>().ok()?;
+    let minutes = digits[2..4].parse::<i32>().ok()?;
+    let seconds = digits.get(4..6).map_or(Some(0), |seconds| seconds.parse::<i32>().ok())?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60 + seconds))
+}
@@ -3,4 +3,7 @@ pub enum CaldavService {
     Apple,
 }
 
-pub mod caldav;
\ No newline at end of file
+pub mod caldav;
+pub mod datetime;
+pub mod participant;
+pub mod task;
\ No newline at end of file
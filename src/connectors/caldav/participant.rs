@@ -0,0 +1,253 @@
+use ical::property::Property;
+
+use crate::models::event::{Attendee, AttendeeStatus, Participant, ParticipantKind, ParticipantRole};
+
+/**
+ * The error types for reading ATTENDEE and ORGANIZER properties.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParticipantError {
+    MissingAddress(String),
+}
+
+impl std::fmt::Display for ParticipantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingAddress(name) => write!(f, "The {} property has no address", name),
+        }
+    }
+}
+
+impl std::error::Error for ParticipantError {}
+
+/**
+ * The PARTSTAT of an attendee. Values this module does not know, such as experimental ones,
+ * are kept as they were written.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParticipationStatus {
+    NeedsAction,
+    Accepted,
+    Declined,
+    Tentative,
+    Delegated,
+    Completed,
+    InProcess,
+    Other(String),
+}
+
+impl ParticipationStatus {
+    fn parse(value: &str) -> Self {
+        match value.to_ascii_uppercase().as_str() {
+            "NEEDS-ACTION" => Self::NeedsAction,
+            "ACCEPTED" => Self::Accepted,
+            "DECLINED" => Self::Declined,
+            "TENTATIVE" => Self::Tentative,
+            "DELEGATED" => Self::Delegated,
+            "COMPLETED" => Self::Completed,
+            "IN-PROCESS" => Self::InProcess,
+            _ => Self::Other(value.to_string()),
+        }
+    }
+}
+
+/**
+ * The ROLE of an attendee.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttendeeRole {
+    Chair,
+    RequiredParticipant,
+    OptionalParticipant,
+    NonParticipant,
+    Other(String),
+}
+
+impl AttendeeRole {
+    fn parse(value: &str) -> Self {
+        match value.to_ascii_uppercase().as_str() {
+            "CHAIR" => Self::Chair,
+            "REQ-PARTICIPANT" => Self::RequiredParticipant,
+            "OPT-PARTICIPANT" => Self::OptionalParticipant,
+            "NON-PARTICIPANT" => Self::NonParticipant,
+            _ => Self::Other(value.to_string()),
+        }
+    }
+}
+
+/**
+ * The CUTYPE of an attendee, whether it is a person, a room or something else.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalendarUserType {
+    Individual,
+    Group,
+    Resource,
+    Room,
+    Unknown,
+    Other(String),
+}
+
+impl CalendarUserType {
+    fn parse(value: &str) -> Self {
+        match value.to_ascii_uppercase().as_str() {
+            "INDIVIDUAL" => Self::Individual,
+            "GROUP" => Self::Group,
+            "RESOURCE" => Self::Resource,
+            "ROOM" => Self::Room,
+            "UNKNOWN" => Self::Unknown,
+            _ => Self::Other(value.to_string()),
+        }
+    }
+}
+
+/**
+ * An ATTENDEE property. Parameters which are missing take the defaults of RFC 5545, so an
+ * attendee without a PARTSTAT has not responded yet. DELEGATED-TO and DELEGATED-FROM list the
+ * addresses the invitation was passed on to or received from.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaldavAttendee {
+    pub address: String,
+    pub name: Option<String>,
+    pub status: ParticipationStatus,
+    pub role: AttendeeRole,
+    pub rsvp: bool,
+    pub user_type: CalendarUserType,
+    pub delegated_to: Vec<String>,
+    pub delegated_from: Vec<String>,
+    pub sent_by: Option<String>,
+}
+
+impl CaldavAttendee {
+    pub fn from_property(property: &Property) -> Result<Self, ParticipantError> {
+        let Some(address) = property.value.clone() else {
+            return Err(ParticipantError::MissingAddress(property.name.clone()));
+        };
+
+        Ok(Self {
+            address,
+            name: param(property, "CN"),
+            status: param(property, "PARTSTAT")
+                .map(|value| ParticipationStatus::parse(&value))
+                .unwrap_or(ParticipationStatus::NeedsAction),
+            role: param(property, "ROLE")
+                .map(|value| AttendeeRole::parse(&value))
+                .unwrap_or(AttendeeRole::RequiredParticipant),
+            rsvp: param(property, "RSVP").is_some_and(|value| value.eq_ignore_ascii_case("TRUE")),
+            user_type: param(property, "CUTYPE")
+                .map(|value| CalendarUserType::parse(&value))
+                .unwrap_or(CalendarUserType::Individual),
+            delegated_to: param_list(property, "DELEGATED-TO"),
+            delegated_from: param_list(property, "DELEGATED-FROM"),
+            sent_by: param(property, "SENT-BY"),
+        })
+    }
+
+    /**
+     * The attendee as it is stored with an event. Statuses events do not know about are still
+     * waiting for a response, and attendees who do not take part are optional. Unknown roles
+     * are read as required and unknown types as unknown, as RFC 5545 asks.
+     */
+    pub fn to_attendee(&self) -> Attendee {
+        let role = match self.role {
+            AttendeeRole::Chair => ParticipantRole::Chair,
+            AttendeeRole::OptionalParticipant => ParticipantRole::Optional,
+            AttendeeRole::NonParticipant => ParticipantRole::NonParticipant,
+            _ => ParticipantRole::Required,
+        };
+        Attendee {
+            email: strip_mailto(&self.address),
+            name: self.name.clone(),
+            status: match self.status {
+                ParticipationStatus::Accepted => AttendeeStatus::Accepted,
+                ParticipationStatus::Declined => AttendeeStatus::Declined,
+                ParticipationStatus::Tentative => AttendeeStatus::Tentative,
+                _ => AttendeeStatus::NeedsAction,
+            },
+            optional: matches!(role, ParticipantRole::Optional | ParticipantRole::NonParticipant),
+            role,
+            rsvp: self.rsvp,
+            kind: match self.user_type {
+                CalendarUserType::Individual => ParticipantKind::Individual,
+                CalendarUserType::Group => ParticipantKind::Group,
+                CalendarUserType::Resource => ParticipantKind::Resource,
+                CalendarUserType::Room => ParticipantKind::Room,
+                _ => ParticipantKind::Unknown,
+            },
+            delegated_to: self.delegated_to.iter().map(|address| strip_mailto(address)).collect(),
+            delegated_from: self.delegated_from.iter().map(|address| strip_mailto(address)).collect(),
+            sent_by: self.sent_by.as_deref().map(strip_mailto),
+        }
+    }
+}
+
+/**
+ * An ORGANIZER property. SENT-BY is set when someone else scheduled the event on behalf of the
+ * organizer.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaldavOrganizer {
+    pub address: String,
+    pub name: Option<String>,
+    pub sent_by: Option<String>,
+}
+
+impl CaldavOrganizer {
+    pub fn from_property(property: &Property) -> Result<Self, ParticipantError> {
+        let Some(address) = property.value.clone() else {
+            return Err(ParticipantError::MissingAddress(property.name.clone()));
+        };
+
+        Ok(Self {
+            address,
+            name: param(property, "CN"),
+            sent_by: param(property, "SENT-BY"),
+        })
+    }
+
+    pub fn to_participant(&self) -> Participant {
+        Participant {
+            email: strip_mailto(&self.address),
+            name: self.name.clone(),
+            sent_by: self.sent_by.as_deref().map(strip_mailto),
+        }
+    }
+}
+
+/**
+ * The value of a parameter of the property, without the quotes around it. Quoted values may
+ * contain commas, which the parser reads as separate values, so they are joined again.
+ */
+fn param(property: &Property, name: &str) -> Option<String> {
+    property.params.iter()
+        .flatten()
+        .find(|(param, _)| param.eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.join(","))
+        .map(|value| value.trim_matches('"').to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/**
+ * The values of a parameter which holds a list of addresses, such as DELEGATED-TO, each
+ * without its quotes.
+ */
+fn param_list(property: &Property, name: &str) -> Vec<String> {
+    property.params.iter()
+        .flatten()
+        .filter(|(param, _)| param.eq_ignore_ascii_case(name))
+        .flat_map(|(_, values)| values.iter())
+        .map(|value| value.trim_matches('"').to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/**
+ * Remove the mailto: scheme from a CAL-ADDRESS value.
+ */
+fn strip_mailto(value: &str) -> String {
+    match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => value[7..].to_string(),
+        _ => value.to_string(),
+    }
+}
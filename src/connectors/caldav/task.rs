@@ -0,0 +1,142 @@
+use std::{collections::HashMap, io::{BufReader, Cursor}};
+
+use ical::{parser::ical::component::IcalTodo, property::Property};
+
+use crate::{ics::unescape_text, models::task::{TaskResult, TaskStatus}};
+
+use super::datetime::{IcalDateTime, IcalDateTimeError, TimeZones};
+
+/**
+ * A VTODO as it was read from a CalDAV server.
+ */
+#[derive(Debug)]
+pub struct CaldavTask {
+    pub uid: String,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub dtstart: Option<IcalDateTime>,
+    pub due: Option<IcalDateTime>,
+    pub completed: Option<IcalDateTime>,
+    pub percent_complete: Option<u8>,
+    pub priority: Option<u8>,
+    pub status: Option<String>,
+    pub recurrence_id: Option<IcalDateTime>,
+    pub rrule: Option<String>,
+}
+
+impl CaldavTask {
+    pub fn from_ical_todo(todo: IcalTodo, time_zones: &TimeZones) -> Result<Self, IcalDateTimeError> {
+        let property_map = todo.properties.into_iter()
+            .map(|property| (property.name.clone(), property))
+            .collect::<HashMap<String, Property>>();
+
+        let get_value = |key: &str| property_map.get(key).and_then(|property| property.value.clone());
+        let get_datetime = |key: &str| property_map.get(key)
+            .map(|property| IcalDateTime::from_property(property, time_zones))
+            .transpose();
+
+        let Some(uid) = get_value("UID") else {
+            return Err(IcalDateTimeError::MissingValue("UID".to_string()));
+        };
+
+        Ok(Self {
+            uid,
+            summary: get_value("SUMMARY").as_deref().map(unescape_text),
+            description: get_value("DESCRIPTION").as_deref().map(unescape_text),
+            dtstart: get_datetime("DTSTART")?,
+            due: get_datetime("DUE")?,
+            completed: get_datetime("COMPLETED")?,
+            // PERCENT-COMPLETE runs from 0 to 100, a PRIORITY of 0 leaves the priority undefined
+            percent_complete: get_value("PERCENT-COMPLETE")
+                .and_then(|value| value.trim().parse::<u8>().ok())
+                .filter(|value| *value <= 100),
+            priority: get_value("PRIORITY")
+                .and_then(|value| value.trim().parse::<u8>().ok())
+                .filter(|value| (1..=9).contains(value)),
+            status: get_value("STATUS"),
+            recurrence_id: get_datetime("RECURRENCE-ID")?,
+            rrule: get_value("RRULE"),
+        })
+    }
+
+    /**
+     * Map the task to the provider-neutral TaskResult. Changed occurrences of a recurring task
+     * are identified in the same way as those of events. A task with a COMPLETED time but no
+     * STATUS is completed.
+     */
+    pub fn to_task_result(&self, etag: &str) -> TaskResult {
+        let external_id = match &self.recurrence_id {
+            Some(recurrence_id) => format!("{}_{}", self.uid, recurrence_id),
+            None => self.uid.clone(),
+        };
+
+        TaskResult {
+            external_id,
+            etag: Some(etag.to_string()),
+            summary: self.summary.clone(),
+            description: self.description.clone(),
+            starts_at: self.dtstart.as_ref().map(IcalDateTime::naive_utc),
+            due_at: self.due.as_ref().map(IcalDateTime::naive_utc),
+            all_day: self.due.as_ref().or(self.dtstart.as_ref()).is_some_and(IcalDateTime::is_date),
+            completed_at: self.completed.as_ref().map(IcalDateTime::naive_utc),
+            percent_complete: self.percent_complete,
+            priority: self.priority,
+            status: match self.status.as_deref() {
+                Some("IN-PROCESS") => TaskStatus::InProcess,
+                Some("COMPLETED") => TaskStatus::Completed,
+                Some("CANCELLED") => TaskStatus::Cancelled,
+                Some(_) => TaskStatus::NeedsAction,
+                None if self.completed.is_some() => TaskStatus::Completed,
+                None => TaskStatus::NeedsAction,
+            },
+            recurrence: self.rrule.as_ref().map(|rrule| format!("RRULE:{}", rrule)),
+            recurring_task_id: self.recurrence_id.as_ref().map(|_| self.uid.clone()),
+            original_starts_at: self.recurrence_id.as_ref().map(IcalDateTime::naive_utc),
+        }
+    }
+}
+
+/**
+ * The tasks of a single calendar object resource.
+ */
+#[derive(Debug)]
+pub struct CaldavCalendarTasks {
+    pub href: String,
+    pub etag: String,
+    pub tasks: Vec<CaldavTask>,
+}
+
+impl CaldavCalendarTasks {
+    /**
+     * Map the tasks of this resource to TaskResults, sharing the etag of the resource.
+     */
+    pub fn to_task_results(&self) -> Vec<TaskResult> {
+        self.tasks.iter()
+            .map(|task| task.to_task_result(&self.etag))
+            .collect()
+    }
+}
+
+/**
+ * Parse the VTODOs of iCalendar data. Tasks which cannot be read are left out.
+ */
+pub fn parse_tasks(calendar_data: &str) -> Vec<CaldavTask> {
+    let reader = ical::IcalParser::new(BufReader::new(Cursor::new(calendar_data.as_bytes())));
+
+    let mut tasks = Vec::new();
+    for calendar in reader {
+        let Ok(calendar) = calendar else {
+            println!("Error parsing calendar data");
+            continue;
+        };
+        let time_zones = TimeZones::from_calendar(&calendar.timezones);
+        for todo in calendar.todos {
+            match CaldavTask::from_ical_todo(todo, &time_zones) {
+                Ok(task) => tasks.push(task),
+                Err(err) => println!("Skipping task: {}", err),
+            }
+        }
+    }
+
+    tasks
+}
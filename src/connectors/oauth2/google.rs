@@ -1,11 +1,13 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
 use reqwest::StatusCode;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{config::Oauth2Config, models::{calendar::CalendarResult, oauth_integration::OauthIntegration}};
+use crate::{config::Oauth2Config, models::{calendar::{Calendar, CalendarResult}, event::{Attendee, AttendeeStatus, Attendees, EventChanges, EventResult, EventStatus, EventTransparency, EventVisibility, Participant, ParticipantKind, ParticipantRole}, oauth_integration::OauthIntegration, watch_channel::{WatchChannel, WatchChannelResult}}};
 
-use super::{Oauth2ConnectorError, Oauth2ServiceConnector};
+use super::{Oauth2ConnectorError, Oauth2ServiceConnector, SendUpdates};
 
 pub struct GoogleConnector {
     pub client: reqwest::Client,
@@ -76,15 +78,186 @@ impl Oauth2ServiceConnector for GoogleConnector {
         }
 
         // Map the calendars to the CalendarResult struct
-        Ok(items.into_iter().map(|e| CalendarResult {
-            external_id: e.id.clone(),
-            name: e.summary.clone(),
-            background_color: e.backgroundColor.clone(),
-            foreground_color: e.foregroundColor.clone(),
-        }).collect::<Vec<CalendarResult>>())
+        Ok(items.into_iter()
+            .map(GoogleCalendarResult::into_calendar_result)
+            .collect::<Vec<CalendarResult>>())
        
     }
 
+    async fn get_events(&self, integration: &OauthIntegration, calendar: &Calendar) -> Result<EventChanges, Oauth2ConnectorError> {
+
+        let mut sync_token = calendar.sync_token.clone();
+        let mut page_token: Option<String> = None;
+        let mut items: Vec<GoogleEvent> = Vec::new();
+
+        // Loop through the events and get all changed events by page
+        let next_sync_token = loop {
+            let result = match self.get_event_page(&calendar.external_id, &sync_token, page_token, integration).await {
+                Ok(result) => result,
+                Err(Oauth2ConnectorError::InvalidStatusError(StatusCode::GONE, _)) if sync_token.is_some() => {
+                    // The sync token expired, start over with a full sync
+                    sync_token = None;
+                    page_token = None;
+                    items.clear();
+                    continue;
+                },
+                Err(err) => {
+                    return Err(err);
+                }
+            };
+            page_token = result.nextPageToken;
+            items.extend(result.items);
+            if page_token.is_none() { break result.nextSyncToken; }
+        };
+
+        // Cancelled events are deleted, unless they are a cancelled instance of a recurring event
+        let mut changes = EventChanges {
+            full: sync_token.is_none(),
+            sync_token: next_sync_token,
+            ..Default::default()
+        };
+        for item in items {
+            if item.status.as_deref() == Some("cancelled") && item.recurringEventId.is_none() {
+                changes.deleted.push(item.id);
+                continue;
+            }
+            let id = item.id.clone();
+            match item.into_event_result() {
+                Some(event) => changes.events.push(event),
+                None => println!("Skipping Google event {} with an invalid start or end", id),
+            }
+        }
+
+        Ok(changes)
+    }
+
+    async fn watch_events(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        token: &str,
+        address: &str,
+    ) -> Result<WatchChannelResult, Oauth2ConnectorError> {
+
+        let url = self.config.api_url(&["calendars", &calendar.external_id, "events", "watch"]);
+
+        let response = self.client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .json(&serde_json::json!({
+                "id": uuid::Uuid::new_v4().to_string(),
+                "type": "web_hook",
+                "address": address,
+                "token": token,
+            }))
+            .send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        if response.status() != StatusCode::OK {
+            return Err(Oauth2ConnectorError::InvalidStatusError(
+                response.status(),
+                response.text().await.unwrap_or("".to_string()),
+            ))
+        }
+
+        let channel = response.json::<GoogleChannel>().await
+            .map_err(Oauth2ConnectorError::ParseResultError)?;
+
+        // The expiration is a unix timestamp in milliseconds, channels last a week by default
+        let expires_at = channel.expiration
+            .and_then(|expiration| expiration.parse::<i64>().ok())
+            .and_then(DateTime::from_timestamp_millis)
+            .map(|expiration| expiration.naive_utc())
+            .unwrap_or_else(|| chrono::Utc::now().naive_utc() + chrono::Duration::days(7));
+
+        Ok(WatchChannelResult {
+            external_id: channel.id,
+            resource_id: Some(channel.resourceId),
+            expires_at,
+        })
+    }
+
+    async fn renew_channel(&self, _integration: &OauthIntegration, _channel: &WatchChannel) -> Result<Option<NaiveDateTime>, Oauth2ConnectorError> {
+        // Google channels cannot be extended
+        Ok(None)
+    }
+
+    async fn stop_channel(&self, integration: &OauthIntegration, channel: &WatchChannel) -> Result<(), Oauth2ConnectorError> {
+        let response = self.client
+            .post(self.config.api_url(&["channels", "stop"]))
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .json(&serde_json::json!({
+                "id": channel.external_id,
+                "resourceId": channel.resource_id,
+            }))
+            .send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        if !response.status().is_success() {
+            return Err(Oauth2ConnectorError::InvalidStatusError(
+                response.status(),
+                response.text().await.unwrap_or("".to_string()),
+            ))
+        }
+
+        Ok(())
+    }
+
+    async fn insert_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        event: &EventResult,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError> {
+        let url = self.event_url(&calendar.external_id, None);
+        let request = self.client.post(url);
+        self.send_event_request(request, integration, event, None, send_updates).await
+    }
+
+    async fn update_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        event: &EventResult,
+        etag: Option<&str>,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError> {
+        let url = self.event_url(&calendar.external_id, Some(&event.external_id));
+        let request = self.client.patch(url);
+        self.send_event_request(request, integration, event, etag, send_updates).await
+    }
+
+    async fn delete_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        external_id: &str,
+        etag: Option<&str>,
+        send_updates: SendUpdates,
+    ) -> Result<(), Oauth2ConnectorError> {
+        let mut request = self.client
+            .delete(self.event_url(&calendar.external_id, Some(external_id)))
+            .query(&[("sendUpdates", send_updates_param(send_updates))])
+            .header("Authorization", format!("Bearer {}", integration.access_token));
+
+        if let Some(etag) = etag {
+            request = request.header("If-Match", etag);
+        }
+
+        let response = request.send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        match response.status() {
+            StatusCode::OK | StatusCode::NO_CONTENT | StatusCode::NOT_FOUND | StatusCode::GONE => Ok(()),
+            StatusCode::PRECONDITION_FAILED => Err(Oauth2ConnectorError::PreconditionFailed),
+            status => Err(Oauth2ConnectorError::InvalidStatusError(
+                status,
+                response.text().await.unwrap_or("".to_string()),
+            )),
+        }
+    }
+
 }
 
 impl GoogleConnector {
@@ -107,7 +280,7 @@ impl GoogleConnector {
         
         // Get the list of calendars from the API
         let response = self.client
-            .get("https://www.googleapis.com/calendar/v3/users/me/calendarList");
+            .get(self.config.api_url(&["users", "me", "calendarList"]));
 
         let response = match sync_token {
             Some(token) => {
@@ -144,6 +317,347 @@ impl GoogleConnector {
             }
         }
     }
+
+    /**
+     * Get a page of the events of a calendar. Without a sync token every event is returned,
+     * with a sync token only the events which changed since the token was issued.
+     */
+    async fn get_event_page(
+        &self,
+        calendar_id: &str,
+        sync_token: &Option<String>,
+        page_token: Option<String>,
+        integration: &OauthIntegration,
+    ) -> Result<EventListResponse, Oauth2ConnectorError> {
+
+        let url = self.config.api_url(&["calendars", calendar_id, "events"]);
+
+        let mut query = vec![("maxResults", String::from("2500"))];
+        if let Some(token) = sync_token {
+            query.push(("syncToken", token.clone()));
+        }
+        if let Some(token) = page_token {
+            query.push(("pageToken", token));
+        }
+
+        let response = self.client
+            .get(url)
+            .query(&query)
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .send().await;
+
+        // Guard to get the response
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                return Err(Oauth2ConnectorError::NetworkError(err));
+            }
+        };
+
+        // Ensure the status code is OK
+        if response.status() != StatusCode::OK {
+            return Err(Oauth2ConnectorError::InvalidStatusError(
+                response.status(),
+                response.text().await.unwrap_or("".to_string()),
+            ))
+        }
+
+        // Guard to get the JSON response
+        match response.json::<EventListResponse>().await {
+            Ok(result) => Ok(result),
+            Err(err) => Err(Oauth2ConnectorError::ParseResultError(err)),
+        }
+    }
+
+    /**
+     * Build the URL of the events of a calendar, or of a single event when an id is given.
+     */
+    fn event_url(&self, calendar_id: &str, event_id: Option<&str>) -> reqwest::Url {
+        match event_id {
+            Some(event_id) => self.config.api_url(&["calendars", calendar_id, "events", event_id]),
+            None => self.config.api_url(&["calendars", calendar_id, "events"]),
+        }
+    }
+
+    /**
+     * Send the event to Google and map the stored event back to an EventResult.
+     */
+    async fn send_event_request(
+        &self,
+        request: reqwest::RequestBuilder,
+        integration: &OauthIntegration,
+        event: &EventResult,
+        etag: Option<&str>,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError> {
+        let mut request = request
+            .query(&[("sendUpdates", send_updates_param(send_updates))])
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .json(&GoogleEventRequest::from_event(event));
+
+        if let Some(etag) = etag {
+            request = request.header("If-Match", etag);
+        }
+
+        let response = request.send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        match response.status() {
+            StatusCode::OK => {},
+            StatusCode::PRECONDITION_FAILED => return Err(Oauth2ConnectorError::PreconditionFailed),
+            status => return Err(Oauth2ConnectorError::InvalidStatusError(
+                status,
+                response.text().await.unwrap_or("".to_string()),
+            )),
+        }
+
+        let event = response.json::<GoogleEvent>().await
+            .map_err(Oauth2ConnectorError::ParseResultError)?;
+        let id = event.id.clone();
+        event.into_event_result()
+            .ok_or_else(|| Oauth2ConnectorError::InvalidResponseError(format!("Google event {} has an invalid start or end", id)))
+    }
+}
+
+fn send_updates_param(send_updates: SendUpdates) -> &'static str {
+    match send_updates {
+        SendUpdates::All => "all",
+        SendUpdates::ExternalOnly => "externalOnly",
+        SendUpdates::None => "none",
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Google API response
+struct GoogleChannel {
+    id: String,
+    resourceId: String,
+    expiration: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Google API response
+struct EventListResponse {
+    #[serde(default)]
+    items: Vec<GoogleEvent>,
+    nextPageToken: Option<String>,
+    nextSyncToken: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Google API response
+pub struct GoogleEvent {
+    id: String,
+    etag: Option<String>,
+    status: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    location: Option<String>,
+    start: Option<GoogleEventDateTime>,
+    end: Option<GoogleEventDateTime>,
+    transparency: Option<String>,
+    visibility: Option<String>,
+    organizer: Option<GoogleEventPerson>,
+    #[serde(default)]
+    attendees: Vec<GoogleEventAttendee>,
+    recurrence: Option<Vec<String>>,
+    recurringEventId: Option<String>,
+    originalStartTime: Option<GoogleEventDateTime>,
+}
+
+impl GoogleEvent {
+    /**
+     * Map the Google event to an EventResult. Cancelled instances of a recurring event only
+     * carry their original start time, which is then used as both their start and end.
+     */
+    pub fn into_event_result(self) -> Option<EventResult> {
+        let original_start = self.originalStartTime.as_ref().and_then(|value| value.parse());
+        let (starts_at, all_day) = match self.start.as_ref().and_then(|value| value.parse()) {
+            Some(start) => start,
+            None => original_start?,
+        };
+        let (ends_at, _) = match self.end.as_ref().and_then(|value| value.parse()) {
+            Some(end) => end,
+            None => (starts_at, all_day),
+        };
+
+        Some(EventResult {
+            external_id: self.id,
+            etag: self.etag,
+            summary: self.summary,
+            description: self.description,
+            location: self.location,
+            starts_at,
+            ends_at,
+            all_day,
+            time_zone: self.start.and_then(|value| value.timeZone),
+            status: match self.status.as_deref() {
+                Some("tentative") => EventStatus::Tentative,
+                Some("cancelled") => EventStatus::Cancelled,
+                _ => EventStatus::Confirmed,
+            },
+            transparency: match self.transparency.as_deref() {
+                Some("transparent") => EventTransparency::Transparent,
+                _ => EventTransparency::Opaque,
+            },
+            visibility: match self.visibility.as_deref() {
+                Some("public") => EventVisibility::Public,
+                Some("private") => EventVisibility::Private,
+                Some("confidential") => EventVisibility::Confidential,
+                _ => EventVisibility::Default,
+            },
+            organizer: self.organizer.and_then(|organizer| Some(Participant {
+                email: organizer.email?,
+                name: organizer.displayName,
+                sent_by: None,
+            })),
+            attendees: Attendees(self.attendees.into_iter().filter_map(|attendee| {
+                let optional = attendee.optional.unwrap_or(false);
+                Some(Attendee {
+                    email: attendee.email?,
+                    name: attendee.displayName,
+                    status: match attendee.responseStatus.as_deref() {
+                        Some("accepted") => AttendeeStatus::Accepted,
+                        Some("declined") => AttendeeStatus::Declined,
+                        Some("tentative") => AttendeeStatus::Tentative,
+                        _ => AttendeeStatus::NeedsAction,
+                    },
+                    optional,
+                    role: if optional { ParticipantRole::Optional } else { ParticipantRole::Required },
+                    rsvp: false,
+                    kind: if attendee.resource == Some(true) { ParticipantKind::Resource } else { ParticipantKind::Individual },
+                    delegated_to: Vec::new(),
+                    delegated_from: Vec::new(),
+                    sent_by: None,
+                })
+            }).collect()),
+            recurrence: self.recurrence.map(|lines| lines.join("\n")),
+            recurring_event_id: self.recurringEventId,
+            original_starts_at: original_start.map(|(value, _)| value),
+        })
+    }
+}
+
+/**
+ * The body sent to create or update an event. Every field is sent, so fields which are empty
+ * on the event are cleared on Google as well.
+ */
+#[derive(Serialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Google API request
+pub struct GoogleEventRequest {
+    summary: Option<String>,
+    description: Option<String>,
+    location: Option<String>,
+    start: GoogleEventDateTime,
+    end: GoogleEventDateTime,
+    status: &'static str,
+    transparency: &'static str,
+    visibility: &'static str,
+    attendees: Vec<GoogleEventAttendee>,
+    recurrence: Option<Vec<String>>,
+}
+
+impl GoogleEventRequest {
+    pub fn from_event(event: &EventResult) -> Self {
+        Self {
+            summary: event.summary.clone(),
+            description: event.description.clone(),
+            location: event.location.clone(),
+            start: GoogleEventDateTime::from_datetime(event.starts_at, event.all_day, &event.time_zone),
+            end: GoogleEventDateTime::from_datetime(event.ends_at, event.all_day, &event.time_zone),
+            status: match event.status {
+                EventStatus::Confirmed => "confirmed",
+                EventStatus::Tentative => "tentative",
+                EventStatus::Cancelled => "cancelled",
+            },
+            transparency: match event.transparency {
+                EventTransparency::Opaque => "opaque",
+                EventTransparency::Transparent => "transparent",
+            },
+            visibility: match event.visibility {
+                EventVisibility::Default => "default",
+                EventVisibility::Public => "public",
+                EventVisibility::Private => "private",
+                EventVisibility::Confidential => "confidential",
+            },
+            attendees: event.attendees.0.iter().map(|attendee| GoogleEventAttendee {
+                email: Some(attendee.email.clone()),
+                displayName: attendee.name.clone(),
+                responseStatus: Some(match attendee.status {
+                    AttendeeStatus::NeedsAction => "needsAction",
+                    AttendeeStatus::Accepted => "accepted",
+                    AttendeeStatus::Declined => "declined",
+                    AttendeeStatus::Tentative => "tentative",
+                }.to_string()),
+                optional: Some(attendee.optional),
+                resource: matches!(attendee.kind, ParticipantKind::Resource | ParticipantKind::Room).then_some(true),
+            }).collect(),
+            recurrence: event.recurrence.as_ref().map(|recurrence| recurrence.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.trim().to_string())
+                .collect()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Google API response
+struct GoogleEventDateTime {
+    date: Option<String>,
+    dateTime: Option<String>,
+    timeZone: Option<String>,
+}
+
+impl GoogleEventDateTime {
+    /**
+     * All-day events are sent as a date, other events as a UTC date time. The time zone is
+     * kept so Google expands recurring events in the right zone.
+     */
+    fn from_datetime(value: NaiveDateTime, all_day: bool, time_zone: &Option<String>) -> Self {
+        if all_day {
+            return Self {
+                date: Some(value.format("%Y-%m-%d").to_string()),
+                dateTime: None,
+                timeZone: None,
+            };
+        }
+        Self {
+            date: None,
+            dateTime: Some(value.and_utc().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            timeZone: time_zone.clone(),
+        }
+    }
+
+    /**
+     * Parse the date or date time to UTC. Returns whether the value is a date, which is how
+     * Google marks all-day events.
+     */
+    fn parse(&self) -> Option<(NaiveDateTime, bool)> {
+        if let Some(date_time) = &self.dateTime {
+            let value = DateTime::parse_from_rfc3339(date_time).ok()?;
+            return Some((value.naive_utc(), false));
+        }
+        let date = NaiveDate::parse_from_str(self.date.as_ref()?, "%Y-%m-%d").ok()?;
+        Some((date.and_hms_opt(0, 0, 0)?, true))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Google API response
+struct GoogleEventPerson {
+    email: Option<String>,
+    displayName: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Google API response
+struct GoogleEventAttendee {
+    email: Option<String>,
+    displayName: Option<String>,
+    responseStatus: Option<String>,
+    optional: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resource: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -157,16 +671,25 @@ struct CalendarListResponse {
 
 #[derive(Deserialize, Debug)]
 #[allow(non_snake_case)] // Allow camel case for Google API response
-struct GoogleCalendarResult {
+pub struct GoogleCalendarResult {
     id: String,
     summary: String,
     backgroundColor: String,
     foregroundColor: String,
-    colorId: String,
-    accessRole: String,
-    primary: bool,
-    selected: bool,
-    timeZone: String,
+}
+
+impl GoogleCalendarResult {
+    /**
+     * Google hands out both colors of the calendar as hex values, so they are kept as is.
+     */
+    pub fn into_calendar_result(self) -> CalendarResult {
+        CalendarResult {
+            external_id: self.id,
+            name: self.summary,
+            background_color: self.backgroundColor,
+            foreground_color: self.foregroundColor,
+        }
+    }
 }
 
 #[derive(Deserialize)]
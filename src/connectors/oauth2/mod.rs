@@ -1,11 +1,13 @@
-mod google;
-mod outlook;
+pub mod google;
+pub mod outlook;
 
 use std::{str::FromStr, sync::Arc};
 
-use chrono::{Duration, Local};
+use chrono::{Duration, Local, NaiveDateTime};
 use diesel::{deserialize::FromSqlRow, expression::AsExpression, serialize::ToSql, Queryable};
-use crate::{config::Oauth2Config, models::{calendar::CalendarResult, oauth_integration::OauthIntegration}, AppState};
+use crate::{config::{Config, Oauth2Config}, models::{calendar::{Calendar, CalendarResult}, event::{EventChanges, EventResult}, oauth_integration::OauthIntegration, watch_channel::{WatchChannel, WatchChannelResult}}, AppState};
+use google::GoogleConnector;
+use outlook::OutlookConnector;
 
 pub trait Oauth2ServiceConnector {
 
@@ -25,12 +27,12 @@ pub trait Oauth2ServiceConnector {
             .post(config.token_url.as_str())
             .form(&params)
             .send().await
-            .unwrap();
+            .map_err(Oauth2ConnectorError::NetworkError)?;
 
         if response.status() != reqwest::StatusCode::OK {
             return Err(Oauth2ConnectorError::InvalidStatusError(
                 response.status(),
-                response.text().await.unwrap(),
+                response.text().await.unwrap_or_default(),
             ));
         }
 
@@ -38,6 +40,12 @@ pub trait Oauth2ServiceConnector {
             Ok(data) => {
                 integration.access_token = data.access_token;
                 integration.expires_at = (Local::now() + Duration::seconds(data.expires_in)).naive_utc();
+                // Some services rotate the refresh token with every refresh
+                if let Some(refresh_token) = data.refresh_token {
+                    integration.refresh_token = refresh_token;
+                }
+                integration.refresh_failures = 0;
+                integration.next_refresh_at = None;
                 integration.save(&mut state.get_connection());
                 Ok(integration.clone())
             },
@@ -61,6 +69,195 @@ pub trait Oauth2ServiceConnector {
     fn get_config(&self) -> &Oauth2Config;
 
     async fn get_calendars(&self, integration: &OauthIntegration) -> Result<Vec<CalendarResult>, Oauth2ConnectorError>;
+
+    /**
+     * Get the events of a calendar which changed since the last sync.
+     */
+    async fn get_events(&self, integration: &OauthIntegration, calendar: &Calendar) -> Result<EventChanges, Oauth2ConnectorError>;
+
+    /**
+     * Register a channel asking the service to call the webhook at the given address whenever
+     * the events of the calendar change. The service sends the token back with every
+     * notification.
+     */
+    async fn watch_events(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        token: &str,
+        address: &str,
+    ) -> Result<WatchChannelResult, Oauth2ConnectorError>;
+
+    /**
+     * Extend a channel before it expires, returning the new expiry. Services which cannot
+     * extend a channel return None, and a new channel is registered in its place.
+     */
+    async fn renew_channel(&self, integration: &OauthIntegration, channel: &WatchChannel) -> Result<Option<NaiveDateTime>, Oauth2ConnectorError>;
+
+    /**
+     * Stop a channel so the service no longer sends notifications for it.
+     */
+    async fn stop_channel(&self, integration: &OauthIntegration, channel: &WatchChannel) -> Result<(), Oauth2ConnectorError>;
+
+    /**
+     * Create an event in a calendar, returning the event as stored by the service.
+     */
+    async fn insert_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        event: &EventResult,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError>;
+
+    /**
+     * Update an event in a calendar, returning the event as stored by the service. With an etag
+     * the event is only updated when it was not changed since.
+     */
+    async fn update_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        event: &EventResult,
+        etag: Option<&str>,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError>;
+
+    /**
+     * Delete an event from a calendar. With an etag the event is only deleted when it was not
+     * changed since. Events which no longer exist are considered deleted.
+     */
+    async fn delete_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        external_id: &str,
+        etag: Option<&str>,
+        send_updates: SendUpdates,
+    ) -> Result<(), Oauth2ConnectorError>;
+}
+
+/**
+ * A connector for any of the OAuth2 services. Used to pick the right connector for an
+ * integration at runtime.
+ */
+pub enum Oauth2Connector {
+    Google(GoogleConnector),
+    Outlook(OutlookConnector),
+}
+
+impl Oauth2Connector {
+    pub fn new(service: &Oauth2Service, config: &Config) -> Self {
+        match service {
+            Oauth2Service::Google => Self::Google(GoogleConnector::new(config.oauth2.google.clone())),
+            Oauth2Service::Outlook => Self::Outlook(OutlookConnector::new(config.oauth2.outlook.clone())),
+        }
+    }
+
+    pub fn service(&self) -> Oauth2Service {
+        match self {
+            Self::Google(_) => Oauth2Service::Google,
+            Self::Outlook(_) => Oauth2Service::Outlook,
+        }
+    }
+}
+
+impl Oauth2ServiceConnector for Oauth2Connector {
+    fn get_config(&self) -> &Oauth2Config {
+        match self {
+            Self::Google(connector) => connector.get_config(),
+            Self::Outlook(connector) => connector.get_config(),
+        }
+    }
+
+    async fn revoke_access_token(&self, integration: &OauthIntegration) -> Result<(), Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.revoke_access_token(integration).await,
+            Self::Outlook(connector) => connector.revoke_access_token(integration).await,
+        }
+    }
+
+    async fn get_calendars(&self, integration: &OauthIntegration) -> Result<Vec<CalendarResult>, Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.get_calendars(integration).await,
+            Self::Outlook(connector) => connector.get_calendars(integration).await,
+        }
+    }
+
+    async fn get_events(&self, integration: &OauthIntegration, calendar: &Calendar) -> Result<EventChanges, Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.get_events(integration, calendar).await,
+            Self::Outlook(connector) => connector.get_events(integration, calendar).await,
+        }
+    }
+
+    async fn watch_events(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        token: &str,
+        address: &str,
+    ) -> Result<WatchChannelResult, Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.watch_events(integration, calendar, token, address).await,
+            Self::Outlook(connector) => connector.watch_events(integration, calendar, token, address).await,
+        }
+    }
+
+    async fn renew_channel(&self, integration: &OauthIntegration, channel: &WatchChannel) -> Result<Option<NaiveDateTime>, Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.renew_channel(integration, channel).await,
+            Self::Outlook(connector) => connector.renew_channel(integration, channel).await,
+        }
+    }
+
+    async fn stop_channel(&self, integration: &OauthIntegration, channel: &WatchChannel) -> Result<(), Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.stop_channel(integration, channel).await,
+            Self::Outlook(connector) => connector.stop_channel(integration, channel).await,
+        }
+    }
+
+    async fn insert_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        event: &EventResult,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.insert_event(integration, calendar, event, send_updates).await,
+            Self::Outlook(connector) => connector.insert_event(integration, calendar, event, send_updates).await,
+        }
+    }
+
+    async fn update_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        event: &EventResult,
+        etag: Option<&str>,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.update_event(integration, calendar, event, etag, send_updates).await,
+            Self::Outlook(connector) => connector.update_event(integration, calendar, event, etag, send_updates).await,
+        }
+    }
+
+    async fn delete_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        external_id: &str,
+        etag: Option<&str>,
+        send_updates: SendUpdates,
+    ) -> Result<(), Oauth2ConnectorError> {
+        match self {
+            Self::Google(connector) => connector.delete_event(integration, calendar, external_id, etag, send_updates).await,
+            Self::Outlook(connector) => connector.delete_event(integration, calendar, external_id, etag, send_updates).await,
+        }
+    }
 }
 
 /**
@@ -70,6 +267,7 @@ pub trait Oauth2ServiceConnector {
 struct Oauth2TokenResponse {
     access_token: String,
     expires_in: i64,
+    refresh_token: Option<String>,
 }
 
 /**
@@ -81,6 +279,19 @@ pub enum Oauth2ConnectorError {
     ParseResultError(reqwest::Error),
     NetworkError(reqwest::Error),
     InvalidStatusError(reqwest::StatusCode, String),
+    InvalidResponseError(String),
+    PreconditionFailed,
+    UnsupportedEvent(String),
+}
+
+/**
+ * Who the service notifies about a change made to an event.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendUpdates {
+    All,
+    ExternalOnly,
+    None,
 }
 
 /**
@@ -1,11 +1,33 @@
-use crate::{config::Oauth2Config, models::{calendar::CalendarResult, integration::Integration, oauth_integration::OauthIntegration}};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use reqwest::StatusCode;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
 
-use super::{Oauth2ConnectorError, Oauth2ServiceConnector};
+use crate::{config::Oauth2Config, helper::{foreground_color, parse_time_zone}, recurrence::{Frequency, RecurrenceDate, RecurrenceRule}, models::{calendar::{Calendar, CalendarResult}, event::{Attendee, AttendeeStatus, Attendees, EventChanges, EventResult, EventStatus, EventTransparency, EventVisibility, Participant, ParticipantKind, ParticipantRole}, oauth_integration::OauthIntegration, watch_channel::{WatchChannel, WatchChannelResult}}};
 
-pub struct OutlookConnector {
+use super::{Oauth2ConnectorError, Oauth2ServiceConnector, SendUpdates};
 
-    pub config: Oauth2Config
 
+/**
+ * The window of the calendar view which is synced, relative to the time of the first sync.
+ */
+const CALENDAR_VIEW_PAST_DAYS: i64 = 90;
+const CALENDAR_VIEW_FUTURE_DAYS: i64 = 365;
+
+/**
+ * A delta link keeps the window of its first request. Once fewer days of the window remain,
+ * the sync starts over with a new window, so the view never falls behind by more than a month.
+ */
+const CALENDAR_VIEW_MIN_FUTURE_DAYS: i64 = 335;
+
+/**
+ * The lifetime of a subscription, just under the 4230 minutes Graph allows for events.
+ */
+const SUBSCRIPTION_LIFETIME_MINUTES: i64 = 4200;
+
+pub struct OutlookConnector {
+    pub client: reqwest::Client,
+    pub config: Oauth2Config
 }
 
 impl Oauth2ServiceConnector for OutlookConnector {
@@ -13,7 +35,7 @@ impl Oauth2ServiceConnector for OutlookConnector {
         &self.config
     }
 
-    async fn revoke_access_token(&self, integration: &OauthIntegration) -> Result<(), Oauth2ConnectorError> {
+    async fn revoke_access_token(&self, _integration: &OauthIntegration) -> Result<(), Oauth2ConnectorError> {
         // Do nothing, as Outlook does not support token revocation
         Err(Oauth2ConnectorError::TokenRevocationError(
             "Outlook does not support token revocation".to_string()
@@ -21,7 +43,217 @@ impl Oauth2ServiceConnector for OutlookConnector {
     }
 
     async fn get_calendars(&self, integration: &OauthIntegration) -> Result<Vec<CalendarResult>, Oauth2ConnectorError> {
-        Ok(Vec::new())
+
+        let mut next_link = Some(self.config.api_url(&["me", "calendars"]).to_string());
+        let mut items: Vec<OutlookCalendarResult> = Vec::new();
+
+        // Follow the next links until every calendar is retrieved
+        while let Some(link) = next_link {
+            let result = self.get_calendar_page(&link, integration).await?;
+            next_link = result.next_link;
+            items.extend(result.value);
+        }
+
+        // Map the calendars to the CalendarResult struct
+        Ok(items.into_iter()
+            .map(OutlookCalendarResult::into_calendar_result)
+            .collect::<Vec<CalendarResult>>())
+    }
+
+    async fn get_events(&self, integration: &OauthIntegration, calendar: &Calendar) -> Result<EventChanges, Oauth2ConnectorError> {
+
+        // Restart with a full sync when the window of the stored delta link runs out
+        let stored_link = calendar.sync_token.as_deref()
+            .and_then(DeltaLink::parse)
+            .filter(|link| link.window_ends_at > Utc::now() + Duration::days(CALENDAR_VIEW_MIN_FUTURE_DAYS));
+        let mut full = stored_link.is_none();
+        let mut delta_link = stored_link.unwrap_or_else(|| self.initial_delta_link(calendar));
+        let mut next_link = delta_link.link.clone();
+        let mut items: Vec<OutlookEvent> = Vec::new();
+
+        // Follow the next links until Graph hands out the delta link for the next sync
+        let next_delta_link = loop {
+            let result = match self.get_event_page(&next_link, integration).await {
+                Ok(result) => result,
+                Err(Oauth2ConnectorError::InvalidStatusError(StatusCode::GONE, _)) if !full => {
+                    // The delta link expired, start over with a full sync
+                    full = true;
+                    delta_link = self.initial_delta_link(calendar);
+                    next_link = delta_link.link.clone();
+                    items.clear();
+                    continue;
+                },
+                Err(err) => {
+                    return Err(err);
+                }
+            };
+            items.extend(result.value);
+            match (result.next_link, result.delta_link) {
+                (Some(link), _) => next_link = link,
+                (None, delta_link) => break delta_link,
+            }
+        };
+
+        // Removed events are reported as tombstones which only carry their id
+        let mut changes = EventChanges {
+            full,
+            sync_token: next_delta_link.map(|link| DeltaLink {
+                link,
+                window_ends_at: delta_link.window_ends_at,
+            }.to_sync_token()),
+            ..Default::default()
+        };
+        for item in items {
+            if item.removed.is_some() {
+                changes.deleted.push(item.id);
+                continue;
+            }
+            let id = item.id.clone();
+            match item.into_event_result() {
+                Some(event) => changes.events.push(event),
+                None => println!("Skipping Outlook event {} with an invalid start or end", id),
+            }
+        }
+
+        Ok(changes)
+    }
+
+    async fn watch_events(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        token: &str,
+        address: &str,
+    ) -> Result<WatchChannelResult, Oauth2ConnectorError> {
+
+        // Graph validates the notification URL before it answers
+        let response = self.client
+            .post(self.config.api_url(&["subscriptions"]))
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .json(&serde_json::json!({
+                "changeType": "created,updated,deleted",
+                "notificationUrl": address,
+                "lifecycleNotificationUrl": address,
+                "resource": format!("/me/calendars/{}/events", calendar.external_id),
+                "expirationDateTime": subscription_expiry().to_rfc3339(),
+                "clientState": token,
+            }))
+            .send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        if response.status() != StatusCode::CREATED {
+            return Err(Oauth2ConnectorError::InvalidStatusError(
+                response.status(),
+                response.text().await.unwrap_or("".to_string()),
+            ))
+        }
+
+        let subscription = response.json::<OutlookSubscription>().await
+            .map_err(Oauth2ConnectorError::ParseResultError)?;
+
+        Ok(WatchChannelResult {
+            expires_at: subscription.expires_at(),
+            external_id: subscription.id,
+            resource_id: None,
+        })
+    }
+
+    async fn renew_channel(&self, integration: &OauthIntegration, channel: &WatchChannel) -> Result<Option<NaiveDateTime>, Oauth2ConnectorError> {
+        let response = self.client
+            .patch(self.config.api_url(&["subscriptions", &channel.external_id]))
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .json(&serde_json::json!({
+                "expirationDateTime": subscription_expiry().to_rfc3339(),
+            }))
+            .send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        if response.status() != StatusCode::OK {
+            return Err(Oauth2ConnectorError::InvalidStatusError(
+                response.status(),
+                response.text().await.unwrap_or("".to_string()),
+            ))
+        }
+
+        let subscription = response.json::<OutlookSubscription>().await
+            .map_err(Oauth2ConnectorError::ParseResultError)?;
+
+        Ok(Some(subscription.expires_at()))
+    }
+
+    async fn stop_channel(&self, integration: &OauthIntegration, channel: &WatchChannel) -> Result<(), Oauth2ConnectorError> {
+        let response = self.client
+            .delete(self.config.api_url(&["subscriptions", &channel.external_id]))
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        // A subscription which is already gone needs no stopping
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(Oauth2ConnectorError::InvalidStatusError(
+                response.status(),
+                response.text().await.unwrap_or("".to_string()),
+            ))
+        }
+
+        Ok(())
+    }
+
+    async fn insert_event(
+        &self,
+        integration: &OauthIntegration,
+        calendar: &Calendar,
+        event: &EventResult,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError> {
+        check_send_updates(event, send_updates)?;
+        let url = self.config.api_url(&["me", "calendars", &calendar.external_id, "events"]);
+        let request = self.client.post(url);
+        self.send_event_request(request, integration, event, None).await
+    }
+
+    async fn update_event(
+        &self,
+        integration: &OauthIntegration,
+        _calendar: &Calendar,
+        event: &EventResult,
+        etag: Option<&str>,
+        send_updates: SendUpdates,
+    ) -> Result<EventResult, Oauth2ConnectorError> {
+        check_send_updates(event, send_updates)?;
+        let request = self.client.patch(self.event_url(&event.external_id));
+        self.send_event_request(request, integration, event, etag).await
+    }
+
+    async fn delete_event(
+        &self,
+        integration: &OauthIntegration,
+        _calendar: &Calendar,
+        external_id: &str,
+        etag: Option<&str>,
+        _send_updates: SendUpdates,
+    ) -> Result<(), Oauth2ConnectorError> {
+        // Graph sends a cancellation to the attendees of a deleted meeting. Events written
+        // without updates have no attendees, so there is nobody to notify.
+        let mut request = self.client
+            .delete(self.event_url(external_id))
+            .header("Authorization", format!("Bearer {}", integration.access_token));
+
+        if let Some(etag) = etag {
+            request = request.header("If-Match", etag);
+        }
+
+        let response = request.send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT | StatusCode::NOT_FOUND => Ok(()),
+            StatusCode::PRECONDITION_FAILED => Err(Oauth2ConnectorError::PreconditionFailed),
+            status => Err(Oauth2ConnectorError::InvalidStatusError(
+                status,
+                response.text().await.unwrap_or("".to_string()),
+            )),
+        }
     }
 
 }
@@ -29,7 +261,742 @@ impl Oauth2ServiceConnector for OutlookConnector {
 impl OutlookConnector {
     pub fn new(config: Oauth2Config) -> Self {
         Self {
-            config
+            config,
+            client: reqwest::Client::new()
+        }
+    }
+
+    /**
+     * Graph returns the calendars in pages. The first page is requested from /me/calendars,
+     * every following page from the @odata.nextLink of the previous page.
+     */
+    async fn get_calendar_page(
+        &self,
+        link: &str,
+        integration: &OauthIntegration,
+    ) -> Result<CalendarListResponse, Oauth2ConnectorError> {
+
+        let response = self.client
+            .get(link)
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .send().await;
+
+        // Guard to get the response
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                return Err(Oauth2ConnectorError::NetworkError(err));
+            }
+        };
+
+        // Ensure the status code is OK
+        if response.status() != StatusCode::OK {
+            return Err(Oauth2ConnectorError::InvalidStatusError(
+                response.status(),
+                response.text().await.unwrap_or("".to_string()),
+            ))
+        }
+
+        // Guard to get the JSON response
+        match response.json::<CalendarListResponse>().await {
+            Ok(result) => Ok(result),
+            Err(err) => Err(Oauth2ConnectorError::ParseResultError(err)),
+        }
+    }
+
+    /**
+     * Build the link for the first delta request of a calendar view. The window is fixed
+     * when the first request is made and carried along in every following delta link.
+     */
+    fn initial_delta_link(&self, calendar: &Calendar) -> DeltaLink {
+        let now = Utc::now();
+        let window_ends_at = now + Duration::days(CALENDAR_VIEW_FUTURE_DAYS);
+        let mut url = self.config.api_url(&["me", "calendars", &calendar.external_id, "calendarView", "delta"]);
+        url.query_pairs_mut()
+            .append_pair("startDateTime", &(now - Duration::days(CALENDAR_VIEW_PAST_DAYS)).to_rfc3339())
+            .append_pair("endDateTime", &window_ends_at.to_rfc3339());
+        DeltaLink {
+            link: url.to_string(),
+            window_ends_at,
+        }
+    }
+
+    /**
+     * Get a page of a calendar view delta. Times are requested in UTC.
+     */
+    async fn get_event_page(
+        &self,
+        link: &str,
+        integration: &OauthIntegration,
+    ) -> Result<EventDeltaResponse, Oauth2ConnectorError> {
+
+        let response = self.client
+            .get(link)
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .header("Prefer", "odata.maxpagesize=100, outlook.timezone=\"UTC\"")
+            .send().await;
+
+        // Guard to get the response
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                return Err(Oauth2ConnectorError::NetworkError(err));
+            }
+        };
+
+        // Ensure the status code is OK
+        if response.status() != StatusCode::OK {
+            return Err(Oauth2ConnectorError::InvalidStatusError(
+                response.status(),
+                response.text().await.unwrap_or("".to_string()),
+            ))
+        }
+
+        // Guard to get the JSON response
+        match response.json::<EventDeltaResponse>().await {
+            Ok(result) => Ok(result),
+            Err(err) => Err(Oauth2ConnectorError::ParseResultError(err)),
+        }
+    }
+
+    /**
+     * Build the URL of a single event. Events are addressed without their calendar in Graph.
+     */
+    fn event_url(&self, event_id: &str) -> reqwest::Url {
+        self.config.api_url(&["me", "events", event_id])
+    }
+
+    /**
+     * Send the event to Graph and map the stored event back to an EventResult. The stored
+     * event is requested in UTC, like the events of a sync.
+     */
+    async fn send_event_request(
+        &self,
+        request: reqwest::RequestBuilder,
+        integration: &OauthIntegration,
+        event: &EventResult,
+        etag: Option<&str>,
+    ) -> Result<EventResult, Oauth2ConnectorError> {
+        let mut request = request
+            .header("Authorization", format!("Bearer {}", integration.access_token))
+            .header("Prefer", "outlook.timezone=\"UTC\"")
+            .json(&OutlookEventRequest::from_event(event)?);
+
+        if let Some(etag) = etag {
+            request = request.header("If-Match", etag);
+        }
+
+        let response = request.send().await
+            .map_err(Oauth2ConnectorError::NetworkError)?;
+
+        match response.status() {
+            StatusCode::OK | StatusCode::CREATED => {},
+            StatusCode::PRECONDITION_FAILED => return Err(Oauth2ConnectorError::PreconditionFailed),
+            status => return Err(Oauth2ConnectorError::InvalidStatusError(
+                status,
+                response.text().await.unwrap_or("".to_string()),
+            )),
+        }
+
+        let event = response.json::<OutlookEvent>().await
+            .map_err(Oauth2ConnectorError::ParseResultError)?;
+        let id = event.id.clone();
+        event.into_event_result()
+            .ok_or_else(|| Oauth2ConnectorError::InvalidResponseError(format!("Outlook event {} has an invalid start or end", id)))
+    }
+}
+
+/**
+ * Graph notifies the attendees of every meeting that is created or changed, and has no way to
+ * leave them out. Events with attendees can therefore only be written when updates are sent.
+ */
+fn check_send_updates(event: &EventResult, send_updates: SendUpdates) -> Result<(), Oauth2ConnectorError> {
+    if send_updates == SendUpdates::None && !event.attendees.0.is_empty() {
+        return Err(Oauth2ConnectorError::UnsupportedEvent(
+            "Outlook notifies the attendees of an event, which cannot be turned off".to_string()
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * The expiry requested for a new or renewed subscription.
+ */
+fn subscription_expiry() -> DateTime<Utc> {
+    Utc::now() + Duration::minutes(SUBSCRIPTION_LIFETIME_MINUTES)
+}
+
+/**
+ * A delta link of a calendar view with the end of its window. Graph does not show the window
+ * in the link, so the end is stored behind it in the sync token of the calendar.
+ */
+struct DeltaLink {
+    link: String,
+    window_ends_at: DateTime<Utc>,
+}
+
+impl DeltaLink {
+    /**
+     * Parse a stored sync token. Tokens without the end of their window cannot be used.
+     */
+    fn parse(sync_token: &str) -> Option<Self> {
+        let (link, window_ends_at) = sync_token.rsplit_once('#')?;
+        Some(Self {
+            link: link.to_string(),
+            window_ends_at: DateTime::parse_from_rfc3339(window_ends_at).ok()?.with_timezone(&Utc),
+        })
+    }
+
+    fn to_sync_token(&self) -> String {
+        format!("{}#{}", self.link, self.window_ends_at.to_rfc3339())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct CalendarListResponse {
+    value: Vec<OutlookCalendarResult>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API response
+pub struct OutlookCalendarResult {
+    id: String,
+    name: String,
+    color: Option<String>,
+    hexColor: Option<String>,
+}
+
+impl OutlookCalendarResult {
+    /**
+     * Graph has no text color for calendars, so it is picked to contrast with the background.
+     */
+    pub fn into_calendar_result(self) -> CalendarResult {
+        let background_color = self.background_color();
+        CalendarResult {
+            external_id: self.id,
+            name: self.name,
+            foreground_color: foreground_color(&background_color),
+            background_color,
+        }
+    }
+
+    /**
+     * Graph only fills in hexColor for calendars with a custom color. Otherwise the named
+     * calendarColor is mapped to the hex value Outlook displays for it.
+     */
+    fn background_color(&self) -> String {
+        if let Some(hex_color) = self.hexColor.as_ref().filter(|value| !value.is_empty()) {
+            return hex_color.to_lowercase();
+        }
+        let color = match self.color.as_deref() {
+            Some("lightBlue") => "#a6d1f5",
+            Some("lightGreen") => "#87d28e",
+            Some("lightOrange") => "#fcab73",
+            Some("lightGray") => "#c0c0c0",
+            Some("lightYellow") => "#f4d07a",
+            Some("lightTeal") => "#6cd3d7",
+            Some("lightPink") => "#f08cc0",
+            Some("lightBrown") => "#d5b59c",
+            Some("lightRed") => "#f1919a",
+            _ => "#0078d4",
+        };
+        color.to_string()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct EventDeltaResponse {
+    #[serde(default)]
+    value: Vec<OutlookEvent>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+    #[serde(rename = "@odata.deltaLink")]
+    delta_link: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API response
+pub struct OutlookEvent {
+    id: String,
+    #[serde(rename = "@odata.etag")]
+    etag: Option<String>,
+    #[serde(rename = "@removed")]
+    removed: Option<serde_json::Value>,
+    subject: Option<String>,
+    bodyPreview: Option<String>,
+    start: Option<OutlookDateTimeTimeZone>,
+    end: Option<OutlookDateTimeTimeZone>,
+    isAllDay: Option<bool>,
+    isCancelled: Option<bool>,
+    showAs: Option<String>,
+    sensitivity: Option<String>,
+    location: Option<OutlookLocation>,
+    organizer: Option<OutlookRecipient>,
+    #[serde(default)]
+    attendees: Vec<OutlookAttendee>,
+    seriesMasterId: Option<String>,
+    originalStart: Option<String>,
+    originalStartTimeZone: Option<String>,
+}
+
+impl OutlookEvent {
+    /**
+     * Map the Graph event to an EventResult. The calendar view returns every occurrence of a
+     * series as its own event, linked to the series through the seriesMasterId.
+     */
+    pub fn into_event_result(self) -> Option<EventResult> {
+        let starts_at = self.start?.parse()?;
+        let ends_at = self.end?.parse()?;
+        let show_as = self.showAs.as_deref();
+
+        Some(EventResult {
+            external_id: self.id,
+            etag: self.etag,
+            summary: self.subject,
+            description: self.bodyPreview.filter(|value| !value.is_empty()),
+            location: self.location
+                .and_then(|location| location.displayName)
+                .filter(|value| !value.is_empty()),
+            starts_at,
+            ends_at,
+            all_day: self.isAllDay.unwrap_or(false),
+            // Outlook names time zones the Windows way
+            time_zone: self.originalStartTimeZone
+                .map(|time_zone| parse_time_zone(&time_zone).map(|time_zone| time_zone.name().to_string()).unwrap_or(time_zone)),
+            status: match (self.isCancelled, show_as) {
+                (Some(true), _) => EventStatus::Cancelled,
+                (_, Some("tentative")) => EventStatus::Tentative,
+                _ => EventStatus::Confirmed,
+            },
+            transparency: match show_as {
+                Some("free") | Some("workingElsewhere") => EventTransparency::Transparent,
+                _ => EventTransparency::Opaque,
+            },
+            // Outlook has no public events, normal ones follow the sharing of the calendar
+            visibility: match self.sensitivity.as_deref() {
+                Some("personal") | Some("private") => EventVisibility::Private,
+                Some("confidential") => EventVisibility::Confidential,
+                _ => EventVisibility::Default,
+            },
+            organizer: self.organizer.and_then(|organizer| organizer.emailAddress.into_participant()),
+            attendees: Attendees(self.attendees.into_iter().filter_map(|attendee| {
+                let participant = attendee.emailAddress.into_participant()?;
+                Some(Attendee {
+                    email: participant.email,
+                    name: participant.name,
+                    status: match attendee.status.and_then(|status| status.response).as_deref() {
+                        Some("accepted") | Some("organizer") => AttendeeStatus::Accepted,
+                        Some("declined") => AttendeeStatus::Declined,
+                        Some("tentativelyAccepted") => AttendeeStatus::Tentative,
+                        _ => AttendeeStatus::NeedsAction,
+                    },
+                    optional: attendee.r#type.as_deref() == Some("optional"),
+                    role: match attendee.r#type.as_deref() {
+                        Some("optional") => ParticipantRole::Optional,
+                        _ => ParticipantRole::Required,
+                    },
+                    rsvp: false,
+                    kind: match attendee.r#type.as_deref() {
+                        Some("resource") => ParticipantKind::Resource,
+                        _ => ParticipantKind::Individual,
+                    },
+                    delegated_to: Vec::new(),
+                    delegated_from: Vec::new(),
+                    sent_by: None,
+                })
+            }).collect()),
+            recurrence: None,
+            recurring_event_id: self.seriesMasterId,
+            original_starts_at: self.originalStart
+                .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+                .map(|value| value.naive_utc()),
+        })
+    }
+}
+
+/**
+ * The body sent to create or update an event. Graph describes recurrence with its own pattern
+ * objects rather than RRULEs, so the RRULE of a series is translated to a patterned recurrence.
+ */
+#[derive(Serialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API request
+pub struct OutlookEventRequest {
+    subject: Option<String>,
+    body: OutlookItemBody,
+    location: OutlookLocationRequest,
+    start: OutlookDateTimeTimeZone,
+    end: OutlookDateTimeTimeZone,
+    isAllDay: bool,
+    showAs: &'static str,
+    sensitivity: &'static str,
+    attendees: Vec<OutlookAttendeeRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recurrence: Option<OutlookPatternedRecurrence>,
+}
+
+impl OutlookEventRequest {
+    /**
+     * Fails for series whose recurrence Outlook cannot describe.
+     */
+    pub fn from_event(event: &EventResult) -> Result<Self, Oauth2ConnectorError> {
+        Ok(Self {
+            subject: event.summary.clone(),
+            body: OutlookItemBody {
+                contentType: "text",
+                content: event.description.clone().unwrap_or_default(),
+            },
+            location: OutlookLocationRequest {
+                displayName: event.location.clone().unwrap_or_default(),
+            },
+            start: OutlookDateTimeTimeZone::from_datetime(event.starts_at, event.all_day, &event.time_zone),
+            end: OutlookDateTimeTimeZone::from_datetime(event.ends_at, event.all_day, &event.time_zone),
+            isAllDay: event.all_day,
+            showAs: match (&event.transparency, &event.status) {
+                (EventTransparency::Transparent, _) => "free",
+                (_, EventStatus::Tentative) => "tentative",
+                _ => "busy",
+            },
+            sensitivity: match event.visibility {
+                EventVisibility::Default | EventVisibility::Public => "normal",
+                EventVisibility::Private => "private",
+                EventVisibility::Confidential => "confidential",
+            },
+            attendees: event.attendees.0.iter().map(|attendee| OutlookAttendeeRequest {
+                emailAddress: OutlookEmailAddressRequest {
+                    address: attendee.email.clone(),
+                    name: attendee.name.clone(),
+                },
+                r#type: match attendee.kind {
+                    ParticipantKind::Resource | ParticipantKind::Room => "resource",
+                    _ if attendee.optional => "optional",
+                    _ => "required",
+                },
+            }).collect(),
+            recurrence: OutlookPatternedRecurrence::from_event(event)?,
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct OutlookPatternedRecurrence {
+    pattern: OutlookRecurrencePattern,
+    range: OutlookRecurrenceRange,
+}
+
+#[derive(Serialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API request
+struct OutlookRecurrencePattern {
+    r#type: &'static str,
+    interval: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    month: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dayOfMonth: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    daysOfWeek: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    firstDayOfWeek: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<&'static str>,
+}
+
+#[derive(Serialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API request
+struct OutlookRecurrenceRange {
+    r#type: &'static str,
+    startDate: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    endDate: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    numberOfOccurrences: Option<u32>,
+    recurrenceTimeZone: String,
+}
+
+impl OutlookPatternedRecurrence {
+    /**
+     * Translate the RRULE of a series. Graph repeats on days, weeks, months or years, on either
+     * fixed days or the nth weekday of a month. Rules it cannot express, and RDATEs or EXDATEs,
+     * are rejected rather than written as a different series.
+     */
+    fn from_event(event: &EventResult) -> Result<Option<Self>, Oauth2ConnectorError> {
+        let Some(recurrence) = event.recurrence.as_deref() else {
+            return Ok(None);
+        };
+        let unsupported = |reason: &str| Oauth2ConnectorError::UnsupportedEvent(reason.to_string());
+
+        let mut lines = recurrence.lines().map(str::trim).filter(|line| !line.is_empty());
+        let rule = match (lines.next().and_then(|line| line.split_once(':')), lines.next()) {
+            (Some((name, rule)), None) if name.eq_ignore_ascii_case("RRULE") => rule,
+            _ => return Err(unsupported("Outlook only supports a recurrence of a single RRULE")),
+        };
+        let rule = RecurrenceRule::parse(rule)
+            .map_err(|err| Oauth2ConnectorError::UnsupportedEvent(err.to_string()))?;
+
+        if rule.frequency < Frequency::Daily
+            || !rule.by_second.is_empty() || !rule.by_minute.is_empty() || !rule.by_hour.is_empty()
+            || !rule.by_year_day.is_empty() || !rule.by_week_no.is_empty() || rule.by_set_pos.len() > 1 {
+            return Err(unsupported("Outlook cannot repeat events by this rule"));
+        }
+
+        let time_zone = event.time_zone.as_deref().and_then(parse_time_zone);
+        let start = local_datetime(event.starts_at, event.all_day, time_zone).date();
+
+        let pattern = match rule.frequency {
+            Frequency::Daily if rule.by_day.is_empty() && rule.by_month_day.is_empty()
+                && rule.by_month.is_empty() && rule.by_set_pos.is_empty() => OutlookRecurrencePattern::new("daily", rule.interval),
+            Frequency::Weekly if rule.by_month_day.is_empty() && rule.by_month.is_empty()
+                && rule.by_set_pos.is_empty() && rule.by_day.iter().all(|(ordinal, _)| ordinal.is_none()) => {
+                let days = match rule.by_day.is_empty() {
+                    true => vec![start.weekday()],
+                    false => rule.by_day.iter().map(|(_, weekday)| *weekday).collect(),
+                };
+                OutlookRecurrencePattern {
+                    daysOfWeek: days.into_iter().map(weekday_name).collect(),
+                    firstDayOfWeek: Some(weekday_name(rule.week_start)),
+                    ..OutlookRecurrencePattern::new("weekly", rule.interval)
+                }
+            },
+            Frequency::Monthly if rule.by_month.is_empty() => month_pattern(&rule, start, "absoluteMonthly", "relativeMonthly")
+                .ok_or_else(|| unsupported("Outlook cannot repeat events by this rule"))?,
+            Frequency::Yearly if rule.by_month.len() <= 1 => OutlookRecurrencePattern {
+                month: Some(rule.by_month.first().copied().unwrap_or(start.month())),
+                ..month_pattern(&rule, start, "absoluteYearly", "relativeYearly")
+                    .ok_or_else(|| unsupported("Outlook cannot repeat events by this rule"))?
+            },
+            _ => return Err(unsupported("Outlook cannot repeat events by this rule")),
+        };
+
+        let end_date = match rule.until {
+            None => None,
+            Some(RecurrenceDate::Date(date)) => Some(date),
+            Some(RecurrenceDate::Utc(until)) => Some(local_datetime(until, event.all_day, time_zone).date()),
+            Some(RecurrenceDate::Local(until, _)) => Some(until.date()),
+        };
+        let range = OutlookRecurrenceRange {
+            r#type: match (rule.count, end_date) {
+                (Some(_), _) => "numbered",
+                (None, Some(_)) => "endDate",
+                (None, None) => "noEnd",
+            },
+            startDate: start.format("%Y-%m-%d").to_string(),
+            endDate: end_date.filter(|_| rule.count.is_none()).map(|date| date.format("%Y-%m-%d").to_string()),
+            numberOfOccurrences: rule.count,
+            recurrenceTimeZone: time_zone.map(|time_zone| time_zone.name()).unwrap_or("UTC").to_string(),
+        };
+
+        Ok(Some(Self { pattern, range }))
+    }
+}
+
+impl OutlookRecurrencePattern {
+    fn new(r#type: &'static str, interval: u32) -> Self {
+        Self {
+            r#type,
+            interval,
+            month: None,
+            dayOfMonth: None,
+            daysOfWeek: Vec::new(),
+            firstDayOfWeek: None,
+            index: None,
+        }
+    }
+}
+
+/**
+ * The pattern of a monthly or yearly rule. A rule repeats on a fixed day of the month, which
+ * defaults to the day the series starts, or on the first to fourth or the last weekday of the
+ * month. The weekday is either numbered in BYDAY or selected with BYSETPOS.
+ */
+fn month_pattern(
+    rule: &RecurrenceRule,
+    start: NaiveDate,
+    absolute: &'static str,
+    relative: &'static str,
+) -> Option<OutlookRecurrencePattern> {
+    if rule.by_day.is_empty() {
+        let day = match rule.by_month_day.as_slice() {
+            [] => start.day(),
+            [day] if *day > 0 => *day as u32,
+            _ => return None,
+        };
+        if !rule.by_set_pos.is_empty() {
+            return None;
         }
+        return Some(OutlookRecurrencePattern {
+            dayOfMonth: Some(day),
+            ..OutlookRecurrencePattern::new(absolute, rule.interval)
+        });
+    }
+
+    if !rule.by_month_day.is_empty() {
+        return None;
+    }
+    let (index, days) = match (rule.by_day.as_slice(), rule.by_set_pos.as_slice()) {
+        ([(Some(ordinal), weekday)], []) => (*ordinal, vec![*weekday]),
+        (days, [position]) if days.iter().all(|(ordinal, _)| ordinal.is_none()) => {
+            (*position, days.iter().map(|(_, weekday)| *weekday).collect())
+        },
+        _ => return None,
+    };
+    let index = match index {
+        1 => "first",
+        2 => "second",
+        3 => "third",
+        4 => "fourth",
+        -1 => "last",
+        _ => return None,
+    };
+
+    Some(OutlookRecurrencePattern {
+        daysOfWeek: days.into_iter().map(weekday_name).collect(),
+        index: Some(index),
+        ..OutlookRecurrencePattern::new(relative, rule.interval)
+    })
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    }
+}
+
+/**
+ * The local time of a UTC date time in the time zone of an event. All-day events start and end
+ * at midnight, so their dates are kept as they are.
+ */
+fn local_datetime(value: NaiveDateTime, all_day: bool, time_zone: Option<Tz>) -> NaiveDateTime {
+    match (all_day, time_zone) {
+        (false, Some(time_zone)) => time_zone.from_utc_datetime(&value).naive_local(),
+        _ => value,
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API request
+struct OutlookItemBody {
+    contentType: &'static str,
+    content: String,
+}
+
+#[derive(Serialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API request
+struct OutlookLocationRequest {
+    displayName: String,
+}
+
+#[derive(Serialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API request
+struct OutlookAttendeeRequest {
+    emailAddress: OutlookEmailAddressRequest,
+    r#type: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+struct OutlookEmailAddressRequest {
+    address: String,
+    name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API response
+pub struct OutlookDateTimeTimeZone {
+    dateTime: String,
+    timeZone: Option<String>,
+}
+
+impl OutlookDateTimeTimeZone {
+    /**
+     * Convert a UTC date time to the local time of the event's time zone, which Graph uses to
+     * show the event and expand its recurrence. Events without a known time zone are written
+     * in UTC.
+     */
+    fn from_datetime(value: NaiveDateTime, all_day: bool, time_zone: &Option<String>) -> Self {
+        let time_zone = time_zone.as_deref().and_then(parse_time_zone);
+        Self {
+            dateTime: local_datetime(value, all_day, time_zone).format("%Y-%m-%dT%H:%M:%S").to_string(),
+            timeZone: Some(time_zone.map(|time_zone| time_zone.name()).unwrap_or("UTC").to_string()),
+        }
+    }
+
+    /**
+     * Parse the date time to UTC. Events are requested in UTC, but IANA and Windows time zones
+     * are converted as well.
+     */
+    pub fn parse(&self) -> Option<NaiveDateTime> {
+        let value = NaiveDateTime::parse_from_str(&self.dateTime, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+        match self.timeZone.as_deref() {
+            None | Some("UTC") => Some(value),
+            Some(time_zone) => {
+                let time_zone = parse_time_zone(time_zone)?;
+                time_zone.from_local_datetime(&value).earliest().map(|value| value.naive_utc())
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API response
+struct OutlookLocation {
+    displayName: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API response
+struct OutlookRecipient {
+    emailAddress: OutlookEmailAddress,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API response
+struct OutlookAttendee {
+    emailAddress: OutlookEmailAddress,
+    r#type: Option<String>,
+    status: Option<OutlookResponseStatus>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OutlookResponseStatus {
+    response: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OutlookEmailAddress {
+    name: Option<String>,
+    address: Option<String>,
+}
+
+impl OutlookEmailAddress {
+    fn into_participant(self) -> Option<Participant> {
+        Some(Participant {
+            email: self.address?,
+            name: self.name,
+            sent_by: None,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph API response
+struct OutlookSubscription {
+    id: String,
+    expirationDateTime: String,
+}
+
+impl OutlookSubscription {
+    /**
+     * Graph answers with the expiry it accepted, which may be earlier than the one requested.
+     */
+    fn expires_at(&self) -> NaiveDateTime {
+        DateTime::parse_from_rfc3339(&self.expirationDateTime)
+            .map(|expiration| expiration.naive_utc())
+            .unwrap_or_else(|_| subscription_expiry().naive_utc())
     }
 }
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use axum::Json;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{availability::{self, slots::{self, AvailabilitySettings, Slot, SlotQuery}, BusyInterval}, middleware::AuthenticatedApp, models::group::Group, AppState};
+
+/**
+ * The longest range free/busy can be requested for at once.
+ */
+const MAX_FREEBUSY_DAYS: i64 = 90;
+
+#[derive(Deserialize)]
+pub struct FreeBusyQuery {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct FreeBusyResponse {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    busy: Vec<BusyInterval>,
+}
+
+/**
+ * Get the combined busy intervals of every calendar in a group. Only the stored events are
+ * used, so the result is as recent as the last sync of each calendar.
+ */
+pub async fn freebusy(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(group_id): axum::extract::Path<i32>,
+    axum::extract::Query(query): axum::extract::Query<FreeBusyQuery>,
+) -> Result<Json<FreeBusyResponse>, (StatusCode, String)> {
+    let Some(group) = find_group(group_id, &authenticated, &state) else {
+        return Err((StatusCode::NOT_FOUND, "Group not found".to_string()));
+    };
+
+    if query.end <= query.start {
+        return Err((StatusCode::BAD_REQUEST, "The end must be after the start".to_string()));
+    }
+    if query.end - query.start > Duration::days(MAX_FREEBUSY_DAYS) {
+        return Err((StatusCode::BAD_REQUEST, format!("The range cannot be longer than {} days", MAX_FREEBUSY_DAYS)));
+    }
+
+    let busy = availability::group_busy(&group, query.start, query.end, &mut state.get_connection());
+    Ok(Json::from(FreeBusyResponse {
+        start: query.start,
+        end: query.end,
+        busy,
+    }))
+}
+
+/**
+ * The longest slot that can be requested, in minutes.
+ */
+const MAX_SLOT_MINUTES: i64 = 24 * 60;
+
+#[derive(Deserialize)]
+pub struct SlotsQuery {
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    duration: i64,
+    interval: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct SlotsResponse {
+    slots: Vec<Slot>,
+}
+
+/**
+ * Get the bookable slots of a group for the requested duration, in minutes. Slots start every
+ * interval, which defaults to the duration. The range defaults to everything between now and
+ * the booking horizon of the group, a start in the past is moved to now.
+ */
+pub async fn slots(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(group_id): axum::extract::Path<i32>,
+    axum::extract::Query(query): axum::extract::Query<SlotsQuery>,
+) -> Result<Json<SlotsResponse>, (StatusCode, String)> {
+    let Some(group) = find_group(group_id, &authenticated, &state) else {
+        return Err((StatusCode::NOT_FOUND, "Group not found".to_string()));
+    };
+    let Some(settings) = AvailabilitySettings::from_group(&group) else {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, "The group has an unknown time zone".to_string()));
+    };
+
+    let interval = query.interval.unwrap_or(query.duration);
+    if !(1..=MAX_SLOT_MINUTES).contains(&query.duration) || !(1..=MAX_SLOT_MINUTES).contains(&interval) {
+        return Err((StatusCode::BAD_REQUEST, format!("The duration and interval must be between 1 and {} minutes", MAX_SLOT_MINUTES)));
+    }
+
+    let now = Utc::now();
+    // Slots in the past cannot be booked
+    let start = query.start.map_or(now, |start| start.max(now));
+    let end = query.end.unwrap_or(now + Duration::days(settings.max_days));
+    if end <= start {
+        return Err((StatusCode::BAD_REQUEST, "The end must be after the start".to_string()));
+    }
+    if end - start > Duration::days(slots::MAX_DAYS) {
+        return Err((StatusCode::BAD_REQUEST, format!("The range cannot be longer than {} days", slots::MAX_DAYS)));
+    }
+
+    let slots = slots::group_slots(&group, &settings, &SlotQuery {
+        start,
+        end,
+        duration: Duration::minutes(query.duration),
+        interval: Duration::minutes(interval),
+    }, now, &mut state.get_connection());
+    Ok(Json::from(SlotsResponse {
+        slots,
+    }))
+}
+
+/**
+ * Find a group by its id, ensuring it belongs to the authenticated app.
+ */
+fn find_group(group_id: i32, authenticated: &AuthenticatedApp, state: &Arc<AppState>) -> Option<Group> {
+    let group = Group::find_by_id(group_id, &mut state.get_connection())?;
+    if group.app_id != authenticated.app.id {
+        return None;
+    }
+    Some(group)
+}
\ No newline at end of file
@@ -0,0 +1,118 @@
+use std::{net::IpAddr, sync::Arc};
+
+use axum::Json;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{connectors::{caldav::caldav, ServiceType}, middleware::AuthenticatedApp, models::{caldav_integration::CaldavIntegration, group::Group, integration::Integration}, AppState};
+
+/**
+ * The server used when no URL is given.
+ */
+const ICLOUD_URL: &str = "https://caldav.icloud.com";
+
+#[derive(Deserialize)]
+pub struct CreateCaldavIntegration {
+    url: Option<String>,
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+pub struct CaldavIntegrationResponse {
+    integration_id: i32,
+}
+
+/**
+ * Connect a CalDAV account, such as iCloud with an app-specific password, to a group. The
+ * credentials are checked with the server before they are stored, which must be reached over
+ * https and may not be on a loopback or private network. The calendars are synced with the
+ * next scheduled sync, or by requesting a sync of the integration.
+ */
+pub async fn store(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(group_id): axum::extract::Path<i32>,
+    Json(body): Json<CreateCaldavIntegration>,
+) -> Result<Json<CaldavIntegrationResponse>, (StatusCode, String)> {
+    let Some(group) = Group::find_by_id(group_id, &mut state.get_connection()) else {
+        return Err((StatusCode::NOT_FOUND, "Group not found".to_string()));
+    };
+    if group.app_id != authenticated.app.id {
+        return Err((StatusCode::NOT_FOUND, "Group not found".to_string()));
+    }
+
+    let url = body.url.unwrap_or(ICLOUD_URL.to_string()).trim_end_matches('/').to_string();
+    if let Err(message) = check_server_url(&url).await {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, message));
+    }
+    if let Err(err) = caldav::get_principal(url.clone(), body.username.clone(), Some(body.password.clone())).await {
+        println!("{:?}", err);
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, "The server did not accept the credentials".to_string()));
+    }
+
+    let integration = Integration::new(&group, &mut state.get_connection(), ServiceType::Apple);
+    match CaldavIntegration::new(&integration, url, body.username, Some(body.password), &mut state.get_connection()) {
+        Ok(_) => Ok(Json::from(CaldavIntegrationResponse {
+            integration_id: integration.id,
+        })),
+        Err(err) => {
+            println!("{:?}", err);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to save the integration".to_string()))
+        },
+    }
+}
+
+/**
+ * Check that the URL is an https URL of a public server. Every address the host resolves to
+ * is checked, so a public name cannot be used to reach an internal service.
+ */
+async fn check_server_url(url: &str) -> Result<(), String> {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return Err("Invalid server URL".to_string());
+    };
+    if url.scheme() != "https" {
+        return Err("The server must be reached over https".to_string());
+    }
+    let Some(host) = url.host_str() else {
+        return Err("Invalid server URL".to_string());
+    };
+    // IPv6 addresses are written in brackets
+    let addresses = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(address) => vec![address],
+        Err(_) => match tokio::net::lookup_host((host, url.port_or_known_default().unwrap_or(443))).await {
+            Ok(addresses) => addresses.map(|address| address.ip()).collect(),
+            Err(_) => return Err("The server could not be found".to_string()),
+        },
+    };
+    if addresses.is_empty() || addresses.iter().any(|address| !is_public(address)) {
+        return Err("The server must not be on a private network".to_string());
+    }
+    Ok(())
+}
+
+fn is_public(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => {
+            let [first, second, ..] = address.octets();
+            !(address.is_loopback()
+                || address.is_private()
+                || address.is_link_local()
+                || address.is_unspecified()
+                || address.is_broadcast()
+                // Shared address space used by carrier-grade NAT
+                || (first == 100 && (64..128).contains(&second)))
+        },
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(address) => is_public(&IpAddr::V4(address)),
+            None => {
+                let first = address.segments()[0];
+                !(address.is_loopback()
+                    || address.is_unspecified()
+                    // Unique local and link-local addresses
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            },
+        },
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use axum::Json;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{import::{self, ImportError, ImportResult, ImportStatus}, middleware::AuthenticatedApp, models::{calendar::Calendar, event::{Event, EventStatus, EventTransparency, EventVisibility}}, recurrence::{self, Occurrence}, sync::SyncError, AppState};
+
+/**
+ * The longest range events can be listed for at once.
+ */
+const MAX_EVENTS_DAYS: i64 = 366;
+
+#[derive(Deserialize)]
+pub struct UpdateCalendar {
+    mirror: bool,
+}
+
+/**
+ * Select a calendar for mirroring or remove it from the selection. Selected calendars receive
+ * busy placeholders for the events of the other selected calendars of their group, and their
+ * own events are mirrored into those calendars.
+ */
+pub async fn update(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(calendar_id): axum::extract::Path<i32>,
+    Json(body): Json<UpdateCalendar>,
+) -> Result<Json<Calendar>, (StatusCode, String)> {
+    let Some(calendar) = find_calendar(calendar_id, &authenticated, &state) else {
+        return Err((StatusCode::NOT_FOUND, "Calendar not found".to_string()));
+    };
+
+    match calendar.update_mirror(body.mirror, &mut state.get_connection()) {
+        Ok(calendar) => Ok(Json::from(calendar)),
+        Err(err) => {
+            println!("{:?}", err);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to save the calendar".to_string()))
+        },
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+/**
+ * A single occurrence of an event. Instances of a recurring series share the external_id of
+ * their series and are told apart by their original_start.
+ */
+#[derive(Serialize)]
+pub struct EventInstance {
+    external_id: String,
+    recurring_event_id: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    location: Option<String>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    all_day: bool,
+    time_zone: Option<String>,
+    status: EventStatus,
+    transparency: EventTransparency,
+    visibility: EventVisibility,
+    original_start: Option<DateTime<Utc>>,
+}
+
+impl From<&Occurrence<'_>> for EventInstance {
+    fn from(occurrence: &Occurrence) -> Self {
+        let event: &Event = occurrence.event;
+        Self {
+            external_id: event.external_id.clone(),
+            recurring_event_id: event.recurring_event_id.clone(),
+            summary: event.summary.clone(),
+            description: event.description.clone(),
+            location: event.location.clone(),
+            start: occurrence.starts_at.and_utc(),
+            end: occurrence.ends_at.and_utc(),
+            all_day: event.all_day,
+            time_zone: event.time_zone.clone(),
+            status: event.status.clone(),
+            transparency: event.transparency.clone(),
+            visibility: event.visibility.clone(),
+            original_start: occurrence.original_starts_at.map(|value| value.and_utc()),
+        }
+    }
+}
+
+/**
+ * List the occurrences of the events of a calendar between start and end. Recurring events are
+ * expanded into their instances.
+ */
+pub async fn events(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(calendar_id): axum::extract::Path<i32>,
+    axum::extract::Query(query): axum::extract::Query<EventsQuery>,
+) -> Result<Json<Vec<EventInstance>>, (StatusCode, String)> {
+    let Some(calendar) = find_calendar(calendar_id, &authenticated, &state) else {
+        return Err((StatusCode::NOT_FOUND, "Calendar not found".to_string()));
+    };
+
+    if query.end <= query.start {
+        return Err((StatusCode::BAD_REQUEST, "The end must be after the start".to_string()));
+    }
+    if query.end - query.start > Duration::days(MAX_EVENTS_DAYS) {
+        return Err((StatusCode::BAD_REQUEST, format!("The range cannot be longer than {} days", MAX_EVENTS_DAYS)));
+    }
+
+    let (start, end) = (query.start.naive_utc(), query.end.naive_utc());
+    let events = Event::find_by_calendar_between(&calendar, start, end, &mut state.get_connection());
+    let instances: Vec<EventInstance> = recurrence::expand_events(&events, start, end)
+        .iter()
+        .map(EventInstance::from)
+        .collect();
+    Ok(Json::from(instances))
+}
+
+#[derive(Serialize)]
+pub struct ImportResponse {
+    imported: usize,
+    duplicates: usize,
+    failed: usize,
+    events: Vec<ImportResult>,
+}
+
+/**
+ * Import the events of an iCalendar file, sent as the request body, into a calendar. Every
+ * event of the file is reported on, events which were imported before are skipped.
+ */
+pub async fn import(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(calendar_id): axum::extract::Path<i32>,
+    body: String,
+) -> Result<Json<ImportResponse>, (StatusCode, String)> {
+    let Some(calendar) = find_calendar(calendar_id, &authenticated, &state) else {
+        return Err((StatusCode::NOT_FOUND, "Calendar not found".to_string()));
+    };
+
+    let events = match import::import_calendar(&calendar, &body, &state).await {
+        Ok(events) => events,
+        Err(ImportError::InvalidCalendar(err)) => return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid iCalendar data: {}", err))),
+        Err(ImportError::SyncError(SyncError::MissingCredentials)) => return Err((StatusCode::UNPROCESSABLE_ENTITY, "Integration has no credentials".to_string())),
+        Err(ImportError::SyncError(err)) => {
+            println!("{:?}", err);
+            return Err((StatusCode::BAD_GATEWAY, "Failed to connect to the service".to_string()));
+        },
+    };
+
+    let count = |status: ImportStatus| events.iter().filter(|event| event.status == status).count();
+    Ok(Json::from(ImportResponse {
+        imported: count(ImportStatus::Imported),
+        duplicates: count(ImportStatus::Duplicate),
+        failed: count(ImportStatus::Failed),
+        events,
+    }))
+}
+
+/**
+ * Find a calendar by its id, ensuring it belongs to a group of the authenticated app.
+ */
+fn find_calendar(calendar_id: i32, authenticated: &AuthenticatedApp, state: &Arc<AppState>) -> Option<Calendar> {
+    let calendar = Calendar::find_by_id(calendar_id, &mut state.get_connection())?;
+    let group = calendar.get_integration(&mut state.get_connection())?
+        .get_group(&mut state.get_connection())?;
+    if group.app_id != authenticated.app.id {
+        return None;
+    }
+    Some(calendar)
+}
\ No newline at end of file
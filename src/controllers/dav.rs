@@ -0,0 +1,579 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{extract::OriginalUri, http::{header, HeaderMap, HeaderValue, Method}, response::{IntoResponse, Response}};
+use base64::prelude::*;
+use chrono::{NaiveDateTime, Utc};
+use reqwest::StatusCode;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+
+use crate::{connectors::caldav::caldav::{Href, MultiStatus, PropfindResponse, Propstat}, ics, models::{event::Event, feed::Feed}, recurrence, AppState};
+
+use super::feed::{feed_event, find_feed_events};
+
+const DAV_NAMESPACE: &str = "DAV:";
+const CALDAV_NAMESPACE: &str = "urn:ietf:params:xml:ns:caldav";
+const CALENDARSERVER_NAMESPACE: &str = "http://calendarserver.org/ns/";
+
+const BASIC_PREFIX: &str = "Basic ";
+const DAV_CAPABILITIES: &str = "1, calendar-access";
+const ALLOWED_METHODS: &str = "OPTIONS, GET, HEAD, PROPFIND, REPORT";
+
+const STATUS_OK: &str = "HTTP/1.1 200 OK";
+const STATUS_NOT_FOUND: &str = "HTTP/1.1 404 Not Found";
+
+/**
+ * The name of the single calendar collection of a group.
+ */
+const CALENDAR_COLLECTION: &str = "calendar";
+
+/**
+ * The resources served under /dav. Every group is a principal whose calendar home holds a
+ * single calendar collection, with an iCalendar resource for every UID of the merged events.
+ */
+#[derive(Debug, Clone, PartialEq)]
+enum DavPath {
+    Root,
+    Principal(i32),
+    Calendar(i32),
+    Resource(i32, String),
+}
+
+impl DavPath {
+    fn parse(path: &str) -> Option<DavPath> {
+        let segments = path.strip_prefix("/dav")?
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<&str>>();
+
+        match segments.as_slice() {
+            [] => Some(DavPath::Root),
+            [group] => Some(DavPath::Principal(group.parse().ok()?)),
+            [group, CALENDAR_COLLECTION] => Some(DavPath::Calendar(group.parse().ok()?)),
+            [group, CALENDAR_COLLECTION, file] => Some(DavPath::Resource(group.parse().ok()?, decode_uid(file.strip_suffix(".ics")?)?)),
+            _ => None,
+        }
+    }
+
+    fn group_id(&self) -> Option<i32> {
+        match self {
+            DavPath::Root => None,
+            DavPath::Principal(group_id) | DavPath::Calendar(group_id) | DavPath::Resource(group_id, _) => Some(*group_id),
+        }
+    }
+}
+
+fn principal_href(group_id: i32) -> String {
+    format!("/dav/{}/", group_id)
+}
+
+fn calendar_href(group_id: i32) -> String {
+    format!("/dav/{}/{}/", group_id, CALENDAR_COLLECTION)
+}
+
+/**
+ * UIDs can contain any character, so resource names are the hex encoded UID.
+ */
+fn resource_href(group_id: i32, uid: &str) -> String {
+    let name = uid.bytes().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    format!("/dav/{}/{}/{}.ics", group_id, CALENDAR_COLLECTION, name)
+}
+
+fn decode_uid(name: &str) -> Option<String> {
+    if !name.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..name.len()).step_by(2)
+        .map(|index| u8::from_str_radix(name.get(index..index + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/**
+ * The events of a feed which share a UID, a series along with its overrides. The iCalendar
+ * data is written once, the same events are served as long as they do not change.
+ */
+struct DavResource {
+    uid: String,
+    events: Vec<Event>,
+    calendar_data: String,
+    etag: String,
+}
+
+/**
+ * Group the events of the feed into resources by their UID. When several calendars of the
+ * group hold the same event, the resource is taken from the first of them.
+ */
+fn find_resources(feed: &Feed, state: &Arc<AppState>) -> Vec<DavResource> {
+    let mut grouped: BTreeMap<String, (i32, Vec<Event>)> = BTreeMap::new();
+    for event in find_feed_events(feed, state) {
+        let uid = event.recurring_event_id.clone().unwrap_or_else(|| event.external_id.clone());
+        let (calendar_id, events) = grouped.entry(uid).or_insert_with(|| (event.calendar_id, Vec::new()));
+        if *calendar_id == event.calendar_id {
+            events.push(event);
+        }
+    }
+
+    grouped.into_iter()
+        .map(|(uid, (_, events))| {
+            let results = events.iter().map(|event| feed_event(feed, event)).collect::<Vec<_>>();
+            let calendar_data = ics::write_calendar(None, &results);
+            let etag = etag(&calendar_data);
+            DavResource { uid, events, calendar_data, etag }
+        })
+        .collect()
+}
+
+/**
+ * The ETag of iCalendar data. The DTSTAMP is the time the data was written, which would change
+ * the ETag on every request.
+ */
+fn etag(calendar_data: &str) -> String {
+    let mut hash = Fnv1a::default();
+    for line in calendar_data.split("\r\n").filter(|line| !line.starts_with("DTSTAMP:")) {
+        hash.write(line);
+    }
+    format!("\"{:016x}\"", hash.0)
+}
+
+/**
+ * The CTag of the calendar collection, which changes whenever any of its resources do.
+ */
+fn ctag(resources: &[DavResource]) -> String {
+    let mut hash = Fnv1a::default();
+    for resource in resources {
+        hash.write(&resource.uid);
+        hash.write(&resource.etag);
+    }
+    format!("{:016x}", hash.0)
+}
+
+/**
+ * The 64-bit FNV-1a hash. Clients keep ETags and CTags across restarts and upgrades of the
+ * server, so unlike the hashers of the standard library its output must never change.
+ */
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    /**
+     * Add a value to the hash, followed by a separator so consecutive values cannot run into
+     * each other.
+     */
+    fn write(&mut self, value: &str) {
+        for byte in value.bytes().chain(std::iter::once(0xff)) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/**
+ * The properties of the resources. Only the requested properties are written, properties which
+ * are not supported are left out of the response.
+ */
+#[derive(Debug, Default, Serialize)]
+struct DavProp {
+    #[serde(rename = "current-user-principal", skip_serializing_if = "Option::is_none")]
+    current_user_principal: Option<Href>,
+    #[serde(rename = "principal-URL", skip_serializing_if = "Option::is_none")]
+    principal_url: Option<Href>,
+    #[serde(rename = "C:calendar-home-set", skip_serializing_if = "Option::is_none")]
+    calendar_home_set: Option<Href>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resourcetype: Option<ResourceType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    displayname: Option<String>,
+    #[serde(rename = "C:supported-calendar-component-set", skip_serializing_if = "Option::is_none")]
+    supported_calendar_component_set: Option<ComponentSet>,
+    #[serde(rename = "CS:getctag", skip_serializing_if = "Option::is_none")]
+    getctag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    getetag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    getcontenttype: Option<String>,
+    #[serde(rename = "C:calendar-data", skip_serializing_if = "Option::is_none")]
+    calendar_data: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ResourceType {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    collection: Option<()>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    principal: Option<()>,
+    #[serde(rename = "C:calendar", skip_serializing_if = "Option::is_none")]
+    calendar: Option<()>,
+}
+
+#[derive(Debug, Serialize)]
+struct ComponentSet {
+    #[serde(rename = "C:comp")]
+    comp: Vec<ComponentName>,
+}
+
+#[derive(Debug, Serialize)]
+struct ComponentName {
+    #[serde(rename = "@name")]
+    name: String,
+}
+
+/**
+ * The properties named in a request. Prefixes are ignored when reading XML, so the element
+ * names match whichever prefix the client bound the namespaces to.
+ */
+#[derive(Debug, Default, Deserialize)]
+struct RequestedProps {
+    #[serde(rename = "current-user-principal")]
+    current_user_principal: Option<IgnoredAny>,
+    #[serde(rename = "principal-URL")]
+    principal_url: Option<IgnoredAny>,
+    #[serde(rename = "calendar-home-set")]
+    calendar_home_set: Option<IgnoredAny>,
+    resourcetype: Option<IgnoredAny>,
+    displayname: Option<IgnoredAny>,
+    #[serde(rename = "supported-calendar-component-set")]
+    supported_calendar_component_set: Option<IgnoredAny>,
+    getctag: Option<IgnoredAny>,
+    getetag: Option<IgnoredAny>,
+    getcontenttype: Option<IgnoredAny>,
+    #[serde(rename = "calendar-data")]
+    calendar_data: Option<IgnoredAny>,
+}
+
+impl DavProp {
+    /**
+     * Keep the requested properties. Without a list of properties, as for an allprop request,
+     * every property except the calendar data is kept.
+     */
+    fn select(self, requested: Option<&RequestedProps>) -> DavProp {
+        let Some(requested) = requested else {
+            return DavProp { calendar_data: None, ..self };
+        };
+        DavProp {
+            current_user_principal: self.current_user_principal.filter(|_| requested.current_user_principal.is_some()),
+            principal_url: self.principal_url.filter(|_| requested.principal_url.is_some()),
+            calendar_home_set: self.calendar_home_set.filter(|_| requested.calendar_home_set.is_some()),
+            resourcetype: self.resourcetype.filter(|_| requested.resourcetype.is_some()),
+            displayname: self.displayname.filter(|_| requested.displayname.is_some()),
+            supported_calendar_component_set: self.supported_calendar_component_set.filter(|_| requested.supported_calendar_component_set.is_some()),
+            getctag: self.getctag.filter(|_| requested.getctag.is_some()),
+            getetag: self.getetag.filter(|_| requested.getetag.is_some()),
+            getcontenttype: self.getcontenttype.filter(|_| requested.getcontenttype.is_some()),
+            calendar_data: self.calendar_data.filter(|_| requested.calendar_data.is_some()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PropfindRequest {
+    prop: Option<RequestedProps>,
+}
+
+/**
+ * The REPORTs which can be run on the calendar collection.
+ */
+#[derive(Debug, Deserialize)]
+enum ReportRequest {
+    #[serde(rename = "calendar-query")]
+    CalendarQuery(CalendarQueryRequest),
+    #[serde(rename = "calendar-multiget")]
+    CalendarMultiget(CalendarMultigetRequest),
+}
+
+#[derive(Debug, Deserialize)]
+struct CalendarQueryRequest {
+    prop: Option<RequestedProps>,
+    filter: Option<QueryFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CalendarMultigetRequest {
+    prop: Option<RequestedProps>,
+    #[serde(default)]
+    href: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryFilter {
+    #[serde(rename = "comp-filter")]
+    comp_filter: Option<QueryCompFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryCompFilter {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "comp-filter")]
+    comp_filter: Option<Box<QueryCompFilter>>,
+    #[serde(rename = "time-range")]
+    time_range: Option<TimeRange>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeRange {
+    #[serde(rename = "@start")]
+    start: Option<String>,
+    #[serde(rename = "@end")]
+    end: Option<String>,
+}
+
+/**
+ * What a calendar-query filter asks for. Only events are served, so a query for other
+ * components matches nothing.
+ */
+enum QueryMatch {
+    Nothing,
+    Events(Option<NaiveDateTime>, Option<NaiveDateTime>),
+}
+
+impl QueryFilter {
+    fn to_match(&self) -> Result<QueryMatch, (StatusCode, String)> {
+        let mut comp_filter = self.comp_filter.as_ref();
+        let mut range = (None, None);
+        while let Some(filter) = comp_filter {
+            if filter.name != "VCALENDAR" && filter.name != "VEVENT" {
+                return Ok(QueryMatch::Nothing);
+            }
+            if let Some(time_range) = &filter.time_range {
+                range = (
+                    time_range.start.as_deref().map(parse_time_range).transpose()?,
+                    time_range.end.as_deref().map(parse_time_range).transpose()?,
+                );
+            }
+            comp_filter = filter.comp_filter.as_deref();
+        }
+        Ok(QueryMatch::Events(range.0, range.1))
+    }
+}
+
+fn parse_time_range(value: &str) -> Result<NaiveDateTime, (StatusCode, String)> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid time-range {}", value)))
+}
+
+/**
+ * Serve the events of a group over CalDAV, so calendar apps can subscribe to the merged
+ * calendar of the group. Clients sign in with the id of the group as username and the token
+ * of one of its feeds as password. The privacy and window of that feed apply, and the calendar
+ * cannot be changed.
+ */
+pub async fn handle(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, (StatusCode, String)> {
+    if method == Method::OPTIONS {
+        return Ok((StatusCode::OK, [("DAV", DAV_CAPABILITIES), (header::ALLOW.as_str(), ALLOWED_METHODS)]).into_response());
+    }
+
+    let Some(feed) = authenticate(&headers, &state) else {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"schedsync\"")],
+            "Unauthorized",
+        ).into_response());
+    };
+
+    let path = DavPath::parse(uri.path())
+        .filter(|path| path.group_id().is_none_or(|group_id| group_id == feed.group_id));
+    let Some(path) = path else {
+        return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+    };
+
+    match (method.as_str(), &path) {
+        ("PROPFIND", _) => propfind(&feed, &path, &headers, &body, &state),
+        ("REPORT", DavPath::Calendar(_)) => report(&feed, &body, &state),
+        ("GET" | "HEAD", DavPath::Resource(_, uid)) => {
+            let resources = find_resources(&feed, &state);
+            let Some(resource) = resources.into_iter().find(|resource| &resource.uid == uid) else {
+                return Err((StatusCode::NOT_FOUND, "Event not found".to_string()));
+            };
+            let Ok(etag) = HeaderValue::from_str(&resource.etag) else {
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Invalid ETag".to_string()));
+            };
+            Ok((
+                [(header::CONTENT_TYPE, HeaderValue::from_static("text/calendar; charset=utf-8")), (header::ETAG, etag)],
+                resource.calendar_data,
+            ).into_response())
+        },
+        _ => Ok((StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOWED_METHODS)], "The calendar is read-only").into_response()),
+    }
+}
+
+/**
+ * Find the feed for the Basic credentials of the request.
+ */
+fn authenticate(headers: &HeaderMap, state: &Arc<AppState>) -> Option<Feed> {
+    let authorization = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = authorization.strip_prefix(BASIC_PREFIX)?;
+    let credential = String::from_utf8(BASE64_STANDARD.decode(encoded).ok()?).ok()?;
+    let (group_id, token) = credential.split_once(':')?;
+    Feed::find_by_token(group_id.parse().ok()?, token, &mut state.get_connection())
+}
+
+fn propfind(feed: &Feed, path: &DavPath, headers: &HeaderMap, body: &str, state: &Arc<AppState>) -> Result<Response, (StatusCode, String)> {
+    let request = match body.trim().is_empty() {
+        true => PropfindRequest::default(),
+        false => quick_xml::de::from_str::<PropfindRequest>(body)
+            .map_err(|err| (StatusCode::BAD_REQUEST, format!("Invalid PROPFIND: {}", err)))?,
+    };
+    let requested = request.prop.as_ref();
+    let depth = headers.get("Depth").and_then(|depth| depth.to_str().ok()).unwrap_or("infinity");
+    let children = depth != "0";
+    let group_id = feed.group_id;
+
+    let mut responses = Vec::new();
+    match path {
+        DavPath::Root => {
+            responses.push(response(String::from("/dav/"), DavProp {
+                current_user_principal: Some(Href { href: principal_href(group_id) }),
+                resourcetype: Some(ResourceType { collection: Some(()), ..Default::default() }),
+                ..Default::default()
+            }.select(requested)));
+            if children {
+                responses.push(response(principal_href(group_id), principal_prop(group_id).select(requested)));
+            }
+        },
+        DavPath::Principal(_) => {
+            responses.push(response(principal_href(group_id), principal_prop(group_id).select(requested)));
+            if children {
+                let resources = find_resources(feed, state);
+                responses.push(response(calendar_href(group_id), calendar_prop(group_id, &resources).select(requested)));
+            }
+        },
+        DavPath::Calendar(_) => {
+            let resources = find_resources(feed, state);
+            responses.push(response(calendar_href(group_id), calendar_prop(group_id, &resources).select(requested)));
+            if children {
+                for resource in &resources {
+                    responses.push(response(resource_href(group_id, &resource.uid), resource_prop(resource).select(requested)));
+                }
+            }
+        },
+        DavPath::Resource(_, uid) => {
+            let resources = find_resources(feed, state);
+            let Some(resource) = resources.iter().find(|resource| &resource.uid == uid) else {
+                return Err((StatusCode::NOT_FOUND, "Event not found".to_string()));
+            };
+            responses.push(response(resource_href(group_id, uid), resource_prop(resource).select(requested)));
+        },
+    }
+
+    multistatus(responses)
+}
+
+fn report(feed: &Feed, body: &str, state: &Arc<AppState>) -> Result<Response, (StatusCode, String)> {
+    let Ok(request) = quick_xml::de::from_str::<ReportRequest>(body) else {
+        return Err((StatusCode::FORBIDDEN, "Unsupported REPORT".to_string()));
+    };
+    let group_id = feed.group_id;
+    let resources = find_resources(feed, state);
+
+    let mut responses = Vec::new();
+    match request {
+        ReportRequest::CalendarQuery(query) => {
+            let query_match = match &query.filter {
+                Some(filter) => filter.to_match()?,
+                None => QueryMatch::Events(None, None),
+            };
+            let QueryMatch::Events(start, end) = query_match else {
+                return multistatus(responses);
+            };
+            let (window_start, window_end) = feed.window(Utc::now().naive_utc());
+            for resource in &resources {
+                if start.is_some() || end.is_some() {
+                    let start = start.unwrap_or(window_start);
+                    let end = end.unwrap_or(window_end);
+                    if recurrence::expand_events(&resource.events, start, end).is_empty() {
+                        continue;
+                    }
+                }
+                responses.push(response(resource_href(group_id, &resource.uid), resource_prop(resource).select(query.prop.as_ref())));
+            }
+        },
+        ReportRequest::CalendarMultiget(multiget) => {
+            for href in multiget.href {
+                let resource = DavPath::parse(&href)
+                    .and_then(|path| match path {
+                        DavPath::Resource(resource_group_id, uid) if resource_group_id == group_id => Some(uid),
+                        _ => None,
+                    })
+                    .and_then(|uid| resources.iter().find(|resource| resource.uid == uid));
+                match resource {
+                    Some(resource) => responses.push(response(href, resource_prop(resource).select(multiget.prop.as_ref()))),
+                    None => responses.push(PropfindResponse {
+                        href,
+                        propstat: vec![Propstat { prop: None, status: STATUS_NOT_FOUND.to_string() }],
+                    }),
+                }
+            }
+        },
+    }
+
+    multistatus(responses)
+}
+
+fn principal_prop(group_id: i32) -> DavProp {
+    DavProp {
+        current_user_principal: Some(Href { href: principal_href(group_id) }),
+        principal_url: Some(Href { href: principal_href(group_id) }),
+        calendar_home_set: Some(Href { href: principal_href(group_id) }),
+        resourcetype: Some(ResourceType { collection: Some(()), principal: Some(()), ..Default::default() }),
+        displayname: Some(format!("Group {}", group_id)),
+        ..Default::default()
+    }
+}
+
+fn calendar_prop(group_id: i32, resources: &[DavResource]) -> DavProp {
+    DavProp {
+        resourcetype: Some(ResourceType { collection: Some(()), calendar: Some(()), ..Default::default() }),
+        displayname: Some(format!("Group {}", group_id)),
+        supported_calendar_component_set: Some(ComponentSet {
+            comp: vec![ComponentName { name: "VEVENT".to_string() }],
+        }),
+        getctag: Some(ctag(resources)),
+        ..Default::default()
+    }
+}
+
+fn resource_prop(resource: &DavResource) -> DavProp {
+    DavProp {
+        resourcetype: Some(ResourceType::default()),
+        getetag: Some(resource.etag.clone()),
+        getcontenttype: Some("text/calendar; charset=utf-8".to_string()),
+        calendar_data: Some(resource.calendar_data.clone()),
+        ..Default::default()
+    }
+}
+
+fn response(href: String, prop: DavProp) -> PropfindResponse<DavProp> {
+    PropfindResponse {
+        href,
+        propstat: vec![Propstat { prop: Some(prop), status: STATUS_OK.to_string() }],
+    }
+}
+
+fn multistatus(responses: Vec<PropfindResponse<DavProp>>) -> Result<Response, (StatusCode, String)> {
+    let body = MultiStatus {
+        xmlns: Some(DAV_NAMESPACE.to_string()),
+        xmlns_caldav: Some(CALDAV_NAMESPACE.to_string()),
+        xmlns_calendarserver: Some(CALENDARSERVER_NAMESPACE.to_string()),
+        response: responses,
+    };
+    let Ok(xml) = quick_xml::se::to_string(&body) else {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to write the response".to_string()));
+    };
+
+    Ok((
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>{}", xml),
+    ).into_response())
+}
@@ -0,0 +1,164 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{http::header, response::IntoResponse, Json};
+use chrono::Utc;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{ics, middleware::AuthenticatedApp, mirror::PLACEHOLDER_DESCRIPTION, models::{event::{Attendees, Event, EventResult}, feed::{Feed, FeedPrivacy}, group::Group}, AppState};
+
+/**
+ * The furthest a feed can reach into the past or the future.
+ */
+const MAX_FEED_DAYS: i32 = 730;
+
+/**
+ * The title of every event in a feed which only shows busy time.
+ */
+const BUSY_SUMMARY: &str = "Busy";
+
+#[derive(Deserialize)]
+pub struct CreateFeed {
+    #[serde(default)]
+    privacy: FeedPrivacy,
+    past_days: Option<i32>,
+    future_days: Option<i32>,
+}
+
+/**
+ * Create a subscription feed for a group. The token in the response is part of the feed URL,
+ * /feed/{group_id}/{token}.ics, and is not shown again.
+ */
+pub async fn store(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(group_id): axum::extract::Path<i32>,
+    Json(body): Json<CreateFeed>,
+) -> Result<Json<Feed>, (StatusCode, String)> {
+    let Some(group) = Group::find_by_id(group_id, &mut state.get_connection()) else {
+        return Err((StatusCode::NOT_FOUND, "Group not found".to_string()));
+    };
+    if group.app_id != authenticated.app.id {
+        return Err((StatusCode::NOT_FOUND, "Group not found".to_string()));
+    }
+
+    let past_days = body.past_days.unwrap_or(30);
+    let future_days = body.future_days.unwrap_or(365);
+    if !(0..=MAX_FEED_DAYS).contains(&past_days) || !(1..=MAX_FEED_DAYS).contains(&future_days) {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("A feed can reach up to {} days into the past and future", MAX_FEED_DAYS)));
+    }
+
+    let token = Uuid::new_v4().simple().to_string();
+    match Feed::new(&group, token, body.privacy, past_days, future_days, &mut state.get_connection()) {
+        Ok(feed) => Ok(Json::from(feed)),
+        Err(err) => {
+            println!("{:?}", err);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to save the feed".to_string()))
+        },
+    }
+}
+
+/**
+ * Delete a feed, after which its URL stops working.
+ */
+pub async fn destroy(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(feed_id): axum::extract::Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let Some(feed) = Feed::find_by_id(feed_id, &mut state.get_connection()) else {
+        return Err((StatusCode::NOT_FOUND, "Feed not found".to_string()));
+    };
+    if feed.get_group(&mut state.get_connection()).app_id != authenticated.app.id {
+        return Err((StatusCode::NOT_FOUND, "Feed not found".to_string()));
+    }
+
+    match feed.delete(&mut state.get_connection()) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(err) => {
+            println!("{:?}", err);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete the feed".to_string()))
+        },
+    }
+}
+
+/**
+ * Serve the events of every calendar of the group within the window of the feed as a single
+ * iCalendar object. The token authenticates the request, calendar apps cannot send any other
+ * credentials when subscribing.
+ */
+pub async fn show(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Path((group_id, file)): axum::extract::Path<(i32, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let feed = file.strip_suffix(".ics")
+        .and_then(|token| Feed::find_by_token(group_id, token, &mut state.get_connection()));
+    let Some(feed) = feed else {
+        return Err((StatusCode::NOT_FOUND, "Feed not found".to_string()));
+    };
+
+    let events = feed_events(&feed, &state);
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        ics::write_calendar(None, &events),
+    ))
+}
+
+/**
+ * The events of the feed. Recurring events are written as their series along with their
+ * overrides, so subscribers expand them themselves.
+ */
+fn feed_events(feed: &Feed, state: &Arc<AppState>) -> Vec<EventResult> {
+    find_feed_events(feed, state)
+        .iter()
+        .map(|event| feed_event(feed, event))
+        .collect()
+}
+
+/**
+ * Find the events within the window of the feed. Busy placeholders are left out, the event
+ * they mirror is in the feed already.
+ *
+ * Instances whose series is not stored, such as the occurrences Outlook syncs, are detached
+ * from it and written as events of their own. Clients drop a RECURRENCE-ID without a series.
+ */
+pub(crate) fn find_feed_events(feed: &Feed, state: &Arc<AppState>) -> Vec<Event> {
+    let group = feed.get_group(&mut state.get_connection());
+    let (start, end) = feed.window(Utc::now().naive_utc());
+
+    let mut events = Event::find_by_group_between(&group, start, end, &mut state.get_connection())
+        .into_iter()
+        .filter(|event| event.description.as_deref() != Some(PLACEHOLDER_DESCRIPTION))
+        .collect::<Vec<Event>>();
+
+    let series = events.iter()
+        .filter(|event| event.recurrence.is_some())
+        .map(|event| (event.calendar_id, event.external_id.clone()))
+        .collect::<HashSet<(i32, String)>>();
+    for event in events.iter_mut() {
+        let Some(recurring_event_id) = &event.recurring_event_id else {
+            continue;
+        };
+        if !series.contains(&(event.calendar_id, recurring_event_id.clone())) {
+            event.recurring_event_id = None;
+            event.original_starts_at = None;
+        }
+    }
+    events
+}
+
+/**
+ * An event as the feed shows it, without its details when the feed only shows busy time.
+ */
+pub(crate) fn feed_event(feed: &Feed, event: &Event) -> EventResult {
+    let mut result = EventResult::from(event);
+    if feed.privacy == FeedPrivacy::Busy {
+        result.summary = Some(BUSY_SUMMARY.to_string());
+        result.description = None;
+        result.location = None;
+        result.organizer = None;
+        result.attendees = Attendees::default();
+    }
+    result
+}
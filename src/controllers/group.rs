@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
 use axum::Json;
+use chrono_tz::Tz;
 use reqwest::StatusCode;
+use serde::Deserialize;
 
-use crate::{middleware::AuthenticatedApp, models::group::Group, AppState};
+use crate::{availability::slots, middleware::AuthenticatedApp, models::group::{Group, WorkingHours}, AppState};
 
 pub async fn store(
     axum::extract::State(state): axum::extract::State<Arc<AppState>>,
@@ -11,4 +13,66 @@ pub async fn store(
 ) -> Result<Json<Group>, StatusCode> {
     let group = authenticated.app.create_group(&mut state.get_connection());
     Ok(Json::from(group))
+}
+
+#[derive(Deserialize)]
+pub struct UpdateGroup {
+    mirroring: Option<bool>,
+    time_zone: Option<String>,
+    working_hours: Option<WorkingHours>,
+    buffer_before: Option<i32>,
+    buffer_after: Option<i32>,
+    minimum_notice: Option<i32>,
+    max_days: Option<i32>,
+}
+
+/**
+ * Update the settings of a group. Only the settings present in the body are changed. Turning
+ * mirroring on or off takes effect with the next sync of each calendar.
+ */
+pub async fn update(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(group_id): axum::extract::Path<i32>,
+    Json(body): Json<UpdateGroup>,
+) -> Result<Json<Group>, (StatusCode, String)> {
+    let Some(mut group) = Group::find_by_id(group_id, &mut state.get_connection()) else {
+        return Err((StatusCode::NOT_FOUND, "Group not found".to_string()));
+    };
+    if group.app_id != authenticated.app.id {
+        return Err((StatusCode::NOT_FOUND, "Group not found".to_string()));
+    }
+
+    if let Some(time_zone) = body.time_zone {
+        if time_zone.parse::<Tz>().is_err() {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, "Unknown time zone".to_string()));
+        }
+        group.time_zone = time_zone;
+    }
+    if let Some(working_hours) = body.working_hours {
+        if working_hours.0.iter().any(|period| period.start >= period.end) {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, "Working hours must end after they start".to_string()));
+        }
+        group.working_hours = working_hours;
+    }
+    if [body.buffer_before, body.buffer_after, body.minimum_notice].iter().flatten().any(|minutes| *minutes < 0) {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, "Buffers and notice cannot be negative".to_string()));
+    }
+    if body.max_days.is_some_and(|max_days| !(1..=slots::MAX_DAYS).contains(&i64::from(max_days))) {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("Max days must be between 1 and {}", slots::MAX_DAYS)));
+    }
+
+    group.mirroring = body.mirroring.unwrap_or(group.mirroring);
+    group.buffer_before = body.buffer_before.unwrap_or(group.buffer_before);
+    group.buffer_after = body.buffer_after.unwrap_or(group.buffer_after);
+    group.minimum_notice = body.minimum_notice.unwrap_or(group.minimum_notice);
+    group.max_days = body.max_days.unwrap_or(group.max_days);
+
+    match group.save(&mut state.get_connection()) {
+        Ok(group) => Ok(Json::from(group)),
+        Err(err) => {
+            println!("{:?}", err);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to save the group".to_string()))
+        },
+    }
 }
\ No newline at end of file
@@ -1,5 +1,12 @@
 pub mod oauth2;
 pub mod group;
+pub mod calendar;
+pub mod availability;
+pub mod feed;
+pub mod caldav;
+pub mod dav;
+pub mod update;
+pub mod webhook;
 
 pub enum RequestError {
     BearerTokenMissing,
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use axum::Json;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{middleware::AuthenticatedApp, models::{calendar::Calendar, event::EventSyncResult, integration::Integration}, sync::{self, IntegrationSyncResult, SyncError}, AppState};
+
+/**
+ * Sync the calendars and events of an integration.
+ */
+pub async fn calendar(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Path(integration_id): axum::extract::Path<i32>,
+) -> Result<Json<IntegrationSyncResult>, (StatusCode, String)> {
+    let Some(integration) = find_integration(integration_id, &authenticated, &state) else {
+        return Err((StatusCode::NOT_FOUND, "Integration not found".to_string()));
+    };
+
+    match sync::sync_integration(&integration, &state).await {
+        Ok(result) => Ok(Json::from(result)),
+        Err(err) => Err(sync_error_response(err)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EventQuery {
+    calendar: i32,
+}
+
+/**
+ * Sync the events of a single calendar.
+ */
+pub async fn event(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Extension(authenticated): axum::extract::Extension<AuthenticatedApp>,
+    axum::extract::Query(query): axum::extract::Query<EventQuery>,
+) -> Result<Json<EventSyncResult>, (StatusCode, String)> {
+    let Some(calendar) = Calendar::find_by_id(query.calendar, &mut state.get_connection()) else {
+        return Err((StatusCode::NOT_FOUND, "Calendar not found".to_string()));
+    };
+
+    // The calendar must belong to an integration of the authenticated app
+    if find_integration(calendar.integration_id, &authenticated, &state).is_none() {
+        return Err((StatusCode::NOT_FOUND, "Calendar not found".to_string()));
+    }
+
+    match sync::sync_calendar(&calendar, &state).await {
+        Ok(result) => Ok(Json::from(result)),
+        Err(err) => Err(sync_error_response(err)),
+    }
+}
+
+/**
+ * Find an integration by its id, ensuring it belongs to a group of the authenticated app.
+ */
+fn find_integration(integration_id: i32, authenticated: &AuthenticatedApp, state: &Arc<AppState>) -> Option<Integration> {
+    let integration = Integration::find_by_id(integration_id, &mut state.get_connection())?;
+    let group = integration.get_group(&mut state.get_connection())?;
+    if group.app_id != authenticated.app.id {
+        return None;
+    }
+    Some(integration)
+}
+
+/**
+ * Map a sync error to the response returned to the client.
+ */
+fn sync_error_response(err: SyncError) -> (StatusCode, String) {
+    match err {
+        SyncError::UnsupportedService => (StatusCode::NOT_IMPLEMENTED, "Syncing is not supported for this service".to_string()),
+        SyncError::MissingCredentials => (StatusCode::UNPROCESSABLE_ENTITY, "Integration has no credentials".to_string()),
+        SyncError::ConnectorError(err) => {
+            println!("{:?}", err);
+            (StatusCode::BAD_GATEWAY, "Failed to sync with the service".to_string())
+        },
+        SyncError::CaldavError(err) => {
+            println!("{:?}", err);
+            (StatusCode::BAD_GATEWAY, "Failed to sync with the service".to_string())
+        },
+        SyncError::DatabaseError(err) => {
+            println!("{:?}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to save the sync result".to_string())
+        },
+    }
+}
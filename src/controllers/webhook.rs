@@ -0,0 +1,129 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{body::Bytes, http::{header, HeaderMap}, response::{IntoResponse, Response}};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{connectors::oauth2::Oauth2Service, helper, models::watch_channel::WatchChannel, sync, AppState};
+
+/**
+ * Receive a push notification from Google. The calendar of the channel is synced in the
+ * background so Google gets its acknowledgement right away.
+ */
+pub async fn google(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> StatusCode {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let Some(channel_id) = header("X-Goog-Channel-ID") else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    let Some(channel) = WatchChannel::find_by_external_id(channel_id, &mut state.get_connection()) else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    // The token proves the notification belongs to a channel we registered
+    if channel.service != Oauth2Service::Google || !helper::secure_eq(header("X-Goog-Channel-Token").unwrap_or(""), &channel.token) {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // The first notification of a channel only confirms it was created
+    if header("X-Goog-Resource-State") == Some("sync") {
+        return StatusCode::OK;
+    }
+
+    let calendar = channel.get_calendar(&mut state.get_connection());
+    tokio::spawn(async move {
+        if let Err(err) = sync::sync_calendar(&calendar, &state).await {
+            println!("Failed to sync calendar {}: {:?}", calendar.id, err);
+        }
+    });
+
+    StatusCode::OK
+}
+
+#[derive(Deserialize)]
+pub struct OutlookWebhookQuery {
+    #[serde(rename = "validationToken")]
+    validation_token: Option<String>,
+}
+
+/**
+ * Receive change and lifecycle notifications from Microsoft Graph. Every notification is handled
+ * in the background so Graph gets its acknowledgement right away.
+ */
+pub async fn outlook(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<OutlookWebhookQuery>,
+    body: Bytes,
+) -> Response {
+    // Graph validates the URL of a new subscription by asking for the token to be echoed back
+    if let Some(validation_token) = query.validation_token {
+        return (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain")], validation_token).into_response();
+    }
+
+    let Ok(notifications) = serde_json::from_slice::<OutlookNotifications>(&body) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let mut synced_calendars = HashSet::new();
+    for notification in notifications.value {
+        let Some(channel) = WatchChannel::find_by_external_id(&notification.subscriptionId, &mut state.get_connection()) else {
+            continue;
+        };
+
+        // The client state proves the notification belongs to a subscription we created
+        if channel.service != Oauth2Service::Outlook || !helper::secure_eq(notification.clientState.as_deref().unwrap_or(""), &channel.token) {
+            println!("Ignoring notification with an invalid client state for subscription {}", channel.external_id);
+            continue;
+        }
+
+        let state = state.clone();
+        match notification.lifecycleEvent.as_deref() {
+            Some("reauthorizationRequired") => {
+                tokio::spawn(async move {
+                    if let Err(err) = sync::renew_channel(&channel, &state).await {
+                        println!("Failed to reauthorize subscription {}: {:?}", channel.external_id, err);
+                    }
+                });
+            },
+            Some("subscriptionRemoved") => {
+                tokio::spawn(async move {
+                    if let Err(err) = sync::replace_channel(&channel, &state).await {
+                        println!("Failed to replace subscription {}: {:?}", channel.external_id, err);
+                    }
+                });
+            },
+            // Changes and missed notifications both call for a delta sync, once per calendar
+            _ => {
+                if !synced_calendars.insert(channel.calendar_id) {
+                    continue;
+                }
+                let calendar = channel.get_calendar(&mut state.get_connection());
+                tokio::spawn(async move {
+                    if let Err(err) = sync::sync_calendar(&calendar, &state).await {
+                        println!("Failed to sync calendar {}: {:?}", calendar.id, err);
+                    }
+                });
+            },
+        }
+    }
+
+    StatusCode::ACCEPTED.into_response()
+}
+
+#[derive(Deserialize, Debug)]
+struct OutlookNotifications {
+    #[serde(default)]
+    value: Vec<OutlookNotification>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)] // Allow camel case for Graph notifications
+struct OutlookNotification {
+    subscriptionId: String,
+    clientState: Option<String>,
+    lifecycleEvent: Option<String>,
+}
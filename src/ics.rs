@@ -0,0 +1,488 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+
+use crate::{connectors::caldav::datetime::iana_time_zone, models::{event::{AttendeeStatus, EventResult, EventStatus, EventTransparency, EventVisibility, ParticipantKind, ParticipantRole}, task::{TaskResult, TaskStatus}}};
+
+const PRODID: &str = "-//schedsync//schedsync-api//EN";
+
+/**
+ * Lines longer than this many octets are folded onto continuation lines.
+ */
+const MAX_LINE_OCTETS: usize = 75;
+
+/**
+ * Serialize events into an iCalendar object. Instances of a recurring event are written with
+ * the UID of their series and a RECURRENCE-ID, so they can share an object with their series.
+ * Events with a time zone are written in its local time, with a VTIMEZONE for every zone.
+ */
+pub fn write_calendar(name: Option<&str>, events: &[EventResult]) -> String {
+    let mut writer = IcsWriter::default();
+    writer.line("BEGIN:VCALENDAR");
+    writer.line("VERSION:2.0");
+    writer.property("PRODID", &[], PRODID);
+    writer.line("CALSCALE:GREGORIAN");
+    if let Some(name) = name {
+        writer.property("X-WR-CALNAME", &[], &escape_text(name));
+    }
+    write_time_zones(&mut writer, events);
+    for event in events {
+        write_event(&mut writer, event);
+    }
+    writer.line("END:VCALENDAR");
+    writer.output
+}
+
+/**
+ * The UID an event is written with.
+ */
+pub fn event_uid(event: &EventResult) -> &str {
+    event.recurring_event_id.as_deref().unwrap_or(&event.external_id)
+}
+
+fn write_event(writer: &mut IcsWriter, event: &EventResult) {
+    writer.line("BEGIN:VEVENT");
+    writer.property("UID", &[], &escape_text(event_uid(event)));
+    writer.property("DTSTAMP", &[], &format_datetime(Utc::now().naive_utc()));
+
+    // Recurrence rules are expanded in the zone of DTSTART, so a series keeps its local time
+    let time_zone = event_time_zone(event);
+    if let (Some(_), Some(original_starts_at)) = (&event.recurring_event_id, event.original_starts_at) {
+        write_zoned_datetime(writer, "RECURRENCE-ID", original_starts_at, event.all_day, time_zone);
+    }
+    write_zoned_datetime(writer, "DTSTART", event.starts_at, event.all_day, time_zone);
+    write_zoned_datetime(writer, "DTEND", event.ends_at, event.all_day, time_zone);
+
+    if let Some(summary) = &event.summary {
+        writer.property("SUMMARY", &[], &escape_text(summary));
+    }
+    if let Some(description) = &event.description {
+        writer.property("DESCRIPTION", &[], &escape_text(description));
+    }
+    if let Some(location) = &event.location {
+        writer.property("LOCATION", &[], &escape_text(location));
+    }
+
+    writer.property("STATUS", &[], match event.status {
+        EventStatus::Confirmed => "CONFIRMED",
+        EventStatus::Tentative => "TENTATIVE",
+        EventStatus::Cancelled => "CANCELLED",
+    });
+    writer.property("TRANSP", &[], match event.transparency {
+        EventTransparency::Opaque => "OPAQUE",
+        EventTransparency::Transparent => "TRANSPARENT",
+    });
+    match event.visibility {
+        EventVisibility::Default => {},
+        EventVisibility::Public => writer.property("CLASS", &[], "PUBLIC"),
+        EventVisibility::Private => writer.property("CLASS", &[], "PRIVATE"),
+        EventVisibility::Confidential => writer.property("CLASS", &[], "CONFIDENTIAL"),
+    }
+
+    if let Some(organizer) = &event.organizer {
+        let sent_by = organizer.sent_by.as_ref().map(|address| format!("mailto:{}", address));
+        let mut params = Vec::new();
+        if let Some(name) = &organizer.name {
+            params.push(("CN", name.as_str()));
+        }
+        if let Some(sent_by) = &sent_by {
+            params.push(("SENT-BY", sent_by.as_str()));
+        }
+        writer.property("ORGANIZER", &params, &format!("mailto:{}", organizer.email));
+    }
+
+    for attendee in event.attendees.0.iter() {
+        let mailto = |addresses: &[String]| addresses.iter()
+            .map(|address| format!("mailto:{}", address))
+            .collect::<Vec<String>>();
+        let delegated_to = mailto(&attendee.delegated_to);
+        let delegated_from = mailto(&attendee.delegated_from);
+        let sent_by = mailto(attendee.sent_by.as_slice());
+
+        let mut params = Vec::new();
+        if let Some(name) = &attendee.name {
+            params.push(("CN", name.as_str()));
+        }
+        params.push(("PARTSTAT", match attendee.status {
+            AttendeeStatus::NeedsAction => "NEEDS-ACTION",
+            AttendeeStatus::Accepted => "ACCEPTED",
+            AttendeeStatus::Declined => "DECLINED",
+            AttendeeStatus::Tentative => "TENTATIVE",
+        }));
+        params.push(("ROLE", match attendee.role {
+            ParticipantRole::Chair => "CHAIR",
+            ParticipantRole::NonParticipant => "NON-PARTICIPANT",
+            _ if attendee.optional => "OPT-PARTICIPANT",
+            _ => "REQ-PARTICIPANT",
+        }));
+        if attendee.rsvp {
+            params.push(("RSVP", "TRUE"));
+        }
+        match attendee.kind {
+            ParticipantKind::Individual => {},
+            ParticipantKind::Group => params.push(("CUTYPE", "GROUP")),
+            ParticipantKind::Resource => params.push(("CUTYPE", "RESOURCE")),
+            ParticipantKind::Room => params.push(("CUTYPE", "ROOM")),
+            ParticipantKind::Unknown => params.push(("CUTYPE", "UNKNOWN")),
+        }
+        params.extend(delegated_to.iter().map(|address| ("DELEGATED-TO", address.as_str())));
+        params.extend(delegated_from.iter().map(|address| ("DELEGATED-FROM", address.as_str())));
+        params.extend(sent_by.iter().map(|address| ("SENT-BY", address.as_str())));
+        writer.property("ATTENDEE", &params, &format!("mailto:{}", attendee.email));
+    }
+
+    // Recurrence is stored as complete iCalendar lines already
+    if let Some(recurrence) = &event.recurrence {
+        recurrence.lines()
+            .filter(|line| !line.trim().is_empty())
+            .for_each(|line| writer.line(line.trim()));
+    }
+
+    writer.line("END:VEVENT");
+}
+
+/**
+ * Serialize tasks into an iCalendar object of VTODOs. Occurrences of a recurring task are
+ * written with the UID of their series and a RECURRENCE-ID, like those of events.
+ */
+pub fn write_tasks(tasks: &[TaskResult]) -> String {
+    let mut writer = IcsWriter::default();
+    writer.line("BEGIN:VCALENDAR");
+    writer.line("VERSION:2.0");
+    writer.property("PRODID", &[], PRODID);
+    for task in tasks {
+        write_task(&mut writer, task);
+    }
+    writer.line("END:VCALENDAR");
+    writer.output
+}
+
+fn write_task(writer: &mut IcsWriter, task: &TaskResult) {
+    writer.line("BEGIN:VTODO");
+    writer.property("UID", &[], &escape_text(task.recurring_task_id.as_deref().unwrap_or(&task.external_id)));
+    writer.property("DTSTAMP", &[], &format_datetime(Utc::now().naive_utc()));
+
+    if let (Some(_), Some(original_starts_at)) = (&task.recurring_task_id, task.original_starts_at) {
+        write_datetime(writer, "RECURRENCE-ID", original_starts_at, task.all_day);
+    }
+    if let Some(starts_at) = task.starts_at {
+        write_datetime(writer, "DTSTART", starts_at, task.all_day);
+    }
+    if let Some(due_at) = task.due_at {
+        write_datetime(writer, "DUE", due_at, task.all_day);
+    }
+    // COMPLETED is always a UTC date and time
+    if let Some(completed_at) = task.completed_at {
+        writer.property("COMPLETED", &[], &format_datetime(completed_at));
+    }
+
+    if let Some(summary) = &task.summary {
+        writer.property("SUMMARY", &[], &escape_text(summary));
+    }
+    if let Some(description) = &task.description {
+        writer.property("DESCRIPTION", &[], &escape_text(description));
+    }
+
+    writer.property("STATUS", &[], match task.status {
+        TaskStatus::NeedsAction => "NEEDS-ACTION",
+        TaskStatus::InProcess => "IN-PROCESS",
+        TaskStatus::Completed => "COMPLETED",
+        TaskStatus::Cancelled => "CANCELLED",
+    });
+    if let Some(percent_complete) = task.percent_complete {
+        writer.property("PERCENT-COMPLETE", &[], &percent_complete.min(100).to_string());
+    }
+    if let Some(priority) = task.priority {
+        writer.property("PRIORITY", &[], &priority.min(9).to_string());
+    }
+
+    if let Some(recurrence) = &task.recurrence {
+        recurrence.lines()
+            .filter(|line| !line.trim().is_empty())
+            .for_each(|line| writer.line(line.trim()));
+    }
+
+    writer.line("END:VTODO");
+}
+
+/**
+ * Write a DATE value for all-day events and a UTC DATE-TIME value otherwise.
+ */
+fn write_datetime(writer: &mut IcsWriter, name: &str, value: NaiveDateTime, all_day: bool) {
+    if all_day {
+        writer.property(name, &[("VALUE", "DATE")], &value.format("%Y%m%d").to_string());
+    } else {
+        writer.property(name, &[], &format_datetime(value));
+    }
+}
+
+/**
+ * Write a UTC value in the local time of the time zone, when there is one.
+ */
+fn write_zoned_datetime(writer: &mut IcsWriter, name: &str, value: NaiveDateTime, all_day: bool, time_zone: Option<Tz>) {
+    match (all_day, time_zone) {
+        (false, Some(time_zone)) => {
+            let local = time_zone.from_utc_datetime(&value).naive_local();
+            writer.property(name, &[("TZID", time_zone.name())], &format_local_datetime(local));
+        },
+        _ => write_datetime(writer, name, value, all_day),
+    }
+}
+
+fn format_datetime(value: NaiveDateTime) -> String {
+    value.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_local_datetime(value: NaiveDateTime) -> String {
+    value.format("%Y%m%dT%H%M%S").to_string()
+}
+
+/**
+ * The time zone the times of a timed event are written in.
+ */
+fn event_time_zone(event: &EventResult) -> Option<Tz> {
+    match event.all_day {
+        true => None,
+        false => event.time_zone.as_deref().and_then(iana_time_zone),
+    }
+}
+
+/**
+ * Write a VTIMEZONE for every time zone the events refer to, either through their own time
+ * zone or through the TZID of their RDATE and EXDATE lines. Each zone is described from the
+ * year before its first event on.
+ */
+fn write_time_zones(writer: &mut IcsWriter, events: &[EventResult]) {
+    let mut time_zones: BTreeMap<String, (Tz, NaiveDateTime)> = BTreeMap::new();
+    let mut add = |tzid: &str, time_zone: Tz, starts_at: NaiveDateTime| {
+        let entry = time_zones.entry(tzid.to_string()).or_insert((time_zone, starts_at));
+        entry.1 = entry.1.min(starts_at);
+    };
+
+    for event in events {
+        if let Some(time_zone) = event_time_zone(event) {
+            add(time_zone.name(), time_zone, event.starts_at);
+        }
+        let lines = event.recurrence.as_deref().unwrap_or_default().lines();
+        for tzid in lines.filter_map(line_tzid) {
+            if let Some(time_zone) = iana_time_zone(tzid) {
+                add(tzid, time_zone, event.starts_at);
+            }
+        }
+    }
+
+    for (tzid, (time_zone, starts_at)) in time_zones {
+        write_time_zone(writer, &tzid, time_zone, starts_at.year() - 1);
+    }
+}
+
+/**
+ * The TZID parameter of a content line, if it has one.
+ */
+fn line_tzid(line: &str) -> Option<&str> {
+    let (name, _) = line.split_once(':')?;
+    name.split(';')
+        .filter_map(|param| param.split_once('='))
+        .find(|(param, _)| param.trim().eq_ignore_ascii_case("TZID"))
+        .map(|(_, value)| value.trim().trim_matches('"'))
+}
+
+/**
+ * Describe the time zone with the offset changes of the given year, repeated yearly on the
+ * same weekday of the month. Zones without changes that year get a single STANDARD observance.
+ */
+fn write_time_zone(writer: &mut IcsWriter, tzid: &str, time_zone: Tz, year: i32) {
+    writer.line("BEGIN:VTIMEZONE");
+    writer.property("TZID", &[], tzid);
+
+    let year_start = NaiveDate::from_ymd_opt(year, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let transitions = offset_transitions(time_zone, year_start, year_start.with_year(year + 1).unwrap());
+    if transitions.is_empty() {
+        let offset = time_zone.offset_from_utc_datetime(&year_start);
+        writer.line("BEGIN:STANDARD");
+        writer.property("DTSTART", &[], "19700101T000000");
+        writer.property("TZOFFSETFROM", &[], &format_offset(offset.base_utc_offset() + offset.dst_offset()));
+        writer.property("TZOFFSETTO", &[], &format_offset(offset.base_utc_offset() + offset.dst_offset()));
+        if let Some(abbreviation) = offset.abbreviation() {
+            writer.property("TZNAME", &[], &escape_text(abbreviation));
+        }
+        writer.line("END:STANDARD");
+    }
+
+    for transition in transitions {
+        let before = time_zone.offset_from_utc_datetime(&(transition - Duration::minutes(1)));
+        let after = time_zone.offset_from_utc_datetime(&transition);
+        let offset_from = before.base_utc_offset() + before.dst_offset();
+        let offset_to = after.base_utc_offset() + after.dst_offset();
+        // The onset is written in the local time in effect before the change
+        let onset = transition + offset_from;
+        let component = match after.dst_offset().is_zero() {
+            true => "STANDARD",
+            false => "DAYLIGHT",
+        };
+
+        writer.line(&format!("BEGIN:{}", component));
+        writer.property("DTSTART", &[], &format_local_datetime(onset));
+        writer.property("TZOFFSETFROM", &[], &format_offset(offset_from));
+        writer.property("TZOFFSETTO", &[], &format_offset(offset_to));
+        if let Some(abbreviation) = after.abbreviation() {
+            writer.property("TZNAME", &[], &escape_text(abbreviation));
+        }
+        let week = match (onset.date() + Duration::days(7)).month() != onset.month() {
+            true => -1,
+            false => (onset.day() as i32 - 1) / 7 + 1,
+        };
+        writer.property("RRULE", &[], &format!("FREQ=YEARLY;BYMONTH={};BYDAY={}{}", onset.month(), week, weekday_code(onset.weekday())));
+        writer.line(&format!("END:{}", component));
+    }
+
+    writer.line("END:VTIMEZONE");
+}
+
+/**
+ * Find the UTC instants in the range at which the offset of the time zone changes. Offsets
+ * change at most once a day, so each day is compared with the next and a change is narrowed
+ * down to the minute.
+ */
+fn offset_transitions(time_zone: Tz, start: NaiveDateTime, end: NaiveDateTime) -> Vec<NaiveDateTime> {
+    let offset = |value: NaiveDateTime| {
+        let offset = time_zone.offset_from_utc_datetime(&value);
+        (offset.base_utc_offset(), offset.dst_offset())
+    };
+
+    let mut transitions = Vec::new();
+    let mut day = start;
+    while day < end {
+        let next = day + Duration::days(1);
+        if offset(day) != offset(next) {
+            let (mut low, mut high) = (0, 24 * 60);
+            while high - low > 1 {
+                let middle = (low + high) / 2;
+                match offset(day + Duration::minutes(middle)) == offset(day) {
+                    true => low = middle,
+                    false => high = middle,
+                }
+            }
+            transitions.push(day + Duration::minutes(high));
+        }
+        day = next;
+    }
+    transitions
+}
+
+fn format_offset(offset: Duration) -> String {
+    let minutes = offset.num_minutes();
+    let sign = if minutes < 0 { '-' } else { '+' };
+    format!("{}{:02}{:02}", sign, minutes.abs() / 60, minutes.abs() % 60)
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/**
+ * Escape a TEXT value as described in RFC 5545 section 3.3.11.
+ */
+pub fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/**
+ * Read an escaped TEXT value, the reverse of escape_text.
+ */
+pub fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/**
+ * Quote a parameter value when it contains characters which are not allowed unquoted. Double
+ * quotes cannot be escaped at all, so they are dropped.
+ */
+fn param_value(value: &str) -> String {
+    let value = value.replace(['"', '\r', '\n'], "");
+    if value.contains([':', ';', ',']) {
+        format!("\"{}\"", value)
+    } else {
+        value
+    }
+}
+
+/**
+ * Builds the content lines of an iCalendar object, folding long lines.
+ */
+#[derive(Default)]
+struct IcsWriter {
+    output: String,
+}
+
+impl IcsWriter {
+    /**
+     * Write a property with its parameters. Consecutive parameters with the same name are
+     * written as one parameter with a list of values.
+     */
+    fn property(&mut self, name: &str, params: &[(&str, &str)], value: &str) {
+        let mut line = name.to_string();
+        let mut previous = None;
+        for (param, param_val) in params {
+            match previous == Some(param) {
+                true => line.push(','),
+                false => line.push_str(&format!(";{}=", param)),
+            }
+            line.push_str(&param_value(param_val));
+            previous = Some(param);
+        }
+        line.push(':');
+        line.push_str(value);
+        self.line(&line);
+    }
+
+    /**
+     * Write a content line, folding it so no line exceeds 75 octets. Lines are only folded
+     * between characters, never inside a multi-byte character.
+     */
+    fn line(&mut self, line: &str) {
+        let mut octets = 0;
+        for c in line.chars() {
+            if octets + c.len_utf8() > MAX_LINE_OCTETS {
+                self.output.push_str("\r\n ");
+                octets = 1;
+            }
+            self.output.push(c);
+            octets += c.len_utf8();
+        }
+        self.output.push_str("\r\n");
+    }
+}
@@ -0,0 +1,280 @@
+use std::{collections::{HashMap, HashSet}, io::{BufReader, Cursor}, sync::Arc};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{connectors::{caldav::{caldav::{self, CaldavEvent, CaldavWriteError}, datetime::TimeZones}, oauth2::{Oauth2Connector, Oauth2ConnectorError, Oauth2ServiceConnector, SendUpdates}, ServiceType}, models::{caldav_integration::CaldavIntegration, calendar::Calendar, event::{EventResult, EventStatus}, event_import::EventImport, oauth_integration::OauthIntegration}, sync::{find_integration, get_caldav_credentials, get_connector, SyncError}, AppState};
+
+/**
+ * The error types for importing iCalendar data. Events which cannot be imported are reported
+ * in the results instead, these errors stop the whole import.
+ */
+#[derive(Debug)]
+pub enum ImportError {
+    InvalidCalendar(String),
+    SyncError(SyncError),
+}
+
+/**
+ * What became of an event of the imported data.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    Imported,
+    Duplicate,
+    Excluded,
+    Failed,
+}
+
+/**
+ * The outcome of importing a single event. The uid is suffixed with the RECURRENCE-ID for
+ * occurrences of a recurring event, and missing when the event has no UID at all.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportResult {
+    pub uid: Option<String>,
+    pub status: ImportStatus,
+    pub external_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/**
+ * An event read from iCalendar data.
+ */
+#[derive(Debug, Clone)]
+pub enum ImportEvent {
+    Event(String, Box<EventResult>),
+    Excluded(String),
+    Duplicate(String),
+    Invalid(Option<String>, String),
+}
+
+/**
+ * Read the VEVENTs of iCalendar data. Events are identified by their UID, suffixed with the
+ * RECURRENCE-ID for occurrences of a recurring event, and later events with the same identity
+ * are duplicates.
+ *
+ * Connectors cannot attach an occurrence to a series they did not create, so occurrences are
+ * excluded from their series with an EXDATE instead. Changed occurrences are imported as events
+ * of their own, cancelled ones only leave the EXDATE behind.
+ */
+pub fn read_calendar(data: &str) -> Result<Vec<ImportEvent>, ImportError> {
+    let reader = ical::IcalParser::new(BufReader::new(Cursor::new(data.as_bytes())));
+
+    let mut events = Vec::new();
+    let mut seen = HashSet::new();
+    for calendar in reader {
+        let calendar = calendar.map_err(|err| ImportError::InvalidCalendar(err.to_string()))?;
+        let time_zones = TimeZones::from_calendar(&calendar.timezones);
+
+        for event in calendar.events {
+            let uid = event.properties.iter()
+                .find(|property| property.name == "UID")
+                .and_then(|property| property.value.clone());
+            let result = CaldavEvent::from_ical_evel(event, &time_zones)
+                .map_err(|err| err.to_string())
+                .and_then(|event| event.to_event_result("").ok_or_else(|| "Invalid event".to_string()));
+
+            match result {
+                Ok(result) if !seen.insert(result.external_id.clone()) => events.push(ImportEvent::Duplicate(result.external_id)),
+                Ok(result) => events.push(ImportEvent::Event(result.external_id.clone(), Box::new(result))),
+                Err(err) => events.push(ImportEvent::Invalid(uid, err)),
+            }
+        }
+    }
+
+    exclude_occurrences(&mut events);
+    for event in events.iter_mut() {
+        if let ImportEvent::Event(_, result) = event {
+            result.external_id = String::new();
+            result.etag = None;
+        }
+    }
+    Ok(events)
+}
+
+/**
+ * Add an EXDATE to every series for each of its occurrences, and detach the occurrences from
+ * their series.
+ */
+fn exclude_occurrences(events: &mut [ImportEvent]) {
+    let mut exdates: HashMap<String, Vec<String>> = HashMap::new();
+    for event in events.iter_mut() {
+        let ImportEvent::Event(uid, result) = event else {
+            continue;
+        };
+        let (Some(series), Some(original_starts_at)) = (result.recurring_event_id.take(), result.original_starts_at.take()) else {
+            continue;
+        };
+
+        let exdate = match result.all_day {
+            true => format!("EXDATE;VALUE=DATE:{}", original_starts_at.format("%Y%m%d")),
+            false => format!("EXDATE:{}", original_starts_at.format("%Y%m%dT%H%M%SZ")),
+        };
+        exdates.entry(series).or_default().push(exdate);
+
+        if result.status == EventStatus::Cancelled {
+            *event = ImportEvent::Excluded(uid.clone());
+        }
+    }
+
+    for event in events.iter_mut() {
+        let ImportEvent::Event(uid, result) = event else {
+            continue;
+        };
+        let (Some(recurrence), Some(exdates)) = (&mut result.recurrence, exdates.get(uid.as_str())) else {
+            continue;
+        };
+        for exdate in exdates {
+            recurrence.push('\n');
+            recurrence.push_str(exdate);
+        }
+    }
+}
+
+/**
+ * The service imported events are written to: the connector of an OAuth2 integration, or the
+ * server of a CalDAV integration.
+ */
+enum ImportTarget {
+    Oauth2(Box<Oauth2Connector>, OauthIntegration),
+    Caldav(CaldavIntegration),
+}
+
+impl ImportTarget {
+    /**
+     * Create the event in the calendar, returning its external_id or why it was not created.
+     * CalDAV servers leave naming the event to the client, so it gets a new UID.
+     */
+    async fn insert_event(&self, calendar: &Calendar, uid: &str, event: &EventResult) -> Result<String, String> {
+        match self {
+            Self::Oauth2(connector, oauth_integration) => {
+                match connector.insert_event(oauth_integration, calendar, event, SendUpdates::None).await {
+                    Ok(created) => Ok(created.external_id),
+                    Err(err) => {
+                        println!("Failed to import event {} into calendar {}: {:?}", uid, calendar.id, err);
+                        Err(describe_error(&err))
+                    },
+                }
+            },
+            Self::Caldav(credentials) => {
+                let event = EventResult {
+                    external_id: Uuid::new_v4().to_string(),
+                    ..event.clone()
+                };
+                match caldav::put_event(&calendar.external_id, &event, None, credentials.url.clone(), credentials.username.clone(), credentials.password.clone()).await {
+                    Ok(_) => Ok(event.external_id),
+                    Err(err) => {
+                        println!("Failed to import event {} into calendar {}: {:?}", uid, calendar.id, err);
+                        Err(describe_caldav_error(&err))
+                    },
+                }
+            },
+        }
+    }
+}
+
+/**
+ * Import iCalendar data into a calendar through the connector of its integration. Events which
+ * were imported into the calendar before are skipped, so the same file can be imported again
+ * after a failure. Attendees are not notified. The imported events are stored with the next
+ * sync of the calendar.
+ */
+pub async fn import_calendar(calendar: &Calendar, data: &str, state: &Arc<AppState>) -> Result<Vec<ImportResult>, ImportError> {
+    let events = read_calendar(data)?;
+
+    let integration = find_integration(calendar, state)
+        .map_err(ImportError::SyncError)?;
+    let target = match integration.service {
+        ServiceType::Apple => ImportTarget::Caldav(get_caldav_credentials(&integration, state).map_err(ImportError::SyncError)?),
+        _ => {
+            let (connector, oauth_integration) = get_connector(&integration, state).await
+                .map_err(ImportError::SyncError)?;
+            ImportTarget::Oauth2(Box::new(connector), oauth_integration)
+        },
+    };
+
+    let mut imported = EventImport::find_by_calendar(calendar, &mut state.get_connection())
+        .into_iter()
+        .map(|import| (import.uid, import.external_id))
+        .collect::<HashMap<String, String>>();
+
+    let mut results = Vec::with_capacity(events.len());
+    for event in events {
+        let result = match event {
+            ImportEvent::Event(uid, event) => match imported.get(&uid) {
+                Some(external_id) => ImportResult {
+                    uid: Some(uid.clone()),
+                    status: ImportStatus::Duplicate,
+                    external_id: Some(external_id.clone()),
+                    error: None,
+                },
+                None => match target.insert_event(calendar, &uid, &event).await {
+                    Ok(external_id) => {
+                        if let Err(err) = EventImport::new(calendar, &uid, &external_id, &mut state.get_connection()) {
+                            println!("Failed to record import of event {}: {:?}", uid, err);
+                        }
+                        imported.insert(uid.clone(), external_id.clone());
+                        ImportResult {
+                            uid: Some(uid),
+                            status: ImportStatus::Imported,
+                            external_id: Some(external_id),
+                            error: None,
+                        }
+                    },
+                    Err(err) => ImportResult {
+                        uid: Some(uid),
+                        status: ImportStatus::Failed,
+                        external_id: None,
+                        error: Some(err),
+                    },
+                },
+            },
+            ImportEvent::Excluded(uid) => ImportResult {
+                uid: Some(uid),
+                status: ImportStatus::Excluded,
+                external_id: None,
+                error: None,
+            },
+            ImportEvent::Duplicate(uid) => ImportResult {
+                uid: Some(uid),
+                status: ImportStatus::Duplicate,
+                external_id: None,
+                error: None,
+            },
+            ImportEvent::Invalid(uid, err) => ImportResult {
+                uid,
+                status: ImportStatus::Failed,
+                external_id: None,
+                error: Some(err),
+            },
+        };
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+/**
+ * Describe why the service did not take an event, without passing on its response.
+ */
+fn describe_error(err: &Oauth2ConnectorError) -> String {
+    match err {
+        Oauth2ConnectorError::InvalidStatusError(status, _) => format!("The service rejected the event with status {}", status.as_u16()),
+        Oauth2ConnectorError::UnsupportedEvent(reason) => reason.clone(),
+        _ => "The service could not be reached".to_string(),
+    }
+}
+
+/**
+ * Describe why the CalDAV server did not take an event, like describe_error.
+ */
+fn describe_caldav_error(err: &CaldavWriteError) -> String {
+    match err {
+        CaldavWriteError::InvalidStatusError(status, _) => format!("The service rejected the event with status {}", status.as_u16()),
+        CaldavWriteError::InvalidEvent(reason) => reason.clone(),
+        CaldavWriteError::PreconditionFailed => "The calendar has an event with the same UID".to_string(),
+        CaldavWriteError::NetworkError(_) => "The service could not be reached".to_string(),
+    }
+}
@@ -10,8 +10,15 @@ pub mod controllers;
 pub mod config;
 pub mod connectors;
 pub mod helper;
+pub mod ics;
+pub mod recurrence;
 pub mod db;
 pub mod middleware;
+pub mod sync;
+pub mod mirror;
+pub mod import;
+pub mod availability;
+pub mod scheduler;
 
 // Test imports
 pub mod test_util;
@@ -43,18 +50,31 @@ impl AppState {
  */
 pub fn build_routes(state: Arc<AppState>) -> Router<()> {
     let update_routes = Router::new()
-    .route("/calendar/:integration", axum::routing::get({
-        
-    }))
-    .route("/event", axum::routing::get({
-        
-    }));
+        .route("/calendar/:integration", axum::routing::get(controllers::update::calendar))
+        .route("/event", axum::routing::get(controllers::update::event))
+            .layer(axum_middleware::from_fn_with_state(state.clone(), middleware::authorize_middleware))
+        // Webhooks are called by the services and authenticate with their channel token
+        .route("/webhook/google", axum::routing::post(controllers::webhook::google))
+        .route("/webhook/outlook", axum::routing::post(controllers::webhook::outlook));
 
     let api_routes = Router::new()
         .route("/group", axum::routing::post(controllers::group::store))
+        .route("/group/:group", axum::routing::patch(controllers::group::update))
+        .route("/group/:group/freebusy", axum::routing::get(controllers::availability::freebusy))
+        .route("/group/:group/slots", axum::routing::get(controllers::availability::slots))
+        .route("/group/:group/feed", axum::routing::post(controllers::feed::store))
+        .route("/group/:group/caldav", axum::routing::post(controllers::caldav::store))
+        .route("/feed/:feed", axum::routing::delete(controllers::feed::destroy))
+        .route("/calendar/:calendar", axum::routing::patch(controllers::calendar::update))
+        .route("/calendar/:calendar/events", axum::routing::get(controllers::calendar::events))
+        .route("/calendar/:calendar/import", axum::routing::post(controllers::calendar::import))
             .layer(axum_middleware::from_fn_with_state(state.clone(), middleware::authorize_middleware));
 
 
+    // Feeds authenticate with the token in their URL
+    let feed_routes = Router::new()
+        .route("/:group/:token", axum::routing::get(controllers::feed::show));
+
     let oauth_routes = Router::new()
         .route("/:service", axum::routing::get(controllers::oauth2::redirect))
         .route("/:service/callback", axum::routing::get(controllers::oauth2::callback));
@@ -63,6 +83,11 @@ pub fn build_routes(state: Arc<AppState>) -> Router<()> {
     let group = Router::new()
         .nest("/update", update_routes)
         .nest("/oauth2", oauth_routes)
+        .nest("/feed", feed_routes)
+        // CalDAV clients authenticate with the id of the group and the token of one of its feeds
+        .route("/dav", axum::routing::any(controllers::dav::handle))
+        .route("/dav/", axum::routing::any(controllers::dav::handle))
+        .route("/dav/*path", axum::routing::any(controllers::dav::handle))
         .nest("/api", api_routes);
 
     Router::new()
@@ -71,9 +96,37 @@ pub fn build_routes(state: Arc<AppState>) -> Router<()> {
 }
 
 /**
- * Run the server with the given router.
+ * Run the server with the given router until a shutdown signal is received.
  */
 pub async fn run_server(router: Router) {
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await.unwrap();
-    axum::serve(listener, router).await.unwrap();
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+}
+
+/**
+ * Resolve once the process is asked to stop, either with Ctrl+C or SIGTERM.
+ */
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("Could not listen for Ctrl+C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Could not listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
\ No newline at end of file
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use schedsync_api::build_routes;
 use schedsync_api::run_server;
+use schedsync_api::scheduler::Scheduler;
 use schedsync_api::AppState;
 use serde::{Deserialize, Serialize};
 
@@ -12,7 +13,9 @@ async fn main() {
     dotenv().ok();
     let state = Arc::new(AppState::new());
     let router = build_routes(state.clone());
+    let scheduler = Scheduler::start(state.clone());
     run_server(router).await;
+    scheduler.shutdown().await;
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -0,0 +1,311 @@
+use std::{collections::{hash_map::Entry, HashMap, HashSet}, sync::Arc};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{connectors::{caldav::caldav::{self, CaldavWriteError}, oauth2::{Oauth2Connector, Oauth2ConnectorError, Oauth2ServiceConnector, SendUpdates}, ServiceType}, models::{caldav_integration::CaldavIntegration, calendar::Calendar, event::{Attendees, Event, EventResult, EventStatus, EventTransparency, EventVisibility}, event_mirror::EventMirror, oauth_integration::OauthIntegration}, sync::{find_integration, get_caldav_credentials, get_connector, SyncError}, AppState};
+
+/**
+ * The title of every busy placeholder.
+ */
+const PLACEHOLDER_SUMMARY: &str = "Busy";
+
+/**
+ * The description of every busy placeholder. Events carrying it are never mirrored themselves,
+ * even before their mirror is recorded.
+ */
+pub const PLACEHOLDER_DESCRIPTION: &str = "Mirrored by schedsync";
+
+/**
+ * The outcome of mirroring the events of a calendar.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MirrorResult {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+/**
+ * Mirror the events of a calendar into the other calendars selected for mirroring in its group.
+ * Every event that blocks time gets a "Busy" placeholder without any of its details in each of
+ * those calendars. Placeholders are updated when the times of their event change, and deleted
+ * when the event is gone, no longer blocks time, or mirroring was turned off.
+ *
+ * Placeholders are never mirrored themselves, so calendars mirroring each other do not loop.
+ * The placeholder of a series repeats like the series does, except for the occurrences which
+ * were changed or cancelled. Changed occurrences get a placeholder of their own. Outlook only
+ * stores the occurrences of a series, those are mirrored one by one.
+ *
+ * Google and Outlook calendars are written through their API, Apple calendars over CalDAV. A
+ * calendar that cannot be written to is skipped, the next sync tries again.
+ */
+pub async fn mirror_calendar(calendar: &Calendar, state: &Arc<AppState>) -> Result<MirrorResult, SyncError> {
+    let Some(group) = find_integration(calendar, state)?.get_group(&mut state.get_connection()) else {
+        return Err(SyncError::DatabaseError(diesel::result::Error::NotFound));
+    };
+
+    let targets = if group.mirroring && calendar.mirror {
+        Calendar::find_mirrored_by_group(&group, &mut state.get_connection())
+            .into_iter()
+            .filter(|target| target.id != calendar.id)
+            .collect::<Vec<Calendar>>()
+    } else {
+        Vec::new()
+    };
+
+    let placeholders = find_mirrored_events(calendar, state)
+        .iter()
+        .map(|event| (event.external_id.clone(), placeholder(event)))
+        .collect::<HashMap<String, EventResult>>();
+
+    let mut existing = EventMirror::find_by_source_calendar(calendar, &mut state.get_connection())
+        .into_iter()
+        .map(|mirror| ((mirror.source_event_id.clone(), mirror.calendar_id), mirror))
+        .collect::<HashMap<(String, i32), EventMirror>>();
+
+    let mut result = MirrorResult::default();
+    let mut connectors = Connectors::default();
+
+    for target in targets.iter() {
+        let Some(connector) = connectors.get(target, state).await else {
+            continue;
+        };
+
+        for (source_event_id, placeholder) in placeholders.iter() {
+            match existing.remove(&(source_event_id.clone(), target.id)) {
+                Some(mirror) if mirror.matches(placeholder) => {},
+                Some(mirror) => {
+                    let mut placeholder = placeholder.clone();
+                    placeholder.external_id = mirror.external_id.clone();
+                    match connector.update_event(target, &placeholder, mirror.etag.as_deref()).await {
+                        Ok(updated) => {
+                            mirror.update(&updated, &mut state.get_connection())
+                                .map_err(SyncError::DatabaseError)?;
+                            result.updated += 1;
+                        },
+                        Err(err) => println!("Failed to update mirror {} in calendar {}: {}", mirror.id, target.id, err),
+                    }
+                },
+                None => {
+                    let created = match connector.insert_event(target, placeholder).await {
+                        Ok(created) => created,
+                        Err(err) => {
+                            println!("Failed to mirror event {} into calendar {}: {}", source_event_id, target.id, err);
+                            continue;
+                        },
+                    };
+
+                    // Another sync of the calendar may have mirrored the event in the meantime
+                    if let Err(err) = EventMirror::new(calendar, source_event_id, target, &created, &mut state.get_connection()) {
+                        println!("Failed to record mirror of event {}, removing it: {:?}", source_event_id, err);
+                        if let Err(err) = connector.delete_event(target, &created.external_id, created.etag.as_deref()).await {
+                            println!("Failed to remove mirror {} from calendar {}: {}", created.external_id, target.id, err);
+                        }
+                        continue;
+                    }
+                    result.created += 1;
+                },
+            }
+        }
+    }
+
+    // Whatever is left has no source event or target calendar anymore
+    for mirror in existing.into_values() {
+        let target = mirror.get_calendar(&mut state.get_connection());
+        let Some(connector) = connectors.get(&target, state).await else {
+            continue;
+        };
+
+        if let Err(err) = connector.delete_event(&target, &mirror.external_id, mirror.etag.as_deref()).await {
+            println!("Failed to remove mirror {} from calendar {}: {}", mirror.id, target.id, err);
+            continue;
+        }
+        mirror.delete(&mut state.get_connection())
+            .map_err(SyncError::DatabaseError)?;
+        result.deleted += 1;
+    }
+
+    Ok(result)
+}
+
+/**
+ * Find the events of the calendar which block time and are not placeholders themselves. Every
+ * exception of a series stored in the same calendar is excluded from the series with an EXDATE,
+ * so it is only mirrored on its own when it still takes place.
+ */
+fn find_mirrored_events(calendar: &Calendar, state: &Arc<AppState>) -> Vec<Event> {
+    let placeholders = EventMirror::find_by_calendar(calendar, &mut state.get_connection())
+        .into_iter()
+        .map(|mirror| mirror.external_id)
+        .collect::<HashSet<String>>();
+
+    let mut events = Event::find_by_calendar(calendar, &mut state.get_connection());
+    let series = events.iter()
+        .filter(|event| event.recurrence.is_some())
+        .map(|event| event.external_id.clone())
+        .collect::<HashSet<String>>();
+
+    let mut exdates: HashMap<String, Vec<String>> = HashMap::new();
+    for event in events.iter() {
+        let (Some(series_id), Some(original_starts_at)) = (&event.recurring_event_id, event.original_starts_at) else {
+            continue;
+        };
+        if !series.contains(series_id) {
+            continue;
+        }
+        let exdate = match event.all_day {
+            true => format!("EXDATE;VALUE=DATE:{}", original_starts_at.format("%Y%m%d")),
+            false => format!("EXDATE:{}", original_starts_at.format("%Y%m%dT%H%M%SZ")),
+        };
+        exdates.entry(series_id.clone()).or_default().push(exdate);
+    }
+
+    for event in events.iter_mut() {
+        let (Some(recurrence), Some(exdates)) = (&mut event.recurrence, exdates.get_mut(&event.external_id)) else {
+            continue;
+        };
+        exdates.sort();
+        for exdate in exdates.iter() {
+            recurrence.push('\n');
+            recurrence.push_str(exdate);
+        }
+    }
+
+    events.into_iter()
+        .filter(|event| !placeholders.contains(&event.external_id))
+        .filter(|event| event.description.as_deref() != Some(PLACEHOLDER_DESCRIPTION))
+        .filter(|event| event.status != EventStatus::Cancelled)
+        .filter(|event| event.transparency == EventTransparency::Opaque)
+        .collect()
+}
+
+/**
+ * Build the placeholder for an event. Only the times are taken over, and the placeholder is
+ * private so people the target calendar is shared with only see the time as busy.
+ */
+fn placeholder(event: &Event) -> EventResult {
+    EventResult {
+        external_id: String::new(),
+        etag: None,
+        summary: Some(PLACEHOLDER_SUMMARY.to_string()),
+        description: Some(PLACEHOLDER_DESCRIPTION.to_string()),
+        location: None,
+        starts_at: event.starts_at,
+        ends_at: event.ends_at,
+        all_day: event.all_day,
+        time_zone: event.time_zone.clone(),
+        status: EventStatus::Confirmed,
+        transparency: EventTransparency::Opaque,
+        visibility: EventVisibility::Private,
+        organizer: None,
+        attendees: Attendees::default(),
+        recurrence: event.recurrence.clone(),
+        recurring_event_id: None,
+        original_starts_at: None,
+    }
+}
+
+/**
+ * Why a placeholder could not be written to its calendar.
+ */
+#[derive(Debug)]
+enum MirrorWriteError {
+    Oauth2(Oauth2ConnectorError),
+    Caldav(CaldavWriteError),
+}
+
+impl std::fmt::Display for MirrorWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Oauth2(err) => write!(f, "{:?}", err),
+            Self::Caldav(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MirrorWriteError {}
+
+/**
+ * The way placeholders are written to the calendars of an integration.
+ */
+enum MirrorTarget {
+    Oauth2(Box<Oauth2Connector>, OauthIntegration),
+    Caldav(CaldavIntegration),
+}
+
+impl MirrorTarget {
+    /**
+     * Create the placeholder in the calendar. CalDAV servers leave naming the event to the
+     * client, so it gets a new UID.
+     */
+    async fn insert_event(&self, calendar: &Calendar, placeholder: &EventResult) -> Result<EventResult, MirrorWriteError> {
+        match self {
+            Self::Oauth2(connector, oauth_integration) => connector.insert_event(oauth_integration, calendar, placeholder, SendUpdates::None).await
+                .map_err(MirrorWriteError::Oauth2),
+            Self::Caldav(credentials) => {
+                let placeholder = EventResult {
+                    external_id: Uuid::new_v4().to_string(),
+                    ..placeholder.clone()
+                };
+                Self::put_event(calendar, placeholder, None, credentials).await
+            },
+        }
+    }
+
+    /**
+     * Overwrite the placeholder in the calendar, unless it was changed since its etag.
+     */
+    async fn update_event(&self, calendar: &Calendar, placeholder: &EventResult, etag: Option<&str>) -> Result<EventResult, MirrorWriteError> {
+        match self {
+            Self::Oauth2(connector, oauth_integration) => connector.update_event(oauth_integration, calendar, placeholder, etag, SendUpdates::None).await
+                .map_err(MirrorWriteError::Oauth2),
+            Self::Caldav(credentials) => Self::put_event(calendar, placeholder.clone(), etag, credentials).await,
+        }
+    }
+
+    async fn delete_event(&self, calendar: &Calendar, external_id: &str, etag: Option<&str>) -> Result<(), MirrorWriteError> {
+        match self {
+            Self::Oauth2(connector, oauth_integration) => connector.delete_event(oauth_integration, calendar, external_id, etag, SendUpdates::None).await
+                .map_err(MirrorWriteError::Oauth2),
+            Self::Caldav(credentials) => caldav::delete_event(&calendar.external_id, external_id, etag, credentials.url.clone(), credentials.username.clone(), credentials.password.clone()).await
+                .map_err(MirrorWriteError::Caldav),
+        }
+    }
+
+    async fn put_event(calendar: &Calendar, placeholder: EventResult, etag: Option<&str>, credentials: &CaldavIntegration) -> Result<EventResult, MirrorWriteError> {
+        let etag = caldav::put_event(&calendar.external_id, &placeholder, etag, credentials.url.clone(), credentials.username.clone(), credentials.password.clone()).await
+            .map_err(MirrorWriteError::Caldav)?;
+        Ok(EventResult { etag, ..placeholder })
+    }
+}
+
+/**
+ * The connectors of the target calendars, loaded once per integration. Integrations without a
+ * connector are remembered as well, so they are only reported once.
+ */
+#[derive(Default)]
+struct Connectors(HashMap<i32, Option<MirrorTarget>>);
+
+impl Connectors {
+    async fn get(&mut self, calendar: &Calendar, state: &Arc<AppState>) -> Option<&MirrorTarget> {
+        if let Entry::Vacant(entry) = self.0.entry(calendar.integration_id) {
+            let connector = match find_integration(calendar, state) {
+                Ok(integration) if matches!(integration.service, ServiceType::Apple) => get_caldav_credentials(&integration, state)
+                    .map(MirrorTarget::Caldav),
+                Ok(integration) => get_connector(&integration, state).await
+                    .map(|(connector, oauth_integration)| MirrorTarget::Oauth2(Box::new(connector), oauth_integration)),
+                Err(err) => Err(err),
+            };
+            let connector = match connector {
+                Ok(connector) => Some(connector),
+                Err(err) => {
+                    println!("Cannot mirror events into integration {}: {:?}", calendar.integration_id, err);
+                    None
+                },
+            };
+            entry.insert(connector);
+        }
+        self.0.get(&calendar.integration_id).and_then(|connector| connector.as_ref())
+    }
+}
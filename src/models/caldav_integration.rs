@@ -0,0 +1,63 @@
+use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+
+use super::integration::Integration;
+
+/**
+ * The credentials of a CalDAV account, such as iCloud. CalDAV servers authenticate every
+ * request with the username and an (app-specific) password.
+ */
+#[derive(Debug)]
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::caldav_integrations)]
+#[diesel(check_for_backend(crate::db::Backend))]
+pub struct CaldavIntegration {
+    pub id: i32,
+    pub integration_id: i32,
+    pub url: String,
+    pub username: String,
+    pub password: Option<String>,
+}
+
+impl CaldavIntegration {
+
+    pub fn new(
+        integration: &Integration,
+        url: String,
+        username: String,
+        password: Option<String>,
+        conn: &mut crate::db::Connection,
+    ) -> Result<Self, diesel::result::Error> {
+        insert_into(crate::schema::caldav_integrations::table)
+            .values(&NewCaldavIntegration {
+                integration_id: integration.id,
+                url,
+                username,
+                password,
+            })
+            .returning(CaldavIntegration::as_returning())
+            .get_result(conn)
+    }
+
+    /**
+     * Find the CalDAV credentials belonging to an integration.
+     */
+    pub fn find_by_integration(integration: &Integration, conn: &mut crate::db::Connection) -> Option<CaldavIntegration> {
+        use crate::schema::caldav_integrations::dsl;
+        let Ok(result) = dsl::caldav_integrations.select(CaldavIntegration::as_select())
+            .filter(dsl::integration_id.eq(integration.id))
+            .first::<CaldavIntegration>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::caldav_integrations)]
+struct NewCaldavIntegration {
+    integration_id: i32,
+    url: String,
+    username: String,
+    password: Option<String>,
+}
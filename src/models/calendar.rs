@@ -1,10 +1,172 @@
+use std::collections::HashMap;
+
+use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, query_builder::AsChangeset, upsert::excluded, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use serde::{Deserialize, Serialize};
+
+use super::{group::Group, integration::Integration};
+
+#[derive(Debug)]
+#[derive(Clone, Queryable, Selectable, AsChangeset, Deserialize, Serialize)]
+#[diesel(table_name = crate::schema::calendars)]
+#[diesel(check_for_backend(crate::db::Backend))]
 pub struct Calendar {
-    pub id: u64,
+    pub id: i32,
+    pub integration_id: i32,
     pub external_id: String,
     pub name: String,
     pub background_color: String,
     pub foreground_color: String,
-    pub integration_id: String,
+    #[serde(skip)]
+    pub sync_token: Option<String>,
+    pub mirror: bool,
+}
+
+impl Calendar {
+
+    pub fn find_by_id(id: i32, conn: &mut crate::db::Connection) -> Option<Calendar> {
+        use crate::schema::calendars::dsl;
+        let Ok(result) = dsl::calendars.select(Calendar::as_select())
+            .filter(dsl::id.eq(id))
+            .first::<Calendar>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+
+    /**
+     * Find all calendars that were synced from the given integration.
+     */
+    pub fn find_by_integration(integration: &Integration, conn: &mut crate::db::Connection) -> Vec<Calendar> {
+        use crate::schema::calendars::dsl;
+        dsl::calendars.select(Calendar::as_select())
+            .filter(dsl::integration_id.eq(integration.id))
+            .order(dsl::id)
+            .load::<Calendar>(conn)
+            .expect("Error loading calendars")
+    }
+
+    /**
+     * Find the calendars of a group selected for mirroring.
+     */
+    pub fn find_mirrored_by_group(group: &Group, conn: &mut crate::db::Connection) -> Vec<Calendar> {
+        use crate::schema::{calendars::dsl, integrations};
+        dsl::calendars.inner_join(integrations::table)
+            .select(Calendar::as_select())
+            .filter(integrations::group_id.eq(group.id))
+            .filter(dsl::mirror.eq(true))
+            .order(dsl::id)
+            .load::<Calendar>(conn)
+            .expect("Error loading calendars")
+    }
+
+    /**
+     * Sync the calendars returned by a connector with the calendars stored for the integration.
+     * Calendars are matched on their external_id: new calendars are created, existing calendars
+     * are updated, and stored calendars missing from the results are deleted.
+     */
+    pub fn sync(
+        integration: &Integration,
+        results: Vec<CalendarResult>,
+        conn: &mut crate::db::Connection,
+    ) -> Result<CalendarSyncResult, diesel::result::Error> {
+        use crate::schema::calendars::dsl;
+
+        conn.transaction(|conn| {
+            let existing = Calendar::find_by_integration(integration, conn)
+                .into_iter()
+                .map(|calendar| (calendar.external_id.clone(), calendar))
+                .collect::<HashMap<String, Calendar>>();
+
+            let mut sync_result = CalendarSyncResult::default();
+
+            for result in results {
+                let previous = existing.get(&result.external_id);
+
+                // Skip the write entirely when nothing has changed
+                if let Some(previous) = previous {
+                    if result.matches(previous) {
+                        sync_result.calendars.push(previous.clone());
+                        continue;
+                    }
+                }
+
+                let calendar = insert_into(crate::schema::calendars::table)
+                    .values(&NewCalendar {
+                        integration_id: integration.id,
+                        external_id: result.external_id,
+                        name: result.name,
+                        background_color: result.background_color,
+                        foreground_color: result.foreground_color,
+                    })
+                    .on_conflict((dsl::integration_id, dsl::external_id))
+                    .do_update()
+                    .set((
+                        dsl::name.eq(excluded(dsl::name)),
+                        dsl::background_color.eq(excluded(dsl::background_color)),
+                        dsl::foreground_color.eq(excluded(dsl::foreground_color)),
+                    ))
+                    .returning(Calendar::as_returning())
+                    .get_result(conn)?;
+
+                match previous {
+                    Some(_) => sync_result.updated += 1,
+                    None => sync_result.created += 1,
+                }
+                sync_result.calendars.push(calendar);
+            }
+
+            // Remove the calendars which no longer exist on the service
+            let synced_ids = sync_result.calendars.iter().map(|c| c.id).collect::<Vec<i32>>();
+            sync_result.deleted = diesel::delete(
+                dsl::calendars
+                    .filter(dsl::integration_id.eq(integration.id))
+                    .filter(dsl::id.ne_all(synced_ids))
+            ).execute(conn)?;
+
+            Ok(sync_result)
+        })
+    }
+
+    /**
+     * Store the token the service returned to continue syncing events from where the last
+     * sync left off. Passing None forces the next sync to be a full sync.
+     */
+    pub fn update_sync_token(&self, sync_token: Option<String>, conn: &mut crate::db::Connection) -> Result<usize, diesel::result::Error> {
+        use crate::schema::calendars::dsl;
+        diesel::update(dsl::calendars.find(self.id))
+            .set(dsl::sync_token.eq(sync_token))
+            .execute(conn)
+    }
+
+    /**
+     * Select the calendar for mirroring, or remove it from the selection.
+     */
+    pub fn update_mirror(&self, mirror: bool, conn: &mut crate::db::Connection) -> Result<Calendar, diesel::result::Error> {
+        use crate::schema::calendars::dsl;
+        diesel::update(dsl::calendars.find(self.id))
+            .set(dsl::mirror.eq(mirror))
+            .returning(Calendar::as_returning())
+            .get_result(conn)
+    }
+
+    pub fn get_integration(&self, conn: &mut crate::db::Connection) -> Option<Integration> {
+        Integration::find_by_id(self.integration_id, conn)
+    }
+
+    pub fn save(&self, conn: &mut crate::db::Connection) -> Self {
+        diesel::update(crate::schema::calendars::table.find(self.id))
+            .set(self)
+            .returning(Calendar::as_returning())
+            .get_result(conn)
+            .expect("Error saving calendar")
+    }
+
+    pub fn delete(&self, conn: &mut crate::db::Connection) -> usize {
+        diesel::delete(crate::schema::calendars::table.find(self.id))
+            .execute(conn)
+            .expect("Error deleting calendar")
+    }
 }
 
 /**
@@ -19,4 +181,35 @@ pub struct CalendarResult {
     pub name: String,
     pub background_color: String,
     pub foreground_color: String,
-}
\ No newline at end of file
+}
+
+impl CalendarResult {
+    fn matches(&self, calendar: &Calendar) -> bool {
+        self.name == calendar.name
+            && self.background_color == calendar.background_color
+            && self.foreground_color == calendar.foreground_color
+    }
+}
+
+/**
+ * The outcome of a calendar sync. Contains every calendar that is still present on the
+ * service along with the number of calendars created, updated and deleted.
+ */
+#[derive(Debug, Default, Serialize)]
+pub struct CalendarSyncResult {
+    #[serde(skip)]
+    pub calendars: Vec<Calendar>,
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::calendars)]
+struct NewCalendar {
+    integration_id: i32,
+    external_id: String,
+    name: String,
+    background_color: String,
+    foreground_color: String,
+}
@@ -0,0 +1,104 @@
+use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, upsert::excluded, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+
+use super::calendar::Calendar;
+
+/**
+ * A calendar object resource of a CalDAV calendar, as it was last synced. The href and etag
+ * are compared with the server on the next sync, the UID links the resource to its events.
+ */
+#[derive(Debug)]
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::calendar_objects)]
+#[diesel(check_for_backend(crate::db::Backend))]
+pub struct CalendarObject {
+    pub id: i32,
+    pub calendar_id: i32,
+    pub href: String,
+    pub etag: String,
+    pub uid: Option<String>,
+}
+
+/**
+ * A resource read from the server. Resources without events, such as tasks, have no UID.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarObjectResult {
+    pub href: String,
+    pub etag: String,
+    pub uid: Option<String>,
+}
+
+impl CalendarObject {
+
+    /**
+     * Find the resources of the given calendar.
+     */
+    pub fn find_by_calendar(calendar: &Calendar, conn: &mut crate::db::Connection) -> Vec<CalendarObject> {
+        use crate::schema::calendar_objects::dsl;
+        dsl::calendar_objects.select(CalendarObject::as_select())
+            .filter(dsl::calendar_id.eq(calendar.id))
+            .order(dsl::id)
+            .load::<CalendarObject>(conn)
+            .expect("Error loading calendar objects")
+    }
+
+    /**
+     * Store the resources which changed and remove the deleted ones. A full sync lists every
+     * resource, so the resources missing from it are removed as well.
+     */
+    pub fn sync(
+        calendar: &Calendar,
+        changed: Vec<CalendarObjectResult>,
+        deleted: &[String],
+        full: bool,
+        conn: &mut crate::db::Connection,
+    ) -> Result<(), diesel::result::Error> {
+        use crate::schema::calendar_objects::dsl;
+
+        conn.transaction(|conn| {
+            let hrefs = changed.iter().map(|result| result.href.clone()).collect::<Vec<String>>();
+            let values = changed.into_iter()
+                .map(|result| NewCalendarObject {
+                    calendar_id: calendar.id,
+                    href: result.href,
+                    etag: result.etag,
+                    uid: result.uid,
+                })
+                .collect::<Vec<NewCalendarObject>>();
+
+            if !values.is_empty() {
+                insert_into(crate::schema::calendar_objects::table)
+                    .values(&values)
+                    .on_conflict((dsl::calendar_id, dsl::href))
+                    .do_update()
+                    .set((dsl::etag.eq(excluded(dsl::etag)), dsl::uid.eq(excluded(dsl::uid))))
+                    .execute(conn)?;
+            }
+
+            if full {
+                diesel::delete(
+                    dsl::calendar_objects
+                        .filter(dsl::calendar_id.eq(calendar.id))
+                        .filter(dsl::href.ne_all(hrefs))
+                ).execute(conn)?;
+            } else if !deleted.is_empty() {
+                diesel::delete(
+                    dsl::calendar_objects
+                        .filter(dsl::calendar_id.eq(calendar.id))
+                        .filter(dsl::href.eq_any(deleted))
+                ).execute(conn)?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::calendar_objects)]
+struct NewCalendarObject {
+    calendar_id: i32,
+    href: String,
+    etag: String,
+    uid: Option<String>,
+}
@@ -0,0 +1,571 @@
+use std::{collections::HashMap, io::Write};
+
+use chrono::NaiveDateTime;
+use diesel::{deserialize::{FromSql, FromSqlRow, Queryable}, expression::AsExpression, insert_into, prelude::Insertable, query_builder::AsChangeset, serialize::ToSql, BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use serde::{Deserialize, Serialize};
+
+use super::{calendar::Calendar, group::Group};
+
+/**
+ * A provider-neutral event. Every connector maps the events of its service into an
+ * EventResult, which is then synced into this model so the rest of the application never has
+ * to care which service an event came from. All timestamps are stored in UTC.
+ */
+#[derive(Debug)]
+#[derive(Clone, Queryable, Selectable, AsChangeset)]
+#[diesel(table_name = crate::schema::events)]
+#[diesel(check_for_backend(crate::db::Backend))]
+#[diesel(treat_none_as_null = true)]
+pub struct Event {
+    pub id: i32,
+    pub calendar_id: i32,
+    pub external_id: String,
+    pub etag: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub all_day: bool,
+    pub time_zone: Option<String>,
+    pub status: EventStatus,
+    pub transparency: EventTransparency,
+    pub visibility: EventVisibility,
+    pub organizer: Option<Participant>,
+    pub attendees: Attendees,
+    pub recurrence: Option<String>,
+    pub recurring_event_id: Option<String>,
+    pub original_starts_at: Option<NaiveDateTime>,
+}
+
+impl Event {
+
+    pub fn find_by_id(id: i32, conn: &mut crate::db::Connection) -> Option<Event> {
+        use crate::schema::events::dsl;
+        let Ok(result) = dsl::events.select(Event::as_select())
+            .filter(dsl::id.eq(id))
+            .first::<Event>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+
+    /**
+     * Find an event of a calendar by the id the service uses for it.
+     */
+    pub fn find_by_external_id(calendar: &Calendar, external_id: &str, conn: &mut crate::db::Connection) -> Option<Event> {
+        use crate::schema::events::dsl;
+        let Ok(result) = dsl::events.select(Event::as_select())
+            .filter(dsl::calendar_id.eq(calendar.id))
+            .filter(dsl::external_id.eq(external_id))
+            .first::<Event>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+
+    /**
+     * Find all events stored for the given calendar.
+     */
+    pub fn find_by_calendar(calendar: &Calendar, conn: &mut crate::db::Connection) -> Vec<Event> {
+        use crate::schema::events::dsl;
+        dsl::events.select(Event::as_select())
+            .filter(dsl::calendar_id.eq(calendar.id))
+            .order(dsl::starts_at)
+            .load::<Event>(conn)
+            .expect("Error loading events")
+    }
+
+    /**
+     * Find the events of every calendar in the group which may occur between start and end:
+     * the events overlapping the range, every series starting before its end, and the
+     * overrides which overlap the range or replace an occurrence that would. Expanding them
+     * with recurrence::expand_events gives the actual occurrences.
+     */
+    pub fn find_by_group_between(group: &Group, start: NaiveDateTime, end: NaiveDateTime, conn: &mut crate::db::Connection) -> Vec<Event> {
+        Self::find_between(EventScope::Group(group.id), start, end, conn)
+    }
+
+    /**
+     * Find the events of the calendar which may occur between start and end, in the same way
+     * as find_by_group_between.
+     */
+    pub fn find_by_calendar_between(calendar: &Calendar, start: NaiveDateTime, end: NaiveDateTime, conn: &mut crate::db::Connection) -> Vec<Event> {
+        Self::find_between(EventScope::Calendar(calendar.id), start, end, conn)
+    }
+
+    fn find_between(scope: EventScope, start: NaiveDateTime, end: NaiveDateTime, conn: &mut crate::db::Connection) -> Vec<Event> {
+        use crate::schema::{calendars, events::dsl, integrations};
+        let query = || {
+            let query = dsl::events.inner_join(calendars::table.inner_join(integrations::table))
+                .select(Event::as_select())
+                .into_boxed();
+            match scope {
+                EventScope::Group(group_id) => query.filter(integrations::group_id.eq(group_id)),
+                EventScope::Calendar(calendar_id) => query.filter(dsl::calendar_id.eq(calendar_id)),
+            }
+        };
+
+        let mut events = query()
+            .filter(dsl::recurring_event_id.is_null())
+            .filter(dsl::starts_at.lt(end).and(dsl::ends_at.gt(start).or(dsl::recurrence.is_not_null())))
+            .load::<Event>(conn)
+            .expect("Error loading events");
+
+        // An override replaces the occurrence of its series starting at original_starts_at, so
+        // it is needed when that occurrence overlaps the range, even if the override does not.
+        // No occurrence lasts longer than the longest series.
+        let longest = events.iter()
+            .filter(|event| event.recurrence.is_some())
+            .map(|event| event.ends_at - event.starts_at)
+            .max()
+            .unwrap_or_default();
+        let overrides = query()
+            .filter(dsl::recurring_event_id.is_not_null())
+            .filter(
+                dsl::starts_at.lt(end).and(dsl::ends_at.gt(start))
+                    .or(dsl::original_starts_at.lt(end).and(dsl::original_starts_at.gt(start - longest)))
+            )
+            .load::<Event>(conn)
+            .expect("Error loading events");
+
+        events.extend(overrides);
+        events.sort_by_key(|event| event.starts_at);
+        events
+    }
+
+    /**
+     * Apply the changes returned by a connector to the events stored for the calendar. Events
+     * are matched on their external_id. When the changes represent a full sync, every stored
+     * event missing from the changes is deleted; otherwise only the events listed as deleted
+     * are removed, along with any exceptions belonging to them.
+     */
+    pub fn sync(
+        calendar: &Calendar,
+        changes: EventChanges,
+        conn: &mut crate::db::Connection,
+    ) -> Result<EventSyncResult, diesel::result::Error> {
+        use crate::schema::events::dsl;
+
+        conn.transaction(|conn| {
+            let existing = Event::find_by_calendar(calendar, conn)
+                .into_iter()
+                .map(|event| (event.external_id.clone(), event))
+                .collect::<HashMap<String, Event>>();
+
+            let mut sync_result = EventSyncResult::default();
+            let mut synced_ids: Vec<i32> = Vec::new();
+
+            for result in changes.events {
+                let previous = existing.get(&result.external_id);
+
+                // Skip the write entirely when nothing has changed
+                if let Some(previous) = previous {
+                    if EventResult::from(previous) == result {
+                        synced_ids.push(previous.id);
+                        continue;
+                    }
+                }
+
+                let values = result.into_new_event(calendar.id);
+                let event = insert_into(crate::schema::events::table)
+                    .values(&values)
+                    .on_conflict((dsl::calendar_id, dsl::external_id))
+                    .do_update()
+                    .set(&values)
+                    .returning(Event::as_returning())
+                    .get_result(conn)?;
+
+                match previous {
+                    Some(_) => sync_result.updated += 1,
+                    None => sync_result.created += 1,
+                }
+                synced_ids.push(event.id);
+            }
+
+            sync_result.deleted = if changes.full {
+                diesel::delete(
+                    dsl::events
+                        .filter(dsl::calendar_id.eq(calendar.id))
+                        .filter(dsl::id.ne_all(synced_ids))
+                ).execute(conn)?
+            } else if !changes.deleted.is_empty() {
+                diesel::delete(
+                    dsl::events
+                        .filter(dsl::calendar_id.eq(calendar.id))
+                        .filter(
+                            dsl::external_id.eq_any(&changes.deleted)
+                                .or(dsl::recurring_event_id.eq_any(&changes.deleted))
+                        )
+                ).execute(conn)?
+            } else {
+                0
+            };
+
+            Ok(sync_result)
+        })
+    }
+
+    pub fn save(&self, conn: &mut crate::db::Connection) -> Self {
+        diesel::update(crate::schema::events::table.find(self.id))
+            .set(self)
+            .returning(Event::as_returning())
+            .get_result(conn)
+            .expect("Error saving event")
+    }
+
+    pub fn delete(&self, conn: &mut crate::db::Connection) -> usize {
+        diesel::delete(crate::schema::events::table.find(self.id))
+            .execute(conn)
+            .expect("Error deleting event")
+    }
+}
+
+/**
+ * The calendars to find events between two dates in: every calendar of a group, or one.
+ */
+#[derive(Clone, Copy)]
+enum EventScope {
+    Group(i32),
+    Calendar(i32),
+}
+
+/**
+ * The intermediate struct every connector maps its events into. Like the CalendarResult, it is
+ * matched on the external_id when syncing.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventResult {
+    pub external_id: String,
+    pub etag: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub all_day: bool,
+    pub time_zone: Option<String>,
+    pub status: EventStatus,
+    pub transparency: EventTransparency,
+    pub visibility: EventVisibility,
+    pub organizer: Option<Participant>,
+    pub attendees: Attendees,
+    pub recurrence: Option<String>,
+    pub recurring_event_id: Option<String>,
+    pub original_starts_at: Option<NaiveDateTime>,
+}
+
+impl EventResult {
+    fn into_new_event(self, calendar_id: i32) -> NewEvent {
+        NewEvent {
+            calendar_id,
+            external_id: self.external_id,
+            etag: self.etag,
+            summary: self.summary,
+            description: self.description,
+            location: self.location,
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+            all_day: self.all_day,
+            time_zone: self.time_zone,
+            status: self.status,
+            transparency: self.transparency,
+            visibility: self.visibility,
+            organizer: self.organizer,
+            attendees: self.attendees,
+            recurrence: self.recurrence,
+            recurring_event_id: self.recurring_event_id,
+            original_starts_at: self.original_starts_at,
+        }
+    }
+}
+
+impl From<&Event> for EventResult {
+    fn from(event: &Event) -> Self {
+        Self {
+            external_id: event.external_id.clone(),
+            etag: event.etag.clone(),
+            summary: event.summary.clone(),
+            description: event.description.clone(),
+            location: event.location.clone(),
+            starts_at: event.starts_at,
+            ends_at: event.ends_at,
+            all_day: event.all_day,
+            time_zone: event.time_zone.clone(),
+            status: event.status.clone(),
+            transparency: event.transparency.clone(),
+            visibility: event.visibility.clone(),
+            organizer: event.organizer.clone(),
+            attendees: event.attendees.clone(),
+            recurrence: event.recurrence.clone(),
+            recurring_event_id: event.recurring_event_id.clone(),
+            original_starts_at: event.original_starts_at,
+        }
+    }
+}
+
+/**
+ * The set of changes a connector found for a calendar. A full sync contains every event of the
+ * calendar, while an incremental sync only contains the changed events and the external ids
+ * of the deleted ones. The sync_token is stored on the calendar for the next incremental sync.
+ */
+#[derive(Debug, Default)]
+pub struct EventChanges {
+    pub events: Vec<EventResult>,
+    pub deleted: Vec<String>,
+    pub full: bool,
+    pub sync_token: Option<String>,
+}
+
+/**
+ * The outcome of an event sync.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EventSyncResult {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+/**
+ * The status of an event.
+ */
+#[derive(Clone, Debug, FromSqlRow, AsExpression, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(sql_type = diesel::sql_types::SmallInt)]
+#[serde(rename_all = "lowercase")]
+pub enum EventStatus {
+    Confirmed,
+    Tentative,
+    Cancelled,
+}
+
+/**
+ * Convert an i16 used in the database to an EventStatus.
+ */
+impl Queryable<diesel::sql_types::SmallInt, crate::db::Backend> for EventStatus {
+    type Row = i16;
+    fn build(row: Self::Row) -> Result<EventStatus, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        match row {
+            1 => Ok(Self::Confirmed),
+            2 => Ok(Self::Tentative),
+            3 => Ok(Self::Cancelled),
+            _ => Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid EventStatus value")))
+        }
+    }
+}
+
+impl ToSql<diesel::sql_types::SmallInt, crate::db::Backend> for EventStatus {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, crate::db::Backend>) -> diesel::serialize::Result {
+        match self {
+            EventStatus::Confirmed => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&1, out),
+            EventStatus::Tentative => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&2, out),
+            EventStatus::Cancelled => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&3, out),
+        }
+    }
+}
+
+/**
+ * Whether an event blocks time on the calendar (opaque) or not (transparent).
+ */
+#[derive(Clone, Debug, FromSqlRow, AsExpression, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(sql_type = diesel::sql_types::SmallInt)]
+#[serde(rename_all = "lowercase")]
+pub enum EventTransparency {
+    Opaque,
+    Transparent,
+}
+
+/**
+ * Convert an i16 used in the database to an EventTransparency.
+ */
+impl Queryable<diesel::sql_types::SmallInt, crate::db::Backend> for EventTransparency {
+    type Row = i16;
+    fn build(row: Self::Row) -> Result<EventTransparency, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        match row {
+            1 => Ok(Self::Opaque),
+            2 => Ok(Self::Transparent),
+            _ => Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid EventTransparency value")))
+        }
+    }
+}
+
+impl ToSql<diesel::sql_types::SmallInt, crate::db::Backend> for EventTransparency {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, crate::db::Backend>) -> diesel::serialize::Result {
+        match self {
+            EventTransparency::Opaque => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&1, out),
+            EventTransparency::Transparent => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&2, out),
+        }
+    }
+}
+
+/**
+ * Who may see the details of an event. Default leaves it to the sharing settings of the calendar.
+ */
+#[derive(Clone, Debug, FromSqlRow, AsExpression, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(sql_type = diesel::sql_types::SmallInt)]
+#[serde(rename_all = "lowercase")]
+pub enum EventVisibility {
+    Default,
+    Public,
+    Private,
+    Confidential,
+}
+
+/**
+ * Convert an i16 used in the database to an EventVisibility.
+ */
+impl Queryable<diesel::sql_types::SmallInt, crate::db::Backend> for EventVisibility {
+    type Row = i16;
+    fn build(row: Self::Row) -> Result<EventVisibility, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        match row {
+            1 => Ok(Self::Default),
+            2 => Ok(Self::Public),
+            3 => Ok(Self::Private),
+            4 => Ok(Self::Confidential),
+            _ => Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid EventVisibility value")))
+        }
+    }
+}
+
+impl ToSql<diesel::sql_types::SmallInt, crate::db::Backend> for EventVisibility {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, crate::db::Backend>) -> diesel::serialize::Result {
+        match self {
+            EventVisibility::Default => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&1, out),
+            EventVisibility::Public => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&2, out),
+            EventVisibility::Private => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&3, out),
+            EventVisibility::Confidential => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&4, out),
+        }
+    }
+}
+
+/**
+ * A person taking part in an event, identified by their email address. Stored as JSON.
+ * sent_by is the address of whoever acts on their behalf, such as an assistant.
+ */
+#[derive(Clone, Debug, FromSqlRow, AsExpression, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(sql_type = diesel::sql_types::Text)]
+pub struct Participant {
+    pub email: String,
+    pub name: Option<String>,
+    pub sent_by: Option<String>,
+}
+
+/**
+ * An attendee of an event along with their response to the invitation. Optional attendees
+ * are those with the optional or non-participant role, for the services which only tell them
+ * from required ones. Delegates are listed by their email address.
+ *
+ * Attendees stored before the role and the fields after it were added read their defaults.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attendee {
+    pub email: String,
+    pub name: Option<String>,
+    pub status: AttendeeStatus,
+    pub optional: bool,
+    #[serde(default)]
+    pub role: ParticipantRole,
+    #[serde(default)]
+    pub rsvp: bool,
+    #[serde(default)]
+    pub kind: ParticipantKind,
+    #[serde(default)]
+    pub delegated_to: Vec<String>,
+    #[serde(default)]
+    pub delegated_from: Vec<String>,
+    pub sent_by: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticipantRole {
+    Chair,
+    #[default]
+    Required,
+    Optional,
+    NonParticipant,
+}
+
+/**
+ * Whether an attendee is a person, a group of people, or a room or other resource.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticipantKind {
+    #[default]
+    Individual,
+    Group,
+    Resource,
+    Room,
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttendeeStatus {
+    NeedsAction,
+    Accepted,
+    Declined,
+    Tentative,
+}
+
+/**
+ * The list of attendees of an event. Stored as a JSON array.
+ */
+#[derive(Clone, Debug, Default, FromSqlRow, AsExpression, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(sql_type = diesel::sql_types::Text)]
+pub struct Attendees(pub Vec<Attendee>);
+
+impl FromSql<diesel::sql_types::Text, crate::db::Backend> for Participant {
+    fn from_sql(bytes: <crate::db::Backend as diesel::backend::Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<diesel::sql_types::Text, crate::db::Backend>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&value)?)
+    }
+}
+
+impl ToSql<diesel::sql_types::Text, crate::db::Backend> for Participant {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, crate::db::Backend>) -> diesel::serialize::Result {
+        out.write_all(serde_json::to_string(self)?.as_bytes())?;
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+impl FromSql<diesel::sql_types::Text, crate::db::Backend> for Attendees {
+    fn from_sql(bytes: <crate::db::Backend as diesel::backend::Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<diesel::sql_types::Text, crate::db::Backend>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&value)?)
+    }
+}
+
+impl ToSql<diesel::sql_types::Text, crate::db::Backend> for Attendees {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, crate::db::Backend>) -> diesel::serialize::Result {
+        out.write_all(serde_json::to_string(self)?.as_bytes())?;
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::events)]
+#[diesel(treat_none_as_null = true)]
+struct NewEvent {
+    calendar_id: i32,
+    external_id: String,
+    etag: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    location: Option<String>,
+    starts_at: NaiveDateTime,
+    ends_at: NaiveDateTime,
+    all_day: bool,
+    time_zone: Option<String>,
+    status: EventStatus,
+    transparency: EventTransparency,
+    visibility: EventVisibility,
+    organizer: Option<Participant>,
+    attendees: Attendees,
+    recurrence: Option<String>,
+    recurring_event_id: Option<String>,
+    original_starts_at: Option<NaiveDateTime>,
+}
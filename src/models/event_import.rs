@@ -0,0 +1,56 @@
+use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+
+use super::calendar::Calendar;
+
+/**
+ * An event written into a calendar by an iCalendar import, identified by its UID in the file.
+ * Occurrences of a recurring event imported on their own are identified by their UID and
+ * RECURRENCE-ID.
+ */
+#[derive(Debug)]
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::event_imports)]
+#[diesel(check_for_backend(crate::db::Backend))]
+pub struct EventImport {
+    pub id: i32,
+    pub calendar_id: i32,
+    pub uid: String,
+    pub external_id: String,
+}
+
+impl EventImport {
+
+    /**
+     * Record the event the service created for the imported UID.
+     */
+    pub fn new(calendar: &Calendar, uid: &str, external_id: &str, conn: &mut crate::db::Connection) -> Result<Self, diesel::result::Error> {
+        insert_into(crate::schema::event_imports::table)
+            .values(&NewEventImport {
+                calendar_id: calendar.id,
+                uid: uid.to_string(),
+                external_id: external_id.to_string(),
+            })
+            .returning(EventImport::as_returning())
+            .get_result(conn)
+    }
+
+    /**
+     * Find the events imported into the given calendar.
+     */
+    pub fn find_by_calendar(calendar: &Calendar, conn: &mut crate::db::Connection) -> Vec<EventImport> {
+        use crate::schema::event_imports::dsl;
+        dsl::event_imports.select(EventImport::as_select())
+            .filter(dsl::calendar_id.eq(calendar.id))
+            .order(dsl::id)
+            .load::<EventImport>(conn)
+            .expect("Error loading event imports")
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::event_imports)]
+struct NewEventImport {
+    calendar_id: i32,
+    uid: String,
+    external_id: String,
+}
@@ -0,0 +1,147 @@
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, query_builder::AsChangeset, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+
+use super::{calendar::Calendar, event::EventResult};
+
+/**
+ * A busy placeholder created in one calendar of a group for an event of another calendar. The
+ * times of the placeholder are kept to tell whether it has to be updated when the source event
+ * changes.
+ */
+#[derive(Debug)]
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::event_mirrors)]
+#[diesel(check_for_backend(crate::db::Backend))]
+pub struct EventMirror {
+    pub id: i32,
+    pub source_calendar_id: i32,
+    pub source_event_id: String,
+    pub calendar_id: i32,
+    pub external_id: String,
+    pub etag: Option<String>,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub all_day: bool,
+    pub time_zone: Option<String>,
+    pub recurrence: Option<String>,
+}
+
+impl EventMirror {
+
+    /**
+     * Record the placeholder the service created for the source event.
+     */
+    pub fn new(
+        source_calendar: &Calendar,
+        source_event_id: &str,
+        calendar: &Calendar,
+        placeholder: &EventResult,
+        conn: &mut crate::db::Connection,
+    ) -> Result<Self, diesel::result::Error> {
+        insert_into(crate::schema::event_mirrors::table)
+            .values(&NewEventMirror {
+                source_calendar_id: source_calendar.id,
+                source_event_id: source_event_id.to_string(),
+                calendar_id: calendar.id,
+                external_id: placeholder.external_id.clone(),
+                etag: placeholder.etag.clone(),
+                starts_at: placeholder.starts_at,
+                ends_at: placeholder.ends_at,
+                all_day: placeholder.all_day,
+                time_zone: placeholder.time_zone.clone(),
+                recurrence: placeholder.recurrence.clone(),
+            })
+            .returning(EventMirror::as_returning())
+            .get_result(conn)
+    }
+
+    /**
+     * Find the placeholders created for the events of the given calendar.
+     */
+    pub fn find_by_source_calendar(calendar: &Calendar, conn: &mut crate::db::Connection) -> Vec<EventMirror> {
+        use crate::schema::event_mirrors::dsl;
+        dsl::event_mirrors.select(EventMirror::as_select())
+            .filter(dsl::source_calendar_id.eq(calendar.id))
+            .order(dsl::id)
+            .load::<EventMirror>(conn)
+            .expect("Error loading event mirrors")
+    }
+
+    /**
+     * Find the placeholders living in the given calendar.
+     */
+    pub fn find_by_calendar(calendar: &Calendar, conn: &mut crate::db::Connection) -> Vec<EventMirror> {
+        use crate::schema::event_mirrors::dsl;
+        dsl::event_mirrors.select(EventMirror::as_select())
+            .filter(dsl::calendar_id.eq(calendar.id))
+            .order(dsl::id)
+            .load::<EventMirror>(conn)
+            .expect("Error loading event mirrors")
+    }
+
+    /**
+     * Whether the placeholder still matches the times of the given placeholder.
+     */
+    pub fn matches(&self, placeholder: &EventResult) -> bool {
+        self.starts_at == placeholder.starts_at
+            && self.ends_at == placeholder.ends_at
+            && self.all_day == placeholder.all_day
+            && self.time_zone == placeholder.time_zone
+            && self.recurrence == placeholder.recurrence
+    }
+
+    /**
+     * Store the placeholder as the service returned it after an update.
+     */
+    pub fn update(&self, placeholder: &EventResult, conn: &mut crate::db::Connection) -> Result<EventMirror, diesel::result::Error> {
+        diesel::update(crate::schema::event_mirrors::table.find(self.id))
+            .set(&EventMirrorChanges {
+                external_id: placeholder.external_id.clone(),
+                etag: placeholder.etag.clone(),
+                starts_at: placeholder.starts_at,
+                ends_at: placeholder.ends_at,
+                all_day: placeholder.all_day,
+                time_zone: placeholder.time_zone.clone(),
+                recurrence: placeholder.recurrence.clone(),
+            })
+            .returning(EventMirror::as_returning())
+            .get_result(conn)
+    }
+
+    pub fn get_calendar(&self, conn: &mut crate::db::Connection) -> Calendar {
+        Calendar::find_by_id(self.calendar_id, conn).unwrap()
+    }
+
+    pub fn delete(&self, conn: &mut crate::db::Connection) -> Result<usize, diesel::result::Error> {
+        diesel::delete(crate::schema::event_mirrors::table.find(self.id))
+            .execute(conn)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::event_mirrors)]
+struct NewEventMirror {
+    source_calendar_id: i32,
+    source_event_id: String,
+    calendar_id: i32,
+    external_id: String,
+    etag: Option<String>,
+    starts_at: NaiveDateTime,
+    ends_at: NaiveDateTime,
+    all_day: bool,
+    time_zone: Option<String>,
+    recurrence: Option<String>,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::event_mirrors)]
+#[diesel(treat_none_as_null = true)]
+struct EventMirrorChanges {
+    external_id: String,
+    etag: Option<String>,
+    starts_at: NaiveDateTime,
+    ends_at: NaiveDateTime,
+    all_day: bool,
+    time_zone: Option<String>,
+    recurrence: Option<String>,
+}
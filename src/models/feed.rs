@@ -0,0 +1,131 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::{deserialize::{FromSqlRow, Queryable}, expression::AsExpression, insert_into, prelude::Insertable, serialize::ToSql, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use serde::{Deserialize, Serialize};
+
+use super::group::Group;
+
+/**
+ * A read-only iCalendar subscription of the events of a group, served at
+ * /feed/{group_id}/{token}.ics. Anyone knowing the token can read the feed.
+ */
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::feeds)]
+#[diesel(check_for_backend(crate::db::Backend))]
+pub struct Feed {
+    pub id: i32,
+    pub group_id: i32,
+    pub token: String,
+    pub privacy: FeedPrivacy,
+    pub past_days: i32,
+    pub future_days: i32,
+}
+
+impl Feed {
+    pub fn new(
+        group: &Group,
+        token: String,
+        privacy: FeedPrivacy,
+        past_days: i32,
+        future_days: i32,
+        conn: &mut crate::db::Connection,
+    ) -> Result<Self, diesel::result::Error> {
+        insert_into(crate::schema::feeds::table)
+            .values(&NewFeed {
+                group_id: group.id,
+                token,
+                privacy,
+                past_days,
+                future_days,
+            })
+            .returning(Feed::as_returning())
+            .get_result(conn)
+    }
+
+    pub fn find_by_id(id: i32, conn: &mut crate::db::Connection) -> Option<Feed> {
+        use crate::schema::feeds::dsl;
+        let Ok(result) = dsl::feeds.select(Feed::as_select())
+            .filter(dsl::id.eq(id))
+            .first::<Feed>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+
+    /**
+     * Find the feed of a group with the given token.
+     */
+    pub fn find_by_token(group_id: i32, token: &str, conn: &mut crate::db::Connection) -> Option<Feed> {
+        use crate::schema::feeds::dsl;
+        let Ok(result) = dsl::feeds.select(Feed::as_select())
+            .filter(dsl::group_id.eq(group_id))
+            .filter(dsl::token.eq(token))
+            .first::<Feed>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+
+    /**
+     * The range of events the feed contains, relative to now.
+     */
+    pub fn window(&self, now: NaiveDateTime) -> (NaiveDateTime, NaiveDateTime) {
+        (now - Duration::days(self.past_days.into()), now + Duration::days(self.future_days.into()))
+    }
+
+    pub fn get_group(&self, conn: &mut crate::db::Connection) -> Group {
+        Group::find_by_id(self.group_id, conn).expect("Feed without group")
+    }
+
+    pub fn delete(&self, conn: &mut crate::db::Connection) -> Result<usize, diesel::result::Error> {
+        use crate::schema::feeds::dsl;
+        diesel::delete(dsl::feeds.find(self.id)).execute(conn)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::feeds)]
+struct NewFeed {
+    group_id: i32,
+    token: String,
+    privacy: FeedPrivacy,
+    past_days: i32,
+    future_days: i32,
+}
+
+/**
+ * How much of the events a feed shows. Busy feeds only show when the group is busy, full feeds
+ * show every detail of the events.
+ */
+#[derive(Clone, Copy, Debug, Default, FromSqlRow, AsExpression, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(sql_type = diesel::sql_types::SmallInt)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedPrivacy {
+    #[default]
+    Busy,
+    Full,
+}
+
+/**
+ * Convert an i16 used in the database to a FeedPrivacy.
+ */
+impl Queryable<diesel::sql_types::SmallInt, crate::db::Backend> for FeedPrivacy {
+    type Row = i16;
+    fn build(row: Self::Row) -> Result<FeedPrivacy, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        match row {
+            1 => Ok(Self::Busy),
+            2 => Ok(Self::Full),
+            _ => Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid FeedPrivacy value")))
+        }
+    }
+}
+
+impl ToSql<diesel::sql_types::SmallInt, crate::db::Backend> for FeedPrivacy {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, crate::db::Backend>) -> diesel::serialize::Result {
+        match self {
+            FeedPrivacy::Busy => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&1, out),
+            FeedPrivacy::Full => ToSql::<diesel::sql_types::SmallInt, crate::db::Backend>::to_sql(&2, out),
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, query_builder::AsChangeset, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use std::io::Write;
+
+use chrono::{NaiveTime, Weekday};
+use diesel::{deserialize::{FromSql, FromSqlRow, Queryable}, expression::AsExpression, insert_into, prelude::Insertable, query_builder::AsChangeset, serialize::ToSql, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Queryable, Selectable, AsChangeset, Deserialize, Serialize)]
@@ -7,6 +10,13 @@ use serde::{Deserialize, Serialize};
 pub struct Group {
     pub id: i32,
     pub app_id: i32,
+    pub mirroring: bool,
+    pub time_zone: String,
+    pub working_hours: WorkingHours,
+    pub buffer_before: i32,
+    pub buffer_after: i32,
+    pub minimum_notice: i32,
+    pub max_days: i32,
 }
 
 impl Group {
@@ -30,6 +40,45 @@ impl Group {
         };
         Some(result)
     }
+
+    pub fn save(&self, conn: &mut crate::db::Connection) -> Result<Group, diesel::result::Error> {
+        diesel::update(crate::schema::groups::table.find(self.id))
+            .set(self)
+            .returning(Group::as_returning())
+            .get_result(conn)
+    }
+}
+
+/**
+ * A period of the week in which the person of a group can be booked, in the time zone of the
+ * group.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkingPeriod {
+    pub weekday: Weekday,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+/**
+ * The working hours of a group. Stored as a JSON array.
+ */
+#[derive(Clone, Debug, Default, FromSqlRow, AsExpression, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(sql_type = diesel::sql_types::Text)]
+pub struct WorkingHours(pub Vec<WorkingPeriod>);
+
+impl FromSql<diesel::sql_types::Text, crate::db::Backend> for WorkingHours {
+    fn from_sql(bytes: <crate::db::Backend as diesel::backend::Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<diesel::sql_types::Text, crate::db::Backend>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&value)?)
+    }
+}
+
+impl ToSql<diesel::sql_types::Text, crate::db::Backend> for WorkingHours {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, crate::db::Backend>) -> diesel::serialize::Result {
+        out.write_all(serde_json::to_string(self)?.as_bytes())?;
+        Ok(diesel::serialize::IsNull::No)
+    }
 }
 
 #[derive(Insertable)]
@@ -1,4 +1,5 @@
-use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, query_builder::AsChangeset, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, query_builder::AsChangeset, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
 
 use crate::{connectors::ServiceType, schema::integrations::group_id};
 
@@ -12,6 +13,7 @@ pub struct Integration {
     pub id: i32,
     pub service: ServiceType,
     pub group_id: i32,
+    pub last_synced_at: Option<NaiveDateTime>,
 }
 
 impl Integration {
@@ -27,6 +29,42 @@ impl Integration {
             .expect("Error saving new oauth integration")
     }
 
+    pub fn find_by_id(id: i32, conn: &mut crate::db::Connection) -> Option<Integration> {
+        use crate::schema::integrations::dsl;
+        let Ok(result) = dsl::integrations.select(Integration::as_select())
+            .filter(dsl::id.eq(id))
+            .first::<Integration>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+
+    /**
+     * Get every integration, oldest sync first. Integrations which were never synced come first.
+     */
+    pub fn all(conn: &mut crate::db::Connection) -> Vec<Integration> {
+        use crate::schema::integrations::dsl;
+        dsl::integrations.select(Integration::as_select())
+            .order((dsl::last_synced_at.is_not_null(), dsl::last_synced_at, dsl::id))
+            .load::<Integration>(conn)
+            .expect("Error loading integrations")
+    }
+
+    /**
+     * Record that the integration was synced just now.
+     */
+    pub fn touch_last_synced(&self, conn: &mut crate::db::Connection) -> Result<usize, diesel::result::Error> {
+        use crate::schema::integrations::dsl;
+        diesel::update(dsl::integrations.find(self.id))
+            .set(dsl::last_synced_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(conn)
+    }
+
+    pub fn get_group(&self, conn: &mut crate::db::Connection) -> Option<Group> {
+        Group::find_by_id(self.group_id, conn)
+    }
+
     pub fn save(&self, conn: &mut crate::db::PooledConnection) -> Self {
         diesel::update(crate::schema::integrations::table.find(self.id))
             .set(self)
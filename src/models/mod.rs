@@ -1,7 +1,15 @@
 pub mod integration;
 pub mod calendar;
+pub mod event;
 pub mod app;
 pub mod oauth_integration;
 pub mod group;
 pub mod app_key;
-pub mod oauth2_state;
\ No newline at end of file
+pub mod oauth2_state;
+pub mod watch_channel;
+pub mod event_mirror;
+pub mod feed;
+pub mod event_import;
+pub mod task;
+pub mod caldav_integration;
+pub mod calendar_object;
\ No newline at end of file
@@ -1,5 +1,5 @@
 use chrono::NaiveDateTime;
-use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, query_builder::AsChangeset, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, query_builder::AsChangeset, BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
 
 use crate::connectors::oauth2::Oauth2Service;
 
@@ -9,12 +9,16 @@ use super::integration::Integration;
 #[derive(Clone, Queryable, Selectable, AsChangeset)]
 #[diesel(table_name = crate::schema::oauth_integrations)]
 #[diesel(check_for_backend(crate::db::Backend))]
+#[diesel(treat_none_as_null = true)]
 pub struct OauthIntegration {
     pub id: i32,
+    pub integration_id: i32,
     pub service: Oauth2Service,
     pub access_token: String,
     pub refresh_token: String,
     pub expires_at: NaiveDateTime,
+    pub refresh_failures: i32,
+    pub next_refresh_at: Option<NaiveDateTime>,
 }
 
 impl OauthIntegration {
@@ -40,6 +44,48 @@ impl OauthIntegration {
             .expect("Error saving new oauth integration")
     }
 
+    /**
+     * Find the OAuth2 credentials belonging to an integration.
+     */
+    pub fn find_by_integration(integration: &Integration, conn: &mut crate::db::Connection) -> Option<OauthIntegration> {
+        use crate::schema::oauth_integrations::dsl;
+        let Ok(result) = dsl::oauth_integrations.select(OauthIntegration::as_select())
+            .filter(dsl::integration_id.eq(integration.id))
+            .first::<OauthIntegration>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+
+    /**
+     * Find the credentials whose access token expires before the given time, skipping the ones
+     * still waiting to retry a failed refresh. The credentials expiring first come first.
+     */
+    pub fn find_expiring(before: NaiveDateTime, conn: &mut crate::db::Connection) -> Vec<OauthIntegration> {
+        use crate::schema::oauth_integrations::dsl;
+        let now = chrono::Utc::now().naive_utc();
+        dsl::oauth_integrations.select(OauthIntegration::as_select())
+            .filter(dsl::expires_at.le(before))
+            .filter(dsl::next_refresh_at.is_null().or(dsl::next_refresh_at.le(now)))
+            .order(dsl::expires_at)
+            .load::<OauthIntegration>(conn)
+            .expect("Error loading expiring oauth integrations")
+    }
+
+    /**
+     * Record a failed token refresh. The credentials are not picked up again until `retry_at`.
+     */
+    pub fn record_refresh_failure(&self, retry_at: NaiveDateTime, conn: &mut crate::db::Connection) -> Result<usize, diesel::result::Error> {
+        use crate::schema::oauth_integrations::dsl;
+        diesel::update(dsl::oauth_integrations.find(self.id))
+            .set((
+                dsl::refresh_failures.eq(dsl::refresh_failures + 1),
+                dsl::next_refresh_at.eq(retry_at),
+            ))
+            .execute(conn)
+    }
+
     pub fn save(&self, conn: &mut crate::db::PooledConnection) -> Self {
         diesel::update(crate::schema::oauth_integrations::table.find(self.id))
             .set(self)
@@ -0,0 +1,38 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/**
+ * The intermediate struct connectors map their tasks (VTODOs) into. Like the EventResult, it is
+ * identified by its external_id. Changed occurrences of a recurring task carry the external_id
+ * of their series as recurring_task_id.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskResult {
+    pub external_id: String,
+    pub etag: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub starts_at: Option<NaiveDateTime>,
+    pub due_at: Option<NaiveDateTime>,
+    pub all_day: bool,
+    pub completed_at: Option<NaiveDateTime>,
+    pub percent_complete: Option<u8>,
+    pub priority: Option<u8>,
+    pub status: TaskStatus,
+    pub recurrence: Option<String>,
+    pub recurring_task_id: Option<String>,
+    pub original_starts_at: Option<NaiveDateTime>,
+}
+
+/**
+ * The progress of a task. Tasks without a status have not been started.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    #[default]
+    NeedsAction,
+    InProcess,
+    Completed,
+    Cancelled,
+}
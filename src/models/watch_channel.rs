@@ -0,0 +1,130 @@
+use chrono::NaiveDateTime;
+use diesel::{deserialize::Queryable, insert_into, prelude::Insertable, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+
+use crate::connectors::oauth2::Oauth2Service;
+
+use super::calendar::Calendar;
+
+/**
+ * A push notification channel registered with a service. The service calls the webhook of the
+ * channel whenever the events of the calendar change.
+ */
+#[derive(Debug)]
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::schema::watch_channels)]
+#[diesel(check_for_backend(crate::db::Backend))]
+pub struct WatchChannel {
+    pub id: i32,
+    pub calendar_id: i32,
+    pub service: Oauth2Service,
+    pub external_id: String,
+    pub resource_id: Option<String>,
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+impl WatchChannel {
+
+    pub fn new(
+        calendar: &Calendar,
+        service: Oauth2Service,
+        token: String,
+        result: WatchChannelResult,
+        conn: &mut crate::db::Connection,
+    ) -> Result<Self, diesel::result::Error> {
+        insert_into(crate::schema::watch_channels::table)
+            .values(&NewWatchChannel {
+                calendar_id: calendar.id,
+                service,
+                external_id: result.external_id,
+                resource_id: result.resource_id,
+                token,
+                expires_at: result.expires_at,
+            })
+            .returning(WatchChannel::as_returning())
+            .get_result(conn)
+    }
+
+    /**
+     * Find a channel by the id the service knows it by.
+     */
+    pub fn find_by_external_id(external_id: &str, conn: &mut crate::db::Connection) -> Option<WatchChannel> {
+        use crate::schema::watch_channels::dsl;
+        let Ok(result) = dsl::watch_channels.select(WatchChannel::as_select())
+            .filter(dsl::external_id.eq(external_id))
+            .first::<WatchChannel>(conn)
+        else {
+            return None;
+        };
+        Some(result)
+    }
+
+    /**
+     * Find the channels watching the given calendar.
+     */
+    pub fn find_by_calendar(calendar: &Calendar, conn: &mut crate::db::Connection) -> Vec<WatchChannel> {
+        use crate::schema::watch_channels::dsl;
+        dsl::watch_channels.select(WatchChannel::as_select())
+            .filter(dsl::calendar_id.eq(calendar.id))
+            .order(dsl::id)
+            .load::<WatchChannel>(conn)
+            .expect("Error loading watch channels")
+    }
+
+    /**
+     * Find the channels expiring before the given time, the ones expiring first come first.
+     */
+    pub fn find_expiring(before: NaiveDateTime, conn: &mut crate::db::Connection) -> Vec<WatchChannel> {
+        use crate::schema::watch_channels::dsl;
+        dsl::watch_channels.select(WatchChannel::as_select())
+            .filter(dsl::expires_at.le(before))
+            .order(dsl::expires_at)
+            .load::<WatchChannel>(conn)
+            .expect("Error loading expiring watch channels")
+    }
+
+    /**
+     * Get the calendar the channel is watching.
+     */
+    pub fn get_calendar(&self, conn: &mut crate::db::Connection) -> Calendar {
+        Calendar::find_by_id(self.calendar_id, conn)
+            .expect("Error loading calendar of watch channel")
+    }
+
+    /**
+     * Store the new expiry of a channel the service extended.
+     */
+    pub fn update_expiry(&self, expires_at: NaiveDateTime, conn: &mut crate::db::Connection) -> Result<usize, diesel::result::Error> {
+        use crate::schema::watch_channels::dsl;
+        diesel::update(dsl::watch_channels.find(self.id))
+            .set(dsl::expires_at.eq(expires_at))
+            .execute(conn)
+    }
+
+    pub fn delete(&self, conn: &mut crate::db::Connection) -> Result<usize, diesel::result::Error> {
+        diesel::delete(crate::schema::watch_channels::table.find(self.id))
+            .execute(conn)
+    }
+
+}
+
+/**
+ * A channel as registered with a service.
+ */
+#[derive(Debug)]
+pub struct WatchChannelResult {
+    pub external_id: String,
+    pub resource_id: Option<String>,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::watch_channels)]
+struct NewWatchChannel {
+    calendar_id: i32,
+    service: Oauth2Service,
+    external_id: String,
+    resource_id: Option<String>,
+    token: String,
+    expires_at: NaiveDateTime,
+}
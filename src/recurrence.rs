@@ -0,0 +1,750 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Timelike, Weekday};
+use chrono_tz::Tz;
+
+use crate::{connectors::caldav::datetime::iana_time_zone, models::event::Event};
+
+/**
+ * Expanding a rule stops after this many periods past the range it is expanded for, so rules
+ * which never or hardly ever match cannot keep a request busy.
+ */
+const MAX_PERIODS: usize = 100_000;
+
+/**
+ * The error types for parsing recurrence rules
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecurrenceError {
+    InvalidLine(String),
+    InvalidRule(String),
+    InvalidDate(String),
+}
+
+impl std::fmt::Display for RecurrenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLine(line) => write!(f, "Invalid recurrence line: {}", line),
+            Self::InvalidRule(rule) => write!(f, "Invalid recurrence rule: {}", rule),
+            Self::InvalidDate(value) => write!(f, "Invalid recurrence date: {}", value),
+        }
+    }
+}
+
+impl std::error::Error for RecurrenceError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Frequency {
+    Secondly,
+    Minutely,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/**
+ * A date or date time value of an RDATE, EXDATE or UNTIL. Local values are in the time zone of
+ * their TZID parameter, or in the time zone of the event when they have none.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecurrenceDate {
+    Date(NaiveDate),
+    Utc(NaiveDateTime),
+    Local(NaiveDateTime, Option<Tz>),
+}
+
+impl RecurrenceDate {
+    /**
+     * Parse a DATE or DATE-TIME value. A trailing Z marks UTC, any other date time is local.
+     */
+    pub fn parse(value: &str, time_zone: Option<Tz>) -> Result<Self, RecurrenceError> {
+        let invalid = || RecurrenceError::InvalidDate(value.to_string());
+        if value.len() == 8 {
+            return NaiveDate::parse_from_str(value, "%Y%m%d").map(Self::Date).map_err(|_| invalid());
+        }
+        match value.strip_suffix('Z') {
+            Some(utc) => NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map(Self::Utc).map_err(|_| invalid()),
+            None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+                .map(|local| Self::Local(local, time_zone))
+                .map_err(|_| invalid()),
+        }
+    }
+
+    /**
+     * The UTC timestamp of the value. Dates take the given time of day, local values without a
+     * TZID are read in the given time zone.
+     */
+    fn to_utc(self, time_zone: &Tz, time: NaiveTime, all_day: bool) -> NaiveDateTime {
+        match self {
+            Self::Date(date) if all_day => date.and_time(NaiveTime::MIN),
+            Self::Date(date) => local_to_utc(time_zone, date.and_time(time)),
+            Self::Utc(value) => value,
+            Self::Local(value, _) if all_day => value.date().and_time(NaiveTime::MIN),
+            Self::Local(value, zone) => local_to_utc(&zone.unwrap_or(*time_zone), value),
+        }
+    }
+}
+
+/**
+ * A parsed RRULE.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub count: Option<u32>,
+    pub until: Option<RecurrenceDate>,
+    pub by_second: Vec<u32>,
+    pub by_minute: Vec<u32>,
+    pub by_hour: Vec<u32>,
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    pub by_month_day: Vec<i32>,
+    pub by_year_day: Vec<i32>,
+    pub by_week_no: Vec<i32>,
+    pub by_month: Vec<u32>,
+    pub by_set_pos: Vec<i32>,
+    pub week_start: Weekday,
+}
+
+impl RecurrenceRule {
+    /**
+     * Parse the value of an RRULE, e.g. FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10.
+     */
+    pub fn parse(value: &str) -> Result<Self, RecurrenceError> {
+        let invalid = || RecurrenceError::InvalidRule(value.to_string());
+
+        let mut rule = Self {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_second: Vec::new(),
+            by_minute: Vec::new(),
+            by_hour: Vec::new(),
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_year_day: Vec::new(),
+            by_week_no: Vec::new(),
+            by_month: Vec::new(),
+            by_set_pos: Vec::new(),
+            week_start: Weekday::Mon,
+        };
+        let mut frequency = None;
+
+        for part in value.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part.split_once('=').ok_or_else(invalid)?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => frequency = Some(match value.to_ascii_uppercase().as_str() {
+                    "SECONDLY" => Frequency::Secondly,
+                    "MINUTELY" => Frequency::Minutely,
+                    "HOURLY" => Frequency::Hourly,
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    _ => return Err(invalid()),
+                }),
+                "INTERVAL" => rule.interval = value.parse().ok().filter(|interval| *interval > 0).ok_or_else(invalid)?,
+                "COUNT" => rule.count = Some(value.parse().map_err(|_| invalid())?),
+                "UNTIL" => rule.until = Some(RecurrenceDate::parse(value, None)?),
+                "BYSECOND" => rule.by_second = parse_list(value, 0, 60).ok_or_else(invalid)?,
+                "BYMINUTE" => rule.by_minute = parse_list(value, 0, 59).ok_or_else(invalid)?,
+                "BYHOUR" => rule.by_hour = parse_list(value, 0, 23).ok_or_else(invalid)?,
+                "BYDAY" => rule.by_day = value.split(',').map(parse_weekday_num).collect::<Option<_>>().ok_or_else(invalid)?,
+                "BYMONTHDAY" => rule.by_month_day = parse_signed_list(value, 31).ok_or_else(invalid)?,
+                "BYYEARDAY" => rule.by_year_day = parse_signed_list(value, 366).ok_or_else(invalid)?,
+                "BYWEEKNO" => rule.by_week_no = parse_signed_list(value, 53).ok_or_else(invalid)?,
+                "BYMONTH" => rule.by_month = parse_list(value, 1, 12).ok_or_else(invalid)?,
+                "BYSETPOS" => rule.by_set_pos = parse_signed_list(value, 366).ok_or_else(invalid)?,
+                "WKST" => rule.week_start = parse_weekday(value).ok_or_else(invalid)?,
+                // Unknown parts are ignored, as RFC 5545 allows extensions
+                _ => {},
+            }
+        }
+
+        rule.frequency = frequency.ok_or_else(invalid)?;
+        Ok(rule)
+    }
+
+    /**
+     * Call emit with every occurrence of the rule in local time, in order, starting with the
+     * start of the series. Expanding stops when emit returns false, or once the rule ends.
+     *
+     * Occurrences before from may be skipped. Rules without a COUNT skip ahead to the period
+     * containing from, so a series which started long ago still reaches the range.
+     */
+    fn expand(&self, start: NaiveDateTime, from: NaiveDateTime, time_zone: &Tz, all_day: bool, mut emit: impl FnMut(NaiveDateTime) -> bool) {
+        let mut count = 0;
+        let mut is_within = |value: NaiveDateTime| -> bool {
+            if self.count.is_some_and(|limit| count >= limit) {
+                return false;
+            }
+            let within_until = match self.until {
+                None => true,
+                Some(RecurrenceDate::Date(date)) => value.date() <= date,
+                Some(RecurrenceDate::Utc(until)) if all_day => value.date() <= until.date(),
+                Some(RecurrenceDate::Utc(until)) => local_to_utc(time_zone, value) <= until,
+                Some(RecurrenceDate::Local(until, _)) => value <= until,
+            };
+            if within_until {
+                count += 1;
+            }
+            within_until
+        };
+
+        // The start of the series is always its first occurrence
+        if !is_within(start) || !emit(start) {
+            return;
+        }
+
+        // The occurrences of a COUNT have to be counted from the start of the series
+        let first_period = match self.count {
+            Some(_) => 0,
+            None => self.period_of(start, from),
+        };
+        let mut empty_periods = 0;
+        for period in first_period..first_period.saturating_add(MAX_PERIODS) {
+            let Some(candidates) = self.period_candidates(start, period) else {
+                return;
+            };
+            if candidates.is_empty() {
+                empty_periods += 1;
+                if empty_periods > MAX_PERIODS / 10 {
+                    return;
+                }
+                continue;
+            }
+            empty_periods = 0;
+
+            for candidate in candidates.into_iter().filter(|candidate| *candidate > start) {
+                if !is_within(candidate) || !emit(candidate) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /**
+     * The period after the start of the series containing the value, rounded down so it can
+     * only be too early.
+     */
+    fn period_of(&self, start: NaiveDateTime, value: NaiveDateTime) -> usize {
+        let elapsed = value - start;
+        let units = match self.frequency {
+            Frequency::Yearly => i64::from(value.year() - start.year()),
+            Frequency::Monthly => i64::from(value.year() - start.year()) * 12 + i64::from(value.month0()) - i64::from(start.month0()),
+            Frequency::Weekly => elapsed.num_weeks(),
+            Frequency::Daily => elapsed.num_days(),
+            Frequency::Hourly => elapsed.num_hours(),
+            Frequency::Minutely => elapsed.num_minutes(),
+            Frequency::Secondly => elapsed.num_seconds(),
+        };
+        let period = units / i64::from(self.interval) - 1;
+        usize::try_from(period).unwrap_or(0)
+    }
+
+    /**
+     * The occurrences the rule produces within the given period after the start of the series,
+     * sorted. Returns None once the periods run past the supported range of dates.
+     */
+    fn period_candidates(&self, start: NaiveDateTime, period: usize) -> Option<Vec<NaiveDateTime>> {
+        let step = i64::try_from(period).ok()? * i64::from(self.interval);
+        let date = start.date();
+
+        let (dates, fixed_time) = match self.frequency {
+            Frequency::Yearly => {
+                let year = i32::try_from(i64::from(date.year()) + step).ok()?;
+                NaiveDate::from_ymd_opt(year, 1, 1)?;
+                (self.yearly_dates(year, date), None)
+            },
+            Frequency::Monthly => {
+                let month = i64::from(date.year()) * 12 + i64::from(date.month0()) + step;
+                let year = i32::try_from(month.div_euclid(12)).ok()?;
+                let month = u32::try_from(month.rem_euclid(12)).ok()? + 1;
+                NaiveDate::from_ymd_opt(year, month, 1)?;
+                let dates = if self.by_month.is_empty() || self.by_month.contains(&month) {
+                    self.monthly_dates(year, month, date)
+                } else {
+                    Vec::new()
+                };
+                (dates, None)
+            },
+            Frequency::Weekly => {
+                let days_into_week = (7 + date.weekday().num_days_from_monday() - self.week_start.num_days_from_monday()) % 7;
+                let week = date.checked_sub_signed(Duration::days(days_into_week.into()))?
+                    .checked_add_signed(Duration::try_weeks(step)?)?;
+                let dates = (0..7)
+                    .filter_map(|day| week.checked_add_signed(Duration::days(day)))
+                    .filter(|day| match self.by_day.is_empty() {
+                        true => day.weekday() == date.weekday(),
+                        false => self.by_day.iter().any(|(_, weekday)| *weekday == day.weekday()),
+                    })
+                    .filter(|day| self.by_month.is_empty() || self.by_month.contains(&day.month()))
+                    .collect();
+                (dates, None)
+            },
+            Frequency::Daily => {
+                let day = date.checked_add_signed(Duration::try_days(step)?)?;
+                (self.filter_dates(vec![day]), None)
+            },
+            Frequency::Hourly | Frequency::Minutely | Frequency::Secondly => {
+                let unit = match self.frequency {
+                    Frequency::Hourly => Duration::try_hours(step)?,
+                    Frequency::Minutely => Duration::try_minutes(step)?,
+                    _ => Duration::try_seconds(step)?,
+                };
+                let value = start.checked_add_signed(unit)?;
+                (self.filter_dates(vec![value.date()]), Some(value.time()))
+            },
+        };
+
+        let mut candidates = Vec::new();
+        for day in dates {
+            for time in self.times(start.time(), fixed_time) {
+                candidates.push(day.and_time(time));
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+
+        if !self.by_set_pos.is_empty() {
+            let length = candidates.len() as i32;
+            let mut selected = self.by_set_pos.iter()
+                .filter_map(|position| match *position {
+                    position if position > 0 && position <= length => Some(candidates[(position - 1) as usize]),
+                    position if position < 0 && -position <= length => Some(candidates[(length + position) as usize]),
+                    _ => None,
+                })
+                .collect::<Vec<NaiveDateTime>>();
+            selected.sort();
+            selected.dedup();
+            candidates = selected;
+        }
+
+        Some(candidates)
+    }
+
+    fn yearly_dates(&self, year: i32, start: NaiveDate) -> Vec<NaiveDate> {
+        let days_in_year = days_in_year(year);
+
+        if !self.by_year_day.is_empty() {
+            let dates = self.by_year_day.iter()
+                .filter_map(|day| resolve_index(*day, days_in_year))
+                .filter_map(|day| NaiveDate::from_yo_opt(year, day))
+                .collect();
+            return self.filter_dates(dates);
+        }
+
+        if !self.by_week_no.is_empty() {
+            let first_week = first_week_start(year, self.week_start);
+            let weeks = (first_week_start(year + 1, self.week_start) - first_week).num_weeks() as u32;
+            let mut dates = Vec::new();
+            for week in self.by_week_no.iter().filter_map(|week| resolve_index(*week, weeks)) {
+                for day in 0..7 {
+                    let date = first_week + Duration::days(i64::from(week - 1) * 7 + day);
+                    let matches = match self.by_day.is_empty() {
+                        true => date.weekday() == start.weekday(),
+                        false => self.by_day.iter().any(|(_, weekday)| *weekday == date.weekday()),
+                    };
+                    if matches && (self.by_month.is_empty() || self.by_month.contains(&date.month())) {
+                        dates.push(date);
+                    }
+                }
+            }
+            return dates;
+        }
+
+        if !self.by_month.is_empty() {
+            return self.by_month.iter()
+                .flat_map(|month| self.monthly_dates(year, *month, start))
+                .collect();
+        }
+
+        if !self.by_month_day.is_empty() {
+            return (1..=12)
+                .flat_map(|month| self.monthly_dates(year, month, start))
+                .collect();
+        }
+
+        if !self.by_day.is_empty() {
+            let days = (1..=days_in_year)
+                .filter_map(|day| NaiveDate::from_yo_opt(year, day))
+                .collect::<Vec<NaiveDate>>();
+            return select_weekdays(&days, &self.by_day);
+        }
+
+        NaiveDate::from_ymd_opt(year, start.month(), start.day()).into_iter().collect()
+    }
+
+    fn monthly_dates(&self, year: i32, month: u32, start: NaiveDate) -> Vec<NaiveDate> {
+        let days = (1..=31)
+            .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+            .collect::<Vec<NaiveDate>>();
+
+        if !self.by_month_day.is_empty() {
+            return self.by_month_day.iter()
+                .filter_map(|day| resolve_index(*day, days.len() as u32))
+                .map(|day| days[(day - 1) as usize])
+                .filter(|date| self.by_day.is_empty() || self.by_day.iter().any(|(_, weekday)| *weekday == date.weekday()))
+                .collect();
+        }
+
+        if !self.by_day.is_empty() {
+            return select_weekdays(&days, &self.by_day);
+        }
+
+        NaiveDate::from_ymd_opt(year, month, start.day()).into_iter().collect()
+    }
+
+    /**
+     * Limit the date of a daily or more frequent rule by the BYMONTH, BYMONTHDAY and BYDAY parts.
+     */
+    fn filter_dates(&self, dates: Vec<NaiveDate>) -> Vec<NaiveDate> {
+        dates.into_iter()
+            .filter(|date| self.by_month.is_empty() || self.by_month.contains(&date.month()))
+            .filter(|date| self.by_month_day.is_empty() || self.by_month_day.iter().any(|day| {
+                resolve_index(*day, days_in_month(date.year(), date.month())) == Some(date.day())
+            }))
+            .filter(|date| self.by_day.is_empty() || self.by_day.iter().any(|(_, weekday)| *weekday == date.weekday()))
+            .collect()
+    }
+
+    /**
+     * The times of day the rule occurs at. Rules more frequent than daily have the time of their
+     * period, which the BYHOUR, BYMINUTE and BYSECOND parts then limit or expand.
+     */
+    fn times(&self, start: NaiveTime, fixed: Option<NaiveTime>) -> Vec<NaiveTime> {
+        let hours = match (fixed, self.frequency) {
+            (Some(time), _) if !self.by_hour.is_empty() && !self.by_hour.contains(&time.hour()) => return Vec::new(),
+            (Some(time), _) => vec![time.hour()],
+            (None, _) if self.by_hour.is_empty() => vec![start.hour()],
+            (None, _) => self.by_hour.clone(),
+        };
+        let minutes = match (fixed, self.frequency) {
+            (Some(time), Frequency::Minutely | Frequency::Secondly) if !self.by_minute.is_empty() && !self.by_minute.contains(&time.minute()) => return Vec::new(),
+            (Some(time), Frequency::Minutely | Frequency::Secondly) => vec![time.minute()],
+            _ if self.by_minute.is_empty() => vec![fixed.unwrap_or(start).minute()],
+            _ => self.by_minute.clone(),
+        };
+        let seconds = match (fixed, self.frequency) {
+            (Some(time), Frequency::Secondly) if !self.by_second.is_empty() && !self.by_second.contains(&time.second()) => return Vec::new(),
+            (Some(time), Frequency::Secondly) => vec![time.second()],
+            _ if self.by_second.is_empty() => vec![fixed.unwrap_or(start).second()],
+            _ => self.by_second.clone(),
+        };
+
+        let mut times = Vec::new();
+        for hour in hours.iter() {
+            for minute in minutes.iter() {
+                for second in seconds.iter() {
+                    // A leap second is read as the last second of the minute
+                    if let Some(time) = NaiveTime::from_hms_opt(*hour, *minute, (*second).min(59)) {
+                        times.push(time);
+                    }
+                }
+            }
+        }
+        times
+    }
+}
+
+/**
+ * The recurrence of an event: its RRULEs, the dates added with RDATE and the dates removed with
+ * EXDATE. This is the format the recurrence of events is stored in, one property per line.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub rules: Vec<RecurrenceRule>,
+    pub dates: Vec<RecurrenceDate>,
+    pub exceptions: Vec<RecurrenceDate>,
+}
+
+impl Recurrence {
+    /**
+     * Parse the recurrence lines of an event. Lines other than RRULE, RDATE and EXDATE are
+     * ignored. RDATE periods only contribute their start, the occurrence keeps the duration of
+     * the event.
+     */
+    pub fn parse(lines: &str) -> Result<Self, RecurrenceError> {
+        let mut recurrence = Self {
+            rules: Vec::new(),
+            dates: Vec::new(),
+            exceptions: Vec::new(),
+        };
+
+        for line in lines.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':')
+                .ok_or_else(|| RecurrenceError::InvalidLine(line.to_string()))?;
+            let mut params = name.split(';');
+            let name = params.next().unwrap_or_default().to_ascii_uppercase();
+            let time_zone = params
+                .filter_map(|param| param.split_once('='))
+                .find(|(param, _)| param.eq_ignore_ascii_case("TZID"))
+                .and_then(|(_, value)| iana_time_zone(value.trim_matches('"')));
+
+            match name.as_str() {
+                "RRULE" => recurrence.rules.push(RecurrenceRule::parse(value)?),
+                "RDATE" | "EXDATE" => {
+                    let dates = value.split(',')
+                        .map(|value| value.split('/').next().unwrap_or_default())
+                        .map(|value| RecurrenceDate::parse(value, time_zone))
+                        .collect::<Result<Vec<RecurrenceDate>, RecurrenceError>>()?;
+                    match name.as_str() {
+                        "RDATE" => recurrence.dates.extend(dates),
+                        _ => recurrence.exceptions.extend(dates),
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        Ok(recurrence)
+    }
+
+    /**
+     * Find the starts of the occurrences which overlap the range from start to end. The series
+     * starts at series_start, in UTC, and every occurrence lasts as long as the first one.
+     * Occurrences are expanded in the local time of the time zone, so they keep their time of
+     * day across daylight saving time changes. All-day events are expanded on their dates.
+     */
+    pub fn occurrences(
+        &self,
+        series_start: NaiveDateTime,
+        duration: Duration,
+        time_zone: Option<&str>,
+        all_day: bool,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Vec<NaiveDateTime> {
+        let time_zone = match all_day {
+            true => Tz::UTC,
+            false => time_zone.and_then(iana_time_zone).unwrap_or(Tz::UTC),
+        };
+        let local_start = time_zone.from_utc_datetime(&series_start).naive_local();
+        let to_utc = |local: NaiveDateTime| match all_day {
+            true => local,
+            false => local_to_utc(&time_zone, local),
+        };
+
+        let mut occurrences = HashSet::new();
+        let mut add = |value: NaiveDateTime| {
+            if value < end && value + duration > start {
+                occurrences.insert(value);
+            }
+        };
+
+        if self.rules.is_empty() {
+            add(series_start);
+        }
+        // Occurrences starting a day before the range, in local time, cover any difference in
+        // offset between the start of the series and the range
+        let from = time_zone.from_utc_datetime(&(start - duration)).naive_local() - Duration::days(1);
+        for rule in self.rules.iter() {
+            rule.expand(local_start, from, &time_zone, all_day, |local| {
+                let value = to_utc(local);
+                if value >= end {
+                    return false;
+                }
+                add(value);
+                true
+            });
+        }
+        for date in self.dates.iter() {
+            add(date.to_utc(&time_zone, local_start.time(), all_day));
+        }
+
+        let mut occurrences = occurrences.into_iter()
+            .filter(|value| !self.exceptions.iter().any(|exception| match exception {
+                RecurrenceDate::Date(date) => time_zone.from_utc_datetime(value).date_naive() == *date,
+                _ => exception.to_utc(&time_zone, local_start.time(), all_day) == *value,
+            }))
+            .collect::<Vec<NaiveDateTime>>();
+        occurrences.sort();
+        occurrences
+    }
+}
+
+/**
+ * A single occurrence of an event. Events which do not repeat have one occurrence, recurring
+ * events have one for every instance of their series. The event of an occurrence is the series
+ * or the override the occurrence was taken from.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Occurrence<'a> {
+    pub event: &'a Event,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub original_starts_at: Option<NaiveDateTime>,
+}
+
+/**
+ * Expand the events into the occurrences which overlap the range from start to end. Overrides
+ * of a series, the events with a recurring_event_id, replace the occurrence of their series
+ * which starts at their original_starts_at. Cancelled overrides are returned as well, so
+ * callers can tell a cancelled instance from one that was never there.
+ *
+ * A series whose recurrence cannot be parsed only occurs at its own start.
+ */
+pub fn expand_events(events: &[Event], start: NaiveDateTime, end: NaiveDateTime) -> Vec<Occurrence<'_>> {
+    let mut overridden: HashMap<(i32, &str), HashSet<NaiveDateTime>> = HashMap::new();
+    for event in events.iter() {
+        if let (Some(series), Some(original_starts_at)) = (&event.recurring_event_id, event.original_starts_at) {
+            overridden.entry((event.calendar_id, series.as_str())).or_default().insert(original_starts_at);
+        }
+    }
+
+    let mut occurrences = Vec::new();
+    for event in events.iter() {
+        let duration = event.ends_at - event.starts_at;
+        let recurrence = match (&event.recurrence, &event.recurring_event_id) {
+            (Some(lines), None) => match Recurrence::parse(lines) {
+                Ok(recurrence) => Some(recurrence),
+                Err(err) => {
+                    println!("Failed to parse the recurrence of event {}: {}", event.id, err);
+                    None
+                },
+            },
+            _ => None,
+        };
+
+        let Some(recurrence) = recurrence else {
+            if event.starts_at < end && event.ends_at > start {
+                occurrences.push(Occurrence {
+                    event,
+                    starts_at: event.starts_at,
+                    ends_at: event.ends_at,
+                    original_starts_at: event.original_starts_at,
+                });
+            }
+            continue;
+        };
+
+        let overrides = overridden.get(&(event.calendar_id, event.external_id.as_str()));
+        for starts_at in recurrence.occurrences(event.starts_at, duration, event.time_zone.as_deref(), event.all_day, start, end) {
+            if overrides.is_some_and(|overrides| overrides.contains(&starts_at)) {
+                continue;
+            }
+            occurrences.push(Occurrence {
+                event,
+                starts_at,
+                ends_at: starts_at + duration,
+                original_starts_at: Some(starts_at),
+            });
+        }
+    }
+
+    occurrences.sort_by_key(|occurrence| occurrence.starts_at);
+    occurrences
+}
+
+/**
+ * Convert a local time of the time zone to UTC. Ambiguous times take their first occurrence.
+ * Times skipped by a daylight saving time change use the offset from before the change, as
+ * RFC 5545 describes, which moves them forward by the length of the gap.
+ */
+pub fn local_to_utc(time_zone: &Tz, local: NaiveDateTime) -> NaiveDateTime {
+    match time_zone.from_local_datetime(&local) {
+        LocalResult::Single(value) | LocalResult::Ambiguous(value, _) => value.naive_utc(),
+        LocalResult::None => {
+            let offset = time_zone.offset_from_utc_datetime(&(local - Duration::days(1))).fix();
+            local - Duration::seconds(offset.local_minus_utc().into())
+        },
+    }
+}
+
+fn parse_list(value: &str, min: u32, max: u32) -> Option<Vec<u32>> {
+    value.split(',')
+        .map(|item| item.parse::<u32>().ok().filter(|item| (min..=max).contains(item)))
+        .collect()
+}
+
+fn parse_signed_list(value: &str, max: i32) -> Option<Vec<i32>> {
+    value.split(',')
+        .map(|item| item.parse::<i32>().ok().filter(|item| *item != 0 && item.abs() <= max))
+        .collect()
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value.to_ascii_uppercase().as_str() {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/**
+ * Parse a BYDAY item like MO, 2TU or -1FR.
+ */
+fn parse_weekday_num(value: &str) -> Option<(Option<i32>, Weekday)> {
+    let split = value.len().checked_sub(2)?;
+    let weekday = parse_weekday(value.get(split..)?)?;
+    let number = match value.get(..split)? {
+        "" => None,
+        number => Some(number.trim_start_matches('+').parse::<i32>().ok().filter(|number| *number != 0)?),
+    };
+    Some((number, weekday))
+}
+
+/**
+ * Pick the weekdays out of a month or year. Numbered weekdays pick the nth one, counting from
+ * the end when negative.
+ */
+fn select_weekdays(days: &[NaiveDate], by_day: &[(Option<i32>, Weekday)]) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    for (number, weekday) in by_day.iter() {
+        let matching = days.iter().filter(|day| day.weekday() == *weekday).copied().collect::<Vec<NaiveDate>>();
+        match number {
+            None => dates.extend(matching),
+            Some(number) => {
+                if let Some(index) = resolve_index(*number, matching.len() as u32) {
+                    dates.push(matching[(index - 1) as usize]);
+                }
+            },
+        }
+    }
+    dates.sort();
+    dates.dedup();
+    dates
+}
+
+/**
+ * Resolve a 1-based index that counts from the end when negative.
+ */
+fn resolve_index(index: i32, length: u32) -> Option<u32> {
+    let length = length as i32;
+    match index {
+        index if index > 0 && index <= length => Some(index as u32),
+        index if index < 0 && -index <= length => Some((length + index + 1) as u32),
+        _ => None,
+    }
+}
+
+/**
+ * The start of the first week of the year, which is the first week with at least four days in
+ * the year.
+ */
+fn first_week_start(year: i32, week_start: Weekday) -> NaiveDate {
+    let january_fourth = NaiveDate::from_ymd_opt(year, 1, 4).unwrap_or_default();
+    let days_into_week = (7 + january_fourth.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    january_fourth - Duration::days(days_into_week.into())
+}
+
+fn days_in_year(year: i32) -> u32 {
+    match NaiveDate::from_ymd_opt(year, 2, 29) {
+        Some(_) => 366,
+        None => 365,
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    (28..=31).rev()
+        .find(|day| NaiveDate::from_ymd_opt(year, month, *day).is_some())
+        .unwrap_or(28)
+}
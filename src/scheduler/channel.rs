@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use crate::{models::watch_channel::WatchChannel, sync, AppState};
+
+use super::Shutdown;
+
+/**
+ * Renew every push notification channel expiring within the configured window. Channels which
+ * fail to renew are retried on the next run until they expire.
+ */
+pub async fn renew_channels(state: Arc<AppState>, shutdown: Shutdown) {
+    let window = chrono::Duration::from_std(state.config.scheduler.watch_renew_window)
+        .expect("Watch renew window is too large");
+    let before = chrono::Utc::now().naive_utc() + window;
+
+    for channel in WatchChannel::find_expiring(before, &mut state.get_connection()) {
+        if shutdown.is_requested() {
+            break;
+        }
+
+        if let Err(err) = sync::renew_channel(&channel, &state).await {
+            println!("Failed to renew channel {}: {:?}", channel.external_id, err);
+        }
+    }
+}
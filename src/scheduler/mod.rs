@@ -0,0 +1,91 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use tokio::{sync::watch, task::JoinSet, time::MissedTickBehavior};
+
+use crate::AppState;
+
+mod sync;
+mod token;
+mod channel;
+
+/**
+ * Runs the background jobs of the application alongside the HTTP server. Every job runs on its
+ * own interval until the scheduler is shut down.
+ */
+pub struct Scheduler {
+    shutdown: watch::Sender<bool>,
+    jobs: JoinSet<()>,
+}
+
+impl Scheduler {
+
+    /**
+     * Start every background job. Nothing is started when the scheduler is disabled.
+     */
+    pub fn start(state: Arc<AppState>) -> Self {
+        let (shutdown, _) = watch::channel(false);
+        let mut scheduler = Self {
+            shutdown,
+            jobs: JoinSet::new(),
+        };
+
+        let config = state.config.scheduler.clone();
+        if !config.enabled {
+            return scheduler;
+        }
+
+        scheduler.spawn(config.token_refresh_interval, state.clone(), token::refresh_tokens);
+        scheduler.spawn(config.watch_renew_interval, state.clone(), channel::renew_channels);
+        scheduler.spawn(config.sync_interval, state, sync::sync_integrations);
+        scheduler
+    }
+
+    /**
+     * Signal every job to stop and wait for the work in progress to finish.
+     */
+    pub async fn shutdown(mut self) {
+        let _ = self.shutdown.send(true);
+        while self.jobs.join_next().await.is_some() {}
+    }
+
+    /**
+     * Run the job on the given interval. The first run starts immediately.
+     */
+    fn spawn<F, Fut>(&mut self, interval: Duration, state: Arc<AppState>, job: F)
+    where
+        F: Fn(Arc<AppState>, Shutdown) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut shutdown = Shutdown(self.shutdown.subscribe());
+        self.jobs.spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {},
+                    _ = shutdown.wait() => break,
+                }
+                job(state.clone(), shutdown.clone()).await;
+            }
+        });
+    }
+}
+
+/**
+ * Handed to every job so long running work can stop early once a shutdown is requested.
+ */
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /**
+     * Wait until a shutdown is requested.
+     */
+    pub async fn wait(&mut self) {
+        let _ = self.0.wait_for(|requested| *requested).await;
+    }
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::{models::integration::Integration, sync::{self, SyncError}, AppState};
+
+use super::Shutdown;
+
+/**
+ * Sync every integration, running at most the configured number of syncs at the same time.
+ * Integrations which have waited the longest since their last sync go first.
+ */
+pub async fn sync_integrations(state: Arc<AppState>, mut shutdown: Shutdown) {
+    let integrations = Integration::all(&mut state.get_connection());
+    let semaphore = Arc::new(Semaphore::new(state.config.scheduler.sync_concurrency));
+    let mut tasks = JoinSet::new();
+
+    for integration in integrations {
+        // Stop handing out work once a shutdown is requested
+        let permit = tokio::select! {
+            permit = semaphore.clone().acquire_owned() => permit.expect("Sync semaphore closed"),
+            _ = shutdown.wait() => break,
+        };
+
+        let state = state.clone();
+        tasks.spawn(async move {
+            let _permit = permit;
+            match sync::sync_integration(&integration, &state).await {
+                Ok(_) | Err(SyncError::UnsupportedService) => {},
+                Err(err) => println!("Failed to sync integration {}: {:?}", integration.id, err),
+            }
+        });
+    }
+
+    // Let the syncs in progress finish
+    while tasks.join_next().await.is_some() {}
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use crate::{connectors::oauth2::{Oauth2Connector, Oauth2ServiceConnector}, models::oauth_integration::OauthIntegration, AppState};
+
+use super::Shutdown;
+
+/**
+ * The delay before retrying the first failed refresh, doubled with every further failure.
+ */
+const RETRY_BASE_SECONDS: i64 = 60;
+
+/**
+ * The longest delay between two refresh attempts.
+ */
+const RETRY_MAX_SECONDS: i64 = 6 * 60 * 60;
+
+/**
+ * Refresh every access token expiring within the configured window, so requests to the services
+ * never go out with a stale token. Failed refreshes are retried with an exponential backoff.
+ */
+pub async fn refresh_tokens(state: Arc<AppState>, shutdown: Shutdown) {
+    let window = chrono::Duration::from_std(state.config.scheduler.token_refresh_window)
+        .expect("Token refresh window is too large");
+    let before = chrono::Utc::now().naive_utc() + window;
+
+    for mut oauth_integration in OauthIntegration::find_expiring(before, &mut state.get_connection()) {
+        if shutdown.is_requested() {
+            break;
+        }
+
+        let connector = Oauth2Connector::new(&oauth_integration.service, &state.config);
+        let Err(err) = connector.new_access_token(&mut oauth_integration, &state).await else {
+            continue;
+        };
+
+        println!("Failed to refresh the access token of integration {}: {:?}", oauth_integration.integration_id, err);
+        let retry_at = chrono::Utc::now().naive_utc() + retry_delay(oauth_integration.refresh_failures);
+        if let Err(err) = oauth_integration.record_refresh_failure(retry_at, &mut state.get_connection()) {
+            println!("{:?}", err);
+        }
+    }
+}
+
+/**
+ * The delay before the next attempt, given the number of refreshes which failed so far.
+ */
+fn retry_delay(failures: i32) -> chrono::Duration {
+    let exponent = failures.clamp(0, 16) as u32;
+    chrono::Duration::seconds((RETRY_BASE_SECONDS * 2_i64.pow(exponent)).min(RETRY_MAX_SECONDS))
+}
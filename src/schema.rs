@@ -21,11 +21,127 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    caldav_integrations (id) {
+        id -> Int4,
+        integration_id -> Int4,
+        url -> Text,
+        #[max_length = 255]
+        username -> Varchar,
+        password -> Nullable<Text>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    calendar_objects (id) {
+        id -> Int4,
+        calendar_id -> Int4,
+        href -> Text,
+        etag -> Text,
+        uid -> Nullable<Text>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    calendars (id) {
+        id -> Int4,
+        integration_id -> Int4,
+        external_id -> Text,
+        name -> Text,
+        #[max_length = 255]
+        background_color -> Varchar,
+        #[max_length = 255]
+        foreground_color -> Varchar,
+        created_at -> Nullable<Timestamp>,
+        sync_token -> Nullable<Text>,
+        mirror -> Bool,
+    }
+}
+
+diesel::table! {
+    event_imports (id) {
+        id -> Int4,
+        calendar_id -> Int4,
+        #[max_length = 255]
+        uid -> Varchar,
+        #[max_length = 255]
+        external_id -> Varchar,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    event_mirrors (id) {
+        id -> Int4,
+        source_calendar_id -> Int4,
+        source_event_id -> Text,
+        calendar_id -> Int4,
+        external_id -> Text,
+        etag -> Nullable<Text>,
+        starts_at -> Timestamp,
+        ends_at -> Timestamp,
+        all_day -> Bool,
+        #[max_length = 255]
+        time_zone -> Nullable<Varchar>,
+        recurrence -> Nullable<Text>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    events (id) {
+        id -> Int4,
+        calendar_id -> Int4,
+        external_id -> Text,
+        etag -> Nullable<Text>,
+        summary -> Nullable<Text>,
+        description -> Nullable<Text>,
+        location -> Nullable<Text>,
+        starts_at -> Timestamp,
+        ends_at -> Timestamp,
+        all_day -> Bool,
+        #[max_length = 255]
+        time_zone -> Nullable<Varchar>,
+        status -> Int2,
+        transparency -> Int2,
+        visibility -> Int2,
+        organizer -> Nullable<Text>,
+        attendees -> Text,
+        recurrence -> Nullable<Text>,
+        recurring_event_id -> Nullable<Text>,
+        original_starts_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    feeds (id) {
+        id -> Int4,
+        group_id -> Int4,
+        #[max_length = 255]
+        token -> Varchar,
+        privacy -> Int2,
+        past_days -> Int4,
+        future_days -> Int4,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     groups (id) {
         id -> Int4,
         app_id -> Int4,
         created_at -> Nullable<Timestamp>,
+        mirroring -> Bool,
+        #[max_length = 255]
+        time_zone -> Varchar,
+        working_hours -> Text,
+        buffer_before -> Int4,
+        buffer_after -> Int4,
+        minimum_notice -> Int4,
+        max_days -> Int4,
     }
 }
 
@@ -35,6 +151,7 @@ diesel::table! {
         service -> Int2,
         group_id -> Int4,
         created_at -> Nullable<Timestamp>,
+        last_synced_at -> Nullable<Timestamp>,
     }
 }
 
@@ -59,20 +176,52 @@ diesel::table! {
         #[max_length = 255]
         refresh_token -> Varchar,
         created_at -> Nullable<Timestamp>,
+        refresh_failures -> Int4,
+        next_refresh_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    watch_channels (id) {
+        id -> Int4,
+        calendar_id -> Int4,
+        service -> Int2,
+        #[max_length = 255]
+        external_id -> Varchar,
+        resource_id -> Nullable<Text>,
+        #[max_length = 255]
+        token -> Varchar,
+        expires_at -> Timestamp,
+        created_at -> Nullable<Timestamp>,
     }
 }
 
 diesel::joinable!(app_keys -> apps (app_id));
+diesel::joinable!(caldav_integrations -> integrations (integration_id));
+diesel::joinable!(calendar_objects -> calendars (calendar_id));
+diesel::joinable!(calendars -> integrations (integration_id));
+diesel::joinable!(event_imports -> calendars (calendar_id));
+diesel::joinable!(events -> calendars (calendar_id));
+diesel::joinable!(feeds -> groups (group_id));
 diesel::joinable!(groups -> apps (app_id));
-diesel::joinable!(integrations -> apps (group_id));
+diesel::joinable!(integrations -> groups (group_id));
 diesel::joinable!(oauth2_states -> groups (group_id));
-diesel::joinable!(oauth_integrations -> apps (integration_id));
+diesel::joinable!(oauth_integrations -> integrations (integration_id));
+diesel::joinable!(watch_channels -> calendars (calendar_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     app_keys,
     apps,
+    caldav_integrations,
+    calendar_objects,
+    calendars,
+    event_imports,
+    event_mirrors,
+    events,
+    feeds,
     groups,
     integrations,
     oauth2_states,
     oauth_integrations,
+    watch_channels,
 );
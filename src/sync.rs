@@ -0,0 +1,385 @@
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+
+use serde::Serialize;
+
+use uuid::Uuid;
+
+use crate::{connectors::{caldav::caldav, oauth2::{Oauth2Connector, Oauth2ConnectorError, Oauth2ServiceConnector}, ServiceType}, models::{caldav_integration::CaldavIntegration, calendar::{Calendar, CalendarSyncResult}, calendar_object::{CalendarObject, CalendarObjectResult}, event::{Event, EventChanges, EventSyncResult}, integration::Integration, oauth_integration::OauthIntegration, watch_channel::WatchChannel}, mirror, AppState};
+
+/**
+ * Access tokens expiring within this many seconds are refreshed before they are used.
+ */
+const ACCESS_TOKEN_EXPIRY_MARGIN: i64 = 60;
+
+/**
+ * The outcome of syncing an integration: the calendar changes and the event changes summed
+ * across every calendar of the integration.
+ */
+#[derive(Debug, Default, Serialize)]
+pub struct IntegrationSyncResult {
+    pub calendars: CalendarSyncResult,
+    pub events: EventSyncResult,
+}
+
+/**
+ * The error types for syncing
+ */
+#[derive(Debug)]
+pub enum SyncError {
+    UnsupportedService,
+    MissingCredentials,
+    ConnectorError(Oauth2ConnectorError),
+    CaldavError(anyhow::Error),
+    DatabaseError(diesel::result::Error),
+}
+
+/**
+ * Sync the calendars of an integration, followed by the events of every calendar.
+ */
+pub async fn sync_integration(integration: &Integration, state: &Arc<AppState>) -> Result<IntegrationSyncResult, SyncError> {
+    if let ServiceType::Apple = integration.service {
+        return sync_caldav_integration(integration, state).await;
+    }
+
+    let (connector, oauth_integration) = get_connector(integration, state).await?;
+
+    let calendars = connector.get_calendars(&oauth_integration).await
+        .map_err(SyncError::ConnectorError)?;
+    let calendars = Calendar::sync(integration, calendars, &mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+
+    let mut events = EventSyncResult::default();
+    for calendar in calendars.calendars.iter() {
+        let result = sync_events(&connector, &oauth_integration, calendar, state).await?;
+        events.created += result.created;
+        events.updated += result.updated;
+        events.deleted += result.deleted;
+    }
+
+    // Calendars without a channel are watched so later changes are pushed to the webhook. A
+    // failure here only means the calendar is left to the scheduled syncs.
+    for calendar in calendars.calendars.iter() {
+        if !WatchChannel::find_by_calendar(calendar, &mut state.get_connection()).is_empty() {
+            continue;
+        }
+        if let Err(err) = watch_events(&connector, &oauth_integration, calendar, state).await {
+            println!("Failed to watch calendar {}: {:?}", calendar.id, err);
+        }
+    }
+
+    for calendar in calendars.calendars.iter() {
+        mirror_events(calendar, state).await;
+    }
+
+    integration.touch_last_synced(&mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+
+    Ok(IntegrationSyncResult {
+        calendars,
+        events,
+    })
+}
+
+/**
+ * Sync the events of a single calendar.
+ */
+pub async fn sync_calendar(calendar: &Calendar, state: &Arc<AppState>) -> Result<EventSyncResult, SyncError> {
+    let integration = find_integration(calendar, state)?;
+    let result = match integration.service {
+        ServiceType::Apple => {
+            let credentials = get_caldav_credentials(&integration, state)?;
+            sync_caldav_events(&credentials, calendar, state).await?
+        },
+        _ => {
+            let (connector, oauth_integration) = get_connector(&integration, state).await?;
+            sync_events(&connector, &oauth_integration, calendar, state).await?
+        },
+    };
+    mirror_events(calendar, state).await;
+    Ok(result)
+}
+
+/**
+ * Sync the calendars of a CalDAV integration, followed by the events of every calendar.
+ * CalDAV servers cannot push changes, so the calendars are left to the scheduled syncs.
+ * Calendars which cannot hold events are not synced.
+ */
+async fn sync_caldav_integration(integration: &Integration, state: &Arc<AppState>) -> Result<IntegrationSyncResult, SyncError> {
+    let credentials = get_caldav_credentials(integration, state)?;
+
+    let principal = caldav::get_principal(credentials.url.clone(), credentials.username.clone(), credentials.password.clone()).await
+        .map_err(SyncError::CaldavError)?;
+    let calendars = caldav::get_calendar(&principal, credentials.url.clone(), credentials.username.clone(), credentials.password.clone()).await
+        .map_err(SyncError::CaldavError)?
+        .iter()
+        .filter(|calendar| calendar.supports_component("VEVENT"))
+        .map(|calendar| calendar.to_calendar_result())
+        .collect();
+    let calendars = Calendar::sync(integration, calendars, &mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+
+    let mut events = EventSyncResult::default();
+    for calendar in calendars.calendars.iter() {
+        let result = sync_caldav_events(&credentials, calendar, state).await?;
+        events.created += result.created;
+        events.updated += result.updated;
+        events.deleted += result.deleted;
+    }
+
+    for calendar in calendars.calendars.iter() {
+        mirror_events(calendar, state).await;
+    }
+
+    integration.touch_last_synced(&mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+
+    Ok(IntegrationSyncResult {
+        calendars,
+        events,
+    })
+}
+
+/**
+ * Sync the events of a CalDAV calendar. Only the resources which changed since the last sync
+ * are downloaded. A deleted resource takes every event with its UID along, and occurrences
+ * which were removed from a changed resource are deleted.
+ */
+async fn sync_caldav_events(credentials: &CaldavIntegration, calendar: &Calendar, state: &Arc<AppState>) -> Result<EventSyncResult, SyncError> {
+    let objects = CalendarObject::find_by_calendar(calendar, &mut state.get_connection());
+    let known_etags = objects.iter()
+        .map(|object| (object.href.clone(), object.etag.clone()))
+        .collect::<HashMap<String, String>>();
+
+    let changes = caldav::sync_events(
+        &calendar.external_id,
+        calendar.sync_token.as_deref(),
+        &known_etags,
+        credentials.url.clone(),
+        credentials.username.clone(),
+        credentials.password.clone(),
+    ).await.map_err(SyncError::CaldavError)?;
+
+    let hrefs = changes.changed.iter()
+        .map(|resource| resource.href.clone())
+        .collect::<Vec<String>>();
+    let resources = match hrefs.is_empty() {
+        true => Vec::new(),
+        false => caldav::get_events_by_href(&calendar.external_id, &hrefs, credentials.url.clone(), credentials.username.clone(), credentials.password.clone()).await
+            .map_err(SyncError::CaldavError)?,
+    };
+
+    let uids = objects.iter()
+        .filter_map(|object| object.uid.as_ref().map(|uid| (object.href.as_str(), uid.as_str())))
+        .collect::<HashMap<&str, &str>>();
+    let mut event_changes = EventChanges {
+        full: changes.full,
+        deleted: changes.deleted.iter()
+            .filter_map(|href| uids.get(href.as_str()).map(|uid| uid.to_string()))
+            .collect(),
+        ..Default::default()
+    };
+
+    let stored = Event::find_by_calendar(calendar, &mut state.get_connection());
+    let mut synced_objects = Vec::with_capacity(resources.len());
+    for resource in resources.iter() {
+        let results = resource.to_event_results();
+        let uid = resource.events.first().map(|event| event.uid.clone());
+
+        if let Some(uid) = uid.as_deref() {
+            let synced = results.iter().map(|result| result.external_id.as_str()).collect::<HashSet<&str>>();
+            event_changes.deleted.extend(stored.iter()
+                .filter(|event| event.recurring_event_id.as_deref() == Some(uid))
+                .filter(|event| !synced.contains(event.external_id.as_str()))
+                .map(|event| event.external_id.clone()));
+        }
+
+        synced_objects.push(CalendarObjectResult {
+            href: resource.href.clone(),
+            etag: resource.etag.clone(),
+            uid,
+        });
+        event_changes.events.extend(results);
+    }
+
+    let result = Event::sync(calendar, event_changes, &mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+    CalendarObject::sync(calendar, synced_objects, &changes.deleted, changes.full, &mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+
+    // Only store the new token once the changes it covers are saved
+    if changes.sync_token.is_some() {
+        calendar.update_sync_token(changes.sync_token, &mut state.get_connection())
+            .map_err(SyncError::DatabaseError)?;
+    }
+
+    Ok(result)
+}
+
+/**
+ * Mirror the events of a calendar after they were synced. Mirroring is a side effect of the
+ * sync, so a failure is only logged.
+ */
+async fn mirror_events(calendar: &Calendar, state: &Arc<AppState>) {
+    if let Err(err) = mirror::mirror_calendar(calendar, state).await {
+        println!("Failed to mirror calendar {}: {:?}", calendar.id, err);
+    }
+}
+
+async fn sync_events(
+    connector: &Oauth2Connector,
+    oauth_integration: &OauthIntegration,
+    calendar: &Calendar,
+    state: &Arc<AppState>,
+) -> Result<EventSyncResult, SyncError> {
+    let mut changes = connector.get_events(oauth_integration, calendar).await
+        .map_err(SyncError::ConnectorError)?;
+    let sync_token = changes.sync_token.take();
+
+    let result = Event::sync(calendar, changes, &mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+
+    // Only store the new token once the changes it covers are saved
+    if sync_token.is_some() {
+        calendar.update_sync_token(sync_token, &mut state.get_connection())
+            .map_err(SyncError::DatabaseError)?;
+    }
+
+    Ok(result)
+}
+
+/**
+ * The integration of a calendar. The integration may have been removed while the calendar was
+ * being synced.
+ */
+pub(crate) fn find_integration(calendar: &Calendar, state: &Arc<AppState>) -> Result<Integration, SyncError> {
+    calendar.get_integration(&mut state.get_connection())
+        .ok_or(SyncError::DatabaseError(diesel::result::Error::NotFound))
+}
+
+/**
+ * Register a push notification channel for the calendar, so its changes are synced as they
+ * happen. Nothing is registered when no webhook URL is configured.
+ */
+pub async fn watch_calendar(calendar: &Calendar, state: &Arc<AppState>) -> Result<Option<WatchChannel>, SyncError> {
+    let integration = find_integration(calendar, state)?;
+    let (connector, oauth_integration) = get_connector(&integration, state).await?;
+    watch_events(&connector, &oauth_integration, calendar, state).await
+}
+
+/**
+ * Renew a channel which is about to expire. Channels the service cannot extend are replaced by
+ * a new channel, as are channels which failed to extend.
+ */
+pub async fn renew_channel(channel: &WatchChannel, state: &Arc<AppState>) -> Result<(), SyncError> {
+    let calendar = channel.get_calendar(&mut state.get_connection());
+    let integration = find_integration(&calendar, state)?;
+    let (connector, oauth_integration) = get_connector(&integration, state).await?;
+
+    match connector.renew_channel(&oauth_integration, channel).await {
+        Ok(Some(expires_at)) => {
+            channel.update_expiry(expires_at, &mut state.get_connection())
+                .map_err(SyncError::DatabaseError)?;
+            return Ok(());
+        },
+        Ok(None) => {},
+        Err(err) => println!("Failed to extend channel {}, replacing it: {:?}", channel.external_id, err),
+    }
+
+    watch_events(&connector, &oauth_integration, &calendar, state).await?;
+    stop_channel_with(&connector, &oauth_integration, channel, state).await
+}
+
+/**
+ * Replace a channel the service removed on its own with a new one. Changes made while the
+ * calendar was not watched are caught up with a sync.
+ */
+pub async fn replace_channel(channel: &WatchChannel, state: &Arc<AppState>) -> Result<EventSyncResult, SyncError> {
+    channel.delete(&mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+
+    let calendar = channel.get_calendar(&mut state.get_connection());
+    watch_calendar(&calendar, state).await?;
+    sync_calendar(&calendar, state).await
+}
+
+/**
+ * Stop a channel and remove it.
+ */
+pub async fn stop_channel(channel: &WatchChannel, state: &Arc<AppState>) -> Result<(), SyncError> {
+    let calendar = channel.get_calendar(&mut state.get_connection());
+    let integration = find_integration(&calendar, state)?;
+    let (connector, oauth_integration) = get_connector(&integration, state).await?;
+    stop_channel_with(&connector, &oauth_integration, channel, state).await
+}
+
+/**
+ * Failing to stop a channel at the service is not fatal, the channel expires on its own.
+ */
+async fn stop_channel_with(
+    connector: &Oauth2Connector,
+    oauth_integration: &OauthIntegration,
+    channel: &WatchChannel,
+    state: &Arc<AppState>,
+) -> Result<(), SyncError> {
+    if let Err(err) = connector.stop_channel(oauth_integration, channel).await {
+        println!("Failed to stop channel {}: {:?}", channel.external_id, err);
+    }
+
+    channel.delete(&mut state.get_connection())
+        .map_err(SyncError::DatabaseError)?;
+    Ok(())
+}
+
+async fn watch_events(
+    connector: &Oauth2Connector,
+    oauth_integration: &OauthIntegration,
+    calendar: &Calendar,
+    state: &Arc<AppState>,
+) -> Result<Option<WatchChannel>, SyncError> {
+    let Some(webhook_url) = state.config.webhook_url.as_ref() else {
+        return Ok(None);
+    };
+
+    let service = connector.service();
+    let token = Uuid::new_v4().to_string();
+    let address = format!("{}/update/webhook/{}", webhook_url.trim_end_matches('/'), service.to_string());
+    let result = connector.watch_events(oauth_integration, calendar, &token, &address).await
+        .map_err(SyncError::ConnectorError)?;
+
+    WatchChannel::new(calendar, service, token, result, &mut state.get_connection())
+        .map(Some)
+        .map_err(SyncError::DatabaseError)
+}
+
+/**
+ * Load the credentials of a CalDAV integration.
+ */
+pub(crate) fn get_caldav_credentials(integration: &Integration, state: &Arc<AppState>) -> Result<CaldavIntegration, SyncError> {
+    CaldavIntegration::find_by_integration(integration, &mut state.get_connection())
+        .ok_or(SyncError::MissingCredentials)
+}
+
+/**
+ * Pick the connector for the service of the integration and load its credentials. The access
+ * token is refreshed first when it is about to expire.
+ */
+pub(crate) async fn get_connector(integration: &Integration, state: &Arc<AppState>) -> Result<(Oauth2Connector, OauthIntegration), SyncError> {
+    let service = match &integration.service {
+        ServiceType::Google(service) | ServiceType::Outlook(service) => service,
+        ServiceType::Apple => return Err(SyncError::UnsupportedService),
+    };
+
+    let Some(mut oauth_integration) = OauthIntegration::find_by_integration(integration, &mut state.get_connection()) else {
+        return Err(SyncError::MissingCredentials);
+    };
+
+    let connector = Oauth2Connector::new(service, &state.config);
+
+    let refresh_at = chrono::Utc::now().naive_utc() + chrono::Duration::seconds(ACCESS_TOKEN_EXPIRY_MARGIN);
+    if oauth_integration.expires_at <= refresh_at {
+        oauth_integration = connector.new_access_token(&mut oauth_integration, state).await
+            .map_err(SyncError::ConnectorError)?;
+    }
+
+    Ok((connector, oauth_integration))
+}
@@ -1,7 +1,67 @@
-use crate::models::{app::App, app_key::AppKey};
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{connectors::ServiceType, models::{app::App, app_key::AppKey, calendar::{Calendar, CalendarResult}, event::{Attendees, Event, EventChanges, EventResult, EventStatus, EventSyncResult, EventTransparency, EventVisibility}, group::Group, integration::Integration}, AppState};
 use base64::prelude::*;
 
 pub fn generate_basic_header(app: &App, key: &AppKey) -> String {
     let token = format!("{}:{}", app.client_id, key.key);
     format!("Basic {}", BASE64_STANDARD.encode(token))
-}
\ No newline at end of file
+}
+
+/**
+ * A time on a day of November 2024, the month the fixtures take place in.
+ */
+pub fn datetime(day: u32, hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 11, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+}
+
+/**
+ * A confirmed event which blocks time, summarized by its id and without any other details.
+ */
+pub fn event_result(external_id: &str, starts_at: NaiveDateTime, ends_at: NaiveDateTime) -> EventResult {
+    EventResult {
+        external_id: external_id.to_string(),
+        etag: None,
+        summary: Some(external_id.to_string()),
+        description: None,
+        location: None,
+        starts_at,
+        ends_at,
+        all_day: false,
+        time_zone: None,
+        status: EventStatus::Confirmed,
+        transparency: EventTransparency::Opaque,
+        visibility: EventVisibility::Default,
+        organizer: None,
+        attendees: Attendees::default(),
+        recurrence: None,
+        recurring_event_id: None,
+        original_starts_at: None,
+    }
+}
+
+/**
+ * Create a calendar in a new integration of the group.
+ */
+pub fn create_calendar(state: &AppState, group: &Group, service: ServiceType, external_id: &str) -> Calendar {
+    let integration = Integration::new(group, &mut state.get_connection(), service);
+    let result = Calendar::sync(&integration, vec![CalendarResult {
+        external_id: external_id.to_string(),
+        name: external_id.to_string(),
+        background_color: "#9a9cff".to_string(),
+        foreground_color: "#000000".to_string(),
+    }], &mut state.get_connection()).unwrap();
+    result.calendars.into_iter().next().unwrap()
+}
+
+/**
+ * Replace the events of the calendar with the given ones, like a full sync does.
+ */
+pub fn sync_events(state: &AppState, calendar: &Calendar, events: Vec<EventResult>) -> EventSyncResult {
+    Event::sync(calendar, EventChanges {
+        events,
+        deleted: vec![],
+        full: true,
+        sync_token: None,
+    }, &mut state.get_connection()).unwrap()
+}
@@ -0,0 +1,313 @@
+use std::sync::Arc;
+
+use axum::{body::Body, http::Request, http};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
+use chrono_tz::Tz;
+use dotenv::dotenv;
+use http_body_util::BodyExt;
+use reqwest::{Method, StatusCode};
+use schedsync_api::{availability::{self, slots::{self, AvailabilitySettings, Slot, SlotQuery}, BusyInterval}, build_routes, connectors::{oauth2::Oauth2Service, ServiceType}, models::{app::App, event::{EventResult, EventStatus, EventTransparency}, group::{Group, WorkingHours, WorkingPeriod}}, test_util::{self, create_calendar, datetime, event_result, sync_events}, AppState};
+use tower::util::ServiceExt;
+
+async fn request_get(state: &Arc<AppState>, uri: String, authorization: String) -> (StatusCode, serde_json::Value) {
+    let router = build_routes(state.clone());
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(Method::GET)
+                .header(http::header::AUTHORIZATION, authorization)
+                .body(Body::empty())
+                .unwrap()
+        ).await.unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[test]
+fn merge_intervals() {
+    let merged = availability::merge_intervals(vec![
+        BusyInterval::new(datetime(4, 14), datetime(4, 15)),
+        BusyInterval::new(datetime(4, 9), datetime(4, 11)),
+        BusyInterval::new(datetime(4, 10), datetime(4, 12)),
+        BusyInterval::new(datetime(4, 12), datetime(4, 13)),
+        BusyInterval::new(datetime(4, 16), datetime(4, 16)),
+    ]);
+
+    assert_eq!(merged, vec![
+        BusyInterval::new(datetime(4, 9), datetime(4, 13)),
+        BusyInterval::new(datetime(4, 14), datetime(4, 15)),
+    ]);
+}
+
+#[tokio::test]
+async fn group_freebusy() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+
+    let mut lunch = event_result("lunch", datetime(4, 12), datetime(4, 13));
+    lunch.transparency = EventTransparency::Transparent;
+    let mut cancelled = event_result("cancelled", datetime(4, 15), datetime(4, 16));
+    cancelled.status = EventStatus::Cancelled;
+
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    sync_events(&state, &calendar, vec![
+        event_result("standup", datetime(4, 9), datetime(4, 10)),
+        event_result("review", datetime(4, 11), datetime(4, 14)),
+        lunch,
+        cancelled,
+    ]);
+    let calendar = create_calendar(&state, &group, ServiceType::Outlook(Oauth2Service::Outlook), "calendar");
+    sync_events(&state, &calendar, vec![
+        event_result("overnight", datetime(3, 22), datetime(4, 9)),
+        event_result("planning", datetime(4, 13), datetime(4, 15)),
+        event_result("later", datetime(5, 9), datetime(5, 10)),
+    ]);
+
+    let (status, body) = request_get(
+        &state,
+        format!("/api/group/{}/freebusy?start=2024-11-04T00:00:00Z&end=2024-11-05T00:00:00Z", group.id),
+        test_util::generate_basic_header(&app, &app_key),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Overlapping and touching events are merged, and intervals are clipped to the range
+    assert_eq!(body["busy"], serde_json::json!([
+        { "start": "2024-11-04T00:00:00Z", "end": "2024-11-04T10:00:00Z" },
+        { "start": "2024-11-04T11:00:00Z", "end": "2024-11-04T15:00:00Z" },
+    ]));
+
+    // Offsets are normalized to UTC
+    let (status, body) = request_get(
+        &state,
+        format!("/api/group/{}/freebusy?start=2024-11-04T10:30:00%2B01:00&end=2024-11-04T12:00:00%2B01:00", group.id),
+        test_util::generate_basic_header(&app, &app_key),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["start"], "2024-11-04T09:30:00Z");
+    assert_eq!(body["busy"], serde_json::json!([
+        { "start": "2024-11-04T09:30:00Z", "end": "2024-11-04T10:00:00Z" },
+    ]));
+
+    let (status, _) = request_get(
+        &state,
+        format!("/api/group/{}/freebusy?start=2024-11-05T00:00:00Z&end=2024-11-04T00:00:00Z", group.id),
+        test_util::generate_basic_header(&app, &app_key),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn all_day_events_in_group_time_zone() {
+    dotenv().ok();
+    let state = AppState::new();
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let group = Group { time_zone: "Europe/Amsterdam".to_string(), ..group }.save(&mut state.get_connection()).unwrap();
+
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    sync_events(&state, &calendar, vec![
+        EventResult { all_day: true, ..event_result("holiday", datetime(5, 0), datetime(6, 0)) },
+    ]);
+
+    // The day starts at midnight in Amsterdam, an hour before midnight UTC
+    let busy = availability::group_busy(&group, datetime(4, 0).and_utc(), datetime(7, 0).and_utc(), &mut state.get_connection());
+    assert_eq!(busy, vec![BusyInterval::new(datetime(4, 23), datetime(5, 23))]);
+
+    // The query starts after midnight UTC, the day is still found
+    let busy = availability::group_busy(&group, datetime(5, 12).and_utc(), datetime(7, 0).and_utc(), &mut state.get_connection());
+    assert_eq!(busy, vec![BusyInterval::new(datetime(5, 12), datetime(5, 23))]);
+    let busy = availability::group_busy(&group, datetime(5, 23).and_utc(), datetime(7, 0).and_utc(), &mut state.get_connection());
+    assert!(busy.is_empty());
+}
+
+#[tokio::test]
+async fn freebusy_of_other_app() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let owner = App::new(&mut state.get_connection());
+    let group = owner.create_group(&mut state.get_connection());
+
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+
+    let (status, _) = request_get(
+        &state,
+        format!("/api/group/{}/freebusy?start=2024-11-04T00:00:00Z&end=2024-11-05T00:00:00Z", group.id),
+        test_util::generate_basic_header(&app, &app_key),
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+fn settings(time_zone: Tz) -> AvailabilitySettings {
+    AvailabilitySettings {
+        time_zone,
+        working_hours: WorkingHours(vec![
+            WorkingPeriod {
+                weekday: Weekday::Mon,
+                start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            },
+            WorkingPeriod {
+                weekday: Weekday::Mon,
+                start: NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(15, 0, 0).unwrap(),
+            },
+        ]),
+        buffer_before: Duration::zero(),
+        buffer_after: Duration::zero(),
+        minimum_notice: Duration::zero(),
+        max_days: 60,
+    }
+}
+
+fn slot_starts(slots: &[Slot]) -> Vec<String> {
+    slots.iter().map(|slot| slot.start.format("%d %H:%M").to_string()).collect()
+}
+
+#[test]
+fn find_slots_in_working_hours() {
+    // Monday the 4th to Tuesday the 5th
+    let query = SlotQuery {
+        start: datetime(4, 0).and_utc(),
+        end: datetime(6, 0).and_utc(),
+        duration: Duration::minutes(60),
+        interval: Duration::minutes(30),
+    };
+    let now = datetime(1, 0).and_utc();
+    let busy = vec![BusyInterval::new(datetime(4, 10), datetime(4, 11))];
+
+    let slots = slots::find_slots(&settings(Tz::UTC), &busy, &query, now);
+    assert_eq!(slot_starts(&slots), vec!["04 09:00", "04 11:00", "04 13:00", "04 13:30", "04 14:00"]);
+    assert_eq!(slots[0].end, datetime(4, 10).and_utc());
+
+    // Buffers keep time free around the busy interval
+    let mut buffered = settings(Tz::UTC);
+    buffered.buffer_before = Duration::minutes(15);
+    buffered.buffer_after = Duration::minutes(30);
+    let slots = slots::find_slots(&buffered, &busy, &query, now);
+    assert_eq!(slot_starts(&slots), vec!["04 13:00", "04 13:30", "04 14:00"]);
+
+    // Nothing can be booked within the minimum notice or beyond the horizon
+    let mut notice = settings(Tz::UTC);
+    notice.minimum_notice = Duration::minutes(90);
+    let slots = slots::find_slots(&notice, &[], &query, datetime(4, 12).and_utc());
+    assert_eq!(slot_starts(&slots), vec!["04 13:30", "04 14:00"]);
+
+    let mut horizon = settings(Tz::UTC);
+    horizon.max_days = 1;
+    let slots = slots::find_slots(&horizon, &[], &query, datetime(3, 11).and_utc());
+    assert_eq!(slot_starts(&slots), vec!["04 09:00", "04 09:30", "04 10:00"]);
+}
+
+#[test]
+fn find_slots_across_daylight_saving_time() {
+    // Amsterdam leaves daylight saving time on October 27th, working hours stay at 09:00 local
+    let query = SlotQuery {
+        start: NaiveDate::from_ymd_opt(2024, 10, 21).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc(),
+        end: NaiveDate::from_ymd_opt(2024, 10, 29).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc(),
+        duration: Duration::minutes(180),
+        interval: Duration::minutes(180),
+    };
+    let now = NaiveDate::from_ymd_opt(2024, 10, 1).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc();
+
+    let slots = slots::find_slots(&settings(chrono_tz::Europe::Amsterdam), &[], &query, now);
+    let starts = slots.iter().map(|slot| slot.start.format("%m-%d %H:%M").to_string()).collect::<Vec<String>>();
+    assert_eq!(starts, vec!["10-21 07:00", "10-28 08:00"]);
+}
+
+#[tokio::test]
+async fn group_slots() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+
+    let now = chrono::Utc::now();
+    let tomorrow = (now + Duration::days(1)).date_naive();
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    sync_events(&state, &calendar, vec![
+        event_result("meeting", tomorrow.and_hms_opt(10, 0, 0).unwrap(), tomorrow.and_hms_opt(11, 0, 0).unwrap()),
+    ]);
+
+    let router = build_routes(state.clone());
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/group/{}", group.id))
+                .method(Method::PATCH)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .header(http::header::AUTHORIZATION, test_util::generate_basic_header(&app, &app_key))
+                .body(Body::from(serde_json::json!({
+                    "time_zone": "UTC",
+                    "working_hours": [{ "weekday": tomorrow.weekday().to_string(), "start": "09:00:00", "end": "12:00:00" }],
+                    "buffer_after": 30,
+                }).to_string()))
+                .unwrap()
+        ).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (status, body) = request_get(
+        &state,
+        format!("/api/group/{}/slots?duration=60&start={}T00:00:00Z&end={}T00:00:00Z", group.id, tomorrow, tomorrow + Duration::days(1)),
+        test_util::generate_basic_header(&app, &app_key),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["slots"], serde_json::json!([
+        { "start": format!("{}T11:00:00Z", tomorrow), "end": format!("{}T12:00:00Z", tomorrow) },
+    ]));
+
+    let (status, _) = request_get(
+        &state,
+        format!("/api/group/{}/slots?duration=0", group.id),
+        test_util::generate_basic_header(&app, &app_key),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = request_get(
+        &state,
+        format!("/api/group/{}/slots?duration=60&end=9999-01-01T00:00:00Z", group.id),
+        test_util::generate_basic_header(&app, &app_key),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn update_group_with_invalid_settings() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+
+    for body in [
+        serde_json::json!({ "time_zone": "Mars/Olympus_Mons" }),
+        serde_json::json!({ "buffer_before": -5 }),
+        serde_json::json!({ "max_days": 0 }),
+        serde_json::json!({ "max_days": 200000000 }),
+        serde_json::json!({ "working_hours": [{ "weekday": "Mon", "start": "17:00:00", "end": "09:00:00" }] }),
+    ] {
+        let router = build_routes(state.clone());
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/group/{}", group.id))
+                    .method(Method::PATCH)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .header(http::header::AUTHORIZATION, test_util::generate_basic_header(&app, &app_key))
+                    .body(Body::from(body.to_string()))
+                    .unwrap()
+            ).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let group = Group::find_by_id(group.id, &mut state.get_connection()).unwrap();
+    assert_eq!(group.time_zone, "UTC");
+    assert_eq!(group.working_hours.0.len(), 5);
+}
@@ -0,0 +1,398 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+
+use axum::{body::{Body, Bytes}, extract::State, http::{self, HeaderMap, Method, Request, StatusCode}, response::IntoResponse, Router};
+use dotenv::dotenv;
+use http_body_util::BodyExt;
+use schedsync_api::{build_routes, connectors::{caldav::caldav::{self, CaldavResource, CaldavSyncToken, CaldavWriteError, PrincipalData}, ServiceType}, models::{app::App, caldav_integration::CaldavIntegration, calendar::Calendar, calendar_object::CalendarObject, event::Event, integration::Integration, task::{TaskResult, TaskStatus}}, sync, test_util::{self, create_calendar, datetime, event_result}, AppState};
+use tower::util::ServiceExt;
+
+const CALENDAR_PATH: &str = "/calendars/home/";
+
+/**
+ * Serve the router on a random local port, returning the base URL.
+ */
+async fn serve(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    format!("http://{}", address)
+}
+
+fn multistatus(body: &str) -> axum::response::Response {
+    (StatusCode::MULTI_STATUS, format!(r#"<?xml version="1.0" encoding="UTF-8"?><d:multistatus xmlns:d="DAV:" xmlns:cs="http://calendarserver.org/ns/">{}</d:multistatus>"#, body)).into_response()
+}
+
+fn etag_response(href: &str, etag: &str) -> String {
+    format!(r#"<d:response><d:href>{}</d:href><d:propstat><d:prop><d:getetag>{}</d:getetag></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"#, href, etag)
+}
+
+async fn sync_collection_server(method: Method, body: Bytes) -> axum::response::Response {
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    if method.as_str() != "REPORT" {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+
+    if body.contains("<d:sync-token/>") || body.contains("<d:sync-token></d:sync-token>") {
+        return multistatus(&format!("{}{}<d:sync-token>token-1</d:sync-token>",
+            etag_response("/calendars/home/a.ics", "\"1\""),
+            etag_response("/calendars/home/b.ics", "\"1\""),
+        ));
+    }
+
+    if body.contains("token-1") {
+        return multistatus(&format!("{}{}<d:sync-token>token-2</d:sync-token>",
+            etag_response("/calendars/home/a.ics", "\"2\""),
+            r#"<d:response><d:href>/calendars/home/b.ics</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>"#,
+        ));
+    }
+
+    if body.contains("broken") {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    // Any other token is no longer valid
+    (StatusCode::FORBIDDEN, r#"<?xml version="1.0" encoding="UTF-8"?><d:error xmlns:d="DAV:"><d:valid-sync-token/></d:error>"#).into_response()
+}
+
+async fn ctag_server(method: Method, headers: HeaderMap) -> axum::response::Response {
+    if method.as_str() != "PROPFIND" {
+        return StatusCode::NOT_IMPLEMENTED.into_response();
+    }
+
+    if headers.get("Depth").is_some_and(|depth| depth == "0") {
+        return multistatus(r#"<d:response><d:href>/calendars/home/</d:href><d:propstat><d:prop><cs:getctag>ctag-2</cs:getctag></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"#);
+    }
+
+    multistatus(&format!("{}{}{}",
+        r#"<d:response><d:href>/calendars/home/</d:href><d:propstat><d:prop><d:getetag/></d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat></d:response>"#,
+        etag_response("/calendars/home/a.ics", "\"1\""),
+        etag_response("/calendars/home/c.ics", "\"1\""),
+    ))
+}
+
+#[tokio::test]
+async fn sync_collection_report() {
+    let url = serve(Router::new().route(CALENDAR_PATH, axum::routing::any(sync_collection_server))).await;
+    let known = HashMap::new();
+
+    // The first sync lists every resource
+    let changes = caldav::sync_events(CALENDAR_PATH, None, &known, url.clone(), "user".to_string(), None).await.unwrap();
+    assert!(changes.full);
+    assert_eq!(changes.changed.len(), 2);
+    assert_eq!(changes.sync_token.as_deref(), Some("sync:token-1"));
+
+    // Later syncs only list what changed since the token
+    let changes = caldav::sync_events(CALENDAR_PATH, changes.sync_token.as_deref(), &known, url.clone(), "user".to_string(), None).await.unwrap();
+    assert!(!changes.full);
+    assert_eq!(changes.changed, vec![CaldavResource {
+        href: "/calendars/home/a.ics".to_string(),
+        etag: "\"2\"".to_string(),
+    }]);
+    assert_eq!(changes.deleted, vec!["/calendars/home/b.ics".to_string()]);
+    assert_eq!(changes.sync_token.as_deref(), Some("sync:token-2"));
+
+    // An expired token starts over with a full sync
+    let changes = caldav::sync_events(CALENDAR_PATH, Some("sync:expired"), &known, url.clone(), "user".to_string(), None).await.unwrap();
+    assert!(changes.full);
+    assert_eq!(changes.changed.len(), 2);
+
+    // Other errors are not mistaken for an expired token
+    let result = caldav::sync_events(CALENDAR_PATH, Some("sync:broken"), &known, url, "user".to_string(), None).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn sync_without_sync_collection_support() {
+    let url = serve(Router::new().route(CALENDAR_PATH, axum::routing::any(ctag_server))).await;
+    let known = HashMap::from([
+        ("/calendars/home/a.ics".to_string(), "\"1\"".to_string()),
+        ("/calendars/home/b.ics".to_string(), "\"1\"".to_string()),
+    ]);
+
+    // Resources are compared with the known etags
+    let changes = caldav::sync_events(CALENDAR_PATH, None, &known, url.clone(), "user".to_string(), None).await.unwrap();
+    assert!(!changes.full);
+    assert_eq!(changes.changed, vec![CaldavResource {
+        href: "/calendars/home/c.ics".to_string(),
+        etag: "\"1\"".to_string(),
+    }]);
+    assert_eq!(changes.deleted, vec!["/calendars/home/b.ics".to_string()]);
+    assert_eq!(changes.sync_token, Some(CaldavSyncToken::Ctag("ctag-2".to_string()).to_string()));
+
+    // Nothing is listed while the ctag is unchanged
+    let changes = caldav::sync_events(CALENDAR_PATH, changes.sync_token.as_deref(), &known, url, "user".to_string(), None).await.unwrap();
+    assert!(changes.changed.is_empty());
+    assert!(changes.deleted.is_empty());
+}
+
+async fn multiget_server(State(requests): State<Arc<AtomicUsize>>, body: Bytes) -> axum::response::Response {
+    requests.fetch_add(1, Ordering::SeqCst);
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let hrefs = regex::Regex::new("<d:href>([^<]+)</d:href>").unwrap();
+
+    // Answer every requested href with an event named after it
+    multistatus(&hrefs.captures_iter(&body).map(|captures| {
+        let href = &captures[1];
+        let uid = href.trim_start_matches(CALENDAR_PATH).trim_end_matches(".ics");
+        format!(
+            r#"<d:response><d:href>{}</d:href><d:propstat><d:prop><d:getetag>"1"</d:getetag><c:calendar-data xmlns:c="urn:ietf:params:xml:ns:caldav">BEGIN:VCALENDAR
+VERSION:2.0
+BEGIN:VEVENT
+UID:{}
+CREATED:20241101T090000Z
+SUMMARY:Event {}
+DTSTART:20241110T090000Z
+DTEND:20241110T100000Z
+END:VEVENT
+END:VCALENDAR
+</c:calendar-data></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"#,
+            href, uid, uid,
+        )
+    }).collect::<String>())
+}
+
+#[tokio::test]
+async fn calendar_multiget_in_batches() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let url = serve(Router::new()
+        .route(CALENDAR_PATH, axum::routing::any(multiget_server))
+        .with_state(requests.clone())).await;
+
+    let hrefs = (0..150).map(|i| format!("{}{}.ics", CALENDAR_PATH, i)).collect::<Vec<String>>();
+    let resources = caldav::get_events_by_href(CALENDAR_PATH, &hrefs, url, "user".to_string(), None).await.unwrap();
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert_eq!(resources.len(), 150);
+    assert_eq!(resources[42].href, format!("{}42.ics", CALENDAR_PATH));
+    assert_eq!(resources[42].events[0].uid, "42");
+    assert_eq!(resources[42].to_event_results()[0].summary.as_deref(), Some("Event 42"));
+}
+
+async fn write_server(method: Method, headers: HeaderMap, body: Bytes) -> axum::response::Response {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    match method.as_str() {
+        "PUT" => {
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            assert!(body.contains("UID:booking-1\r\n"));
+            match (header("If-None-Match"), header("If-Match")) {
+                (Some("*"), None) => (StatusCode::CREATED, [("ETag", "\"1\"")]).into_response(),
+                (None, Some("\"1\"")) => (StatusCode::NO_CONTENT, [("ETag", "\"2\"")]).into_response(),
+                _ => StatusCode::PRECONDITION_FAILED.into_response(),
+            }
+        },
+        "DELETE" => match header("If-Match") {
+            Some("\"2\"") | None => StatusCode::NO_CONTENT.into_response(),
+            _ => StatusCode::PRECONDITION_FAILED.into_response(),
+        },
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+#[tokio::test]
+async fn write_events() {
+    let url = serve(Router::new().route("/calendars/home/booking-1.ics", axum::routing::any(write_server))).await;
+    let event = event_result("booking-1", datetime(20, 9), datetime(20, 10));
+
+    // Create, then update with the returned etag
+    let etag = caldav::put_event(CALENDAR_PATH, &event, None, url.clone(), "user".to_string(), None).await.unwrap();
+    assert_eq!(etag.as_deref(), Some("\"1\""));
+    let etag = caldav::put_event(CALENDAR_PATH, &event, etag.as_deref(), url.clone(), "user".to_string(), None).await.unwrap();
+    assert_eq!(etag.as_deref(), Some("\"2\""));
+
+    // Stale etags are rejected
+    let result = caldav::put_event(CALENDAR_PATH, &event, Some("\"0\""), url.clone(), "user".to_string(), None).await;
+    assert!(matches!(result, Err(CaldavWriteError::PreconditionFailed)));
+    let result = caldav::delete_event(CALENDAR_PATH, "booking-1", Some("\"0\""), url.clone(), "user".to_string(), None).await;
+    assert!(matches!(result, Err(CaldavWriteError::PreconditionFailed)));
+
+    caldav::delete_event(CALENDAR_PATH, "booking-1", etag.as_deref(), url, "user".to_string(), None).await.unwrap();
+}
+
+/**
+ * A calendar home holding one calendar which only accepts events.
+ */
+async fn event_calendar_server(method: Method) -> axum::response::Response {
+    match method.as_str() {
+        "PROPFIND" => multistatus(r#"<d:response><d:href>/calendars/home/</d:href><d:propstat><d:prop><d:displayname>Home</d:displayname><d:resourcetype><d:collection/><c:calendar xmlns:c="urn:ietf:params:xml:ns:caldav"/></d:resourcetype><c:supported-calendar-component-set xmlns:c="urn:ietf:params:xml:ns:caldav"><c:comp name="VEVENT"/></c:supported-calendar-component-set></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"#),
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+#[tokio::test]
+async fn tasks_in_event_calendar() {
+    let url = serve(Router::new().route("/user/calendars", axum::routing::any(event_calendar_server))).await;
+    let principal = PrincipalData { user_id: "user".to_string(), path: "/user/".to_string() };
+    let calendars = caldav::get_calendar(&principal, url.clone(), "user".to_string(), None).await.unwrap();
+    assert_eq!(calendars.len(), 1);
+    let calendar = &calendars[0];
+    assert!(calendar.supports_component("VEVENT"));
+    assert!(!calendar.supports_component("VTODO"));
+
+    // Neither reading nor writing tasks reaches the server
+    assert!(caldav::get_tasks(calendar, url.clone(), "user".to_string(), None).await.is_err());
+    let task = TaskResult {
+        external_id: "groceries".to_string(),
+        etag: None,
+        summary: Some("Groceries".to_string()),
+        description: None,
+        starts_at: None,
+        due_at: Some(datetime(20, 18)),
+        all_day: false,
+        completed_at: None,
+        percent_complete: None,
+        priority: None,
+        status: TaskStatus::NeedsAction,
+        recurrence: None,
+        recurring_task_id: None,
+        original_starts_at: None,
+    };
+    let result = caldav::put_task(calendar, &task, None, url, "user".to_string(), None).await;
+    assert!(matches!(result, Err(CaldavWriteError::InvalidEvent(_))));
+}
+
+const STANDUP: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:standup\r
+CREATED:20241101T090000Z\r
+SUMMARY:Standup\r
+DTSTART:20241104T090000Z\r
+DTEND:20241104T091500Z\r
+RRULE:FREQ=WEEKLY\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+CREATED:20241101T090000Z\r
+SUMMARY:Late standup\r
+RECURRENCE-ID:20241111T090000Z\r
+DTSTART:20241111T100000Z\r
+DTEND:20241111T101500Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+const REVIEW: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:review\r
+CREATED:20241101T090000Z\r
+SUMMARY:Review\r
+DTSTART:20241105T090000Z\r
+DTEND:20241105T100000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+/**
+ * A calendar whose standup series loses its override after the first sync, while the review
+ * is deleted.
+ */
+async fn calendar_server(State(changed): State<Arc<AtomicUsize>>, body: Bytes) -> axum::response::Response {
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    if body.contains("sync-collection") {
+        if body.contains("token-1") {
+            changed.store(1, Ordering::SeqCst);
+            return multistatus(&format!("{}{}<d:sync-token>token-2</d:sync-token>",
+                etag_response("/calendars/home/standup.ics", "\"2\""),
+                r#"<d:response><d:href>/calendars/home/review.ics</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>"#,
+            ));
+        }
+        return multistatus(&format!("{}{}<d:sync-token>token-1</d:sync-token>",
+            etag_response("/calendars/home/standup.ics", "\"1\""),
+            etag_response("/calendars/home/review.ics", "\"1\""),
+        ));
+    }
+
+    let standup = match changed.load(Ordering::SeqCst) {
+        0 => ("\"1\"", STANDUP.to_string()),
+        _ => ("\"2\"", STANDUP.split("BEGIN:VEVENT").take(2).collect::<Vec<&str>>().join("BEGIN:VEVENT") + "END:VCALENDAR\r\n"),
+    };
+    let resource = |href: &str, etag: &str, data: &str| format!(
+        r#"<d:response><d:href>{}</d:href><d:propstat><d:prop><d:getetag>{}</d:getetag><c:calendar-data xmlns:c="urn:ietf:params:xml:ns:caldav">{}</c:calendar-data></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"#,
+        href, etag, data,
+    );
+    let mut responses = String::new();
+    if body.contains("standup.ics") {
+        responses += &resource("/calendars/home/standup.ics", standup.0, &standup.1);
+    }
+    if body.contains("review.ics") {
+        responses += &resource("/calendars/home/review.ics", "\"1\"", REVIEW);
+    }
+    multistatus(&responses)
+}
+
+#[tokio::test]
+async fn sync_caldav_calendar() {
+    dotenv().ok();
+    let url = serve(Router::new()
+        .route(CALENDAR_PATH, axum::routing::any(calendar_server))
+        .with_state(Arc::new(AtomicUsize::new(0)))).await;
+
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Apple, CALENDAR_PATH);
+    let integration = calendar.get_integration(&mut state.get_connection()).unwrap();
+    CaldavIntegration::new(&integration, url, "user".to_string(), Some("secret".to_string()), &mut state.get_connection()).unwrap();
+
+    // The first sync downloads every resource and stores the token
+    let result = sync::sync_calendar(&calendar, &state).await.unwrap();
+    assert_eq!(result.created, 3);
+    let calendar = Calendar::find_by_id(calendar.id, &mut state.get_connection()).unwrap();
+    assert_eq!(calendar.sync_token.as_deref(), Some("sync:token-1"));
+    assert_eq!(CalendarObject::find_by_calendar(&calendar, &mut state.get_connection()).len(), 2);
+
+    // The next sync removes the deleted resource and the override removed from the series
+    let result = sync::sync_calendar(&calendar, &state).await.unwrap();
+    assert_eq!(result.deleted, 2);
+    let events = Event::find_by_calendar(&calendar, &mut state.get_connection());
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].external_id, "standup");
+    assert_eq!(events[0].etag.as_deref(), Some("\"2\""));
+
+    let calendar = Calendar::find_by_id(calendar.id, &mut state.get_connection()).unwrap();
+    assert_eq!(calendar.sync_token.as_deref(), Some("sync:token-2"));
+    let objects = CalendarObject::find_by_calendar(&calendar, &mut state.get_connection());
+    assert_eq!(objects.len(), 1);
+    assert_eq!(objects[0].uid.as_deref(), Some("standup"));
+}
+
+#[tokio::test]
+async fn connect_to_private_server() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+
+    // Nothing is sent to servers reached without https or on internal networks
+    for (url, message) in [
+        ("http://caldav.example.com", "The server must be reached over https"),
+        ("https://localhost:8443", "The server must not be on a private network"),
+        ("https://127.0.0.1", "The server must not be on a private network"),
+        ("https://10.1.2.3/dav", "The server must not be on a private network"),
+        ("https://169.254.169.254", "The server must not be on a private network"),
+        ("https://[::1]", "The server must not be on a private network"),
+        ("https://[::ffff:192.168.1.1]", "The server must not be on a private network"),
+        ("https://[fd00::1]", "The server must not be on a private network"),
+        ("not a url", "Invalid server URL"),
+    ] {
+        let response = build_routes(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/group/{}/caldav", group.id))
+                    .method(Method::POST)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .header(http::header::AUTHORIZATION, test_util::generate_basic_header(&app, &app_key))
+                    .body(Body::from(serde_json::json!({ "url": url, "username": "user", "password": "secret" }).to_string()))
+                    .unwrap()
+            ).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY, "{}", url);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(String::from_utf8_lossy(&body), message);
+    }
+    assert!(Integration::all(&mut state.get_connection()).iter().all(|integration| integration.group_id != group.id));
+}
@@ -0,0 +1,65 @@
+use dotenv::dotenv;
+use schedsync_api::{connectors::{oauth2::Oauth2Service, ServiceType}, models::{app::App, calendar::{Calendar, CalendarResult}, integration::Integration}, AppState};
+
+fn calendar_result(external_id: &str, name: &str) -> CalendarResult {
+    CalendarResult {
+        external_id: external_id.to_string(),
+        name: name.to_string(),
+        background_color: "#9a9cff".to_string(),
+        foreground_color: "#000000".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn sync_calendars() {
+    dotenv().ok();
+    let state = AppState::new();
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let integration = Integration::new(&group, &mut state.get_connection(), ServiceType::Google(Oauth2Service::Google));
+
+    // The first sync creates every calendar
+    let result = Calendar::sync(&integration, vec![
+        calendar_result("primary", "Primary"),
+        calendar_result("work", "Work"),
+    ], &mut state.get_connection()).unwrap();
+
+    assert_eq!(result.created, 2);
+    assert_eq!(result.updated, 0);
+    assert_eq!(result.deleted, 0);
+
+    // Renamed calendars are updated, unchanged calendars are left alone and missing calendars are removed
+    let result = Calendar::sync(&integration, vec![
+        calendar_result("primary", "Personal"),
+        calendar_result("holidays", "Holidays"),
+    ], &mut state.get_connection()).unwrap();
+
+    assert_eq!(result.created, 1);
+    assert_eq!(result.updated, 1);
+    assert_eq!(result.deleted, 1);
+
+    let calendars = Calendar::find_by_integration(&integration, &mut state.get_connection());
+    let names = calendars.iter().map(|c| c.name.as_str()).collect::<Vec<&str>>();
+    assert_eq!(names, vec!["Personal", "Holidays"]);
+
+    // Syncing the same results again is a no-op
+    let result = Calendar::sync(&integration, vec![
+        calendar_result("primary", "Personal"),
+        calendar_result("holidays", "Holidays"),
+    ], &mut state.get_connection()).unwrap();
+
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 0));
+    assert_eq!(result.calendars.len(), 2);
+
+    // The event sync token survives calendar syncs
+    let calendar = &result.calendars[0];
+    calendar.update_sync_token(Some("token".to_string()), &mut state.get_connection()).unwrap();
+    Calendar::sync(&integration, vec![
+        calendar_result("primary", "Primary"),
+        calendar_result("holidays", "Holidays"),
+    ], &mut state.get_connection()).unwrap();
+
+    let calendar = Calendar::find_by_id(calendar.id, &mut state.get_connection()).unwrap();
+    assert_eq!(calendar.name, "Primary");
+    assert_eq!(calendar.sync_token.as_deref(), Some("token"));
+}
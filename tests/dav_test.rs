@@ -0,0 +1,293 @@
+use std::sync::Arc;
+
+use axum::{body::Body, http::Request, http};
+use base64::prelude::*;
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use dotenv::dotenv;
+use http_body_util::BodyExt;
+use reqwest::{Method, StatusCode};
+use schedsync_api::{build_routes, connectors::{caldav::caldav, oauth2::Oauth2Service, ServiceType}, models::{app::App, event::EventResult, feed::{Feed, FeedPrivacy}, group::Group}, recurrence::Recurrence, test_util::{self, create_calendar, sync_events}, AppState};
+use tower::util::ServiceExt;
+
+/**
+ * An event of an hour whose summary must not show up in busy feeds.
+ */
+fn event_result(external_id: &str, starts_at: NaiveDateTime) -> EventResult {
+    EventResult {
+        summary: Some(format!("Secret {}", external_id)),
+        ..test_util::event_result(external_id, starts_at, starts_at + Duration::hours(1))
+    }
+}
+
+fn create_events(state: &AppState, group: &Group) -> NaiveDateTime {
+    let calendar = create_calendar(state, group, ServiceType::Google(Oauth2Service::Google), "primary");
+
+    let now = Utc::now().naive_utc();
+    let mut standup = event_result("standup", now + Duration::days(1));
+    standup.recurrence = Some("RRULE:FREQ=DAILY;COUNT=5".to_string());
+    let mut moved = event_result("standup_1", now + Duration::days(2) + Duration::hours(3));
+    moved.recurring_event_id = Some("standup".to_string());
+    moved.original_starts_at = Some(now + Duration::days(2));
+
+    sync_events(state, &calendar, vec![
+        standup,
+        moved,
+        event_result("planning", now + Duration::days(10)),
+        event_result("team/lunch@example.com", now + Duration::days(20)),
+    ]);
+    now
+}
+
+fn create_feed(state: &AppState, group: &Group, privacy: FeedPrivacy) -> Feed {
+    Feed::new(group, format!("token{}", group.id), privacy, 30, 365, &mut state.get_connection()).unwrap()
+}
+
+fn authorization(feed: &Feed) -> String {
+    format!("Basic {}", BASE64_STANDARD.encode(format!("{}:{}", feed.group_id, feed.token)))
+}
+
+async fn request(state: &Arc<AppState>, method: &str, uri: String, authorization: Option<String>, depth: Option<&str>, body: &str) -> (StatusCode, http::HeaderMap, String) {
+    let mut builder = Request::builder()
+        .uri(uri)
+        .method(Method::from_bytes(method.as_bytes()).unwrap())
+        .header(http::header::CONTENT_TYPE, "application/xml");
+    if let Some(authorization) = authorization {
+        builder = builder.header(http::header::AUTHORIZATION, authorization);
+    }
+    if let Some(depth) = depth {
+        builder = builder.header("Depth", depth);
+    }
+
+    let router = build_routes(state.clone());
+    let response = router
+        .oneshot(builder.body(Body::from(body.to_string())).unwrap())
+        .await.unwrap();
+
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/**
+ * The hrefs of the resources in a multistatus body.
+ */
+/**
+ * The 64-bit FNV-1a hash of the lines of iCalendar data other than DTSTAMP, each followed by a
+ * 0xff separator, which is how resource ETags are computed.
+ */
+fn fnv1a(calendar_data: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for line in calendar_data.split("\r\n").filter(|line| !line.starts_with("DTSTAMP:")) {
+        for byte in line.bytes().chain(std::iter::once(0xff)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+fn event_hrefs(body: &str) -> Vec<String> {
+    body.split("<href>")
+        .skip(1)
+        .map(|part| part.split("</href>").next().unwrap().to_string())
+        .filter(|href| href.ends_with(".ics"))
+        .collect()
+}
+
+#[tokio::test]
+async fn discover_calendar() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    create_events(&state, &group);
+    let feed = create_feed(&state, &group, FeedPrivacy::Busy);
+
+    let (status, headers, _) = request(&state, "OPTIONS", "/dav/".to_string(), None, None, "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("DAV").unwrap().to_str().unwrap().contains("calendar-access"));
+
+    let (status, _, body) = request(
+        &state,
+        "PROPFIND",
+        "/dav/".to_string(),
+        Some(authorization(&feed)),
+        Some("0"),
+        r#"<?xml version="1.0"?><d:propfind xmlns:d="DAV:"><d:prop><d:current-user-principal/></d:prop></d:propfind>"#,
+    ).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert!(body.contains(r#"<multistatus xmlns="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav" xmlns:CS="http://calendarserver.org/ns/">"#));
+    assert!(body.contains(&format!("<current-user-principal><href>/dav/{}/</href></current-user-principal>", group.id)));
+    assert!(!body.contains("resourcetype"));
+
+    let (status, _, body) = request(
+        &state,
+        "PROPFIND",
+        format!("/dav/{}/", group.id),
+        Some(authorization(&feed)),
+        Some("0"),
+        r#"<A:propfind xmlns:A="DAV:" xmlns:B="urn:ietf:params:xml:ns:caldav"><A:prop><B:calendar-home-set/></A:prop></A:propfind>"#,
+    ).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert!(body.contains(&format!("<C:calendar-home-set><href>/dav/{}/</href></C:calendar-home-set>", group.id)));
+
+    // The calendar home lists the calendar collection
+    let (status, _, body) = request(&state, "PROPFIND", format!("/dav/{}/", group.id), Some(authorization(&feed)), Some("1"), "").await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert!(body.contains(&format!("<href>/dav/{}/calendar/</href>", group.id)));
+    assert!(body.contains("<resourcetype><collection/><C:calendar/></resourcetype>"));
+    assert!(body.contains(r#"<C:supported-calendar-component-set><C:comp name="VEVENT"/></C:supported-calendar-component-set>"#));
+    assert!(body.contains("<CS:getctag>"));
+
+    // The collection lists a resource for every UID
+    let (status, _, body) = request(&state, "PROPFIND", format!("/dav/{}/calendar/", group.id), Some(authorization(&feed)), Some("1"), "").await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert_eq!(event_hrefs(&body).len(), 3);
+    assert!(!body.contains("calendar-data"));
+}
+
+#[tokio::test]
+async fn report_events() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let now = create_events(&state, &group);
+    let feed = create_feed(&state, &group, FeedPrivacy::Busy);
+
+    let query = |start: NaiveDateTime, end: NaiveDateTime| format!(
+        r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+            <d:prop><d:getetag/><c:calendar-data/></d:prop>
+            <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT">
+                <c:time-range start="{}" end="{}"/>
+            </c:comp-filter></c:comp-filter></c:filter>
+        </c:calendar-query>"#,
+        start.format("%Y%m%dT%H%M%SZ"),
+        end.format("%Y%m%dT%H%M%SZ"),
+    );
+
+    // Only the recurring event occurs within the first week, with its override
+    let (status, _, body) = request(
+        &state,
+        "REPORT",
+        format!("/dav/{}/calendar/", group.id),
+        Some(authorization(&feed)),
+        Some("1"),
+        &query(now, now + Duration::days(7)),
+    ).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    let hrefs = event_hrefs(&body);
+    assert_eq!(hrefs.len(), 1);
+    assert!(body.contains("<getetag>"));
+    assert_eq!(body.matches("UID:standup").count(), 2);
+    assert!(body.contains("RECURRENCE-ID:"));
+    assert!(body.contains("SUMMARY:Busy"));
+    assert!(!body.contains("Secret"));
+
+    let (status, _, body) = request(
+        &state,
+        "REPORT",
+        format!("/dav/{}/calendar/", group.id),
+        Some(authorization(&feed)),
+        Some("1"),
+        &query(now + Duration::days(15), now + Duration::days(25)),
+    ).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert!(body.contains("UID:team/lunch@example.com"));
+    let lunch = event_hrefs(&body).pop().unwrap();
+
+    // Unknown resources are reported as missing
+    let multiget = format!(
+        r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+            <d:prop><d:getetag/></d:prop>
+            <d:href>{}</d:href>
+            <d:href>/dav/{}/calendar/6d697373696e67.ics</d:href>
+        </c:calendar-multiget>"#,
+        hrefs[0],
+        group.id,
+    );
+    let (status, _, body) = request(&state, "REPORT", format!("/dav/{}/calendar/", group.id), Some(authorization(&feed)), Some("1"), &multiget).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert_eq!(body.matches("HTTP/1.1 200 OK").count(), 1);
+    assert_eq!(body.matches("HTTP/1.1 404 Not Found").count(), 1);
+    assert!(!body.contains("calendar-data"));
+
+    // Resources can be fetched on their own, with an ETag that does not depend on the build
+    let (status, headers, body) = request(&state, "GET", lunch, Some(authorization(&feed)), None, "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers.get(http::header::ETAG).unwrap().to_str().unwrap(), format!("\"{:016x}\"", fnv1a(&body)));
+    assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(body.contains("UID:team/lunch@example.com\r\n"));
+}
+
+#[tokio::test]
+async fn report_series_in_local_time() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    let feed = create_feed(&state, &group, FeedPrivacy::Busy);
+
+    // Mondays at 19:00 in Toronto, which is Tuesday 00:00 UTC in winter and Monday 23:00 UTC in summer
+    let utc = |month: u32, day: u32, hour: u32, minute: u32| NaiveDate::from_ymd_opt(2025, month, day).unwrap().and_hms_opt(hour, minute, 0).unwrap();
+    let mut evening = event_result("evening", utc(1, 7, 0, 0) - Duration::days(63));
+    evening.time_zone = Some("America/Toronto".to_string());
+    evening.recurrence = Some("RRULE:FREQ=WEEKLY;BYDAY=MO".to_string());
+    sync_events(&state, &calendar, vec![evening]);
+
+    // The server finds the occurrence of Monday 10 March after the change to daylight saving time
+    let (start, end) = (utc(3, 10, 22, 30), utc(3, 10, 23, 30));
+    let query = format!(
+        r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+            <d:prop><d:getetag/></d:prop>
+            <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT">
+                <c:time-range start="{}" end="{}"/>
+            </c:comp-filter></c:comp-filter></c:filter>
+        </c:calendar-query>"#,
+        start.format("%Y%m%dT%H%M%SZ"),
+        end.format("%Y%m%dT%H%M%SZ"),
+    );
+    let (status, _, body) = request(&state, "REPORT", format!("/dav/{}/calendar/", group.id), Some(authorization(&feed)), Some("1"), &query).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    let href = event_hrefs(&body).pop().unwrap();
+
+    // The resource is written in local time, so a client expands the same occurrence
+    let (status, _, body) = request(&state, "GET", href, Some(authorization(&feed)), None, "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\r\nDTSTART;TZID=America/Toronto:20241104T190000\r\n"));
+    assert!(body.contains("\r\nBEGIN:VTIMEZONE\r\nTZID:America/Toronto\r\n"));
+
+    let event = caldav::parse_events(&body).pop().unwrap().to_event_result("").unwrap();
+    let occurrences = Recurrence::parse(event.recurrence.as_deref().unwrap()).unwrap()
+        .occurrences(event.starts_at, event.ends_at - event.starts_at, event.time_zone.as_deref(), false, start, end);
+    assert_eq!(occurrences, vec![utc(3, 10, 23, 0)]);
+}
+
+#[tokio::test]
+async fn dav_requires_feed_token() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let other_group = app.create_group(&mut state.get_connection());
+    let feed = create_feed(&state, &group, FeedPrivacy::Full);
+    let other_feed = create_feed(&state, &other_group, FeedPrivacy::Full);
+
+    let (status, headers, _) = request(&state, "PROPFIND", format!("/dav/{}/calendar/", group.id), None, Some("0"), "").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(headers.get(http::header::WWW_AUTHENTICATE).is_some());
+
+    let invalid = format!("Basic {}", BASE64_STANDARD.encode(format!("{}:invalid", group.id)));
+    let (status, _, _) = request(&state, "PROPFIND", format!("/dav/{}/calendar/", group.id), Some(invalid), Some("0"), "").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The token of one group does not give access to another
+    let (status, _, _) = request(&state, "PROPFIND", format!("/dav/{}/calendar/", group.id), Some(authorization(&other_feed)), Some("0"), "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // The calendar is read-only
+    let (status, _, _) = request(&state, "PUT", format!("/dav/{}/calendar/6576656e74.ics", group.id), Some(authorization(&feed)), None, "BEGIN:VCALENDAR").await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+}
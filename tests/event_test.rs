@@ -0,0 +1,107 @@
+use chrono::Duration;
+use dotenv::dotenv;
+use schedsync_api::{connectors::{caldav::{caldav::CaldavEvent, datetime::IcalDateTime, participant::{AttendeeRole, CaldavAttendee, CaldavOrganizer, CalendarUserType, ParticipationStatus}}, oauth2::Oauth2Service, ServiceType}, models::{app::App, event::{AttendeeStatus, Event, EventChanges, EventStatus, EventTransparency, EventVisibility}}, test_util::{create_calendar, datetime, event_result}, AppState};
+
+#[tokio::test]
+async fn sync_events() {
+    dotenv().ok();
+    let state = AppState::new();
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+
+    let mut exception = event_result("standup_20241105T100000Z", datetime(4, 10), datetime(4, 11));
+    exception.recurring_event_id = Some("standup".to_string());
+    exception.original_starts_at = Some(datetime(5, 10));
+
+    let mut standup = event_result("standup", datetime(4, 10), datetime(4, 11));
+    standup.recurrence = Some("RRULE:FREQ=DAILY".to_string());
+
+    // A full sync creates every event
+    let result = Event::sync(&calendar, EventChanges {
+        events: vec![standup, exception, event_result("lunch", datetime(4, 10), datetime(4, 11)), event_result("review", datetime(4, 10), datetime(4, 11))],
+        deleted: vec![],
+        full: true,
+        sync_token: None,
+    }, &mut state.get_connection()).unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (4, 0, 0));
+
+    // An incremental sync updates changed events and removes deleted series with their exceptions
+    let mut lunch = event_result("lunch", datetime(4, 10), datetime(4, 11));
+    lunch.transparency = EventTransparency::Transparent;
+    let result = Event::sync(&calendar, EventChanges {
+        events: vec![lunch],
+        deleted: vec!["standup".to_string()],
+        full: false,
+        sync_token: None,
+    }, &mut state.get_connection()).unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 1, 2));
+
+    let lunch = Event::find_by_external_id(&calendar, "lunch", &mut state.get_connection()).unwrap();
+    assert_eq!(lunch.transparency, EventTransparency::Transparent);
+
+    // A full sync removes every event that is no longer returned
+    let result = Event::sync(&calendar, EventChanges {
+        events: vec![event_result("review", datetime(4, 10), datetime(4, 11))],
+        deleted: vec![],
+        full: true,
+        sync_token: None,
+    }, &mut state.get_connection()).unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 1));
+    assert_eq!(Event::find_by_calendar(&calendar, &mut state.get_connection()).len(), 1);
+}
+
+#[test]
+fn map_caldav_event() {
+    let event = CaldavEvent {
+        uid: "standup".to_string(),
+        created: Some("20241101T090000Z".to_string()),
+        last_modified: None,
+        summary: Some("Standup".to_string()),
+        description: Some("Agenda".to_string()),
+        dtstart: IcalDateTime::Utc(datetime(5, 15).and_utc()),
+        dtend: Some(IcalDateTime::Utc(datetime(5, 15).and_utc() + Duration::minutes(30))),
+        status: Some("TENTATIVE".to_string()),
+        organizer: Some(CaldavOrganizer {
+            address: "mailto:organizer@example.com".to_string(),
+            name: Some("Organizer".to_string()),
+            sent_by: None,
+        }),
+        recurrence_id: Some(IcalDateTime::Utc(datetime(5, 10).and_utc())),
+        rrule: None,
+        rdate: vec![],
+        exdate: vec![],
+        location: None,
+        transp: Some("TRANSPARENT".to_string()),
+        class: Some("PRIVATE".to_string()),
+        categories: None,
+        attach: None,
+        attendees: vec![CaldavAttendee {
+            address: "MAILTO:attendee@example.com".to_string(),
+            name: None,
+            status: ParticipationStatus::Accepted,
+            role: AttendeeRole::OptionalParticipant,
+            rsvp: false,
+            user_type: CalendarUserType::Individual,
+            delegated_to: vec![],
+            delegated_from: vec![],
+            sent_by: None,
+        }],
+    };
+
+    let result = event.to_event_result("\"etag\"").unwrap();
+    assert_eq!(result.external_id, "standup_20241105T100000Z");
+    assert_eq!(result.recurring_event_id.as_deref(), Some("standup"));
+    assert_eq!(result.original_starts_at, Some(datetime(5, 10)));
+    assert_eq!(result.starts_at, datetime(5, 15));
+    assert_eq!(result.summary.as_deref(), Some("Standup"));
+    assert_eq!(result.description.as_deref(), Some("Agenda"));
+    assert_eq!(result.status, EventStatus::Tentative);
+    assert_eq!(result.transparency, EventTransparency::Transparent);
+    assert_eq!(result.visibility, EventVisibility::Private);
+    assert_eq!(result.organizer.unwrap().email, "organizer@example.com");
+    assert_eq!(result.attendees.0[0].email, "attendee@example.com");
+    assert_eq!(result.attendees.0[0].status, AttendeeStatus::Accepted);
+    assert!(result.attendees.0[0].optional);
+    assert!(!result.all_day);
+}
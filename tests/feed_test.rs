@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use axum::{body::Body, http::Request, http};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use dotenv::dotenv;
+use http_body_util::BodyExt;
+use reqwest::{Method, StatusCode};
+use schedsync_api::{build_routes, connectors::{oauth2::Oauth2Service, ServiceType}, mirror::PLACEHOLDER_DESCRIPTION, models::{app::App, app_key::AppKey, event::EventResult, group::Group}, test_util::{self, create_calendar, sync_events}, AppState};
+use tower::util::ServiceExt;
+
+/**
+ * An event of an hour with details which must not show up in busy feeds.
+ */
+fn event_result(external_id: &str, starts_at: NaiveDateTime) -> EventResult {
+    EventResult {
+        summary: Some(format!("Secret {}", external_id)),
+        description: Some("Agenda".to_string()),
+        location: Some("Boardroom".to_string()),
+        ..test_util::event_result(external_id, starts_at, starts_at + Duration::hours(1))
+    }
+}
+
+fn create_events(state: &AppState, group: &Group) {
+    let calendar = create_calendar(state, group, ServiceType::Google(Oauth2Service::Google), "primary");
+
+    let now = Utc::now().naive_utc();
+    let mut placeholder = event_result("placeholder", now + Duration::days(2));
+    placeholder.description = Some(PLACEHOLDER_DESCRIPTION.to_string());
+
+    // An occurrence the way Outlook syncs them, without its series
+    let mut occurrence = event_result("weekly_occurrence", now + Duration::days(3));
+    occurrence.recurring_event_id = Some("weekly".to_string());
+    occurrence.original_starts_at = Some(occurrence.starts_at);
+
+    sync_events(state, &calendar, vec![
+        event_result("planning", now + Duration::days(1)),
+        event_result("retro", now - Duration::days(3)),
+        event_result("offsite", now + Duration::days(60)),
+        placeholder,
+        occurrence,
+    ]);
+}
+
+async fn request(state: &Arc<AppState>, method: Method, uri: String, authorization: Option<String>, body: Option<serde_json::Value>) -> (StatusCode, Option<String>, String) {
+    let mut builder = Request::builder()
+        .uri(uri)
+        .method(method)
+        .header(http::header::CONTENT_TYPE, "application/json");
+    if let Some(authorization) = authorization {
+        builder = builder.header(http::header::AUTHORIZATION, authorization);
+    }
+
+    let router = build_routes(state.clone());
+    let response = router
+        .oneshot(builder.body(Body::from(body.map(|body| body.to_string()).unwrap_or_default())).unwrap())
+        .await.unwrap();
+
+    let status = response.status();
+    let content_type = response.headers().get(http::header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn create_feed(state: &Arc<AppState>, app: &App, app_key: &AppKey, group: &Group, body: serde_json::Value) -> serde_json::Value {
+    let (status, _, body) = request(
+        state,
+        Method::POST,
+        format!("/api/group/{}/feed", group.id),
+        Some(test_util::generate_basic_header(app, app_key)),
+        Some(body),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    serde_json::from_str(&body).unwrap()
+}
+
+#[tokio::test]
+async fn busy_feed() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    create_events(&state, &group);
+
+    let feed = create_feed(&state, &app, &app_key, &group, serde_json::json!({ "future_days": 30 })).await;
+    assert_eq!(feed["privacy"], "busy");
+    assert_eq!(feed["past_days"], 30);
+
+    let (status, content_type, body) = request(
+        &state,
+        Method::GET,
+        format!("/feed/{}/{}.ics", group.id, feed["token"].as_str().unwrap()),
+        None,
+        None,
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("text/calendar; charset=utf-8"));
+    assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+
+    // Only the events within the window, without their details
+    assert!(body.contains("UID:planning\r\n"));
+    assert!(body.contains("UID:retro\r\n"));
+    assert!(!body.contains("UID:offsite\r\n"));
+    assert!(!body.contains("UID:placeholder\r\n"));
+    assert_eq!(body.matches("SUMMARY:Busy\r\n").count(), 3);
+
+    // The occurrence is written as an event of its own
+    assert!(body.contains("UID:weekly_occurrence\r\n"));
+    assert!(!body.contains("UID:weekly\r\n"));
+    assert!(!body.contains("RECURRENCE-ID"));
+    assert!(!body.contains("Secret"));
+    assert!(!body.contains("Agenda"));
+    assert!(!body.contains("Boardroom"));
+}
+
+#[tokio::test]
+async fn full_feed() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    create_events(&state, &group);
+
+    let feed = create_feed(&state, &app, &app_key, &group, serde_json::json!({ "privacy": "full", "past_days": 0 })).await;
+    let (status, _, body) = request(
+        &state,
+        Method::GET,
+        format!("/feed/{}/{}.ics", group.id, feed["token"].as_str().unwrap()),
+        None,
+        None,
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("SUMMARY:Secret planning\r\n"));
+    assert!(body.contains("LOCATION:Boardroom\r\n"));
+    assert!(body.contains("UID:offsite\r\n"));
+    assert!(!body.contains("UID:retro\r\n"));
+}
+
+#[tokio::test]
+async fn recurring_feed_keeps_time_zone() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+
+    // Weekly at 11:00 in Amsterdam since before the end of daylight saving time
+    let starts_at = NaiveDate::from_ymd_opt(2024, 10, 21).unwrap().and_hms_opt(9, 0, 0).unwrap();
+    let mut series = event_result("standup", starts_at);
+    series.time_zone = Some("Europe/Amsterdam".to_string());
+    series.recurrence = Some("RRULE:FREQ=WEEKLY".to_string());
+    sync_events(&state, &calendar, vec![series]);
+
+    let feed = create_feed(&state, &app, &app_key, &group, serde_json::json!({})).await;
+    let (status, _, body) = request(
+        &state,
+        Method::GET,
+        format!("/feed/{}/{}.ics", group.id, feed["token"].as_str().unwrap()),
+        None,
+        None,
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The series is written in local time, so subscribers expand it at 11:00 all year
+    assert!(body.contains("UID:standup\r\n"));
+    assert!(body.contains("\r\nDTSTART;TZID=Europe/Amsterdam:20241021T110000\r\n"));
+    assert!(body.contains("\r\nRRULE:FREQ=WEEKLY\r\n"));
+    assert!(body.contains("\r\nBEGIN:VTIMEZONE\r\nTZID:Europe/Amsterdam\r\n"));
+    assert!(!body.contains("DTSTART:20241021T090000Z"));
+}
+
+#[tokio::test]
+async fn feed_with_invalid_token() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let other_group = app.create_group(&mut state.get_connection());
+
+    let feed = create_feed(&state, &app, &app_key, &group, serde_json::json!({})).await;
+    let token = feed["token"].as_str().unwrap();
+
+    for uri in [
+        format!("/feed/{}/invalid.ics", group.id),
+        format!("/feed/{}/{}", group.id, token),
+        format!("/feed/{}/{}.ics", other_group.id, token),
+    ] {
+        let (status, _, _) = request(&state, Method::GET, uri, None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    // Deleting the feed revokes its URL
+    let (status, _, _) = request(
+        &state,
+        Method::DELETE,
+        format!("/api/feed/{}", feed["id"]),
+        Some(test_util::generate_basic_header(&app, &app_key)),
+        None,
+    ).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _, _) = request(&state, Method::GET, format!("/feed/{}/{}.ics", group.id, token), None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn manage_feed_of_other_app() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let owner = App::new(&mut state.get_connection());
+    let owner_key = owner.create_key(&mut state.get_connection());
+    let group = owner.create_group(&mut state.get_connection());
+    let feed = create_feed(&state, &owner, &owner_key, &group, serde_json::json!({})).await;
+
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+
+    let (status, _, _) = request(
+        &state,
+        Method::POST,
+        format!("/api/group/{}/feed", group.id),
+        Some(test_util::generate_basic_header(&app, &app_key)),
+        Some(serde_json::json!({})),
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = request(
+        &state,
+        Method::DELETE,
+        format!("/api/feed/{}", feed["id"]),
+        Some(test_util::generate_basic_header(&app, &app_key)),
+        None,
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = request(
+        &state,
+        Method::POST,
+        format!("/api/group/{}/feed", group.id),
+        Some(test_util::generate_basic_header(&owner, &owner_key)),
+        Some(serde_json::json!({ "future_days": 5000 })),
+    ).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
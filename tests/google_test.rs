@@ -0,0 +1,263 @@
+use schedsync_api::{connectors::oauth2::google::{GoogleCalendarResult, GoogleEvent, GoogleEventRequest}, models::event::{Attendee, AttendeeStatus, Attendees, EventResult, EventStatus, EventTransparency, EventVisibility, ParticipantKind, ParticipantRole}, test_util::{self, datetime}};
+use serde_json::json;
+
+fn event(value: serde_json::Value) -> Option<EventResult> {
+    serde_json::from_value::<GoogleEvent>(value).unwrap().into_event_result()
+}
+
+fn request(event: &EventResult) -> serde_json::Value {
+    serde_json::to_value(GoogleEventRequest::from_event(event)).unwrap()
+}
+
+#[test]
+fn map_timed_event() {
+    let event = event(json!({
+        "kind": "calendar#event",
+        "id": "standup",
+        "etag": "\"3456\"",
+        "status": "confirmed",
+        "summary": "Standup",
+        "description": "Agenda",
+        "location": "Room 1",
+        "start": { "dateTime": "2024-11-04T11:00:00+01:00", "timeZone": "Europe/Amsterdam" },
+        "end": { "dateTime": "2024-11-04T11:15:00+01:00", "timeZone": "Europe/Amsterdam" },
+        "transparency": "transparent",
+        "visibility": "private",
+        "organizer": { "email": "organizer@example.com", "displayName": "Organizer" },
+        "attendees": [
+            { "email": "john@example.com", "responseStatus": "accepted" },
+            { "email": "jane@example.com", "displayName": "Jane", "responseStatus": "tentative", "optional": true },
+            { "email": "room@resource.calendar.google.com", "responseStatus": "needsAction", "resource": true },
+            { "displayName": "No address", "responseStatus": "declined" },
+        ],
+        "recurrence": ["RRULE:FREQ=WEEKLY;BYDAY=MO", "EXDATE;TZID=Europe/Amsterdam:20241111T110000"],
+    })).unwrap();
+
+    assert_eq!(event.external_id, "standup");
+    assert_eq!(event.etag.as_deref(), Some("\"3456\""));
+    assert_eq!(event.summary.as_deref(), Some("Standup"));
+    assert_eq!(event.description.as_deref(), Some("Agenda"));
+    assert_eq!(event.location.as_deref(), Some("Room 1"));
+    // Times are stored in UTC
+    assert_eq!(event.starts_at, datetime(4, 10));
+    assert_eq!(event.ends_at, datetime(4, 10) + chrono::Duration::minutes(15));
+    assert!(!event.all_day);
+    assert_eq!(event.time_zone.as_deref(), Some("Europe/Amsterdam"));
+    assert_eq!(event.status, EventStatus::Confirmed);
+    assert_eq!(event.transparency, EventTransparency::Transparent);
+    assert_eq!(event.visibility, EventVisibility::Private);
+    let organizer = event.organizer.unwrap();
+    assert_eq!((organizer.email.as_str(), organizer.name.as_deref()), ("organizer@example.com", Some("Organizer")));
+
+    // Attendees without an address are skipped
+    let attendees = event.attendees.0;
+    assert_eq!(attendees.len(), 3);
+    assert_eq!(attendees[0].status, AttendeeStatus::Accepted);
+    assert_eq!((attendees[0].role, attendees[0].kind), (ParticipantRole::Required, ParticipantKind::Individual));
+    assert_eq!(attendees[1].name.as_deref(), Some("Jane"));
+    assert_eq!(attendees[1].status, AttendeeStatus::Tentative);
+    assert!(attendees[1].optional);
+    assert_eq!(attendees[1].role, ParticipantRole::Optional);
+    assert_eq!(attendees[2].status, AttendeeStatus::NeedsAction);
+    assert_eq!(attendees[2].kind, ParticipantKind::Resource);
+
+    assert_eq!(event.recurrence.as_deref(), Some("RRULE:FREQ=WEEKLY;BYDAY=MO\nEXDATE;TZID=Europe/Amsterdam:20241111T110000"));
+    assert_eq!(event.recurring_event_id, None);
+    assert_eq!(event.original_starts_at, None);
+}
+
+#[test]
+fn map_all_day_event() {
+    let event = event(json!({
+        "id": "holiday",
+        "status": "tentative",
+        "summary": "Holiday",
+        "start": { "date": "2024-11-04" },
+        "end": { "date": "2024-11-06" },
+    })).unwrap();
+
+    assert!(event.all_day);
+    assert_eq!(event.starts_at, datetime(4, 0));
+    assert_eq!(event.ends_at, datetime(6, 0));
+    assert_eq!(event.time_zone, None);
+    assert_eq!(event.status, EventStatus::Tentative);
+    assert_eq!(event.transparency, EventTransparency::Opaque);
+    assert_eq!(event.visibility, EventVisibility::Default);
+    assert_eq!(event.organizer, None);
+    assert!(event.attendees.0.is_empty());
+    assert_eq!(event.recurrence, None);
+}
+
+#[test]
+fn map_recurring_instances() {
+    // A moved instance of a series keeps its original start
+    let event = event(json!({
+        "id": "standup_20241111T100000Z",
+        "status": "confirmed",
+        "summary": "Late standup",
+        "start": { "dateTime": "2024-11-11T12:00:00+01:00", "timeZone": "Europe/Amsterdam" },
+        "end": { "dateTime": "2024-11-11T12:15:00+01:00", "timeZone": "Europe/Amsterdam" },
+        "recurringEventId": "standup",
+        "originalStartTime": { "dateTime": "2024-11-11T11:00:00+01:00", "timeZone": "Europe/Amsterdam" },
+    })).unwrap();
+    assert_eq!(event.starts_at, datetime(11, 11));
+    assert_eq!(event.recurring_event_id.as_deref(), Some("standup"));
+    assert_eq!(event.original_starts_at, Some(datetime(11, 10)));
+    assert_eq!(event.status, EventStatus::Confirmed);
+
+    // A cancelled instance only carries its original start, which is used as its start and end
+    let event = self::event(json!({
+        "id": "standup_20241118T100000Z",
+        "status": "cancelled",
+        "recurringEventId": "standup",
+        "originalStartTime": { "dateTime": "2024-11-18T10:00:00Z" },
+    })).unwrap();
+    assert_eq!(event.status, EventStatus::Cancelled);
+    assert_eq!(event.summary, None);
+    assert_eq!((event.starts_at, event.ends_at), (datetime(18, 10), datetime(18, 10)));
+    assert_eq!(event.original_starts_at, Some(datetime(18, 10)));
+    assert!(!event.all_day);
+
+    // Cancelled instances of all-day series stay all-day
+    let event = self::event(json!({
+        "id": "holiday_20241118",
+        "status": "cancelled",
+        "recurringEventId": "holiday",
+        "originalStartTime": { "date": "2024-11-18" },
+    })).unwrap();
+    assert!(event.all_day);
+    assert_eq!(event.starts_at, datetime(18, 0));
+
+    // Events without any usable start are skipped
+    assert_eq!(self::event(json!({ "id": "broken", "status": "cancelled" })), None);
+    assert_eq!(self::event(json!({ "id": "broken", "start": { "dateTime": "tomorrow" } })), None);
+}
+
+#[test]
+fn write_event_request() {
+    let event = EventResult {
+        summary: Some("Standup".to_string()),
+        time_zone: Some("Europe/Amsterdam".to_string()),
+        status: EventStatus::Tentative,
+        transparency: EventTransparency::Transparent,
+        visibility: EventVisibility::Confidential,
+        attendees: Attendees(vec![
+            Attendee {
+                email: "john@example.com".to_string(),
+                name: Some("John".to_string()),
+                status: AttendeeStatus::Accepted,
+                optional: true,
+                role: ParticipantRole::Optional,
+                rsvp: false,
+                kind: ParticipantKind::Individual,
+                delegated_to: vec![],
+                delegated_from: vec![],
+                sent_by: None,
+            },
+            Attendee {
+                email: "room@example.com".to_string(),
+                name: None,
+                status: AttendeeStatus::NeedsAction,
+                optional: false,
+                role: ParticipantRole::NonParticipant,
+                rsvp: false,
+                kind: ParticipantKind::Room,
+                delegated_to: vec![],
+                delegated_from: vec![],
+                sent_by: None,
+            },
+        ]),
+        recurrence: Some("RRULE:FREQ=WEEKLY;BYDAY=MO\n  \nEXDATE:20241111T100000Z\n".to_string()),
+        ..test_util::event_result("standup", datetime(4, 10), datetime(4, 11))
+    };
+
+    let request = request(&event);
+    assert_eq!(request["summary"], "Standup");
+    // Times are sent in UTC, with the time zone the series is expanded in
+    assert_eq!(request["start"], json!({ "date": null, "dateTime": "2024-11-04T10:00:00Z", "timeZone": "Europe/Amsterdam" }));
+    assert_eq!(request["end"], json!({ "date": null, "dateTime": "2024-11-04T11:00:00Z", "timeZone": "Europe/Amsterdam" }));
+    assert_eq!(request["status"], "tentative");
+    assert_eq!(request["transparency"], "transparent");
+    assert_eq!(request["visibility"], "confidential");
+    assert_eq!(request["attendees"], json!([
+        { "email": "john@example.com", "displayName": "John", "responseStatus": "accepted", "optional": true },
+        { "email": "room@example.com", "displayName": null, "responseStatus": "needsAction", "optional": false, "resource": true },
+    ]));
+    // Blank lines of the recurrence are dropped
+    assert_eq!(request["recurrence"], json!(["RRULE:FREQ=WEEKLY;BYDAY=MO", "EXDATE:20241111T100000Z"]));
+
+    // All-day events are sent as dates
+    let request = self::request(&EventResult {
+        starts_at: datetime(4, 0),
+        ends_at: datetime(6, 0),
+        all_day: true,
+        ..event.clone()
+    });
+    assert_eq!(request["start"], json!({ "date": "2024-11-04", "dateTime": null, "timeZone": null }));
+    assert_eq!(request["end"], json!({ "date": "2024-11-06", "dateTime": null, "timeZone": null }));
+}
+
+#[test]
+fn write_empty_fields_as_null() {
+    // Every field is sent, so fields which are cleared on the event are cleared on Google as well
+    let request = request(&EventResult {
+        summary: None,
+        status: EventStatus::Cancelled,
+        ..test_util::event_result("standup", datetime(4, 10), datetime(4, 11))
+    });
+    assert_eq!(request, json!({
+        "summary": null,
+        "description": null,
+        "location": null,
+        "start": { "date": null, "dateTime": "2024-11-04T10:00:00Z", "timeZone": null },
+        "end": { "date": null, "dateTime": "2024-11-04T11:00:00Z", "timeZone": null },
+        "status": "cancelled",
+        "transparency": "opaque",
+        "visibility": "default",
+        "attendees": [],
+        "recurrence": null,
+    }));
+}
+
+#[test]
+fn map_calendar() {
+    let calendar = serde_json::from_value::<GoogleCalendarResult>(json!({
+        "kind": "calendar#calendarListEntry",
+        "etag": "\"1401131222998000\"",
+        "id": "john@example.com",
+        "summary": "John",
+        "timeZone": "America/Toronto",
+        "colorId": "17",
+        "backgroundColor": "#9a9cff",
+        "foregroundColor": "#000000",
+        "selected": true,
+        "accessRole": "owner",
+        "defaultReminders": [{ "method": "popup", "minutes": 30 }],
+        "primary": true,
+    })).unwrap().into_calendar_result();
+
+    assert_eq!(calendar.external_id, "john@example.com");
+    assert_eq!(calendar.name, "John");
+    assert_eq!(calendar.background_color, "#9a9cff");
+    assert_eq!(calendar.foreground_color, "#000000");
+
+    // Google leaves out primary and selected for other calendars
+    let calendar = serde_json::from_value::<GoogleCalendarResult>(json!({
+        "kind": "calendar#calendarListEntry",
+        "etag": "\"1701131222998000\"",
+        "id": "en.dutch#holiday@group.v.calendar.google.com",
+        "summary": "Holidays in the Netherlands",
+        "description": "Holidays and Observances in the Netherlands",
+        "timeZone": "Europe/Amsterdam",
+        "colorId": "8",
+        "backgroundColor": "#16a765",
+        "foregroundColor": "#000000",
+        "accessRole": "reader",
+        "defaultReminders": [],
+        "conferenceProperties": { "allowedConferenceSolutionTypes": ["hangoutsMeet"] },
+    })).unwrap().into_calendar_result();
+
+    assert_eq!(calendar.external_id, "en.dutch#holiday@group.v.calendar.google.com");
+    assert_eq!(calendar.name, "Holidays in the Netherlands");
+    assert_eq!(calendar.background_color, "#16a765");
+}
@@ -0,0 +1,156 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use schedsync_api::connectors::caldav::{caldav, datetime::{IcalDateTime, TimeZones}};
+
+fn datetime(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+}
+
+/**
+ * A calendar with a VTIMEZONE the way Outlook writes it, which the IANA database does not know.
+ */
+fn calendar(events: &str) -> String {
+    format!("BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Test//Test//EN\r
+BEGIN:VTIMEZONE\r
+TZID:W. Europe Standard Time\r
+BEGIN:STANDARD\r
+DTSTART:16010101T030000\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=10\r
+END:STANDARD\r
+BEGIN:DAYLIGHT\r
+DTSTART:16010101T020000\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=3\r
+END:DAYLIGHT\r
+END:VTIMEZONE\r
+{}END:VCALENDAR\r
+", events)
+}
+
+fn event(uid: &str, properties: &str) -> String {
+    format!("BEGIN:VEVENT\r\nUID:{}\r\nCREATED:20241101T090000Z\r\nSUMMARY:{}\r\n{}END:VEVENT\r\n", uid, uid, properties)
+}
+
+#[test]
+fn parse_embedded_time_zone() {
+    let events = caldav::parse_events(&calendar(&[
+        event("summer", "DTSTART;TZID=W. Europe Standard Time:20240715T090000\r\nDTEND;TZID=W. Europe Standard Time:20240715T100000\r\n"),
+        event("winter", "DTSTART;TZID=W. Europe Standard Time:20241105T090000\r\nDTEND;TZID=W. Europe Standard Time:20241105T100000\r\n"),
+        event("gap", "DTSTART;TZID=W. Europe Standard Time:20240331T023000\r\nDTEND;TZID=W. Europe Standard Time:20240331T033000\r\n"),
+        event("overlap", "DTSTART;TZID=W. Europe Standard Time:20241027T023000\r\nDTEND;TZID=W. Europe Standard Time:20241027T033000\r\n"),
+    ].concat()));
+    assert_eq!(events.len(), 4);
+
+    let summer = events[0].to_event_result("\"etag\"").unwrap();
+    assert_eq!(summer.starts_at, datetime(2024, 7, 15, 7, 0));
+    assert_eq!(summer.ends_at, datetime(2024, 7, 15, 8, 0));
+    assert!(!summer.all_day);
+    // Recurrences are expanded in the IANA time zone the Windows name stands for
+    assert_eq!(summer.time_zone.as_deref(), Some("Europe/Berlin"));
+    let IcalDateTime::Zoned(value, tzid) = &events[0].dtstart else {
+        panic!("Expected a zoned time, got {:?}", events[0].dtstart);
+    };
+    assert_eq!(value.offset().local_minus_utc(), 7200);
+    assert_eq!(tzid, "W. Europe Standard Time");
+
+    let winter = events[1].to_event_result("\"etag\"").unwrap();
+    assert_eq!(winter.starts_at, datetime(2024, 11, 5, 8, 0));
+
+    // 02:30 does not exist and is moved forward by the length of the gap
+    assert_eq!(events[2].dtstart.naive_utc(), datetime(2024, 3, 31, 1, 30));
+    assert_eq!(events[2].dtend.as_ref().unwrap().naive_utc(), datetime(2024, 3, 31, 1, 30));
+
+    // 02:30 happens twice, the first one is taken
+    assert_eq!(events[3].dtstart.naive_utc(), datetime(2024, 10, 27, 0, 30));
+}
+
+#[test]
+fn parse_iana_time_zone() {
+    let events = caldav::parse_events(&calendar(&event(
+        "standup",
+        "DTSTART;TZID=America/New_York:20241105T090000\r\nDTEND;TZID=America/New_York:20241105T093000\r\nRRULE:FREQ=DAILY;COUNT=5\r\n",
+    )));
+
+    let result = events[0].to_event_result("\"etag\"").unwrap();
+    assert_eq!(result.starts_at, datetime(2024, 11, 5, 14, 0));
+    assert_eq!(result.ends_at, datetime(2024, 11, 5, 14, 30));
+    assert_eq!(result.time_zone.as_deref(), Some("America/New_York"));
+    assert_eq!(result.recurrence.as_deref(), Some("RRULE:FREQ=DAILY;COUNT=5"));
+}
+
+#[test]
+fn parse_mozilla_time_zone() {
+    // Without CREATED and SUMMARY, and in a time zone the calendar does not define
+    let events = caldav::parse_events(&calendar(
+        "BEGIN:VEVENT\r\nUID:standup\r\nDTSTART;TZID=/mozilla.org/20050126_1/Europe/Amsterdam:20241105T090000\r\nEND:VEVENT\r\n",
+    ));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].created, None);
+    assert_eq!(events[0].summary, None);
+
+    let result = events[0].to_event_result("\"etag\"").unwrap();
+    assert_eq!(result.starts_at, datetime(2024, 11, 5, 8, 0));
+    assert_eq!(result.time_zone.as_deref(), Some("Europe/Amsterdam"));
+}
+
+#[test]
+fn parse_escaped_text() {
+    let events = caldav::parse_events(&calendar(
+        "BEGIN:VEVENT\r\nUID:lunch\r\nDTSTART:20241105T120000Z\r\nSUMMARY:Lunch\\, with dessert\r\nDESCRIPTION:Bring:\\n- plates\\; cups\r\nLOCATION:Room 1\\\\2\r\nEND:VEVENT\r\n",
+    ));
+
+    let result = events[0].to_event_result("\"etag\"").unwrap();
+    assert_eq!(result.summary.as_deref(), Some("Lunch, with dessert"));
+    assert_eq!(result.description.as_deref(), Some("Bring:\n- plates; cups"));
+    assert_eq!(result.location.as_deref(), Some("Room 1\\2"));
+}
+
+#[test]
+fn parse_dates_and_floating_times() {
+    let events = caldav::parse_events(&calendar(&[
+        event("holiday", "DTSTART;VALUE=DATE:20241225\r\n"),
+        event("floating", "DTSTART:20241105T090000\r\nDTEND:20241105T100000\r\n"),
+        event("unknown", "DTSTART;TZID=Mars/Olympus_Mons:20241105T090000\r\nDTEND;TZID=Mars/Olympus_Mons:20241105T100000\r\n"),
+        event("utc", "DTSTART:20241105T090000Z\r\nDTEND:20241105T100000Z\r\n"),
+        event("missing", "DTEND:20241105T100000Z\r\n"),
+        event("invalid", "DTSTART:yesterday\r\n"),
+    ].concat()));
+    assert_eq!(events.len(), 4);
+
+    // Without DTEND an all-day event lasts a single day
+    let holiday = events[0].to_event_result("\"etag\"").unwrap();
+    assert!(holiday.all_day);
+    assert_eq!(holiday.starts_at, datetime(2024, 12, 25, 0, 0));
+    assert_eq!(holiday.ends_at, datetime(2024, 12, 26, 0, 0));
+
+    assert_eq!(events[1].dtstart, IcalDateTime::Floating(datetime(2024, 11, 5, 9, 0)));
+    assert_eq!(events[1].dtstart.naive_utc(), datetime(2024, 11, 5, 9, 0));
+    assert_eq!(events[2].dtstart, IcalDateTime::Floating(datetime(2024, 11, 5, 9, 0)));
+    assert_eq!(events[3].dtstart, IcalDateTime::Utc(datetime(2024, 11, 5, 9, 0).and_utc()));
+}
+
+#[test]
+fn recurrence_id_in_time_zone() {
+    let events = caldav::parse_events(&calendar(&event(
+        "standup",
+        "RECURRENCE-ID;TZID=Europe/Amsterdam:20241106T090000\r\nDTSTART;TZID=Europe/Amsterdam:20241106T140000\r\nDTEND;TZID=Europe/Amsterdam:20241106T150000\r\n",
+    )));
+
+    let result = events[0].to_event_result("\"etag\"").unwrap();
+    assert_eq!(result.external_id, "standup_20241106T080000Z");
+    assert_eq!(result.original_starts_at, Some(datetime(2024, 11, 6, 8, 0)));
+    assert_eq!(result.starts_at, datetime(2024, 11, 6, 13, 0));
+}
+
+#[test]
+fn format_values() {
+    let time_zones = TimeZones::default();
+    assert_eq!(IcalDateTime::parse("20241225", None, &time_zones).unwrap().to_string(), "20241225");
+    assert_eq!(IcalDateTime::parse("20241105T090000", None, &time_zones).unwrap().to_string(), "20241105T090000");
+    assert_eq!(IcalDateTime::parse("20240715T090000", Some("Europe/Amsterdam"), &time_zones).unwrap().to_string(), "20240715T070000Z");
+    assert!(IcalDateTime::parse("2024-07-15", None, &time_zones).is_err());
+}
@@ -0,0 +1,94 @@
+use schedsync_api::{connectors::caldav::{caldav, participant::{AttendeeRole, CalendarUserType, ParticipationStatus}}, models::event::{AttendeeStatus, ParticipantKind, ParticipantRole}};
+
+const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Test//Test//EN\r
+BEGIN:VEVENT\r
+UID:review\r
+SUMMARY:Review\r
+DTSTART:20241105T090000Z\r
+DTEND:20241105T100000Z\r
+ORGANIZER;CN=\"Doe, Jane\";SENT-BY=\"mailto:assistant@example.com\":mailto:jane@example.com\r
+ATTENDEE;CN=Jane Doe;PARTSTAT=ACCEPTED;ROLE=CHAIR:mailto:jane@example.com\r
+ATTENDEE;CN=John;PARTSTAT=tentative;ROLE=OPT-PARTICIPANT;RSVP=TRUE:MAILTO:john@example.com\r
+ATTENDEE;PARTSTAT=DELEGATED;ROLE=NON-PARTICIPANT;DELEGATED-TO=\"mailto:a@example.com\",\"mailto:b@example.com\";SENT-BY=\"mailto:assistant@example.com\":mailto:observer@example.com\r
+ATTENDEE;CUTYPE=ROOM;PARTSTAT=X-MAYBE;RSVP=FALSE:mailto:boardroom@example.com\r
+ATTENDEE:mailto:pending@example.com\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+#[test]
+fn read_every_attendee() {
+    let events = caldav::parse_events(CALENDAR);
+    let attendees = &events[0].attendees;
+    assert_eq!(attendees.len(), 5);
+
+    assert_eq!(attendees[0].address, "mailto:jane@example.com");
+    assert_eq!(attendees[0].name.as_deref(), Some("Jane Doe"));
+    assert_eq!(attendees[0].status, ParticipationStatus::Accepted);
+    assert_eq!(attendees[0].role, AttendeeRole::Chair);
+
+    // Parameter values are not case-sensitive
+    assert_eq!(attendees[1].status, ParticipationStatus::Tentative);
+    assert_eq!(attendees[1].role, AttendeeRole::OptionalParticipant);
+    assert!(attendees[1].rsvp);
+
+    assert_eq!(attendees[2].status, ParticipationStatus::Delegated);
+    assert_eq!(attendees[2].role, AttendeeRole::NonParticipant);
+    assert_eq!(attendees[2].delegated_to, vec!["mailto:a@example.com", "mailto:b@example.com"]);
+    assert_eq!(attendees[2].sent_by.as_deref(), Some("mailto:assistant@example.com"));
+
+    assert_eq!(attendees[3].user_type, CalendarUserType::Room);
+    assert_eq!(attendees[3].status, ParticipationStatus::Other("X-MAYBE".to_string()));
+    assert!(!attendees[3].rsvp);
+
+    // Missing parameters take their defaults
+    assert_eq!(attendees[4].name, None);
+    assert_eq!(attendees[4].status, ParticipationStatus::NeedsAction);
+    assert_eq!(attendees[4].role, AttendeeRole::RequiredParticipant);
+    assert_eq!(attendees[4].user_type, CalendarUserType::Individual);
+    assert!(!attendees[4].rsvp);
+}
+
+#[test]
+fn read_organizer() {
+    let events = caldav::parse_events(CALENDAR);
+    let organizer = events[0].organizer.as_ref().unwrap();
+    assert_eq!(organizer.address, "mailto:jane@example.com");
+    assert_eq!(organizer.name.as_deref(), Some("Doe, Jane"));
+    assert_eq!(organizer.sent_by.as_deref(), Some("mailto:assistant@example.com"));
+}
+
+#[test]
+fn map_attendees_to_event() {
+    let events = caldav::parse_events(CALENDAR);
+    let result = events[0].to_event_result("\"etag\"").unwrap();
+
+    let organizer = result.organizer.unwrap();
+    assert_eq!(organizer.email, "jane@example.com");
+    assert_eq!(organizer.name.as_deref(), Some("Doe, Jane"));
+    assert_eq!(organizer.sent_by.as_deref(), Some("assistant@example.com"));
+
+    let attendees = result.attendees.0;
+    assert_eq!(attendees.len(), 5);
+    assert_eq!(attendees[1].email, "john@example.com");
+    assert_eq!(attendees[1].name.as_deref(), Some("John"));
+    assert_eq!(
+        attendees.iter().map(|attendee| attendee.status.clone()).collect::<Vec<AttendeeStatus>>(),
+        vec![AttendeeStatus::Accepted, AttendeeStatus::Tentative, AttendeeStatus::NeedsAction, AttendeeStatus::NeedsAction, AttendeeStatus::NeedsAction],
+    );
+    assert_eq!(
+        attendees.iter().map(|attendee| attendee.optional).collect::<Vec<bool>>(),
+        vec![false, true, true, false, false],
+    );
+    assert_eq!(
+        attendees.iter().map(|attendee| attendee.role).collect::<Vec<ParticipantRole>>(),
+        vec![ParticipantRole::Chair, ParticipantRole::Optional, ParticipantRole::NonParticipant, ParticipantRole::Required, ParticipantRole::Required],
+    );
+    assert!(attendees[1].rsvp);
+    assert_eq!(attendees[2].delegated_to, vec!["a@example.com", "b@example.com"]);
+    assert_eq!(attendees[2].sent_by.as_deref(), Some("assistant@example.com"));
+    assert_eq!(attendees[3].kind, ParticipantKind::Room);
+    assert_eq!(attendees[4].kind, ParticipantKind::Individual);
+}
@@ -0,0 +1,122 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use schedsync_api::{connectors::caldav::task, ics, models::task::{TaskResult, TaskStatus}};
+
+fn datetime(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 11, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+}
+
+const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Test//Test//EN\r
+BEGIN:VTODO\r
+UID:groceries\r
+SUMMARY:Groceries\r
+DESCRIPTION:Milk\\, eggs\r
+DTSTART;TZID=Europe/Amsterdam:20241104T090000\r
+DUE;TZID=Europe/Amsterdam:20241104T180000\r
+PERCENT-COMPLETE:40\r
+PRIORITY:1\r
+STATUS:IN-PROCESS\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:taxes\r
+SUMMARY:Taxes\r
+DUE;VALUE=DATE:20241130\r
+COMPLETED:20241120T101500Z\r
+PRIORITY:0\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:plants\r
+SUMMARY:Water the plants\r
+DUE:20241105T080000Z\r
+RRULE:FREQ=WEEKLY\r
+PERCENT-COMPLETE:250\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:plants\r
+RECURRENCE-ID:20241112T080000Z\r
+SUMMARY:Water the plants\r
+DUE:20241113T080000Z\r
+STATUS:CANCELLED\r
+END:VTODO\r
+BEGIN:VTODO\r
+SUMMARY:Without UID\r
+END:VTODO\r
+BEGIN:VEVENT\r
+UID:standup\r
+DTSTART:20241105T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+#[test]
+fn read_tasks() {
+    let tasks = task::parse_tasks(CALENDAR);
+    assert_eq!(tasks.len(), 4);
+
+    let groceries = tasks[0].to_task_result("\"etag\"");
+    assert_eq!(groceries.external_id, "groceries");
+    assert_eq!(groceries.etag.as_deref(), Some("\"etag\""));
+    assert_eq!(groceries.description.as_deref(), Some("Milk, eggs"));
+    assert_eq!(groceries.starts_at, Some(datetime(4, 8, 0)));
+    assert_eq!(groceries.due_at, Some(datetime(4, 17, 0)));
+    assert!(!groceries.all_day);
+    assert_eq!(groceries.percent_complete, Some(40));
+    assert_eq!(groceries.priority, Some(1));
+    assert_eq!(groceries.status, TaskStatus::InProcess);
+
+    // A completion time without a status completes the task, priority 0 is undefined
+    let taxes = tasks[1].to_task_result("");
+    assert!(taxes.all_day);
+    assert_eq!(taxes.due_at, Some(datetime(30, 0, 0)));
+    assert_eq!(taxes.completed_at, Some(datetime(20, 10, 15)));
+    assert_eq!(taxes.status, TaskStatus::Completed);
+    assert_eq!(taxes.priority, None);
+
+    let plants = tasks[2].to_task_result("");
+    assert_eq!(plants.recurrence.as_deref(), Some("RRULE:FREQ=WEEKLY"));
+    assert_eq!(plants.percent_complete, None);
+    assert_eq!(plants.status, TaskStatus::NeedsAction);
+
+    let skipped = tasks[3].to_task_result("");
+    assert_eq!(skipped.external_id, "plants_20241112T080000Z");
+    assert_eq!(skipped.recurring_task_id.as_deref(), Some("plants"));
+    assert_eq!(skipped.original_starts_at, Some(datetime(12, 8, 0)));
+    assert_eq!(skipped.status, TaskStatus::Cancelled);
+}
+
+#[test]
+fn write_and_read_task() {
+    let task = TaskResult {
+        external_id: "report".to_string(),
+        etag: None,
+        summary: Some("Report; draft".to_string()),
+        description: None,
+        starts_at: None,
+        due_at: Some(datetime(22, 0, 0)),
+        all_day: true,
+        completed_at: Some(datetime(21, 16, 30)),
+        percent_complete: Some(100),
+        priority: Some(5),
+        status: TaskStatus::Completed,
+        recurrence: None,
+        recurring_task_id: None,
+        original_starts_at: None,
+    };
+
+    let output = ics::write_tasks(std::slice::from_ref(&task));
+    assert!(output.contains("\r\nBEGIN:VTODO\r\nUID:report\r\n"));
+    assert!(output.contains("\r\nDUE;VALUE=DATE:20241122\r\n"));
+    assert!(output.contains("\r\nCOMPLETED:20241121T163000Z\r\n"));
+    assert!(output.contains("\r\nSUMMARY:Report\\; draft\r\n"));
+    assert!(output.contains("\r\nSTATUS:COMPLETED\r\n"));
+    assert!(!output.contains("DTSTART"));
+
+    let tasks = task::parse_tasks(&output);
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].to_task_result("\"etag\""), TaskResult {
+        etag: Some("\"etag\"".to_string()),
+        summary: Some("Report; draft".to_string()),
+        ..task
+    });
+}
@@ -0,0 +1,162 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use schedsync_api::{connectors::caldav::caldav, ics, recurrence::Recurrence, models::event::{Attendee, AttendeeStatus, Attendees, EventResult, EventStatus, Participant, ParticipantKind, ParticipantRole}, test_util::{self, datetime}};
+
+fn event() -> EventResult {
+    EventResult {
+        summary: Some("Planning, review; retro".to_string()),
+        description: Some("First line\nSecond line with a very long text that needs to be folded over multiple lines".to_string()),
+        status: EventStatus::Tentative,
+        organizer: Some(Participant {
+            email: "owner@example.com".to_string(),
+            name: Some("Owner, Jr.".to_string()),
+            sent_by: None,
+        }),
+        attendees: Attendees(vec![Attendee {
+            email: "guest@example.com".to_string(),
+            name: None,
+            status: AttendeeStatus::Accepted,
+            optional: true,
+            role: ParticipantRole::Optional,
+            rsvp: false,
+            kind: ParticipantKind::Individual,
+            delegated_to: vec![],
+            delegated_from: vec![],
+            sent_by: None,
+        }, Attendee {
+            email: "boardroom@example.com".to_string(),
+            name: None,
+            status: AttendeeStatus::NeedsAction,
+            optional: false,
+            role: ParticipantRole::NonParticipant,
+            rsvp: true,
+            kind: ParticipantKind::Room,
+            delegated_to: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+            delegated_from: vec![],
+            sent_by: Some("assistant@example.com".to_string()),
+        }]),
+        recurrence: Some("RRULE:FREQ=WEEKLY;COUNT=4\nEXDATE:20241127T090000Z".to_string()),
+        ..test_util::event_result("event-1", datetime(20, 9), datetime(20, 10) + Duration::minutes(30))
+    }
+}
+
+#[test]
+fn write_event() {
+    let output = ics::write_calendar(None, &[event()]);
+
+    assert!(output.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(output.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+    assert!(output.contains("\r\nUID:event-1\r\n"));
+    assert!(output.contains("\r\nDTSTART:20241120T090000Z\r\n"));
+    assert!(output.contains("\r\nDTEND:20241120T103000Z\r\n"));
+    assert!(output.contains("\r\nSUMMARY:Planning\\, review\\; retro\r\n"));
+    assert!(output.contains("\r\nSTATUS:TENTATIVE\r\n"));
+    assert!(output.contains("\r\nORGANIZER;CN=\"Owner, Jr.\":mailto:owner@example.com\r\n"));
+    assert!(output.contains("\r\nATTENDEE;PARTSTAT=ACCEPTED;ROLE=OPT-PARTICIPANT:mailto:guest@example.com\r\n"));
+    let unfolded = output.replace("\r\n ", "");
+    assert!(unfolded.contains("\r\nATTENDEE;PARTSTAT=NEEDS-ACTION;ROLE=NON-PARTICIPANT;RSVP=TRUE;CUTYPE=ROOM;DELEGATED-TO=\"mailto:a@example.com\",\"mailto:b@example.com\";SENT-BY=\"mailto:assistant@example.com\":mailto:boardroom@example.com\r\n"));
+    assert!(output.contains("\r\nRRULE:FREQ=WEEKLY;COUNT=4\r\nEXDATE:20241127T090000Z\r\n"));
+
+    // Long lines are folded, unfolding them restores the value
+    assert!(output.split("\r\n").all(|line| line.len() <= 75));
+    assert!(output.replace("\r\n ", "").contains("DESCRIPTION:First line\\nSecond line with a very long text that needs to be folded over multiple lines\r\n"));
+}
+
+#[test]
+fn write_recurring_instance_and_all_day_event() {
+    let mut instance = event();
+    instance.external_id = "event-1_20241127T090000Z".to_string();
+    instance.recurring_event_id = Some("event-1".to_string());
+    instance.original_starts_at = Some(datetime(27, 9));
+    instance.recurrence = None;
+
+    let mut all_day = event();
+    all_day.external_id = "event-2".to_string();
+    all_day.all_day = true;
+    all_day.ends_at = datetime(21, 0);
+
+    let output = ics::write_calendar(Some("Team"), &[instance, all_day]);
+
+    assert!(output.contains("\r\nX-WR-CALNAME:Team\r\n"));
+    assert!(output.contains("\r\nUID:event-1\r\nDTSTAMP:"));
+    assert!(output.contains("\r\nRECURRENCE-ID:20241127T090000Z\r\n"));
+    assert!(output.contains("\r\nDTSTART;VALUE=DATE:20241120\r\nDTEND;VALUE=DATE:20241121\r\n"));
+}
+
+fn utc(month: u32, day: u32, hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+}
+
+#[test]
+fn write_series_across_daylight_saving_time() {
+    // Weekly at 11:00 in Amsterdam, which is 09:00 UTC before and 10:00 UTC after the change
+    let series = EventResult {
+        time_zone: Some("Europe/Amsterdam".to_string()),
+        recurrence: Some("RRULE:FREQ=WEEKLY;COUNT=3\nEXDATE;TZID=W. Europe Standard Time:20241104T110000".to_string()),
+        ..test_util::event_result("standup", utc(10, 21, 9), utc(10, 21, 9) + Duration::minutes(15))
+    };
+    let mut instance = test_util::event_result("standup_20241028T100000Z", utc(10, 28, 12), utc(10, 28, 13));
+    instance.recurring_event_id = Some("standup".to_string());
+    instance.original_starts_at = Some(utc(10, 28, 10));
+    instance.time_zone = Some("Europe/Amsterdam".to_string());
+
+    let output = ics::write_calendar(None, &[series.clone(), instance]);
+    let unfolded = output.replace("\r\n ", "");
+
+    assert!(unfolded.contains("\r\nDTSTART;TZID=Europe/Amsterdam:20241021T110000\r\nDTEND;TZID=Europe/Amsterdam:20241021T111500\r\n"));
+    assert!(unfolded.contains("\r\nRECURRENCE-ID;TZID=Europe/Amsterdam:20241028T110000\r\nDTSTART;TZID=Europe/Amsterdam:20241028T130000\r\n"));
+
+    // Every zone is described once before the events, also those of EXDATE and RDATE lines
+    assert!(unfolded.find("BEGIN:VTIMEZONE").unwrap() < unfolded.find("BEGIN:VEVENT").unwrap());
+    assert_eq!(unfolded.matches("\r\nTZID:Europe/Amsterdam\r\n").count(), 1);
+    assert!(unfolded.contains("\r\nTZID:W. Europe Standard Time\r\n"));
+
+    let mut mozilla = series.clone();
+    mozilla.recurrence = Some("RRULE:FREQ=WEEKLY\nRDATE;TZID=/mozilla.org/20050126_1/Europe/Berlin:20241110T120000".to_string());
+    assert!(ics::write_calendar(None, &[mozilla]).contains("\r\nTZID:/mozilla.org/20050126_1/Europe/Berlin\r\n"));
+    assert!(unfolded.contains("BEGIN:DAYLIGHT\r\nDTSTART:20230326T020000\r\nTZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\nTZNAME:CEST\r\nRRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r\nEND:DAYLIGHT\r\n"));
+    assert!(unfolded.contains("BEGIN:STANDARD\r\nDTSTART:20231029T030000\r\nTZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\nTZNAME:CET\r\nRRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r\nEND:STANDARD\r\n"));
+
+    // Reading the object back expands the series on the same local time
+    let events = caldav::parse_events(&output);
+    let parsed = events.iter().find(|event| event.recurrence_id.is_none()).unwrap().to_event_result("").unwrap();
+    assert_eq!(parsed.starts_at, utc(10, 21, 9));
+    assert_eq!(parsed.time_zone.as_deref(), Some("Europe/Amsterdam"));
+    let occurrences = Recurrence::parse(parsed.recurrence.as_deref().unwrap()).unwrap().occurrences(
+        parsed.starts_at,
+        Duration::minutes(15),
+        parsed.time_zone.as_deref(),
+        false,
+        utc(10, 1, 0),
+        utc(12, 1, 0),
+    );
+    assert_eq!(occurrences, vec![utc(10, 21, 9), utc(10, 28, 10)]);
+}
+
+#[test]
+fn write_series_on_local_weekday() {
+    // Monday 19:00 in Toronto is Tuesday in UTC, the rule keeps the local Monday
+    let event = EventResult {
+        time_zone: Some("America/Toronto".to_string()),
+        recurrence: Some("RRULE:FREQ=WEEKLY;BYDAY=MO".to_string()),
+        ..test_util::event_result("evening", utc(11, 5, 0), utc(11, 5, 1))
+    };
+    let output = ics::write_calendar(None, &[event]);
+
+    assert!(output.contains("\r\nDTSTART;TZID=America/Toronto:20241104T190000\r\n"));
+    assert!(output.contains("\r\nRRULE:FREQ=WEEKLY;BYDAY=MO\r\n"));
+    assert!(output.contains("BEGIN:DAYLIGHT\r\nDTSTART:20230312T020000\r\nTZOFFSETFROM:-0500\r\nTZOFFSETTO:-0400\r\nTZNAME:EDT\r\nRRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU\r\n"));
+    assert!(output.contains("BEGIN:STANDARD\r\nDTSTART:20231105T020000\r\nTZOFFSETFROM:-0400\r\nTZOFFSETTO:-0500\r\nTZNAME:EST\r\nRRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=1SU\r\n"));
+}
+
+#[test]
+fn write_time_zone_without_changes() {
+    let event = EventResult {
+        time_zone: Some("Asia/Tokyo".to_string()),
+        ..test_util::event_result("lunch", utc(11, 4, 3), utc(11, 4, 4))
+    };
+    let output = ics::write_calendar(None, &[event]);
+
+    assert!(output.contains("\r\nDTSTART;TZID=Asia/Tokyo:20241104T120000\r\n"));
+    assert!(output.contains("BEGIN:STANDARD\r\nDTSTART:19700101T000000\r\nTZOFFSETFROM:+0900\r\nTZOFFSETTO:+0900\r\nTZNAME:JST\r\nEND:STANDARD\r\n"));
+    assert!(!output.contains("BEGIN:DAYLIGHT"));
+}
@@ -0,0 +1,236 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{body::Body, extract::{Path, State}, http::Request, http, response::IntoResponse, Router};
+use chrono::Duration;
+use dotenv::dotenv;
+use http_body_util::BodyExt;
+use reqwest::{Method, StatusCode};
+use schedsync_api::{build_routes, connectors::{oauth2::Oauth2Service, ServiceType}, import::{self, ImportError, ImportEvent}, models::{app::App, caldav_integration::CaldavIntegration, calendar::Calendar, event_import::EventImport, oauth_integration::OauthIntegration}, test_util::{self, create_calendar, datetime}, AppState};
+use tower::util::ServiceExt;
+
+const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Test//Test//EN\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\r
+DTSTART;TZID=Europe/Amsterdam:20241104T090000\r
+DTEND;TZID=Europe/Amsterdam:20241104T091500\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\r
+RECURRENCE-ID;TZID=Europe/Amsterdam:20241106T090000\r
+DTSTART;TZID=Europe/Amsterdam:20241106T140000\r
+DTEND;TZID=Europe/Amsterdam:20241106T141500\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\r
+RECURRENCE-ID;TZID=Europe/Amsterdam:20241111T090000\r
+DTSTART;TZID=Europe/Amsterdam:20241111T090000\r
+DTEND;TZID=Europe/Amsterdam:20241111T091500\r
+STATUS:CANCELLED\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:holiday\r
+SUMMARY:Holiday\r
+DTSTART;VALUE=DATE:20241225\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:holiday\r
+SUMMARY:Holiday again\r
+DTSTART;VALUE=DATE:20241225\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:broken\r
+SUMMARY:Broken\r
+DTSTART:tomorrow\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Anonymous\r
+DTSTART:20241105T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+async fn request_import(state: &Arc<AppState>, calendar: &Calendar, body: &str, authorization: String) -> (StatusCode, serde_json::Value) {
+    let router = build_routes(state.clone());
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/calendar/{}/import", calendar.id))
+                .method(Method::POST)
+                .header(http::header::CONTENT_TYPE, "text/calendar")
+                .header(http::header::AUTHORIZATION, authorization)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        ).await.unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[test]
+fn read_calendar() {
+    let events = import::read_calendar(CALENDAR).unwrap();
+    assert_eq!(events.len(), 7);
+
+    // Occurrences are excluded from their series, the moved one becomes an event of its own
+    let ImportEvent::Event(uid, series) = &events[0] else {
+        panic!("Expected an event, got {:?}", events[0]);
+    };
+    assert_eq!(uid, "standup");
+    assert_eq!(series.external_id, "");
+    assert_eq!(series.etag, None);
+    assert_eq!(series.time_zone.as_deref(), Some("Europe/Amsterdam"));
+    assert_eq!(series.recurrence.as_deref(), Some("RRULE:FREQ=WEEKLY;BYDAY=MO,WE\nEXDATE:20241106T080000Z\nEXDATE:20241111T080000Z"));
+
+    let ImportEvent::Event(uid, moved) = &events[1] else {
+        panic!("Expected an event, got {:?}", events[1]);
+    };
+    assert_eq!(uid, "standup_20241106T080000Z");
+    assert_eq!(moved.starts_at, datetime(6, 13));
+    assert_eq!(moved.recurring_event_id, None);
+    assert_eq!(moved.original_starts_at, None);
+
+    assert!(matches!(&events[2], ImportEvent::Excluded(uid) if uid == "standup_20241111T080000Z"));
+
+    // Without DTEND an all-day event lasts a day
+    let ImportEvent::Event(_, holiday) = &events[3] else {
+        panic!("Expected an event, got {:?}", events[3]);
+    };
+    assert!(holiday.all_day);
+    assert_eq!(holiday.ends_at - holiday.starts_at, Duration::days(1));
+
+    assert!(matches!(&events[4], ImportEvent::Duplicate(uid) if uid == "holiday"));
+    assert!(matches!(&events[5], ImportEvent::Invalid(Some(uid), _) if uid == "broken"));
+    assert!(matches!(&events[6], ImportEvent::Invalid(None, _)));
+}
+
+#[test]
+fn read_invalid_calendar() {
+    assert!(matches!(import::read_calendar("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n"), Err(ImportError::InvalidCalendar(_))));
+}
+
+#[tokio::test]
+async fn import_previously_imported_events() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+
+    // A token which does not expire soon, so nothing is sent to the service
+    OauthIntegration::new(
+        &calendar.get_integration(&mut state.get_connection()).unwrap(),
+        &mut state.get_connection(),
+        Oauth2Service::Google,
+        "access".to_string(),
+        "refresh".to_string(),
+        chrono::Utc::now().naive_utc() + Duration::hours(1),
+    );
+    for (uid, external_id) in [("standup", "abc"), ("standup_20241106T080000Z", "def"), ("holiday", "ghi")] {
+        EventImport::new(&calendar, uid, external_id, &mut state.get_connection()).unwrap();
+    }
+
+    let (status, body) = request_import(&state, &calendar, CALENDAR, test_util::generate_basic_header(&app, &app_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["imported"], 0);
+    assert_eq!(body["duplicates"], 4);
+    assert_eq!(body["failed"], 2);
+
+    let statuses = body["events"].as_array().unwrap().iter()
+        .map(|event| event["status"].as_str().unwrap())
+        .collect::<Vec<&str>>();
+    assert_eq!(statuses, vec!["duplicate", "duplicate", "excluded", "duplicate", "duplicate", "failed", "failed"]);
+    assert_eq!(body["events"][0]["external_id"], "abc");
+    assert_eq!(body["events"][5]["uid"], "broken");
+    assert!(body["events"][6]["uid"].is_null());
+
+    let (status, _) = request_import(&state, &calendar, "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n", test_util::generate_basic_header(&app, &app_key)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+type CaldavServer = Arc<Mutex<Vec<(String, String)>>>;
+
+/**
+ * Store every event written to the calendar, the way a CalDAV server creates resources.
+ */
+async fn put_event(State(server): State<CaldavServer>, Path(file): Path<String>, body: String) -> impl IntoResponse {
+    server.lock().unwrap().push((file, body));
+    (StatusCode::CREATED, [(http::header::ETAG, "\"1\"")])
+}
+
+#[tokio::test]
+async fn import_into_caldav_calendar() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Apple, "/calendars/home/");
+
+    let (status, _) = request_import(&state, &calendar, CALENDAR, test_util::generate_basic_header(&app, &app_key)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let server = CaldavServer::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let router = Router::new()
+        .route("/calendars/home/:file", axum::routing::put(put_event))
+        .with_state(server.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    let integration = calendar.get_integration(&mut state.get_connection()).unwrap();
+    CaldavIntegration::new(&integration, url, "user".to_string(), Some("secret".to_string()), &mut state.get_connection()).unwrap();
+
+    let (status, body) = request_import(&state, &calendar, CALENDAR, test_util::generate_basic_header(&app, &app_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["imported"], 3);
+    assert_eq!(body["failed"], 2);
+
+    // Every event is written to a resource named after its new UID
+    let written = server.lock().unwrap().clone();
+    assert_eq!(written.len(), 3);
+    let external_id = body["events"][0]["external_id"].as_str().unwrap();
+    assert_eq!(written[0].0, format!("{}.ics", external_id));
+    assert!(written[0].1.contains(&format!("UID:{}\r\n", external_id)));
+    assert!(written[0].1.contains("SUMMARY:Standup\r\n"));
+    assert!(written[0].1.contains("EXDATE:20241106T080000Z\r\n"));
+
+    let other = App::new(&mut state.get_connection());
+    let other_key = other.create_key(&mut state.get_connection());
+    let (status, _) = request_import(&state, &calendar, CALENDAR, test_util::generate_basic_header(&other, &other_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn import_untranslatable_recurrence_into_outlook() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Outlook(Oauth2Service::Outlook), "primary");
+    OauthIntegration::new(
+        &calendar.get_integration(&mut state.get_connection()).unwrap(),
+        &mut state.get_connection(),
+        Oauth2Service::Outlook,
+        "access".to_string(),
+        "refresh".to_string(),
+        chrono::Utc::now().naive_utc() + Duration::hours(1),
+    );
+
+    // Graph cannot repeat events every hour, the event is rejected before anything is sent
+    let data = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:reminder\r\nDTSTART:20241104T090000Z\r\nRRULE:FREQ=HOURLY\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let (status, body) = request_import(&state, &calendar, data, test_util::generate_basic_header(&app, &app_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["failed"], 1);
+    assert_eq!(body["events"][0]["status"], "failed");
+    assert_eq!(body["events"][0]["error"], "Outlook cannot repeat events by this rule");
+}
@@ -0,0 +1,498 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use axum::{body::Body, extract::{Path, State}, http::Request, http, response::IntoResponse, routing, Json, Router};
+use chrono::Duration;
+use dotenv::dotenv;
+use http_body_util::BodyExt;
+use reqwest::{Method, StatusCode};
+use schedsync_api::{build_routes, connectors::{oauth2::Oauth2Service, ServiceType}, mirror, models::{app::App, caldav_integration::CaldavIntegration, calendar::Calendar, event::{Event, EventChanges, EventResult, EventStatus, EventTransparency}, event_mirror::EventMirror, group::Group, oauth_integration::OauthIntegration}, test_util::{self, create_calendar, datetime, event_result, sync_events}, AppState};
+use tower::util::ServiceExt;
+
+/**
+ * The events a mock Google server holds, keyed by calendar and event id.
+ */
+#[derive(Default)]
+struct GoogleEvents {
+    events: HashMap<(String, String), serde_json::Value>,
+    inserted: usize,
+}
+
+type GoogleServer = Arc<Mutex<GoogleEvents>>;
+
+async fn insert_google_event(
+    State(server): State<GoogleServer>,
+    Path(calendar): Path<String>,
+    Json(mut event): Json<serde_json::Value>,
+) -> axum::response::Response {
+    let mut server = server.lock().unwrap();
+    server.inserted += 1;
+    let id = format!("busy-{}", server.inserted);
+    event["id"] = serde_json::json!(id);
+    event["etag"] = serde_json::json!("\"1\"");
+    server.events.insert((calendar, id), event.clone());
+    Json(event).into_response()
+}
+
+async fn update_google_event(
+    State(server): State<GoogleServer>,
+    Path((calendar, id)): Path<(String, String)>,
+    Json(mut event): Json<serde_json::Value>,
+) -> axum::response::Response {
+    let mut server = server.lock().unwrap();
+    let Some(stored) = server.events.get_mut(&(calendar, id.clone())) else {
+        return http::StatusCode::NOT_FOUND.into_response();
+    };
+    let etag = stored["etag"].as_str().unwrap().trim_matches('"').parse::<u32>().unwrap() + 1;
+    event["id"] = serde_json::json!(id);
+    event["etag"] = serde_json::json!(format!("\"{}\"", etag));
+    *stored = event.clone();
+    Json(event).into_response()
+}
+
+async fn delete_google_event(
+    State(server): State<GoogleServer>,
+    Path((calendar, id)): Path<(String, String)>,
+) -> http::StatusCode {
+    match server.lock().unwrap().events.remove(&(calendar, id)) {
+        Some(_) => http::StatusCode::NO_CONTENT,
+        None => http::StatusCode::NOT_FOUND,
+    }
+}
+
+/**
+ * Serve a mock of the Google Calendar events API on a random local port, returning its URL.
+ */
+async fn google_server(server: GoogleServer) -> String {
+    let router = Router::new()
+        .route("/calendars/:calendar/events", routing::post(insert_google_event))
+        .route("/calendars/:calendar/events/:id", routing::patch(update_google_event).delete(delete_google_event))
+        .with_state(server);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    format!("http://{}", address)
+}
+
+/**
+ * Build a state whose Google connector talks to the mock server.
+ */
+async fn mock_state(server: &GoogleServer) -> Arc<AppState> {
+    let mut state = AppState::new();
+    state.config.oauth2.google.api_url = google_server(server.clone()).await;
+    Arc::new(state)
+}
+
+/**
+ * Create a Google calendar selected for mirroring, with a token which does not expire soon.
+ */
+fn create_google_target(state: &AppState, group: &Group, external_id: &str) -> Calendar {
+    let calendar = create_calendar(state, group, ServiceType::Google(Oauth2Service::Google), external_id);
+    OauthIntegration::new(
+        &calendar.get_integration(&mut state.get_connection()).unwrap(),
+        &mut state.get_connection(),
+        Oauth2Service::Google,
+        "access".to_string(),
+        "refresh".to_string(),
+        chrono::Utc::now().naive_utc() + Duration::hours(1),
+    );
+    calendar.update_mirror(true, &mut state.get_connection()).unwrap()
+}
+
+fn mirrored_events(server: &GoogleServer, calendar: &str) -> Vec<serde_json::Value> {
+    let server = server.lock().unwrap();
+    let mut events = server.events.iter()
+        .filter(|((event_calendar, _), _)| event_calendar == calendar)
+        .map(|(_, event)| event.clone())
+        .collect::<Vec<serde_json::Value>>();
+    events.sort_by_key(|event| event["start"]["dateTime"].as_str().unwrap_or_default().to_string());
+    events
+}
+
+async fn request_update(state: &Arc<AppState>, uri: String, body: &str, authorization: String) -> (StatusCode, serde_json::Value) {
+    let router = build_routes(state.clone());
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(Method::PATCH)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .header(http::header::AUTHORIZATION, authorization)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        ).await.unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn update_mirroring_settings() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+
+    let other = App::new(&mut state.get_connection());
+    let other_key = other.create_key(&mut state.get_connection());
+
+    // Groups and calendars of other apps are not found
+    let (status, _) = request_update(&state, format!("/api/group/{}", group.id), r#"{"mirroring":true}"#, test_util::generate_basic_header(&other, &other_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = request_update(&state, format!("/api/calendar/{}", calendar.id), r#"{"mirror":true}"#, test_util::generate_basic_header(&other, &other_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = request_update(&state, format!("/api/group/{}", group.id), r#"{"mirroring":true}"#, test_util::generate_basic_header(&app, &app_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["mirroring"], true);
+
+    let (status, body) = request_update(&state, format!("/api/calendar/{}", calendar.id), r#"{"mirror":true}"#, test_util::generate_basic_header(&app, &app_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["mirror"], true);
+
+    assert!(Group::find_by_id(group.id, &mut state.get_connection()).unwrap().mirroring);
+    assert!(Calendar::find_by_id(calendar.id, &mut state.get_connection()).unwrap().mirror);
+}
+
+#[tokio::test]
+async fn find_mirrored_calendars() {
+    dotenv().ok();
+    let state = AppState::new();
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let google = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    let outlook = create_calendar(&state, &group, ServiceType::Outlook(Oauth2Service::Outlook), "calendar");
+    create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "holidays");
+
+    let other_app = App::new(&mut state.get_connection());
+    let other_group = other_app.create_group(&mut state.get_connection());
+    let other = create_calendar(&state, &other_group, ServiceType::Google(Oauth2Service::Google), "primary");
+
+    google.update_mirror(true, &mut state.get_connection()).unwrap();
+    outlook.update_mirror(true, &mut state.get_connection()).unwrap();
+    other.update_mirror(true, &mut state.get_connection()).unwrap();
+
+    let calendars = Calendar::find_mirrored_by_group(&group, &mut state.get_connection());
+    let ids = calendars.iter().map(|calendar| calendar.id).collect::<Vec<i32>>();
+    assert_eq!(ids, vec![google.id, outlook.id]);
+}
+
+#[tokio::test]
+async fn record_event_mirrors() {
+    dotenv().ok();
+    let state = AppState::new();
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let source = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    let target = create_calendar(&state, &group, ServiceType::Outlook(Oauth2Service::Outlook), "calendar");
+
+    let mirror = EventMirror::new(&source, "lunch", &target, &event_result("busy-1", datetime(4, 10), datetime(4, 11)), &mut state.get_connection()).unwrap();
+
+    // Each event is mirrored into a calendar only once
+    assert!(EventMirror::new(&source, "lunch", &target, &event_result("busy-2", datetime(4, 10), datetime(4, 11)), &mut state.get_connection()).is_err());
+
+    let mut moved = event_result("busy-1", datetime(4, 10), datetime(4, 11));
+    assert!(mirror.matches(&moved));
+    moved.starts_at = datetime(4, 12);
+    moved.ends_at = datetime(4, 13);
+    assert!(!mirror.matches(&moved));
+
+    let mirror = mirror.update(&moved, &mut state.get_connection()).unwrap();
+    assert!(mirror.matches(&moved));
+
+    let mirrors = EventMirror::find_by_calendar(&target, &mut state.get_connection());
+    assert_eq!(mirrors.len(), 1);
+    assert_eq!(mirrors[0].external_id, "busy-1");
+    assert_eq!(EventMirror::find_by_source_calendar(&source, &mut state.get_connection()).len(), 1);
+    assert!(EventMirror::find_by_source_calendar(&target, &mut state.get_connection()).is_empty());
+}
+
+#[tokio::test]
+async fn mirror_into_calendar_without_credentials() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let group = Group { mirroring: true, ..group }.save(&mut state.get_connection()).unwrap();
+    let source = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    let source = source.update_mirror(true, &mut state.get_connection()).unwrap();
+    let target = create_calendar(&state, &group, ServiceType::Apple, "home");
+    target.update_mirror(true, &mut state.get_connection()).unwrap();
+
+    sync_events(&state, &source, vec![event_result("lunch", datetime(4, 10), datetime(4, 11))]);
+
+    // Calendars without credentials are skipped without failing the mirroring
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 0));
+    assert!(EventMirror::find_by_calendar(&target, &mut state.get_connection()).is_empty());
+}
+
+type CaldavServer = Arc<Mutex<HashMap<String, String>>>;
+
+/**
+ * Store the events written to the calendar by file name, and remove them again on delete.
+ */
+async fn put_caldav_event(State(server): State<CaldavServer>, Path(file): Path<String>, body: String) -> impl IntoResponse {
+    let mut server = server.lock().unwrap();
+    let etag = format!("\"{}\"", server.len() + 1);
+    server.insert(file, body);
+    (http::StatusCode::CREATED, [(http::header::ETAG, etag)])
+}
+
+async fn delete_caldav_event(State(server): State<CaldavServer>, Path(file): Path<String>) -> http::StatusCode {
+    match server.lock().unwrap().remove(&file) {
+        Some(_) => http::StatusCode::NO_CONTENT,
+        None => http::StatusCode::NOT_FOUND,
+    }
+}
+
+#[tokio::test]
+async fn mirror_into_caldav_calendar() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let group = Group { mirroring: true, ..group }.save(&mut state.get_connection()).unwrap();
+    let source = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    let source = source.update_mirror(true, &mut state.get_connection()).unwrap();
+    let target = create_calendar(&state, &group, ServiceType::Apple, "/calendars/home/");
+    let target = target.update_mirror(true, &mut state.get_connection()).unwrap();
+
+    let server = CaldavServer::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let router = Router::new()
+        .route("/calendars/home/:file", routing::put(put_caldav_event).delete(delete_caldav_event))
+        .with_state(server.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    let integration = target.get_integration(&mut state.get_connection()).unwrap();
+    CaldavIntegration::new(&integration, url, "user".to_string(), Some("secret".to_string()), &mut state.get_connection()).unwrap();
+
+    sync_events(&state, &source, vec![event_result("lunch", datetime(4, 10), datetime(4, 11))]);
+
+    // The placeholder is written to a resource named after its new UID
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (1, 0, 0));
+    let mirror = EventMirror::find_by_calendar(&target, &mut state.get_connection()).remove(0);
+    assert_eq!(mirror.etag.as_deref(), Some("\"1\""));
+    let data = server.lock().unwrap().get(&format!("{}.ics", mirror.external_id)).unwrap().clone();
+    assert!(data.contains(&format!("UID:{}\r\n", mirror.external_id)));
+    assert!(data.contains("SUMMARY:Busy\r\n"));
+    assert!(data.contains("DTSTART:20241104T100000Z\r\n"));
+
+    // Moving the event overwrites the placeholder
+    sync_events(&state, &source, vec![event_result("lunch", datetime(4, 12), datetime(4, 13))]);
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 1, 0));
+    let data = server.lock().unwrap().get(&format!("{}.ics", mirror.external_id)).unwrap().clone();
+    assert!(data.contains("DTSTART:20241104T120000Z\r\n"));
+
+    // Turning mirroring off removes the placeholder
+    let source = source.update_mirror(false, &mut state.get_connection()).unwrap();
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 1));
+    assert!(server.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn mirror_events_into_calendar() {
+    dotenv().ok();
+    let server = GoogleServer::default();
+    let state = mock_state(&server).await;
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let group = Group { mirroring: true, ..group }.save(&mut state.get_connection()).unwrap();
+    let source = create_calendar(&state, &group, ServiceType::Outlook(Oauth2Service::Outlook), "calendar");
+    let source = source.update_mirror(true, &mut state.get_connection()).unwrap();
+    create_google_target(&state, &group, "primary");
+
+    // Outlook stores the occurrences of a series without the series itself
+    let occurrence = |external_id: &str, day: u32| EventResult {
+        recurring_event_id: Some("standup".to_string()),
+        original_starts_at: Some(datetime(day, 9)),
+        ..event_result(external_id, datetime(day, 9), datetime(day, 10))
+    };
+    sync_events(&state, &source, vec![
+        event_result("lunch", datetime(4, 10), datetime(4, 11)),
+        EventResult { transparency: EventTransparency::Transparent, ..event_result("holiday", datetime(4, 10), datetime(4, 11)) },
+        EventResult { status: EventStatus::Cancelled, ..event_result("cancelled", datetime(4, 10), datetime(4, 11)) },
+        occurrence("standup-1", 5),
+        occurrence("standup-2", 6),
+    ]);
+
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (3, 0, 0));
+
+    // Only the times are taken over
+    let events = mirrored_events(&server, "primary");
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0]["summary"], "Busy");
+    assert_eq!(events[0]["description"], mirror::PLACEHOLDER_DESCRIPTION);
+    assert_eq!(events[0]["visibility"], "private");
+    assert_eq!(events[0]["transparency"], "opaque");
+    assert_eq!(events[0]["start"]["dateTime"], "2024-11-04T10:00:00Z");
+    assert_eq!(events[1]["start"]["dateTime"], "2024-11-05T09:00:00Z");
+    assert_eq!(events[2]["start"]["dateTime"], "2024-11-06T09:00:00Z");
+
+    // Nothing changed, so nothing is sent
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 0));
+
+    // Moved events move their placeholder, deleted events remove it
+    Event::sync(&source, EventChanges {
+        events: vec![event_result("lunch", datetime(4, 12), datetime(4, 13))],
+        deleted: vec!["standup-1".to_string()],
+        full: false,
+        sync_token: None,
+    }, &mut state.get_connection()).unwrap();
+
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 1, 1));
+    let events = mirrored_events(&server, "primary");
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["start"]["dateTime"], "2024-11-04T12:00:00Z");
+    assert_eq!(events[0]["etag"], "\"2\"");
+    assert_eq!(events[1]["start"]["dateTime"], "2024-11-06T09:00:00Z");
+
+    // Turning mirroring off removes every placeholder
+    let source = source.update_mirror(false, &mut state.get_connection()).unwrap();
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 2));
+    assert!(mirrored_events(&server, "primary").is_empty());
+    assert!(EventMirror::find_by_source_calendar(&source, &mut state.get_connection()).is_empty());
+}
+
+#[tokio::test]
+async fn mirror_series_once() {
+    dotenv().ok();
+    let server = GoogleServer::default();
+    let state = mock_state(&server).await;
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let group = Group { mirroring: true, ..group }.save(&mut state.get_connection()).unwrap();
+    let source = create_calendar(&state, &group, ServiceType::Outlook(Oauth2Service::Outlook), "calendar");
+    let source = source.update_mirror(true, &mut state.get_connection()).unwrap();
+    create_google_target(&state, &group, "primary");
+
+    // A series is mirrored once, without any of its exceptions
+    sync_events(&state, &source, vec![
+        EventResult { recurrence: Some("RRULE:FREQ=DAILY;COUNT=5".to_string()), ..event_result("standup", datetime(4, 10), datetime(4, 11)) },
+    ]);
+
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (1, 0, 0));
+    let events = mirrored_events(&server, "primary");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["recurrence"], serde_json::json!(["RRULE:FREQ=DAILY;COUNT=5"]));
+}
+
+#[tokio::test]
+async fn mirror_moved_occurrence() {
+    dotenv().ok();
+    let server = GoogleServer::default();
+    let state = mock_state(&server).await;
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let group = Group { mirroring: true, ..group }.save(&mut state.get_connection()).unwrap();
+    let source = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "calendar");
+    let source = source.update_mirror(true, &mut state.get_connection()).unwrap();
+    create_google_target(&state, &group, "primary");
+
+    // The moved occurrence is taken out of the series and mirrored at its new time
+    sync_events(&state, &source, vec![
+        EventResult { recurrence: Some("RRULE:FREQ=DAILY;COUNT=5".to_string()), ..event_result("standup", datetime(4, 10), datetime(4, 11)) },
+        EventResult {
+            recurring_event_id: Some("standup".to_string()),
+            original_starts_at: Some(datetime(5, 10)),
+            ..event_result("standup_20241105T100000Z", datetime(5, 12), datetime(5, 13))
+        },
+    ]);
+
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (2, 0, 0));
+    let events = mirrored_events(&server, "primary");
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["recurrence"], serde_json::json!(["RRULE:FREQ=DAILY;COUNT=5", "EXDATE:20241105T100000Z"]));
+    assert_eq!(events[1]["start"]["dateTime"], "2024-11-05T12:00:00Z");
+    assert_eq!(events[1]["recurrence"], serde_json::Value::Null);
+
+    // Nothing changed, so nothing is sent
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 0));
+}
+
+#[tokio::test]
+async fn mirror_cancelled_occurrence() {
+    dotenv().ok();
+    let server = GoogleServer::default();
+    let state = mock_state(&server).await;
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let group = Group { mirroring: true, ..group }.save(&mut state.get_connection()).unwrap();
+    let source = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "calendar");
+    let source = source.update_mirror(true, &mut state.get_connection()).unwrap();
+    create_google_target(&state, &group, "primary");
+
+    sync_events(&state, &source, vec![
+        EventResult { recurrence: Some("RRULE:FREQ=DAILY;COUNT=5".to_string()), ..event_result("standup", datetime(4, 10), datetime(4, 11)) },
+    ]);
+    mirror::mirror_calendar(&source, &state).await.unwrap();
+
+    // Cancelling an occurrence only excludes it from the placeholder of the series
+    Event::sync(&source, EventChanges {
+        events: vec![EventResult {
+            status: EventStatus::Cancelled,
+            recurring_event_id: Some("standup".to_string()),
+            original_starts_at: Some(datetime(6, 10)),
+            ..event_result("standup_20241106T100000Z", datetime(6, 10), datetime(6, 10))
+        }],
+        deleted: vec![],
+        full: false,
+        sync_token: None,
+    }, &mut state.get_connection()).unwrap();
+
+    let result = mirror::mirror_calendar(&source, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 1, 0));
+    let events = mirrored_events(&server, "primary");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["recurrence"], serde_json::json!(["RRULE:FREQ=DAILY;COUNT=5", "EXDATE:20241106T100000Z"]));
+}
+
+#[tokio::test]
+async fn mirror_calendars_without_loop() {
+    dotenv().ok();
+    let server = GoogleServer::default();
+    let state = mock_state(&server).await;
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let group = Group { mirroring: true, ..group }.save(&mut state.get_connection()).unwrap();
+    let work = create_google_target(&state, &group, "work");
+    let home = create_google_target(&state, &group, "home");
+
+    sync_events(&state, &work, vec![event_result("lunch", datetime(4, 10), datetime(4, 11))]);
+
+    let result = mirror::mirror_calendar(&work, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (1, 0, 0));
+    let placeholder = EventMirror::find_by_calendar(&home, &mut state.get_connection()).remove(0);
+
+    // The placeholder comes back with the next sync of the other calendar, but is not mirrored
+    // back, even when the service dropped its description
+    sync_events(&state, &home, vec![
+        EventResult { description: None, ..event_result(&placeholder.external_id, datetime(4, 10), datetime(4, 11)) },
+        EventResult { description: Some(mirror::PLACEHOLDER_DESCRIPTION.to_string()), ..event_result("unrecorded", datetime(4, 10), datetime(4, 11)) },
+    ]);
+
+    let result = mirror::mirror_calendar(&home, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 0));
+    assert!(mirrored_events(&server, "work").is_empty());
+
+    let result = mirror::mirror_calendar(&work, &state).await.unwrap();
+    assert_eq!((result.created, result.updated, result.deleted), (0, 0, 0));
+    assert_eq!(mirrored_events(&server, "home").len(), 1);
+}
@@ -0,0 +1,41 @@
+use dotenv::dotenv;
+use schedsync_api::{connectors::{oauth2::Oauth2Service, ServiceType}, models::{app::App, integration::Integration, oauth_integration::OauthIntegration}, AppState};
+
+#[tokio::test]
+async fn find_expiring_oauth_integrations() {
+    dotenv().ok();
+    let state = AppState::new();
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let now = chrono::Utc::now().naive_utc();
+
+    let integration = Integration::new(&group, &mut state.get_connection(), ServiceType::Google(Oauth2Service::Google));
+    let expiring = OauthIntegration::new(&integration, &mut state.get_connection(), Oauth2Service::Google,
+        "access".to_string(), "refresh".to_string(), now + chrono::Duration::minutes(5));
+
+    let integration = Integration::new(&group, &mut state.get_connection(), ServiceType::Google(Oauth2Service::Google));
+    let valid = OauthIntegration::new(&integration, &mut state.get_connection(), Oauth2Service::Google,
+        "access".to_string(), "refresh".to_string(), now + chrono::Duration::hours(1));
+
+    let find_ids = || OauthIntegration::find_expiring(now + chrono::Duration::minutes(10), &mut state.get_connection())
+        .into_iter()
+        .map(|oauth_integration| oauth_integration.id)
+        .collect::<Vec<i32>>();
+
+    let ids = find_ids();
+    assert!(ids.contains(&expiring.id));
+    assert!(!ids.contains(&valid.id));
+
+    // Failed refreshes are skipped until the retry time has passed
+    expiring.record_refresh_failure(now + chrono::Duration::minutes(1), &mut state.get_connection()).unwrap();
+    assert!(!find_ids().contains(&expiring.id));
+
+    let expiring = OauthIntegration::find_by_integration(
+        &Integration::find_by_id(expiring.integration_id, &mut state.get_connection()).unwrap(),
+        &mut state.get_connection(),
+    ).unwrap();
+    assert_eq!(expiring.refresh_failures, 1);
+
+    expiring.record_refresh_failure(now - chrono::Duration::minutes(1), &mut state.get_connection()).unwrap();
+    assert!(find_ids().contains(&expiring.id));
+}
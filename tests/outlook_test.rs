@@ -0,0 +1,382 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, http::{HeaderMap, StatusCode, Uri}, response::IntoResponse, routing, Json, Router};
+use chrono::{DateTime, Duration, Utc};
+use schedsync_api::{config::Oauth2Config, connectors::oauth2::{outlook::{OutlookCalendarResult, OutlookConnector, OutlookDateTimeTimeZone, OutlookEvent, OutlookEventRequest}, Oauth2ConnectorError, Oauth2Service, Oauth2ServiceConnector}, models::{calendar::{Calendar, CalendarResult}, oauth_integration::OauthIntegration, event::{Attendee, AttendeeStatus, Attendees, EventResult, EventStatus, EventTransparency, EventVisibility, ParticipantKind, ParticipantRole}}, test_util::{self, datetime}};
+use serde_json::json;
+
+fn event_result(recurrence: Option<&str>) -> EventResult {
+    EventResult {
+        summary: Some("Standup".to_string()),
+        time_zone: Some("Europe/Amsterdam".to_string()),
+        recurrence: recurrence.map(|recurrence| recurrence.to_string()),
+        ..test_util::event_result("standup", datetime(4, 10), datetime(4, 11))
+    }
+}
+
+fn event(value: serde_json::Value) -> Option<EventResult> {
+    serde_json::from_value::<OutlookEvent>(value).unwrap().into_event_result()
+}
+
+fn calendar(value: serde_json::Value) -> CalendarResult {
+    serde_json::from_value::<OutlookCalendarResult>(value).unwrap().into_calendar_result()
+}
+
+fn request(event: &EventResult) -> serde_json::Value {
+    serde_json::to_value(OutlookEventRequest::from_event(event).unwrap()).unwrap()
+}
+
+#[test]
+fn write_event_request() {
+    let event = EventResult {
+        transparency: EventTransparency::Transparent,
+        attendees: Attendees(vec![Attendee {
+            email: "john@example.com".to_string(),
+            name: None,
+            status: AttendeeStatus::NeedsAction,
+            optional: true,
+            role: ParticipantRole::Optional,
+            rsvp: false,
+            kind: ParticipantKind::Individual,
+            delegated_to: vec![],
+            delegated_from: vec![],
+            sent_by: None,
+        }]),
+        ..event_result(None)
+    };
+
+    let request = request(&event);
+    assert_eq!(request["subject"], "Standup");
+    assert_eq!(request["body"], json!({ "contentType": "text", "content": "" }));
+    // Times are written in the local time of the event
+    assert_eq!(request["start"], json!({ "dateTime": "2024-11-04T11:00:00", "timeZone": "Europe/Amsterdam" }));
+    assert_eq!(request["end"], json!({ "dateTime": "2024-11-04T12:00:00", "timeZone": "Europe/Amsterdam" }));
+    assert_eq!(request["isAllDay"], false);
+    assert_eq!(request["showAs"], "free");
+    assert_eq!(request["attendees"], json!([{ "emailAddress": { "address": "john@example.com", "name": null }, "type": "optional" }]));
+    assert!(request.get("recurrence").is_none());
+
+    // All-day events keep their dates
+    let request = self::request(&EventResult {
+        starts_at: datetime(4, 0),
+        ends_at: datetime(5, 0),
+        all_day: true,
+        time_zone: None,
+        status: EventStatus::Tentative,
+        ..event_result(None)
+    });
+    assert_eq!(request["start"], json!({ "dateTime": "2024-11-04T00:00:00", "timeZone": "UTC" }));
+    assert_eq!(request["isAllDay"], true);
+    assert_eq!(request["showAs"], "tentative");
+}
+
+#[test]
+fn write_recurrence() {
+    let request = request(&event_result(Some("RRULE:FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10")));
+    assert_eq!(request["recurrence"], json!({
+        "pattern": { "type": "weekly", "interval": 1, "daysOfWeek": ["monday", "wednesday"], "firstDayOfWeek": "monday" },
+        "range": { "type": "numbered", "startDate": "2024-11-04", "numberOfOccurrences": 10, "recurrenceTimeZone": "Europe/Amsterdam" },
+    }));
+
+    let request = self::request(&event_result(Some("RRULE:FREQ=DAILY;INTERVAL=2;UNTIL=20241130T230000Z")));
+    assert_eq!(request["recurrence"], json!({
+        "pattern": { "type": "daily", "interval": 2 },
+        "range": { "type": "endDate", "startDate": "2024-11-04", "endDate": "2024-12-01", "recurrenceTimeZone": "Europe/Amsterdam" },
+    }));
+
+    let request = self::request(&event_result(Some("RRULE:FREQ=MONTHLY")));
+    assert_eq!(request["recurrence"]["pattern"], json!({ "type": "absoluteMonthly", "interval": 1, "dayOfMonth": 4 }));
+    assert_eq!(request["recurrence"]["range"]["type"], "noEnd");
+
+    let request = self::request(&event_result(Some("RRULE:FREQ=MONTHLY;BYDAY=-1FR")));
+    assert_eq!(request["recurrence"]["pattern"], json!({ "type": "relativeMonthly", "interval": 1, "daysOfWeek": ["friday"], "index": "last" }));
+
+    let request = self::request(&event_result(Some("RRULE:FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=1")));
+    assert_eq!(
+        request["recurrence"]["pattern"],
+        json!({ "type": "relativeMonthly", "interval": 1, "daysOfWeek": ["monday", "tuesday", "wednesday", "thursday", "friday"], "index": "first" }),
+    );
+
+    let request = self::request(&event_result(Some("RRULE:FREQ=YEARLY")));
+    assert_eq!(request["recurrence"]["pattern"], json!({ "type": "absoluteYearly", "interval": 1, "month": 11, "dayOfMonth": 4 }));
+
+    let request = self::request(&event_result(Some("RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU")));
+    assert_eq!(request["recurrence"]["pattern"], json!({ "type": "relativeYearly", "interval": 1, "month": 3, "daysOfWeek": ["sunday"], "index": "second" }));
+}
+
+#[test]
+fn reject_unsupported_recurrence() {
+    for recurrence in [
+        "RRULE:FREQ=HOURLY",
+        "RRULE:FREQ=WEEKLY\nEXDATE:20241111T100000Z",
+        "RRULE:FREQ=MONTHLY;BYDAY=MO",
+        "RRULE:FREQ=MONTHLY;BYMONTHDAY=-1",
+        "RRULE:FREQ=YEARLY;BYMONTH=1,7",
+        "RRULE:FREQ=MONTHLY;BYDAY=5MO",
+        "RDATE:20241111T100000Z",
+    ] {
+        let result = OutlookEventRequest::from_event(&event_result(Some(recurrence)));
+        assert!(matches!(result, Err(Oauth2ConnectorError::UnsupportedEvent(_))), "{}", recurrence);
+    }
+}
+
+#[test]
+fn parse_date_time() {
+    let parse = |value: serde_json::Value| serde_json::from_value::<OutlookDateTimeTimeZone>(value).unwrap().parse();
+
+    assert_eq!(parse(json!({ "dateTime": "2024-11-04T10:00:00.0000000", "timeZone": "UTC" })), Some(datetime(4, 10)));
+    assert_eq!(parse(json!({ "dateTime": "2024-11-04T10:00:00" })), Some(datetime(4, 10)));
+    assert_eq!(parse(json!({ "dateTime": "2024-11-04T11:00:00", "timeZone": "Europe/Amsterdam" })), Some(datetime(4, 10)));
+    // Outlook writes Windows time zone names
+    assert_eq!(parse(json!({ "dateTime": "2024-11-04T11:00:00", "timeZone": "W. Europe Standard Time" })), Some(datetime(4, 10)));
+    assert_eq!(parse(json!({ "dateTime": "2024-11-04T02:00:00", "timeZone": "Pacific Standard Time" })), Some(datetime(4, 10)));
+    assert_eq!(parse(json!({ "dateTime": "2024-11-04T10:00:00", "timeZone": "Mars Standard Time" })), None);
+    assert_eq!(parse(json!({ "dateTime": "4 November", "timeZone": "UTC" })), None);
+}
+
+#[test]
+fn map_timed_event() {
+    let event = event(json!({
+        "@odata.etag": "W/\"DwAAABYAAAA=\"",
+        "id": "AAMkAGI2",
+        "subject": "Standup",
+        "bodyPreview": "Agenda",
+        "start": { "dateTime": "2024-11-04T10:00:00.0000000", "timeZone": "UTC" },
+        "end": { "dateTime": "2024-11-04T10:15:00.0000000", "timeZone": "UTC" },
+        "originalStartTimeZone": "W. Europe Standard Time",
+        "isAllDay": false,
+        "isCancelled": false,
+        "showAs": "workingElsewhere",
+        "sensitivity": "personal",
+        "location": { "displayName": "Room 1" },
+        "organizer": { "emailAddress": { "name": "Organizer", "address": "organizer@example.com" } },
+        "attendees": [
+            { "type": "required", "status": { "response": "accepted", "time": "2024-11-01T09:00:00Z" }, "emailAddress": { "name": "John", "address": "john@example.com" } },
+            { "type": "optional", "status": { "response": "tentativelyAccepted" }, "emailAddress": { "address": "jane@example.com" } },
+            { "type": "resource", "status": { "response": "none" }, "emailAddress": { "name": "Room 1", "address": "room@example.com" } },
+            { "type": "required", "emailAddress": { "name": "No address" } },
+        ],
+        "seriesMasterId": null,
+        "originalStart": null,
+    })).unwrap();
+
+    assert_eq!(event.external_id, "AAMkAGI2");
+    assert_eq!(event.etag.as_deref(), Some("W/\"DwAAABYAAAA=\""));
+    assert_eq!(event.summary.as_deref(), Some("Standup"));
+    assert_eq!(event.description.as_deref(), Some("Agenda"));
+    assert_eq!(event.location.as_deref(), Some("Room 1"));
+    assert_eq!(event.starts_at, datetime(4, 10));
+    assert_eq!(event.ends_at, datetime(4, 10) + chrono::Duration::minutes(15));
+    assert!(!event.all_day);
+    // Windows time zone names are stored as IANA names
+    assert_eq!(event.time_zone.as_deref(), Some("Europe/Berlin"));
+    assert_eq!(event.status, EventStatus::Confirmed);
+    assert_eq!(event.transparency, EventTransparency::Transparent);
+    assert_eq!(event.visibility, EventVisibility::Private);
+    let organizer = event.organizer.unwrap();
+    assert_eq!((organizer.email.as_str(), organizer.name.as_deref()), ("organizer@example.com", Some("Organizer")));
+
+    // Attendees without an address are skipped
+    let attendees = event.attendees.0;
+    assert_eq!(attendees.len(), 3);
+    assert_eq!(attendees[0].name.as_deref(), Some("John"));
+    assert_eq!(attendees[0].status, AttendeeStatus::Accepted);
+    assert_eq!(attendees[0].role, ParticipantRole::Required);
+    assert_eq!(attendees[1].status, AttendeeStatus::Tentative);
+    assert_eq!(attendees[1].role, ParticipantRole::Optional);
+    assert!(attendees[1].optional);
+    assert_eq!(attendees[2].status, AttendeeStatus::NeedsAction);
+    assert_eq!(attendees[2].kind, ParticipantKind::Resource);
+
+    assert_eq!(event.recurrence, None);
+    assert_eq!(event.recurring_event_id, None);
+    assert_eq!(event.original_starts_at, None);
+}
+
+#[test]
+fn map_all_day_event() {
+    let event = event(json!({
+        "id": "AAMkAGI3",
+        "subject": "Holiday",
+        "bodyPreview": "",
+        "start": { "dateTime": "2024-11-04T00:00:00.0000000", "timeZone": "UTC" },
+        "end": { "dateTime": "2024-11-06T00:00:00.0000000", "timeZone": "UTC" },
+        "isAllDay": true,
+        "showAs": "tentative",
+        "sensitivity": "normal",
+        "location": { "displayName": "" },
+    })).unwrap();
+
+    assert!(event.all_day);
+    assert_eq!((event.starts_at, event.ends_at), (datetime(4, 0), datetime(6, 0)));
+    // Empty texts are not kept
+    assert_eq!(event.description, None);
+    assert_eq!(event.location, None);
+    assert_eq!(event.time_zone, None);
+    assert_eq!(event.status, EventStatus::Tentative);
+    assert_eq!(event.transparency, EventTransparency::Opaque);
+    assert_eq!(event.visibility, EventVisibility::Default);
+}
+
+#[test]
+fn map_cancelled_occurrence() {
+    let event = event(json!({
+        "id": "AAMkAGI4",
+        "subject": "Canceled: Standup",
+        "start": { "dateTime": "2024-11-11T11:00:00.0000000", "timeZone": "UTC" },
+        "end": { "dateTime": "2024-11-11T11:15:00.0000000", "timeZone": "UTC" },
+        "isCancelled": true,
+        "showAs": "free",
+        "seriesMasterId": "AAMkAGI2",
+        "originalStart": "2024-11-11T10:00:00Z",
+        "originalStartTimeZone": "Europe/Amsterdam",
+    })).unwrap();
+
+    assert_eq!(event.status, EventStatus::Cancelled);
+    assert_eq!(event.starts_at, datetime(11, 11));
+    assert_eq!(event.time_zone.as_deref(), Some("Europe/Amsterdam"));
+    // Occurrences link to their series, which carries no recurrence of its own in the view
+    assert_eq!(event.recurring_event_id.as_deref(), Some("AAMkAGI2"));
+    assert_eq!(event.original_starts_at, Some(datetime(11, 10)));
+    assert_eq!(event.recurrence, None);
+
+    // Events without a usable start or end are skipped
+    assert_eq!(self::event(json!({ "id": "AAMkAGI5", "@removed": { "reason": "deleted" } })), None);
+    assert_eq!(self::event(json!({
+        "id": "AAMkAGI6",
+        "start": { "dateTime": "2024-11-11T11:00:00", "timeZone": "Mars Standard Time" },
+        "end": { "dateTime": "2024-11-11T12:00:00", "timeZone": "UTC" },
+    })), None);
+}
+
+#[test]
+fn write_empty_fields() {
+    // Graph clears fields which are sent empty, only the recurrence is left out
+    let request = request(&EventResult {
+        summary: None,
+        ..test_util::event_result("standup", datetime(4, 10), datetime(4, 11))
+    });
+    assert_eq!(request, json!({
+        "subject": null,
+        "body": { "contentType": "text", "content": "" },
+        "location": { "displayName": "" },
+        "start": { "dateTime": "2024-11-04T10:00:00", "timeZone": "UTC" },
+        "end": { "dateTime": "2024-11-04T11:00:00", "timeZone": "UTC" },
+        "isAllDay": false,
+        "showAs": "busy",
+        "sensitivity": "normal",
+        "attendees": [],
+    }));
+}
+
+#[test]
+fn map_calendar_colors() {
+    // Custom colors are kept, with a text color which contrasts with them
+    let calendar = calendar(json!({ "id": "AAMkAGI1", "name": "Calendar", "color": "auto", "hexColor": "#1F3A5C" }));
+    assert_eq!(calendar.external_id, "AAMkAGI1");
+    assert_eq!(calendar.name, "Calendar");
+    assert_eq!((calendar.background_color.as_str(), calendar.foreground_color.as_str()), ("#1f3a5c", "#ffffff"));
+
+    // Named colors are mapped to the hex value Outlook shows
+    let calendar = self::calendar(json!({ "id": "AAMkAGI2", "name": "Birthdays", "color": "lightYellow", "hexColor": "" }));
+    assert_eq!((calendar.background_color.as_str(), calendar.foreground_color.as_str()), ("#f4d07a", "#000000"));
+    let calendar = self::calendar(json!({ "id": "AAMkAGI3", "name": "Holidays", "color": "lightGreen" }));
+    assert_eq!(calendar.background_color, "#87d28e");
+
+    // Calendars without a color get the Outlook blue
+    let calendar = self::calendar(json!({ "id": "AAMkAGI4", "name": "Shared", "color": "auto", "hexColor": null }));
+    assert_eq!((calendar.background_color.as_str(), calendar.foreground_color.as_str()), ("#0078d4", "#ffffff"));
+}
+
+/**
+ * A mock of the Graph calendar view delta of the "primary" calendar. The first request of a
+ * window returns an event, the delta link returns a tombstone and the "expired" link is gone.
+ */
+async fn delta_server(State(requests): State<Arc<Mutex<Vec<String>>>>, headers: HeaderMap, uri: Uri) -> axum::response::Response {
+    requests.lock().unwrap().push(uri.to_string());
+    let base = format!("http://{}", headers["host"].to_str().unwrap());
+    match uri.path() {
+        "/me/calendars/primary/calendarView/delta" => Json(json!({
+            "value": [{
+                "id": "standup",
+                "subject": "Standup",
+                "start": { "dateTime": "2024-11-04T10:00:00.0000000", "timeZone": "UTC" },
+                "end": { "dateTime": "2024-11-04T10:15:00.0000000", "timeZone": "UTC" },
+            }],
+            "@odata.deltaLink": format!("{}/delta?$deltatoken=1", base),
+        })).into_response(),
+        "/delta" => Json(json!({
+            "value": [{ "id": "review", "@removed": { "reason": "deleted" } }],
+            "@odata.deltaLink": format!("{}/delta?$deltatoken=2", base),
+        })).into_response(),
+        _ => StatusCode::GONE.into_response(),
+    }
+}
+
+#[tokio::test]
+async fn refresh_delta_window() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let router = Router::new().fallback(routing::get(delta_server)).with_state(requests.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let connector = OutlookConnector::new(Oauth2Config {
+        authorization_url: String::new(),
+        token_url: String::new(),
+        revoke_url: String::new(),
+        api_url: url.clone(),
+        client_id: String::new(),
+        client_secret: String::new(),
+        redirect_uri: String::new(),
+        scope: String::new(),
+    });
+    let integration = OauthIntegration {
+        id: 1,
+        integration_id: 1,
+        service: Oauth2Service::Outlook,
+        access_token: "access".to_string(),
+        refresh_token: "refresh".to_string(),
+        expires_at: Utc::now().naive_utc() + Duration::hours(1),
+        refresh_failures: 0,
+        next_refresh_at: None,
+    };
+    let calendar = |sync_token: Option<String>| Calendar {
+        id: 1,
+        integration_id: 1,
+        external_id: "primary".to_string(),
+        name: "Calendar".to_string(),
+        background_color: "#0078d4".to_string(),
+        foreground_color: "#ffffff".to_string(),
+        sync_token,
+        mirror: false,
+    };
+    let window_end = |sync_token: &str| DateTime::parse_from_rfc3339(sync_token.rsplit_once('#').unwrap().1).unwrap().with_timezone(&Utc);
+
+    // The first sync opens a window of a year, whose end is kept with the delta link
+    let changes = connector.get_events(&integration, &calendar(None)).await.unwrap();
+    assert!(changes.full);
+    assert_eq!(changes.events[0].external_id, "standup");
+    let sync_token = changes.sync_token.unwrap();
+    assert!(sync_token.starts_with(&format!("{}/delta?$deltatoken=1#", url)));
+    assert!((window_end(&sync_token) - (Utc::now() + Duration::days(365))).num_minutes().abs() < 1);
+
+    // Following syncs use the delta link without the window end
+    let changes = connector.get_events(&integration, &calendar(Some(sync_token.clone()))).await.unwrap();
+    assert!(!changes.full);
+    assert_eq!(changes.deleted, vec!["review".to_string()]);
+    assert_eq!(requests.lock().unwrap().last().unwrap(), "/delta?$deltatoken=1");
+    assert_eq!(window_end(&changes.sync_token.unwrap()), window_end(&sync_token));
+
+    // A window which is about to run out, or a link without a window, starts over with a full sync
+    let exhausted = format!("{}/delta?$deltatoken=1#{}", url, (Utc::now() + Duration::days(300)).to_rfc3339());
+    for sync_token in [exhausted, format!("{}/delta?$deltatoken=1", url), format!("{}/expired#{}", url, window_end(&sync_token).to_rfc3339())] {
+        let changes = connector.get_events(&integration, &calendar(Some(sync_token.clone()))).await.unwrap();
+        assert!(changes.full, "{}", sync_token);
+        assert_eq!(changes.events.len(), 1);
+        assert!(requests.lock().unwrap().last().unwrap().starts_with("/me/calendars/primary/calendarView/delta?startDateTime="));
+        assert!(changes.sync_token.unwrap().starts_with(&format!("{}/delta?$deltatoken=1#", url)));
+    }
+}
@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use axum::{body::Body, http::Request, http};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use dotenv::dotenv;
+use http_body_util::BodyExt;
+use reqwest::{Method, StatusCode};
+use schedsync_api::{availability, build_routes, connectors::{oauth2::Oauth2Service, ServiceType}, models::{app::App, event::{Event, EventResult, EventStatus}}, recurrence::{self, Recurrence, RecurrenceError}, test_util::{self, create_calendar, event_result, sync_events}, AppState};
+use tower::util::ServiceExt;
+
+fn datetime(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+}
+
+fn format(occurrences: &[NaiveDateTime]) -> Vec<String> {
+    occurrences.iter().map(|value| value.format("%Y-%m-%d %H:%M").to_string()).collect()
+}
+
+fn expand(lines: &str, series_start: NaiveDateTime, time_zone: Option<&str>, start: NaiveDateTime, end: NaiveDateTime) -> Vec<String> {
+    let recurrence = Recurrence::parse(lines).unwrap();
+    format(&recurrence.occurrences(series_start, Duration::hours(1), time_zone, false, start, end))
+}
+
+/**
+ * A weekly standup on Monday and Wednesday at 09:00 UTC from Monday November 4th 2024, with its
+ * second occurrence moved to the afternoon and its third one cancelled.
+ */
+fn standup_series() -> Vec<EventResult> {
+    let mut series = event_result("standup", datetime(2024, 11, 4, 9), datetime(2024, 11, 4, 10));
+    series.recurrence = Some("RRULE:FREQ=WEEKLY;BYDAY=MO,WE".to_string());
+
+    let mut moved = event_result("standup_20241106T090000Z", datetime(2024, 11, 6, 14), datetime(2024, 11, 6, 15));
+    moved.recurring_event_id = Some("standup".to_string());
+    moved.original_starts_at = Some(datetime(2024, 11, 6, 9));
+
+    let mut cancelled = event_result("standup_20241111T090000Z", datetime(2024, 11, 11, 9), datetime(2024, 11, 11, 10));
+    cancelled.status = EventStatus::Cancelled;
+    cancelled.recurring_event_id = Some("standup".to_string());
+    cancelled.original_starts_at = Some(datetime(2024, 11, 11, 9));
+
+    vec![series, moved, cancelled]
+}
+
+#[test]
+fn weekly_with_count() {
+    let occurrences = expand(
+        "RRULE:FREQ=WEEKLY;BYDAY=TU,TH;COUNT=5",
+        datetime(2024, 11, 5, 9),
+        None,
+        datetime(2024, 11, 1, 0),
+        datetime(2025, 1, 1, 0),
+    );
+    assert_eq!(occurrences, vec![
+        "2024-11-05 09:00",
+        "2024-11-07 09:00",
+        "2024-11-12 09:00",
+        "2024-11-14 09:00",
+        "2024-11-19 09:00",
+    ]);
+
+    // Occurrences before the range still count towards COUNT
+    let occurrences = expand(
+        "RRULE:FREQ=WEEKLY;BYDAY=TU,TH;COUNT=5",
+        datetime(2024, 11, 5, 9),
+        None,
+        datetime(2024, 11, 13, 0),
+        datetime(2025, 1, 1, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-11-14 09:00", "2024-11-19 09:00"]);
+}
+
+#[test]
+fn monthly_last_friday_and_set_position() {
+    let occurrences = expand(
+        "RRULE:FREQ=MONTHLY;BYDAY=-1FR;COUNT=3",
+        datetime(2024, 11, 29, 15),
+        None,
+        datetime(2024, 11, 1, 0),
+        datetime(2025, 6, 1, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-11-29 15:00", "2024-12-27 15:00", "2025-01-31 15:00"]);
+
+    // The last working day of the month
+    let occurrences = expand(
+        "RRULE:FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+        datetime(2024, 11, 29, 15),
+        None,
+        datetime(2024, 11, 1, 0),
+        datetime(2025, 4, 1, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-11-29 15:00", "2024-12-31 15:00", "2025-01-31 15:00", "2025-02-28 15:00", "2025-03-31 15:00"]);
+}
+
+#[test]
+fn until_and_interval() {
+    let occurrences = expand(
+        "RRULE:FREQ=DAILY;INTERVAL=2;UNTIL=20241110T090000Z",
+        datetime(2024, 11, 4, 9),
+        None,
+        datetime(2024, 11, 1, 0),
+        datetime(2025, 1, 1, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-11-04 09:00", "2024-11-06 09:00", "2024-11-08 09:00", "2024-11-10 09:00"]);
+}
+
+#[test]
+fn added_and_excluded_dates() {
+    let occurrences = expand(
+        "RRULE:FREQ=DAILY;COUNT=4\nEXDATE:20241105T090000Z,20241106T090000Z\nRDATE;TZID=Europe/Amsterdam:20241110T120000",
+        datetime(2024, 11, 4, 9),
+        None,
+        datetime(2024, 11, 1, 0),
+        datetime(2025, 1, 1, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-11-04 09:00", "2024-11-07 09:00", "2024-11-10 11:00"]);
+}
+
+#[test]
+fn keeps_local_time_across_daylight_saving_time() {
+    // Amsterdam leaves daylight saving time on October 27th, 09:00 local moves from 07:00 to
+    // 08:00 UTC
+    let occurrences = expand(
+        "RRULE:FREQ=WEEKLY;COUNT=3",
+        datetime(2024, 10, 21, 7),
+        Some("Europe/Amsterdam"),
+        datetime(2024, 10, 1, 0),
+        datetime(2024, 12, 1, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-10-21 07:00", "2024-10-28 08:00", "2024-11-04 08:00"]);
+
+    // 02:30 does not exist on March 31st 2024 and moves forward by the length of the gap
+    let occurrences = expand(
+        "RRULE:FREQ=DAILY;COUNT=3",
+        datetime(2024, 3, 30, 1) + Duration::minutes(30),
+        Some("Europe/Amsterdam"),
+        datetime(2024, 3, 1, 0),
+        datetime(2024, 4, 30, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-03-30 01:30", "2024-03-31 01:30", "2024-04-01 00:30"]);
+}
+
+#[test]
+fn windows_and_mozilla_time_zones() {
+    // Exchange names the zone of the series the Windows way
+    let occurrences = expand(
+        "RRULE:FREQ=WEEKLY;COUNT=2",
+        datetime(2024, 10, 21, 7),
+        Some("W. Europe Standard Time"),
+        datetime(2024, 10, 1, 0),
+        datetime(2024, 12, 1, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-10-21 07:00", "2024-10-28 08:00"]);
+
+    // Excluded and added dates with Windows and Mozilla TZIDs
+    let occurrences = expand(
+        "RRULE:FREQ=DAILY;COUNT=3\nEXDATE;TZID=W. Europe Standard Time:20241105T100000\nRDATE;TZID=/mozilla.org/20050126_1/Europe/Berlin:20241110T120000",
+        datetime(2024, 11, 4, 9),
+        Some("Europe/Amsterdam"),
+        datetime(2024, 11, 1, 0),
+        datetime(2025, 1, 1, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-11-04 09:00", "2024-11-06 09:00", "2024-11-10 11:00"]);
+}
+
+#[test]
+fn series_started_long_ago() {
+    let occurrences = expand(
+        "RRULE:FREQ=MINUTELY;INTERVAL=15",
+        datetime(2020, 1, 1, 0),
+        Some("Europe/Amsterdam"),
+        datetime(2024, 11, 4, 9),
+        datetime(2024, 11, 4, 10),
+    );
+    assert_eq!(occurrences, vec!["2024-11-04 08:15", "2024-11-04 08:30", "2024-11-04 08:45", "2024-11-04 09:00", "2024-11-04 09:15", "2024-11-04 09:30", "2024-11-04 09:45"]);
+
+    let occurrences = expand(
+        "RRULE:FREQ=HOURLY;BYHOUR=9,17",
+        datetime(1900, 1, 1, 9),
+        None,
+        datetime(2024, 11, 4, 0),
+        datetime(2024, 11, 5, 0),
+    );
+    assert_eq!(occurrences, vec!["2024-11-04 09:00", "2024-11-04 17:00"]);
+}
+
+#[test]
+fn invalid_recurrence() {
+    assert!(matches!(Recurrence::parse("RRULE:INTERVAL=2"), Err(RecurrenceError::InvalidRule(_))));
+    assert!(matches!(Recurrence::parse("RRULE:FREQ=FORTNIGHTLY"), Err(RecurrenceError::InvalidRule(_))));
+    assert!(matches!(Recurrence::parse("EXDATE:yesterday"), Err(RecurrenceError::InvalidDate(_))));
+    assert!(matches!(Recurrence::parse("RRULE"), Err(RecurrenceError::InvalidLine(_))));
+}
+
+#[tokio::test]
+async fn expand_with_overrides() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    sync_events(&state, &calendar, standup_series());
+
+    let (start, end) = (datetime(2024, 11, 4, 0), datetime(2024, 11, 16, 0));
+    let events = Event::find_by_calendar_between(&calendar, start, end, &mut state.get_connection());
+    let occurrences = recurrence::expand_events(&events, start, end);
+
+    let summary = occurrences.iter()
+        .map(|occurrence| (occurrence.event.external_id.as_str(), occurrence.starts_at, occurrence.original_starts_at))
+        .collect::<Vec<_>>();
+    assert_eq!(summary, vec![
+        ("standup", datetime(2024, 11, 4, 9), Some(datetime(2024, 11, 4, 9))),
+        ("standup_20241106T090000Z", datetime(2024, 11, 6, 14), Some(datetime(2024, 11, 6, 9))),
+        ("standup_20241111T090000Z", datetime(2024, 11, 11, 9), Some(datetime(2024, 11, 11, 9))),
+        ("standup", datetime(2024, 11, 13, 9), Some(datetime(2024, 11, 13, 9))),
+    ]);
+
+    // Overrides are only loaded when they or the occurrence they replace overlap the range
+    let events = Event::find_by_calendar_between(&calendar, datetime(2024, 11, 6, 14), datetime(2024, 11, 6, 15), &mut state.get_connection());
+    let ids = events.iter().map(|event| event.external_id.as_str()).collect::<Vec<_>>();
+    assert_eq!(ids, vec!["standup", "standup_20241106T090000Z"]);
+    let events = Event::find_by_calendar_between(&calendar, datetime(2024, 11, 11, 9), datetime(2024, 11, 11, 10), &mut state.get_connection());
+    let ids = events.iter().map(|event| event.external_id.as_str()).collect::<Vec<_>>();
+    assert_eq!(ids, vec!["standup", "standup_20241111T090000Z"]);
+    let events = Event::find_by_calendar_between(&calendar, datetime(2024, 11, 6, 9), datetime(2024, 11, 6, 10), &mut state.get_connection());
+    let ids = events.iter().map(|event| event.external_id.as_str()).collect::<Vec<_>>();
+    assert_eq!(ids, vec!["standup", "standup_20241106T090000Z"]);
+    let events = Event::find_by_calendar_between(&calendar, datetime(2024, 11, 18, 0), datetime(2024, 11, 19, 0), &mut state.get_connection());
+    assert_eq!(events.len(), 1);
+
+    // The cancelled occurrence does not block time, the moved one blocks its new time
+    let busy = availability::group_busy(&group, start.and_utc(), datetime(2024, 11, 12, 0).and_utc(), &mut state.get_connection());
+    assert_eq!(busy, vec![
+        availability::BusyInterval::new(datetime(2024, 11, 4, 9), datetime(2024, 11, 4, 10)),
+        availability::BusyInterval::new(datetime(2024, 11, 6, 14), datetime(2024, 11, 6, 15)),
+    ]);
+}
+
+#[tokio::test]
+async fn list_calendar_events() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let calendar = create_calendar(&state, &group, ServiceType::Google(Oauth2Service::Google), "primary");
+    sync_events(&state, &calendar, standup_series());
+
+    let router = build_routes(state.clone());
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/calendar/{}/events?start=2024-11-05T00:00:00Z&end=2024-11-12T00:00:00Z", calendar.id))
+                .method(Method::GET)
+                .header(http::header::AUTHORIZATION, test_util::generate_basic_header(&app, &app_key))
+                .body(Body::empty())
+                .unwrap()
+        ).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let instances = body.as_array().unwrap();
+    assert_eq!(instances.len(), 2);
+    assert_eq!(instances[0]["external_id"], "standup_20241106T090000Z");
+    assert_eq!(instances[0]["recurring_event_id"], "standup");
+    assert_eq!(instances[0]["start"], "2024-11-06T14:00:00Z");
+    assert_eq!(instances[0]["original_start"], "2024-11-06T09:00:00Z");
+    assert_eq!(instances[1]["status"], "cancelled");
+
+    let other = App::new(&mut state.get_connection());
+    let other_key = other.create_key(&mut state.get_connection());
+    let router = build_routes(state.clone());
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/calendar/{}/events?start=2024-11-05T00:00:00Z&end=2024-11-12T00:00:00Z", calendar.id))
+                .method(Method::GET)
+                .header(http::header::AUTHORIZATION, test_util::generate_basic_header(&other, &other_key))
+                .body(Body::empty())
+                .unwrap()
+        ).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
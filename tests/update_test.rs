@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use axum::{body::Body, http::Request, http};
+use dotenv::dotenv;
+use reqwest::{Method, StatusCode};
+use schedsync_api::{build_routes, connectors::{oauth2::Oauth2Service, ServiceType}, models::{app::App, integration::Integration}, test_util, AppState};
+use tower::util::ServiceExt;
+
+async fn request_sync(state: &Arc<AppState>, uri: String, authorization: Option<String>) -> StatusCode {
+    let router = build_routes(state.clone());
+    let mut request = Request::builder()
+        .uri(uri)
+        .method(Method::GET);
+    if let Some(authorization) = authorization {
+        request = request.header(http::header::AUTHORIZATION, authorization);
+    }
+    router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn sync_integration_invalid_client() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let status = request_sync(&state, "/update/calendar/1".to_string(), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn sync_integration_of_other_app() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let owner = App::new(&mut state.get_connection());
+    let group = owner.create_group(&mut state.get_connection());
+    let integration = Integration::new(&group, &mut state.get_connection(), ServiceType::Google(Oauth2Service::Google));
+
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+
+    let status = request_sync(
+        &state,
+        format!("/update/calendar/{}", integration.id),
+        Some(test_util::generate_basic_header(&app, &app_key)),
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn sync_integration_without_credentials() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let integration = Integration::new(&group, &mut state.get_connection(), ServiceType::Google(Oauth2Service::Google));
+
+    let status = request_sync(
+        &state,
+        format!("/update/calendar/{}", integration.id),
+        Some(test_util::generate_basic_header(&app, &app_key)),
+    ).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn sync_caldav_integration_without_credentials() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let app = App::new(&mut state.get_connection());
+    let app_key = app.create_key(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let integration = Integration::new(&group, &mut state.get_connection(), ServiceType::Apple);
+
+    let status = request_sync(
+        &state,
+        format!("/update/calendar/{}", integration.id),
+        Some(test_util::generate_basic_header(&app, &app_key)),
+    ).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
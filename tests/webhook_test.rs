@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use axum::{body::Body, http::Request};
+use dotenv::dotenv;
+use reqwest::{Method, StatusCode};
+use schedsync_api::{build_routes, connectors::{oauth2::Oauth2Service, ServiceType}, models::{app::App, calendar::{Calendar, CalendarResult}, integration::Integration, watch_channel::{WatchChannel, WatchChannelResult}}, AppState};
+use tower::util::ServiceExt;
+
+async fn notify_google(state: &Arc<AppState>, headers: Vec<(&str, &str)>) -> StatusCode {
+    let router = build_routes(state.clone());
+    let mut request = Request::builder()
+        .uri("/update/webhook/google")
+        .method(Method::POST);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+}
+
+fn create_channel(state: &Arc<AppState>) -> WatchChannel {
+    let app = App::new(&mut state.get_connection());
+    let group = app.create_group(&mut state.get_connection());
+    let integration = Integration::new(&group, &mut state.get_connection(), ServiceType::Google(Oauth2Service::Google));
+    let calendars = Calendar::sync(&integration, vec![CalendarResult {
+        external_id: "primary".to_string(),
+        name: "Primary".to_string(),
+        background_color: "#9a9cff".to_string(),
+        foreground_color: "#000000".to_string(),
+    }], &mut state.get_connection()).unwrap();
+
+    WatchChannel::new(&calendars.calendars[0], Oauth2Service::Google, uuid::Uuid::new_v4().to_string(), WatchChannelResult {
+        external_id: uuid::Uuid::new_v4().to_string(),
+        resource_id: Some("resource".to_string()),
+        expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::days(7),
+    }, &mut state.get_connection()).unwrap()
+}
+
+#[tokio::test]
+async fn google_notification_for_unknown_channel() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let status = notify_google(&state, vec![
+        ("X-Goog-Channel-ID", "unknown"),
+        ("X-Goog-Channel-Token", "token"),
+    ]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn google_notification_with_invalid_token() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let channel = create_channel(&state);
+    let status = notify_google(&state, vec![
+        ("X-Goog-Channel-ID", &channel.external_id),
+        ("X-Goog-Channel-Token", "invalid"),
+        ("X-Goog-Resource-State", "exists"),
+    ]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn google_sync_notification() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let channel = create_channel(&state);
+    let status = notify_google(&state, vec![
+        ("X-Goog-Channel-ID", &channel.external_id),
+        ("X-Goog-Channel-Token", &channel.token),
+        ("X-Goog-Resource-State", "sync"),
+    ]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn outlook_validation_handshake() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let router = build_routes(state.clone());
+    let request = Request::builder()
+        .uri("/update/webhook/outlook?validationToken=Validation%3A%20Testing")
+        .method(Method::POST)
+        .body(Body::empty())
+        .unwrap();
+
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    assert_eq!(body, "Validation: Testing");
+}
+
+#[tokio::test]
+async fn outlook_malformed_notification() {
+    dotenv().ok();
+    let state = Arc::new(AppState::new());
+    let router = build_routes(state.clone());
+    let request = Request::builder()
+        .uri("/update/webhook/outlook")
+        .method(Method::POST)
+        .body(Body::from("not json"))
+        .unwrap();
+
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}